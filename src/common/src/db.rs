@@ -1,9 +1,28 @@
-use futures_util::{TryFuture, TryStreamExt};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::Duration,
+};
+
+use futures_util::{Stream, StreamExt, TryFuture, TryFutureExt, TryStreamExt};
 use prost::Message;
-use proto::common::mysql_desc;
-use sqlx::{Arguments, ConnectOptions};
+use proto::common::{
+    mysql_desc::{self, connection_opts::SslMode},
+    DataTypeEnum, Entry,
+};
+use sqlx::{
+    mysql::{MySqlDatabaseError, MySqlSslMode},
+    Arguments, Column, ConnectOptions, Connection, Executor, Row, TypeInfo, ValueRef,
+};
+
+use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::types::TypedValue;
+use crate::{net::retry::RetryPolicy, types::TypedValue};
 
 /// Connection of MySQL
 ///
@@ -19,6 +38,654 @@ use crate::types::TypedValue;
 pub struct MysqlConn {
     conn_opts: mysql_desc::ConnectionOpts,
     inner: Option<sqlx::MySqlConnection>,
+    decode_opts: DecodeOptions,
+    /// idle connections opened by [MysqlConn::warm_pool]
+    pool: Vec<sqlx::MySqlConnection>,
+    /// the limit of estimated payload size checked by [MysqlConn::execute]
+    max_payload_size: Option<usize>,
+    gauge: PoolGauge,
+    /// the samples recorded by the sampler started by [MysqlConn::sample_pool_utilization]
+    utilization_history: Option<Arc<Mutex<VecDeque<UtilizationSample>>>>,
+}
+
+/// [PoolGauge] counts the connections of a [MysqlConn] and the ones in use.
+/// It's shared with the utilization sampler, so it can be read while the [MysqlConn] is busy.
+#[derive(Clone, Debug, Default)]
+struct PoolGauge {
+    in_use: Arc<AtomicUsize>,
+    total: Arc<AtomicUsize>,
+}
+
+impl PoolGauge {
+    /// mark a connection in use until the returned guard is dropped
+    fn acquire(&self) -> InUseGuard {
+        self.in_use.fetch_add(1, Ordering::SeqCst);
+        InUseGuard {
+            in_use: self.in_use.clone(),
+        }
+    }
+
+    fn sample(&self) -> UtilizationSample {
+        UtilizationSample {
+            at: tokio::time::Instant::now(),
+            in_use: self.in_use.load(Ordering::SeqCst),
+            total: self.total.load(Ordering::SeqCst),
+        }
+    }
+}
+
+struct InUseGuard {
+    in_use: Arc<AtomicUsize>,
+}
+
+impl Drop for InUseGuard {
+    fn drop(&mut self) {
+        self.in_use.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The pool utilization of a [MysqlConn] at a moment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtilizationSample {
+    pub at: tokio::time::Instant,
+    /// number of connections running a statement or holding a transaction
+    pub in_use: usize,
+    /// number of connections held, including the idle ones
+    pub total: usize,
+}
+
+impl UtilizationSample {
+    /// the fraction of connections in use, from 0.0 to 1.0. It's 0.0 if no connection is held.
+    pub fn utilization(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            (self.in_use as f64 / self.total as f64).min(1.0)
+        }
+    }
+}
+
+/// size of the SRID prefixing the WKB of a geometry value
+const GEOMETRY_SRID_SIZE: usize = 4;
+
+/// max number of connections opened simultaneously by [MysqlConn::warm_pool]
+const WARM_UP_CONCURRENCY: usize = 4;
+
+/// Options of decoding the columns of a [sqlx::mysql::MySqlRow] into [TypedValue]
+#[derive(Clone)]
+pub struct DecodeOptions {
+    /// MySQL has no native boolean type and stores booleans as TINYINT(1).
+    /// This predicate decides whether a TINYINT(1) value is decoded as `true`. By default, non-zero is `true` and zero is `false`.
+    pub truthiness: Arc<dyn Fn(i8) -> bool + Send + Sync>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            truthiness: Arc::new(|value| value != 0),
+        }
+    }
+}
+
+/// Decode a [sqlx::mysql::MySqlRow] into a list of [TypedValue] according to the types of columns
+///
+/// - NULL values are decoded as [TypedValue::Null]
+/// - TINYINT(1) columns are decoded as [TypedValue::Boolean] by [DecodeOptions::truthiness]
+/// - integer columns are decoded as [TypedValue::BigInt]. Unsigned BIGINT values which overflow i64 are decoded as [TypedValue::Number]
+/// - FLOAT, DOUBLE and DECIMAL columns are decoded as [TypedValue::Number]
+/// - character columns are decoded as [TypedValue::String]
+/// - JSON columns are decoded by [TypedValue::from_json_value]
+/// - binary columns, e.g. BINARY and BLOB, are decoded as [TypedValue::Bytes]
+/// - BIT columns are decoded as [TypedValue::BigInt] if the value fits in i64, otherwise as [TypedValue::Bytes]
+/// - GEOMETRY columns are decoded as [TypedValue::Bytes] of the WKB of the geometry, see [decode_geometry]
+/// - other columns are decoded as [TypedValue::Invalid]
+pub fn decode_row(
+    row: &sqlx::mysql::MySqlRow,
+    opts: &DecodeOptions,
+) -> Result<Vec<TypedValue>, sqlx::Error> {
+    (0..row.len())
+        .map(|index| decode_column(row, index, opts))
+        .collect()
+}
+
+/// Convert a batch of [sqlx::mysql::MySqlRow] into the rows of proto pipeline, e.g. [proto::common::KeyedDataEvent::data].
+/// Each column is decoded by [decode_row] according to its type and becomes an [Entry] tagged with the decoded [DataTypeEnum].
+pub fn rows_to_proto(
+    rows: &[sqlx::mysql::MySqlRow],
+    opts: &DecodeOptions,
+) -> Result<Vec<Vec<Entry>>, sqlx::Error> {
+    rows.iter()
+        .map(|row| decode_row(row, opts).map(|values| values.iter().map(Entry::from).collect()))
+        .collect()
+}
+
+fn decode_column(
+    row: &sqlx::mysql::MySqlRow,
+    index: usize,
+    opts: &DecodeOptions,
+) -> Result<TypedValue, sqlx::Error> {
+    if row.try_get_raw(index)?.is_null() {
+        return Ok(TypedValue::Null);
+    }
+
+    let value = match row.column(index).type_info().name() {
+        "BOOLEAN" => TypedValue::Boolean((opts.truthiness)(row.try_get_unchecked::<i8, _>(index)?)),
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" | "YEAR" => {
+            TypedValue::BigInt(row.try_get_unchecked::<i64, _>(index)?)
+        }
+        "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+        | "BIGINT UNSIGNED" => {
+            let value = row.try_get_unchecked::<u64, _>(index)?;
+            i64::try_from(value)
+                .map(TypedValue::BigInt)
+                .unwrap_or(TypedValue::Number(value as f64))
+        }
+        "BIT" => decode_bits(&row.try_get_unchecked::<Vec<u8>, _>(index)?),
+        "FLOAT" => TypedValue::Number(row.try_get_unchecked::<f32, _>(index)? as f64),
+        "DOUBLE" => TypedValue::Number(row.try_get_unchecked::<f64, _>(index)?),
+        "DECIMAL" => row
+            .try_get_unchecked::<String, _>(index)?
+            .parse::<f64>()
+            .map(TypedValue::Number)
+            .map_err(|err| sqlx::Error::ColumnDecode {
+                index: index.to_string(),
+                source: Box::new(err),
+            })?,
+        "CHAR" | "VARCHAR" | "TEXT" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" | "SET" => {
+            TypedValue::String(row.try_get_unchecked::<String, _>(index)?)
+        }
+        "JSON" => {
+            serde_json::from_str::<serde_json::Value>(&row.try_get_unchecked::<String, _>(index)?)
+                .map(TypedValue::from_json_value)
+                .map_err(|err| sqlx::Error::ColumnDecode {
+                    index: index.to_string(),
+                    source: Box::new(err),
+                })?
+        }
+        "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+            TypedValue::Bytes(row.try_get_unchecked::<Vec<u8>, _>(index)?)
+        }
+        "GEOMETRY" => decode_geometry(&row.try_get_unchecked::<Vec<u8>, _>(index)?),
+        _ => TypedValue::Invalid,
+    };
+
+    Ok(value)
+}
+
+/// Error of binding [TypedValue] arguments to the placeholders of a statement
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindError {
+    /// the argument at `index` has a type that can't be bound to a MySQL placeholder
+    UnsupportedType {
+        index: usize,
+        data_type: DataTypeEnum,
+    },
+    /// the argument at `index` doesn't match the kind of a homogeneous batch
+    KindMismatch {
+        index: usize,
+        expected: ValueKind,
+        data_type: DataTypeEnum,
+    },
+    /// the number of `?` placeholders in the statement differs from the number of arguments
+    PlaceholderMismatch {
+        placeholders: usize,
+        arguments: usize,
+    },
+    /// the array or object argument at `index` has an element of a type that can't be bound
+    UnsupportedElement {
+        index: usize,
+        data_type: DataTypeEnum,
+    },
+    /// the row at `index` of a batch insert has a different number of values from the columns
+    RowWidthMismatch {
+        index: usize,
+        columns: usize,
+        values: usize,
+    },
+    /// a batch insert without columns or rows, or updating a column on conflict which isn't inserted
+    InvalidBatch(String),
+}
+
+impl Display for BindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindError::UnsupportedType { index, data_type } => f.write_fmt(format_args!(
+                "unsupported type of argument {}: {:?}",
+                index, data_type
+            )),
+            BindError::KindMismatch {
+                index,
+                expected,
+                data_type,
+            } => f.write_fmt(format_args!(
+                "argument {} is {:?}, expected {:?}",
+                index, data_type, expected
+            )),
+            BindError::PlaceholderMismatch {
+                placeholders,
+                arguments,
+            } => f.write_fmt(format_args!(
+                "statement has {} placeholders, but {} arguments are given",
+                placeholders, arguments
+            )),
+            BindError::UnsupportedElement { index, data_type } => f.write_fmt(format_args!(
+                "unsupported type of an element of argument {}: {:?}",
+                index, data_type
+            )),
+            BindError::RowWidthMismatch {
+                index,
+                columns,
+                values,
+            } => f.write_fmt(format_args!(
+                "row {} has {} values, but {} columns are inserted",
+                index, values, columns
+            )),
+            BindError::InvalidBatch(reason) => {
+                f.write_fmt(format_args!("invalid batch insert: {}", reason))
+            }
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+impl From<BindError> for sqlx::Error {
+    fn from(err: BindError) -> Self {
+        sqlx::Error::Protocol(err.to_string())
+    }
+}
+
+/// Error of a streaming query interrupted midway, e.g. by a dropped connection or a failed row callback.
+/// `processed` rows were handled successfully before the error, so the caller can resume from a cursor instead of reprocessing them.
+#[derive(Debug)]
+pub struct StreamInterrupted {
+    pub processed: usize,
+    pub source: sqlx::Error,
+}
+
+impl Display for StreamInterrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "stream interrupted after {} rows processed: {}",
+            self.processed, self.source
+        ))
+    }
+}
+
+impl std::error::Error for StreamInterrupted {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<sqlx::Error> for StreamInterrupted {
+    fn from(source: sqlx::Error) -> Self {
+        Self {
+            processed: 0,
+            source,
+        }
+    }
+}
+
+impl From<StreamInterrupted> for sqlx::Error {
+    fn from(err: StreamInterrupted) -> Self {
+        err.source
+    }
+}
+
+/// Count the `?` placeholders of a statement. The ones inside quoted strings or identifiers, or inside comments, are not placeholders.
+/// The content of an executable comment `/*! ... */` is a part of the statement for MySQL, so its placeholders are counted.
+pub fn count_placeholders(statement: &str) -> usize {
+    let mut count = 0;
+    let mut quote = None;
+    let mut chars = statement.chars();
+    while let Some(c) = chars.next() {
+        let rest = chars.as_str();
+        match (quote, c) {
+            // skip the escaped character, e.g. \' within a string
+            (Some('\'') | Some('"'), '\\') => {
+                chars.next();
+            }
+            // a doubled quote within a string is parsed as two adjacent quoted strings, which gives the same count
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '?') => count += 1,
+            // a line comment lasts until the end of the line. `--` starts a comment only if it's followed by a whitespace or control character
+            (None, '#') => chars = skip_until(rest, "\n").chars(),
+            (None, '-')
+                if rest.starts_with('-')
+                    && rest[1..]
+                        .chars()
+                        .next()
+                        .is_none_or(|c| c.is_whitespace() || c.is_control()) =>
+            {
+                chars = skip_until(rest, "\n").chars()
+            }
+            (None, '/') if rest.starts_with('*') && !rest.starts_with("*!") => {
+                chars = skip_until(&rest[1..], "*/").chars()
+            }
+            (None, _) => {}
+        }
+    }
+    count
+}
+
+/// the rest of `statement` after the first `end`, or nothing if there's no `end`
+fn skip_until<'a>(statement: &'a str, end: &str) -> &'a str {
+    statement
+        .find(end)
+        .map(|index| &statement[index + end.len()..])
+        .unwrap_or_default()
+}
+
+/// Check that the number of `?` placeholders of the statement matches the number of arguments,
+/// so that the mistake is reported locally with a friendly error before the statement is sent.
+pub fn validate_statement(statement: &str, arg_count: usize) -> Result<(), BindError> {
+    let placeholders = count_placeholders(statement);
+    if placeholders == arg_count {
+        Ok(())
+    } else {
+        Err(BindError::PlaceholderMismatch {
+            placeholders,
+            arguments: arg_count,
+        })
+    }
+}
+
+/// Bind [TypedValue] arguments to [sqlx::mysql::MySqlArguments] in order.
+/// It's the single place which decides how each variant of [TypedValue] is bound.
+///
+/// - [TypedValue::Null] is bound as SQL NULL
+/// - [TypedValue::Array] and [TypedValue::Object] are bound as the text of JSON, which MySQL parses when it's stored into a JSON column.
+///   Their elements must be representable in JSON, i.e. anything but [TypedValue::Invalid] and [TypedValue::Bytes]
+/// - [TypedValue::Bytes] is bound as binary data, e.g. a BLOB or the WKB of a geometry inserted by `ST_GeomFromWKB(?)`
+/// - [TypedValue::Invalid], i.e. `undefined`, can't be bound and fails with [BindError::UnsupportedType]
+pub fn build_mysql_args(
+    arguments: &[TypedValue],
+) -> Result<sqlx::mysql::MySqlArguments, BindError> {
+    let mut mysql_arg = sqlx::mysql::MySqlArguments::default();
+    for (index, val) in arguments.iter().enumerate() {
+        match val {
+            TypedValue::String(v) => mysql_arg.add(v),
+            TypedValue::BigInt(v) => mysql_arg.add(v),
+            TypedValue::Boolean(v) => mysql_arg.add(v),
+            TypedValue::Number(v) => mysql_arg.add(v),
+            TypedValue::Bytes(v) => mysql_arg.add(v),
+            TypedValue::Null => mysql_arg.add(None::<String>),
+            TypedValue::Array(v) => {
+                check_json_elements(index, v.iter())?;
+                mysql_arg.add(val.to_json_value().to_string())
+            }
+            TypedValue::Object(v) => {
+                check_json_elements(index, v.values())?;
+                mysql_arg.add(val.to_json_value().to_string())
+            }
+            TypedValue::Invalid => {
+                return Err(BindError::UnsupportedType {
+                    index,
+                    data_type: val.get_type(),
+                })
+            }
+        }
+    }
+
+    Ok(mysql_arg)
+}
+
+fn check_json_elements<'a, I: Iterator<Item = &'a TypedValue>>(
+    index: usize,
+    mut elements: I,
+) -> Result<(), BindError> {
+    elements.try_for_each(|element| match element {
+        TypedValue::String(_)
+        | TypedValue::BigInt(_)
+        | TypedValue::Boolean(_)
+        | TypedValue::Number(_)
+        | TypedValue::Null => Ok(()),
+        TypedValue::Array(v) => check_json_elements(index, v.iter()),
+        TypedValue::Object(v) => check_json_elements(index, v.values()),
+        TypedValue::Invalid | TypedValue::Bytes(_) => Err(BindError::UnsupportedElement {
+            index,
+            data_type: element.get_type(),
+        }),
+    })
+}
+
+/// What a batch insert does with a row whose primary or unique key collides with an existing row
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy<'a> {
+    /// fail the whole statement, which is the plain `INSERT`
+    #[default]
+    Error,
+    /// keep the existing row and skip the new one by `INSERT IGNORE`.
+    /// Note that MySQL also downgrades the other errors of the statement to warnings, e.g. a value truncated to fit its column.
+    Ignore,
+    /// replace all the inserted columns of the existing row by `ON DUPLICATE KEY UPDATE`
+    Overwrite,
+    /// replace only these columns of the existing row by `ON DUPLICATE KEY UPDATE`, and keep the others.
+    /// Each of them must be one of the inserted columns.
+    UpdateColumns(Vec<&'a str>),
+}
+
+/// quote an identifier by backticks, so that a reserved word or a name with special characters can be a table or column name
+fn quote_identifier(identifier: &str) -> String {
+    format!("`{}`", identifier.replace('`', "``"))
+}
+
+/// Build the statement which inserts `rows` rows of `columns` into `table` in one round trip, resolving the key conflicts by `policy`.
+/// The values of the rows are bound to the `?` placeholders row by row, in the order of `columns`.
+///
+/// For example, 2 rows of `id` and `name` with [ConflictPolicy::UpdateColumns] of `name` builds:
+///
+/// ```sql
+/// INSERT INTO `t` (`id`, `name`) VALUES (?, ?), (?, ?) ON DUPLICATE KEY UPDATE `name` = VALUES(`name`)
+/// ```
+pub fn build_insert_batch(
+    table: &str,
+    columns: &[&str],
+    rows: usize,
+    policy: &ConflictPolicy,
+) -> Result<String, BindError> {
+    if columns.is_empty() {
+        return Err(BindError::InvalidBatch("no column to insert".to_string()));
+    }
+    if rows == 0 {
+        return Err(BindError::InvalidBatch("no row to insert".to_string()));
+    }
+    let updated = match policy {
+        ConflictPolicy::Error | ConflictPolicy::Ignore => vec![],
+        ConflictPolicy::Overwrite => columns.to_vec(),
+        ConflictPolicy::UpdateColumns(updated) => {
+            if updated.is_empty() {
+                return Err(BindError::InvalidBatch(
+                    "no column to update on conflict".to_string(),
+                ));
+            }
+            if let Some(column) = updated.iter().find(|column| !columns.contains(column)) {
+                return Err(BindError::InvalidBatch(format!(
+                    "column {} updated on conflict is not inserted",
+                    column
+                )));
+            }
+            updated.clone()
+        }
+    };
+
+    let row = format!("({})", vec!["?"; columns.len()].join(", "));
+    let mut statement = format!(
+        "{} INTO {} ({}) VALUES {}",
+        if *policy == ConflictPolicy::Ignore {
+            "INSERT IGNORE"
+        } else {
+            "INSERT"
+        },
+        quote_identifier(table),
+        columns
+            .iter()
+            .map(|column| quote_identifier(column))
+            .collect::<Vec<_>>()
+            .join(", "),
+        vec![row; rows].join(", ")
+    );
+    if !updated.is_empty() {
+        statement.push_str(" ON DUPLICATE KEY UPDATE ");
+        statement.push_str(
+            &updated
+                .iter()
+                .map(|column| {
+                    let column = quote_identifier(column);
+                    format!("{} = VALUES({})", column, column)
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+    Ok(statement)
+}
+
+/// Error of a statement whose estimated payload exceeds the configured limit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayloadTooLarge {
+    /// estimated size of the payload, in bytes
+    pub estimated: usize,
+    /// the configured limit, in bytes
+    pub limit: usize,
+}
+
+impl Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "payload too large, estimated: {} bytes, limit: {} bytes",
+            self.estimated, self.limit
+        ))
+    }
+}
+
+impl std::error::Error for PayloadTooLarge {}
+
+impl From<PayloadTooLarge> for sqlx::Error {
+    fn from(err: PayloadTooLarge) -> Self {
+        sqlx::Error::Protocol(err.to_string())
+    }
+}
+
+/// header of a COM_STMT_EXECUTE packet: packet header, command, statement id, flags, iteration count and the new-params-bound flag
+const STMT_EXECUTE_HEADER_SIZE: usize = 15;
+
+/// Estimate the size of the packets sent to execute `statement` with `arguments`, in bytes.
+///
+/// The estimation is an upper bound of the COM_STMT_PREPARE and COM_STMT_EXECUTE packets: it counts the statement itself,
+/// the null bitmap, two bytes of type per argument, and the binary-encoded values.
+/// Strings are counted with the longest length-encoded prefix.
+pub fn estimate_payload_size(statement: &str, arguments: &[TypedValue]) -> usize {
+    let values = arguments
+        .iter()
+        .map(|val| match val {
+            TypedValue::String(v) => v.len() + 9,
+            TypedValue::BigInt(_) | TypedValue::Number(_) => 8,
+            TypedValue::Boolean(_) => 1,
+            TypedValue::Bytes(v) => v.len() + 9,
+            TypedValue::Array(_) | TypedValue::Object(_) => {
+                val.to_json_value().to_string().len() + 9
+            }
+            _ => 0,
+        })
+        .sum::<usize>();
+
+    statement.len()
+        + STMT_EXECUTE_HEADER_SIZE
+        + arguments.len().div_ceil(8)
+        + arguments.len() * 2
+        + values
+}
+
+/// Check the estimated payload size of `statement` with `arguments` against `limit` before sending it
+pub fn check_payload_size(
+    statement: &str,
+    arguments: &[TypedValue],
+    limit: usize,
+) -> Result<(), PayloadTooLarge> {
+    let estimated = estimate_payload_size(statement, arguments);
+    if estimated > limit {
+        Err(PayloadTooLarge { estimated, limit })
+    } else {
+        Ok(())
+    }
+}
+
+/// The kinds of [TypedValue] which can be bound to MySQL placeholders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    BigInt,
+    Boolean,
+    Number,
+}
+
+/// Bind a batch of [TypedValue] arguments which are all of the same `kind`, e.g. 10,000 integers of a bulk operation.
+/// The whole batch is checked against `kind` before anything is bound, so that the buffers of the arguments are reserved once
+/// for all the values instead of growing as they're added. An element of another kind is rejected with [BindError::KindMismatch].
+pub fn build_mysql_args_homogeneous(
+    kind: ValueKind,
+    values: &[TypedValue],
+) -> Result<sqlx::mysql::MySqlArguments, BindError> {
+    let mut size = 0;
+    for (index, val) in values.iter().enumerate() {
+        size += match (kind, val) {
+            (ValueKind::String, TypedValue::String(v)) => v.len() + 9,
+            (ValueKind::BigInt, TypedValue::BigInt(_))
+            | (ValueKind::Number, TypedValue::Number(_)) => 8,
+            (ValueKind::Boolean, TypedValue::Boolean(_)) => 1,
+            _ => {
+                return Err(BindError::KindMismatch {
+                    index,
+                    expected: kind,
+                    data_type: val.get_type(),
+                })
+            }
+        };
+    }
+
+    let mut mysql_arg = sqlx::mysql::MySqlArguments::default();
+    mysql_arg.reserve(values.len(), size);
+    values.iter().for_each(|val| match val {
+        TypedValue::String(v) => mysql_arg.add(v),
+        TypedValue::BigInt(v) => mysql_arg.add(v),
+        TypedValue::Boolean(v) => mysql_arg.add(v),
+        TypedValue::Number(v) => mysql_arg.add(v),
+        _ => unreachable!("the kind of every value has been checked"),
+    });
+
+    Ok(mysql_arg)
+}
+
+/// MySQL stores a geometry as a 4-byte SRID followed by its well-known binary (WKB), which is returned by the server as is.
+/// The SRID is stripped so that the value can be bound back by `ST_GeomFromWKB(?)`, or `ST_GeomFromWKB(?, srid)` to keep the SRID.
+pub fn decode_geometry(bytes: &[u8]) -> TypedValue {
+    match bytes.get(GEOMETRY_SRID_SIZE..) {
+        Some(wkb) => TypedValue::Bytes(wkb.to_vec()),
+        None => TypedValue::Invalid,
+    }
+}
+
+/// MySQL sends a BIT(n) value as big-endian bytes.
+/// It's decoded into [TypedValue::BigInt] if it fits in i64, otherwise into [TypedValue::Bytes] of the raw bytes, which is bound back as binary.
+fn decode_bits(bytes: &[u8]) -> TypedValue {
+    let significant = bytes
+        .iter()
+        .skip_while(|byte| **byte == 0)
+        .copied()
+        .collect::<Vec<_>>();
+    if significant.len() < 8 || (significant.len() == 8 && significant[0] < 0x80) {
+        TypedValue::BigInt(
+            significant
+                .iter()
+                .fold(0i64, |value, byte| (value << 8) | *byte as i64),
+        )
+    } else {
+        TypedValue::Bytes(bytes.to_vec())
+    }
 }
 
 impl MysqlConn {
@@ -58,22 +725,48 @@ impl MysqlConn {
         statement: &str,
         arguments: Vec<TypedValue>,
     ) -> Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> {
-        let mut mysql_arg = sqlx::mysql::MySqlArguments::default();
-        arguments.iter().for_each(|val| match val {
-            TypedValue::String(v) => mysql_arg.add(v),
-            TypedValue::BigInt(v) => mysql_arg.add(v),
-            TypedValue::Boolean(v) => mysql_arg.add(v),
-            TypedValue::Number(v) => mysql_arg.add(v),
-            _ => {}
-        });
+        let mysql_arg = prepare_execute(statement, &arguments, self.max_payload_size)?;
 
         self.connect().await?;
+        let _in_use = self.gauge.acquire();
 
         sqlx::query_with(statement, mysql_arg)
             .execute(self.inner.as_mut().unwrap())
             .await
     }
 
+    /// # Insert rows in a batch
+    ///
+    /// Insert `rows` of `columns` into `table` by a single statement built by [build_insert_batch],
+    /// and resolve the rows colliding with the existing ones by `policy`.
+    /// Each row must have a value for every column, otherwise [BindError::RowWidthMismatch] is returned before anything is sent.
+    ///
+    /// The affected rows of the result follow MySQL: a row inserted counts 1, a row updated on conflict counts 2,
+    /// and a row ignored or updated to the same values counts 0.
+    pub async fn insert_batch(
+        &mut self,
+        table: &str,
+        columns: &[&str],
+        rows: Vec<Vec<TypedValue>>,
+        policy: &ConflictPolicy<'_>,
+    ) -> Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> {
+        let statement = build_insert_batch(table, columns, rows.len(), policy)?;
+        if let Some((index, row)) = rows
+            .iter()
+            .enumerate()
+            .find(|(_, row)| row.len() != columns.len())
+        {
+            return Err(BindError::RowWidthMismatch {
+                index,
+                columns: columns.len(),
+                values: row.len(),
+            }
+            .into());
+        }
+        self.execute(&statement, rows.into_iter().flatten().collect())
+            .await
+    }
+
     /// # TryForEach, processing elements iteratively
     /// The result set of a SQL statement can be processed by a [MysqlConn] instance iteratively with calling method `try_for_each`.
     /// It has four arguments:
@@ -83,7 +776,8 @@ impl MysqlConn {
     /// - Mysql Client [sqlx::mysql::MySqlConnection].
     /// - the processor for each element which will return value implements trait [TryFuture<Ok = (), Error = sqlx::Error>]
     ///
-    /// The return value is a [Result]
+    /// The return value is the number of processed rows. If the stream is interrupted midway, [StreamInterrupted] reports
+    /// how many rows were processed successfully before the error.
     ///
     /// # Example
     /// ```
@@ -123,42 +817,722 @@ impl MysqlConn {
         &mut self,
         statement: &str,
         arguments: Vec<TypedValue>,
-        mut f: F,
-    ) -> Result<(), sqlx::Error> {
-        let mut mysql_arg = sqlx::mysql::MySqlArguments::default();
-        arguments.iter().for_each(|val| match val {
-            TypedValue::String(v) => mysql_arg.add(v),
-            TypedValue::BigInt(v) => mysql_arg.add(v),
-            TypedValue::Boolean(v) => mysql_arg.add(v),
-            TypedValue::Number(v) => mysql_arg.add(v),
-            _ => {}
-        });
+        f: F,
+    ) -> Result<usize, StreamInterrupted> {
+        self.connect().await?;
+        let _in_use = self.gauge.acquire();
+        try_for_each_row(self.inner.as_mut().unwrap(), statement, arguments, None, f).await
+    }
 
+    /// # Fetch all results and decode them into [TypedValue]
+    /// Each row of the result set is decoded by [decode_row] with the [DecodeOptions] of this [MysqlConn].
+    pub async fn fetch_typed(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
         self.connect().await?;
-        sqlx::query_with(statement, mysql_arg)
-            .fetch(self.inner.as_mut().unwrap())
-            .try_for_each(|row| f(row))
-            .await
+        let _in_use = self.gauge.acquire();
+        fetch_typed_rows(
+            self.inner.as_mut().unwrap(),
+            statement,
+            arguments,
+            &self.decode_opts,
+            None,
+        )
+        .await
+    }
+
+    /// # Fetch the results lazily as a stream of decoded rows
+    /// Each row is decoded by [decode_row] with the [DecodeOptions] of this [MysqlConn] once it's polled, so the stream can be
+    /// combined with the other stream combinators, e.g. `.take(100)` or `.try_collect()`, without a stateful closure.
+    ///
+    /// The connection is established on the first poll. Errors of connecting, binding or decoding are yielded as items.
+    /// If the stream is dropped before it ends, the unread rows are drained by the next query on the connection, see [drain_connection].
+    pub fn fetch<'a>(
+        &'a mut self,
+        statement: &'a str,
+        arguments: Vec<TypedValue>,
+    ) -> impl Stream<Item = Result<Vec<TypedValue>, sqlx::Error>> + 'a {
+        let decode_opts = self.decode_opts.clone();
+        let rows = async move {
+            let mysql_arg = prepare_execute(statement, &arguments, None)?;
+            self.connect().await?;
+            let in_use = self.gauge.acquire();
+            let rows = sqlx::query_with(statement, mysql_arg)
+                .fetch(self.inner.as_mut().unwrap())
+                .map(move |row| {
+                    let _in_use = &in_use;
+                    row.and_then(|row| decode_row(&row, &decode_opts))
+                });
+            Ok::<_, sqlx::Error>(rows)
+        };
+        futures_util::stream::once(rows).try_flatten()
+    }
+
+    /// # Fetch at most `limit` results and decode them into [TypedValue]
+    /// The same as [MysqlConn::fetch_typed], but it stops reading the result set once `limit` rows are decoded.
+    /// The rest of the result set is consumed by [drain_connection], so the connection is clean for the next query.
+    /// If the connection can't be drained, it's closed and [MysqlConn] will reconnect on its next query.
+    pub async fn fetch_typed_limited(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+        limit: usize,
+    ) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
+        self.connect().await?;
+        let _in_use = self.gauge.acquire();
+        let conn = self.inner.as_mut().unwrap();
+        let result =
+            fetch_typed_rows(conn, statement, arguments, &self.decode_opts, Some(limit)).await;
+        if result.is_err() && drain_connection(conn).await.is_err() {
+            self.inner = None;
+            self.update_gauge();
+        }
+        result
+    }
+
+    /// # Begin a read-only transaction with a consistent snapshot
+    /// The transaction runs in `REPEATABLE READ` and is started by `START TRANSACTION WITH CONSISTENT SNAPSHOT`,
+    /// so all queries within the returned [SnapshotTransaction] see the same view of the database,
+    /// no matter how many writes are committed by other sessions in the meantime.
+    pub async fn begin_snapshot(&mut self) -> Result<SnapshotTransaction<'_>, sqlx::Error> {
+        self.connect().await?;
+        let conn = self.inner.as_mut().unwrap();
+        conn.execute("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .await?;
+        conn.execute("START TRANSACTION WITH CONSISTENT SNAPSHOT, READ ONLY")
+            .await?;
+
+        Ok(SnapshotTransaction {
+            _in_use: self.gauge.acquire(),
+            conn: &mut self.inner,
+            decode_opts: self.decode_opts.clone(),
+            finished: false,
+        })
+    }
+
+    /// # Begin a transaction
+    /// The statements run by the returned [MysqlTransaction] take effect atomically once it's committed by [MysqlTransaction::commit].
+    /// If it's dropped without being committed, the transaction is rolled back before the next query on the connection.
+    pub async fn begin(&mut self) -> Result<MysqlTransaction<'_>, sqlx::Error> {
+        self.connect().await?;
+        let in_use = self.gauge.acquire();
+        let tx = self.inner.as_mut().unwrap().begin().await?;
+
+        Ok(MysqlTransaction {
+            _in_use: in_use,
+            tx,
+            decode_opts: self.decode_opts.clone(),
+            max_payload_size: self.max_payload_size,
+        })
+    }
+
+    /// Replace the [DecodeOptions] used by [MysqlConn::fetch_typed]
+    pub fn with_decode_options(mut self, decode_opts: DecodeOptions) -> Self {
+        self.decode_opts = decode_opts;
+        self
+    }
+
+    /// Reject the statements whose estimated payload exceeds `max_payload_size` bytes in [MysqlConn::execute] before sending them.
+    /// It should be no greater than the `max_allowed_packet` of the MySQL server, otherwise the server fails the statement mid-send.
+    pub fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+        self.max_payload_size = Some(max_payload_size);
+        self
+    }
+
+    /// # Open connections in advance
+    /// Proactively open connections until this [MysqlConn] holds `min_connections`, so that the first queries don't pay the connection-establishment cost.
+    /// At most [WARM_UP_CONCURRENCY] connections are opened simultaneously. The errors of the failed ones are returned.
+    pub async fn warm_pool(&mut self, min_connections: usize) -> Vec<sqlx::Error> {
+        let missing = min_connections.saturating_sub(self.pool_size());
+        let conn_opts = &self.conn_opts;
+        let results = futures_util::stream::iter((0..missing).map(|_| open(conn_opts)))
+            .buffer_unordered(WARM_UP_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut errors = vec![];
+        results.into_iter().for_each(|result| match result {
+            Ok(conn) => self.pool.push(conn),
+            Err(err) => {
+                tracing::error!("fail to open mysql connection: {}", err);
+                errors.push(err)
+            }
+        });
+        self.update_gauge();
+        errors
+    }
+
+    /// # Sample the pool utilization periodically
+    /// Spawn a background task recording a [UtilizationSample] every `period` into a ring buffer of the latest `capacity` samples,
+    /// which can be read by [MysqlConn::pool_utilization_history] to tell sustained saturation from brief spikes.
+    /// The task stops once this [MysqlConn] is dropped. Calling it again replaces the previous sampler.
+    pub fn sample_pool_utilization(&mut self, period: Duration, capacity: usize) {
+        let history = Arc::new(Mutex::new(VecDeque::with_capacity(capacity)));
+        let weak_history = Arc::downgrade(&history);
+        self.utilization_history = Some(history);
+        tokio::spawn(sample_utilization(
+            self.gauge.clone(),
+            weak_history,
+            period,
+            capacity.max(1),
+        ));
+    }
+
+    /// the pool utilization samples recorded by [MysqlConn::sample_pool_utilization], from the oldest to the latest
+    pub fn pool_utilization_history(&self) -> Vec<UtilizationSample> {
+        self.utilization_history
+            .as_ref()
+            .map(|history| history.lock().unwrap().iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn update_gauge(&self) {
+        self.gauge.total.store(self.pool_size(), Ordering::SeqCst);
+    }
+
+    /// number of connections held by this [MysqlConn], including the idle ones
+    pub fn pool_size(&self) -> usize {
+        self.pool.len() + usize::from(self.inner.is_some())
     }
 
     async fn connect(&mut self) -> Result<(), sqlx::Error> {
         if self.inner.is_none() {
-            let opts = sqlx::mysql::MySqlConnectOptions::new()
-                .host(&self.conn_opts.host)
-                .port(3306)
-                .username(&self.conn_opts.username)
-                .password(&self.conn_opts.password)
-                .database(&self.conn_opts.database);
-
-            opts.connect().await.map(|conn| self.inner = Some(conn))
-        } else {
-            Ok(())
+            let conn = match self.pool.pop() {
+                Some(conn) => conn,
+                None => open(&self.conn_opts).await?,
+            };
+            self.inner = Some(conn);
+        }
+        self.update_gauge();
+        Ok(())
+    }
+
+    /// # Connect with retries
+    /// Establish the connection eagerly, retrying by `policy` while the server isn't ready yet, e.g. MySQL is still starting in an orchestrated startup.
+    /// The errors which retrying can't fix, like authentication failures, are returned immediately. See [is_transient_connect_error].
+    pub async fn connect_with_retry(&mut self, policy: &RetryPolicy) -> Result<(), sqlx::Error> {
+        if self.inner.is_none() && self.pool.is_empty() {
+            let conn_opts = &self.conn_opts;
+            let conn = policy
+                .run_with(|| open(conn_opts), is_transient_connect_error)
+                .await?;
+            self.inner = Some(conn);
+        }
+        self.connect().await
+    }
+
+    /// # Connect over an established stream
+    /// Perform the MySQL handshake over `stream`, e.g. an SSH tunnel or a custom transport, instead of dialing the host and port of the options.
+    /// The new connection replaces the current one of this [MysqlConn]; the idle connections are kept.
+    ///
+    /// sqlx can only dial sockets by itself, so the stream is exposed on an ephemeral loopback port accepting a single connection,
+    /// and the bytes are relayed between that connection and the stream. As sqlx connects to `127.0.0.1`,
+    /// [SslMode::VerifyIdentity] can't match the certificate of the server behind the stream.
+    pub async fn connect_over_stream<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+        &mut self,
+        stream: S,
+    ) -> Result<(), sqlx::Error> {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let relay = tokio::spawn(relay_stream(listener, stream));
+
+        let opts = connect_options(&self.conn_opts)?
+            .host(&Ipv4Addr::LOCALHOST.to_string())
+            .port(port);
+        let conn = match opts.connect().await {
+            Ok(mut conn) => init_session(&mut conn, &self.conn_opts).await.map(|_| conn),
+            Err(err) => Err(err),
+        };
+        match conn {
+            Ok(conn) => {
+                self.inner = Some(conn);
+                self.update_gauge();
+                Ok(())
+            }
+            Err(err) => {
+                relay.abort();
+                Err(err)
+            }
         }
     }
 
     pub fn close(&mut self) {
         self.conn_opts.clear();
         self.inner = None;
+        self.pool.clear();
+        self.update_gauge();
+    }
+
+    /// # Reopen with new options
+    /// Drop the connections held by this [MysqlConn] and restore the options wiped by [MysqlConn::close],
+    /// so that a closed [MysqlConn] can be reused. It connects with `conn_opts` on its next query.
+    pub fn reopen(&mut self, conn_opts: mysql_desc::ConnectionOpts) {
+        self.close();
+        self.conn_opts = conn_opts;
+    }
+}
+
+/// A read-only transaction with a consistent snapshot, created by [MysqlConn::begin_snapshot]
+///
+/// The transaction should be finished by [SnapshotTransaction::commit] or [SnapshotTransaction::rollback].
+/// If it's dropped before finishing, the underlying connection is closed so that the server rolls the transaction back,
+/// and the [MysqlConn] will reconnect on its next query.
+pub struct SnapshotTransaction<'a> {
+    _in_use: InUseGuard,
+    conn: &'a mut Option<sqlx::MySqlConnection>,
+    decode_opts: DecodeOptions,
+    finished: bool,
+}
+
+impl<'a> SnapshotTransaction<'a> {
+    /// Process the result set within the snapshot iteratively, the same as [MysqlConn::try_for_each]
+    pub async fn try_for_each<
+        Fut: TryFuture<Ok = (), Error = sqlx::Error>,
+        F: FnMut(sqlx::mysql::MySqlRow) -> Fut,
+    >(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+        f: F,
+    ) -> Result<usize, StreamInterrupted> {
+        try_for_each_row(self.conn()?, statement, arguments, None, f).await
+    }
+
+    /// Fetch all results within the snapshot, the same as [MysqlConn::fetch_typed]
+    pub async fn fetch_typed(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
+        let decode_opts = self.decode_opts.clone();
+        fetch_typed_rows(self.conn()?, statement, arguments, &decode_opts, None).await
+    }
+
+    pub async fn commit(mut self) -> Result<(), sqlx::Error> {
+        self.finish("COMMIT").await
+    }
+
+    pub async fn rollback(mut self) -> Result<(), sqlx::Error> {
+        self.finish("ROLLBACK").await
+    }
+
+    async fn finish(&mut self, statement: &str) -> Result<(), sqlx::Error> {
+        self.conn()?.execute(statement).await?;
+        self.finished = true;
+        Ok(())
+    }
+
+    fn conn(&mut self) -> Result<&mut sqlx::MySqlConnection, sqlx::Error> {
+        self.conn.as_mut().ok_or(sqlx::Error::PoolClosed)
+    }
+}
+
+impl<'a> Drop for SnapshotTransaction<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.conn.take();
+        }
+    }
+}
+
+/// A transaction created by [MysqlConn::begin], wrapping a [sqlx::Transaction]
+///
+/// The transaction should be finished by [MysqlTransaction::commit] or [MysqlTransaction::rollback].
+/// If it's dropped before finishing, it's rolled back, so a batch failing midway leaves nothing behind.
+pub struct MysqlTransaction<'a> {
+    _in_use: InUseGuard,
+    tx: sqlx::Transaction<'a, sqlx::MySql>,
+    decode_opts: DecodeOptions,
+    max_payload_size: Option<usize>,
+}
+
+impl<'a> MysqlTransaction<'a> {
+    /// Execute the statement within the transaction, the same as [MysqlConn::execute]
+    pub async fn execute(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> {
+        let mysql_arg = prepare_execute(statement, &arguments, self.max_payload_size)?;
+        sqlx::query_with(statement, mysql_arg)
+            .execute(&mut *self.tx)
+            .await
+    }
+
+    /// Process the result set within the transaction iteratively, the same as [MysqlConn::try_for_each]
+    pub async fn try_for_each<
+        Fut: TryFuture<Ok = (), Error = sqlx::Error>,
+        F: FnMut(sqlx::mysql::MySqlRow) -> Fut,
+    >(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+        f: F,
+    ) -> Result<usize, StreamInterrupted> {
+        try_for_each_row(&mut self.tx, statement, arguments, None, f).await
+    }
+
+    /// Fetch all results within the transaction, the same as [MysqlConn::fetch_typed]
+    pub async fn fetch_typed(
+        &mut self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
+        fetch_typed_rows(&mut self.tx, statement, arguments, &self.decode_opts, None).await
+    }
+
+    pub async fn commit(self) -> Result<(), sqlx::Error> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> Result<(), sqlx::Error> {
+        self.tx.rollback().await
+    }
+}
+
+/// A pool of MySQL connections, backed by [sqlx::MySqlPool]
+///
+/// Unlike [MysqlConn], which holds its connections by itself and requires `&mut self` to run a query,
+/// [MysqlConnPool] acquires a connection from the pool for each query and returns it once the query finishes.
+/// At most `max_connections` connections are opened, and the callers wait for an idle one if all are in use.
+/// The connections are opened lazily by the first queries. It's cheap to clone, and the clones share the same pool,
+/// so it can be shared by many tasks.
+#[derive(Clone)]
+pub struct MysqlConnPool {
+    pool: sqlx::MySqlPool,
+    decode_opts: DecodeOptions,
+    /// the limit of estimated payload size checked by [MysqlConnPool::execute]
+    max_payload_size: Option<usize>,
+}
+
+impl MysqlConnPool {
+    pub fn new(
+        conn_opts: &mysql_desc::ConnectionOpts,
+        max_connections: u32,
+    ) -> Result<Self, sqlx::Error> {
+        let session_opts = Arc::new(conn_opts.clone());
+        let pool = sqlx::mysql::MySqlPoolOptions::new()
+            .max_connections(max_connections.max(1))
+            .after_connect(move |conn, _| {
+                let session_opts = session_opts.clone();
+                Box::pin(async move { init_session(conn, &session_opts).await })
+            })
+            .connect_lazy_with(connect_options(conn_opts)?);
+
+        Ok(Self {
+            pool,
+            decode_opts: Default::default(),
+            max_payload_size: None,
+        })
+    }
+
+    /// Replace the [DecodeOptions] used by [MysqlConnPool::fetch_typed]
+    pub fn with_decode_options(mut self, decode_opts: DecodeOptions) -> Self {
+        self.decode_opts = decode_opts;
+        self
+    }
+
+    /// Reject the statements whose estimated payload exceeds `max_payload_size` bytes in [MysqlConnPool::execute], the same as [MysqlConn::with_max_payload_size]
+    pub fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+        self.max_payload_size = Some(max_payload_size);
+        self
+    }
+
+    /// Execute the statement on a connection of the pool, the same as [MysqlConn::execute]
+    pub async fn execute(
+        &self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<sqlx::mysql::MySqlQueryResult, sqlx::Error> {
+        let mysql_arg = prepare_execute(statement, &arguments, self.max_payload_size)?;
+        sqlx::query_with(statement, mysql_arg)
+            .execute(&self.pool)
+            .await
+    }
+
+    /// Process the result set on a connection of the pool iteratively, the same as [MysqlConn::try_for_each].
+    /// The connection is held until all the rows are processed.
+    pub async fn try_for_each<
+        Fut: TryFuture<Ok = (), Error = sqlx::Error>,
+        F: FnMut(sqlx::mysql::MySqlRow) -> Fut,
+    >(
+        &self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+        f: F,
+    ) -> Result<usize, StreamInterrupted> {
+        let mut conn = self.pool.acquire().await?;
+        try_for_each_row(&mut conn, statement, arguments, None, f).await
+    }
+
+    /// Fetch all results on a connection of the pool, the same as [MysqlConn::fetch_typed]
+    pub async fn fetch_typed(
+        &self,
+        statement: &str,
+        arguments: Vec<TypedValue>,
+    ) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
+        let mut conn = self.pool.acquire().await?;
+        fetch_typed_rows(&mut conn, statement, arguments, &self.decode_opts, None).await
+    }
+
+    /// number of connections opened by the pool, including the ones in use
+    pub fn size(&self) -> u32 {
+        self.pool.size()
+    }
+
+    /// number of idle connections of the pool
+    pub fn num_idle(&self) -> usize {
+        self.pool.num_idle()
+    }
+
+    /// Close all the connections of the pool. It waits for the connections in use to be returned,
+    /// and the queries after closing fail with [sqlx::Error::PoolClosed].
+    pub async fn close(&self) {
+        self.pool.close().await
+    }
+}
+
+/// validate the statement and its arguments, and bind the arguments
+fn prepare_execute(
+    statement: &str,
+    arguments: &[TypedValue],
+    max_payload_size: Option<usize>,
+) -> Result<sqlx::mysql::MySqlArguments, sqlx::Error> {
+    validate_statement(statement, arguments.len())?;
+    if let Some(limit) = max_payload_size {
+        check_payload_size(statement, arguments, limit)?;
+    }
+    Ok(build_mysql_args(arguments)?)
+}
+
+async fn try_for_each_row<
+    Fut: TryFuture<Ok = (), Error = sqlx::Error>,
+    F: FnMut(sqlx::mysql::MySqlRow) -> Fut,
+>(
+    conn: &mut sqlx::MySqlConnection,
+    statement: &str,
+    arguments: Vec<TypedValue>,
+    limit: Option<usize>,
+    f: F,
+) -> Result<usize, StreamInterrupted> {
+    validate_statement(statement, arguments.len()).map_err(sqlx::Error::from)?;
+    let mysql_arg = build_mysql_args(&arguments).map_err(sqlx::Error::from)?;
+
+    let rows = sqlx::query_with(statement, mysql_arg).fetch(&mut *conn);
+    let processed = match limit {
+        Some(limit) => try_for_each_counted(rows.take(limit), f).await?,
+        None => try_for_each_counted(rows, f).await?,
+    };
+    if limit.is_some() {
+        drain_connection(conn)
+            .await
+            .map_err(|source| StreamInterrupted { processed, source })?;
+    }
+    Ok(processed)
+}
+
+/// # Drain the pending results of a connection
+/// If a result set isn't read to the end, e.g. its stream is dropped early, the unread rows stay in the connection.
+/// It consumes all of them and checks the connection is responsive, so that the connection is clean for reuse.
+/// A connection which fails to be drained is in a bad state and shouldn't be reused.
+pub async fn drain_connection(conn: &mut sqlx::MySqlConnection) -> Result<(), sqlx::Error> {
+    // the pending results are consumed before the ping is sent
+    conn.ping().await
+}
+
+/// process the rows of the stream one by one, and count the rows processed successfully
+async fn try_for_each_counted<
+    R,
+    S: Stream<Item = Result<R, sqlx::Error>>,
+    Fut: TryFuture<Ok = (), Error = sqlx::Error>,
+    F: FnMut(R) -> Fut,
+>(
+    stream: S,
+    mut f: F,
+) -> Result<usize, StreamInterrupted> {
+    futures_util::pin_mut!(stream);
+    let mut processed = 0;
+    while let Some(row) = stream.next().await {
+        let result = match row {
+            Ok(row) => f(row).into_future().await,
+            Err(err) => Err(err),
+        };
+        if let Err(source) = result {
+            return Err(StreamInterrupted { processed, source });
+        }
+        processed += 1;
+    }
+    Ok(processed)
+}
+
+async fn fetch_typed_rows(
+    conn: &mut sqlx::MySqlConnection,
+    statement: &str,
+    arguments: Vec<TypedValue>,
+    decode_opts: &DecodeOptions,
+    limit: Option<usize>,
+) -> Result<Vec<Vec<TypedValue>>, StreamInterrupted> {
+    let mut rows = vec![];
+    try_for_each_row(conn, statement, arguments, limit, |row| {
+        let result = decode_row(&row, decode_opts).map(|values| rows.push(values));
+        futures_util::future::ready(result)
+    })
+    .await?;
+
+    Ok(rows)
+}
+
+async fn sample_utilization(
+    gauge: PoolGauge,
+    history: Weak<Mutex<VecDeque<UtilizationSample>>>,
+    period: Duration,
+    capacity: usize,
+) {
+    let mut interval = tokio::time::interval(period);
+    loop {
+        interval.tick().await;
+        match history.upgrade() {
+            Some(history) => {
+                let mut history = history.lock().unwrap();
+                if history.len() == capacity {
+                    history.pop_front();
+                }
+                history.push_back(gauge.sample());
+            }
+            None => return,
+        }
+    }
+}
+
+/// accept a single connection on `listener` and relay the bytes between it and `stream` until either side closes
+async fn relay_stream<S: AsyncRead + AsyncWrite + Unpin>(
+    listener: tokio::net::TcpListener,
+    mut stream: S,
+) {
+    match listener.accept().await {
+        Ok((mut socket, _)) => {
+            drop(listener);
+            if let Err(err) = tokio::io::copy_bidirectional(&mut socket, &mut stream).await {
+                tracing::warn!("mysql stream relay is closed: {}", err);
+            }
+        }
+        Err(err) => tracing::error!(
+            "fail to accept the connection of mysql stream relay: {}",
+            err
+        ),
+    }
+}
+
+/// build the options to connect with. A port out of the range of u16 is rejected rather than truncated.
+fn connect_options(
+    conn_opts: &mysql_desc::ConnectionOpts,
+) -> Result<sqlx::mysql::MySqlConnectOptions, sqlx::Error> {
+    if u16::try_from(conn_opts.port).is_err() {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "invalid mysql port {} of host {}, it should be within 1-65535",
+                conn_opts.port, conn_opts.host
+            )
+            .into(),
+        ));
+    }
+    if let Some(name) = conn_opts
+        .extra_params
+        .keys()
+        .find(|name| !is_variable_name(name))
+    {
+        return Err(sqlx::Error::Configuration(
+            format!("invalid mysql session variable {:?}", name).into(),
+        ));
+    }
+
+    let opts = sqlx::mysql::MySqlConnectOptions::new()
+        .host(&conn_opts.host)
+        .port(conn_opts.port())
+        .username(&conn_opts.username)
+        .password(&conn_opts.password)
+        .database(&conn_opts.database)
+        .ssl_mode(ssl_mode(conn_opts.ssl_mode()));
+    if conn_opts.ssl_ca.is_empty() {
+        Ok(opts)
+    } else {
+        Ok(opts.ssl_ca(&conn_opts.ssl_ca))
+    }
+}
+
+async fn open(
+    conn_opts: &mysql_desc::ConnectionOpts,
+) -> Result<sqlx::MySqlConnection, sqlx::Error> {
+    let mut conn = connect_options(conn_opts)?.connect().await?;
+    init_session(&mut conn, conn_opts).await?;
+    Ok(conn)
+}
+
+/// MySQL error numbers of the servers which can't accept connections for now
+const ER_CON_COUNT_ERROR: u16 = 1040;
+const ER_SERVER_SHUTDOWN: u16 = 1053;
+const ER_SERVER_OFFLINE_MODE: u16 = 3032;
+
+/// whether opening a connection failed by `err` may succeed if it's retried, e.g. the server isn't up yet or has too many connections.
+/// Configuration errors and the errors returned by the server, like authentication failures or unknown databases, aren't transient.
+pub fn is_transient_connect_error(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(err) => err
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .map(|err| {
+                matches!(
+                    err.number(),
+                    ER_CON_COUNT_ERROR | ER_SERVER_SHUTDOWN | ER_SERVER_OFFLINE_MODE
+                )
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// apply the session settings to a newly opened connection
+async fn init_session(
+    conn: &mut sqlx::MySqlConnection,
+    conn_opts: &mysql_desc::ConnectionOpts,
+) -> Result<(), sqlx::Error> {
+    if conn_opts.max_execution_time_ms > 0 {
+        sqlx::query(&format!(
+            "SET SESSION max_execution_time = {}",
+            conn_opts.max_execution_time_ms
+        ))
+        .execute(&mut *conn)
+        .await?;
+    }
+    // sorted, so that the variables depending on each other are set in a predictable order
+    let mut extra_params = conn_opts.extra_params.iter().collect::<Vec<_>>();
+    extra_params.sort();
+    for (name, value) in extra_params {
+        let statement = format!("SET SESSION {} = ?", name);
+        // the numeric variables reject the values bound as strings
+        let query = match value.parse::<i64>() {
+            Ok(number) => sqlx::query(&statement).bind(number),
+            Err(_) => sqlx::query(&statement).bind(value),
+        };
+        query.execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// whether `name` is a plain MySQL variable name, which is safe to be formatted into a statement
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn ssl_mode(mode: SslMode) -> MySqlSslMode {
+    match mode {
+        SslMode::Preferred => MySqlSslMode::Preferred,
+        SslMode::Disabled => MySqlSslMode::Disabled,
+        SslMode::Required => MySqlSslMode::Required,
+        SslMode::VerifyCa => MySqlSslMode::VerifyCa,
+        SslMode::VerifyIdentity => MySqlSslMode::VerifyIdentity,
     }
 }
 
@@ -167,6 +1541,870 @@ impl From<mysql_desc::ConnectionOpts> for MysqlConn {
         Self {
             conn_opts,
             inner: None,
+            decode_opts: Default::default(),
+            pool: vec![],
+            max_payload_size: None,
+            gauge: Default::default(),
+            utilization_history: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use proto::common::{
+        mysql_desc::{self, connection_opts::SslMode},
+        DataTypeEnum,
+    };
+    use sqlx::{mysql::MySqlSslMode, Arguments};
+
+    use crate::{net::retry::RetryPolicy, types::TypedValue};
+
+    use super::{
+        build_insert_batch, build_mysql_args, build_mysql_args_homogeneous, check_payload_size,
+        decode_bits, decode_geometry, estimate_payload_size, is_transient_connect_error,
+        try_for_each_counted, validate_statement, BindError, ConflictPolicy, MysqlConn,
+        MysqlConnPool, PayloadTooLarge, ValueKind,
+    };
+
+    #[test]
+    fn test_build_mysql_args() {
+        let arguments = vec![
+            TypedValue::String("lightflus".to_string()),
+            TypedValue::BigInt(10),
+            TypedValue::Boolean(true),
+            TypedValue::Number(1.5),
+            TypedValue::Null,
+            TypedValue::Bytes(vec![0, 255]),
+            TypedValue::Object(BTreeMap::from([(
+                "name".to_string(),
+                TypedValue::String("lightflus".to_string()),
+            )])),
+        ];
+        let result = build_mysql_args(&arguments);
+        assert!(result.is_ok());
+        let mysql_arg = result.unwrap();
+        assert_eq!(mysql_arg.len(), 7);
+
+        let mut expected = sqlx::mysql::MySqlArguments::default();
+        expected.add("lightflus".to_string());
+        expected.add(10i64);
+        expected.add(true);
+        expected.add(1.5f64);
+        expected.add(None::<String>);
+        expected.add(vec![0u8, 255]);
+        expected.add(r#"{"name":"lightflus"}"#.to_string());
+        assert_eq!(format!("{:?}", mysql_arg), format!("{:?}", expected));
+
+        assert_eq!(build_mysql_args(&[]).map(|args| args.len()), Ok(0));
+    }
+
+    #[test]
+    fn test_build_mysql_args_unsupported_type() {
+        // `undefined` has no counterpart in SQL
+        let result = build_mysql_args(&[TypedValue::BigInt(1), TypedValue::Invalid]);
+        assert_eq!(
+            result.map(|args| args.len()),
+            Err(BindError::UnsupportedType {
+                index: 1,
+                data_type: DataTypeEnum::Unspecified
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_mysql_args_array() {
+        let array = TypedValue::Array(vec![
+            TypedValue::BigInt(1),
+            TypedValue::String("lightflus".to_string()),
+            TypedValue::Array(vec![TypedValue::Boolean(true), TypedValue::Number(1.5)]),
+        ]);
+        let mysql_arg = build_mysql_args(&[array]).unwrap();
+        let mut expected = sqlx::mysql::MySqlArguments::default();
+        expected.add(r#"[1,"lightflus",[true,1.5]]"#.to_string());
+        assert_eq!(format!("{:?}", mysql_arg), format!("{:?}", expected));
+
+        let mysql_arg = build_mysql_args(&[TypedValue::Array(vec![
+            TypedValue::Null,
+            TypedValue::Object(BTreeMap::from([("k".to_string(), TypedValue::BigInt(1))])),
+        ])])
+        .unwrap();
+        let mut expected = sqlx::mysql::MySqlArguments::default();
+        expected.add(r#"[null,{"k":1}]"#.to_string());
+        assert_eq!(format!("{:?}", mysql_arg), format!("{:?}", expected));
+
+        let result = build_mysql_args(&[
+            TypedValue::Null,
+            TypedValue::Array(vec![TypedValue::Array(vec![TypedValue::Invalid])]),
+        ]);
+        assert_eq!(
+            result.map(|args| args.len()),
+            Err(BindError::UnsupportedElement {
+                index: 1,
+                data_type: DataTypeEnum::Unspecified
+            })
+        );
+        let result = build_mysql_args(&[TypedValue::Object(BTreeMap::from([(
+            "k".to_string(),
+            TypedValue::Bytes(vec![]),
+        )]))]);
+        assert_eq!(
+            result.map(|args| args.len()),
+            Err(BindError::UnsupportedElement {
+                index: 0,
+                data_type: DataTypeEnum::Bytes
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_mysql_args_homogeneous() {
+        let values = (0..10_000).map(TypedValue::BigInt).collect::<Vec<_>>();
+        let args = build_mysql_args_homogeneous(ValueKind::BigInt, &values);
+        assert_eq!(args.map(|args| args.len()), Ok(10_000));
+        let args = build_mysql_args(&values);
+        assert_eq!(args.map(|args| args.len()), Ok(10_000));
+
+        let strings = ["a", "bc", ""]
+            .map(|v| TypedValue::String(v.to_string()))
+            .to_vec();
+        assert_eq!(
+            build_mysql_args_homogeneous(ValueKind::String, &strings).map(|args| args.len()),
+            Ok(3)
+        );
+
+        let mut values = values;
+        values[42] = TypedValue::String("stray".to_string());
+        assert_eq!(
+            build_mysql_args_homogeneous(ValueKind::BigInt, &values).map(|args| args.len()),
+            Err(BindError::KindMismatch {
+                index: 42,
+                expected: ValueKind::BigInt,
+                data_type: DataTypeEnum::String,
+            })
+        );
+        assert_eq!(
+            build_mysql_args_homogeneous(
+                ValueKind::String,
+                &[TypedValue::String("a".to_string()), TypedValue::Null]
+            )
+            .map(|args| args.len()),
+            Err(BindError::KindMismatch {
+                index: 1,
+                expected: ValueKind::String,
+                data_type: DataTypeEnum::Null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_bits() {
+        assert_eq!(decode_bits(&[0b1010_0101]), TypedValue::BigInt(165));
+        assert_eq!(decode_bits(&[0x01, 0x00]), TypedValue::BigInt(256));
+        assert_eq!(decode_bits(&[]), TypedValue::BigInt(0));
+        assert_eq!(
+            decode_bits(&[0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            TypedValue::BigInt(i64::MAX)
+        );
+        assert_eq!(
+            decode_bits(&[0x80, 0, 0, 0, 0, 0, 0, 0x01]),
+            TypedValue::Bytes(vec![0x80, 0, 0, 0, 0, 0, 0, 0x01])
+        );
+    }
+
+    #[test]
+    fn test_decode_bits_round_trip() {
+        // a BIT(64) value which overflows i64 is bound back as the same binary it's read from
+        let bits = vec![0xff, 0, 0, 0, 0, 0, 0x80, 0x01];
+        let mut expected = sqlx::mysql::MySqlArguments::default();
+        expected.add(bits.clone());
+        let value = decode_bits(&bits);
+        assert_eq!(value, TypedValue::Bytes(bits));
+        assert_eq!(
+            format!("{:?}", build_mysql_args(&[value]).unwrap()),
+            format!("{:?}", expected)
+        );
+
+        // a BIT(64) value which fits in i64 is bound back as the same integer
+        let mut expected = sqlx::mysql::MySqlArguments::default();
+        expected.add(0x0102i64);
+        let value = decode_bits(&[0, 0, 0, 0, 0, 0, 0x01, 0x02]);
+        assert_eq!(value, TypedValue::BigInt(0x0102));
+        assert_eq!(
+            format!("{:?}", build_mysql_args(&[value]).unwrap()),
+            format!("{:?}", expected)
+        );
+    }
+
+    #[test]
+    fn test_decode_geometry() {
+        // POINT(1 2) in little-endian WKB
+        let mut wkb = vec![0x01, 0x01, 0x00, 0x00, 0x00];
+        wkb.extend_from_slice(&1f64.to_le_bytes());
+        wkb.extend_from_slice(&2f64.to_le_bytes());
+
+        // the value stored by MySQL is prefixed with the SRID
+        let mut stored = 4326u32.to_le_bytes().to_vec();
+        stored.extend_from_slice(&wkb);
+        assert_eq!(decode_geometry(&stored), TypedValue::Bytes(wkb.clone()));
+        assert_eq!(decode_geometry(&[0, 0]), TypedValue::Invalid);
+
+        let args = build_mysql_args(&[TypedValue::Bytes(wkb.clone())]);
+        assert!(args.is_ok());
+        // binary data is counted the same as a string of the same length
+        let statement = "select ST_GeomFromWKB(?)";
+        assert_eq!(
+            estimate_payload_size(statement, &[TypedValue::String("x".repeat(wkb.len()))]),
+            estimate_payload_size(statement, &[TypedValue::Bytes(wkb)])
+        );
+    }
+
+    #[test]
+    fn test_estimate_payload_size() {
+        let statement = "insert into t values (?, ?, ?)";
+        let arguments = vec![
+            TypedValue::String("lightflus".to_string()),
+            TypedValue::BigInt(1),
+            TypedValue::Boolean(true),
+        ];
+        let estimated = estimate_payload_size(statement, &arguments);
+        assert_eq!(estimated, statement.len() + 15 + 1 + 6 + 18 + 8 + 1);
+        assert!(check_payload_size(statement, &arguments, estimated).is_ok());
+        assert_eq!(
+            check_payload_size(statement, &arguments, estimated - 1),
+            Err(PayloadTooLarge {
+                estimated,
+                limit: estimated - 1
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_rejects_oversized_batch() {
+        let mut conn = MysqlConn::from(proto::common::mysql_desc::ConnectionOpts {
+            host: "unreachable.invalid".to_string(),
+            ..Default::default()
+        })
+        .with_max_payload_size(1024);
+
+        let rows = 100;
+        let statement = format!("insert into t (v) values {}", vec!["(?)"; rows].join(", "));
+        let arguments = vec![TypedValue::String("x".repeat(64)); rows];
+
+        // the statement is rejected before connecting to the unreachable host
+        let result = conn.execute(&statement, arguments).await;
+        assert!(result.is_err());
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("payload too large"), "{}", err);
+        assert!(err.contains("limit: 1024 bytes"), "{}", err);
+        assert_eq!(conn.pool_size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_interrupted_reports_processed_rows() {
+        // the connection drops after 500 of 1000 rows
+        let rows = (0..1000).map(|row| {
+            if row < 500 {
+                Ok(row)
+            } else {
+                Err(sqlx::Error::Io(std::io::Error::from(
+                    std::io::ErrorKind::ConnectionReset,
+                )))
+            }
+        });
+        let mut handled = vec![];
+        let result = try_for_each_counted(futures_util::stream::iter(rows), |row| {
+            handled.push(row);
+            futures_util::future::ready(Ok(()))
+        })
+        .await;
+        let err = result.unwrap_err();
+        assert_eq!(err.processed, 500);
+        assert_eq!(handled.len(), 500);
+        assert!(matches!(err.source, sqlx::Error::Io(_)));
+
+        // the failed row is not counted
+        let rows = (0..10).map(Ok::<_, sqlx::Error>);
+        let result = try_for_each_counted(futures_util::stream::iter(rows), |row| {
+            futures_util::future::ready(if row == 7 {
+                Err(sqlx::Error::RowNotFound)
+            } else {
+                Ok(())
+            })
+        })
+        .await;
+        assert_eq!(result.unwrap_err().processed, 7);
+
+        let rows = (0..10).map(Ok::<_, sqlx::Error>);
+        let result = try_for_each_counted(futures_util::stream::iter(rows), |_| {
+            futures_util::future::ready(Ok(()))
+        })
+        .await;
+        assert_eq!(result.unwrap(), 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pool_utilization_history() {
+        use std::{sync::atomic::Ordering, time::Duration};
+
+        let mut conn = MysqlConn::from(proto::common::mysql_desc::ConnectionOpts::default());
+        conn.gauge.total.store(4, Ordering::SeqCst);
+        conn.sample_pool_utilization(Duration::from_secs(1), 8);
+
+        // idle, then half of the connections in use, then saturated for a while, then idle again
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let mut in_use = (0..2).map(|_| conn.gauge.acquire()).collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        in_use.extend((0..2).map(|_| conn.gauge.acquire()));
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        drop(in_use);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let history = conn.pool_utilization_history();
+        assert_eq!(
+            history
+                .iter()
+                .map(|sample| sample.utilization())
+                .collect::<Vec<_>>(),
+            vec![0.0, 0.5, 1.0, 1.0, 1.0, 0.0]
+        );
+        assert!(history.iter().all(|sample| sample.total == 4));
+
+        // only the latest samples are kept
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let history = conn.pool_utilization_history();
+        assert_eq!(history.len(), 8);
+        assert_eq!(history[0].utilization(), 1.0);
+        assert!(history.windows(2).all(|pair| pair[0].at < pair[1].at));
+    }
+
+    #[test]
+    fn test_build_insert_batch() {
+        let columns = ["id", "name", "score"];
+        let cases = [
+            (
+                ConflictPolicy::Error,
+                "INSERT INTO `t` (`id`, `name`, `score`) VALUES (?, ?, ?), (?, ?, ?)",
+            ),
+            (
+                ConflictPolicy::Ignore,
+                "INSERT IGNORE INTO `t` (`id`, `name`, `score`) VALUES (?, ?, ?), (?, ?, ?)",
+            ),
+            (
+                ConflictPolicy::Overwrite,
+                "INSERT INTO `t` (`id`, `name`, `score`) VALUES (?, ?, ?), (?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE `id` = VALUES(`id`), `name` = VALUES(`name`), `score` = VALUES(`score`)",
+            ),
+            (
+                ConflictPolicy::UpdateColumns(vec!["score"]),
+                "INSERT INTO `t` (`id`, `name`, `score`) VALUES (?, ?, ?), (?, ?, ?) \
+                 ON DUPLICATE KEY UPDATE `score` = VALUES(`score`)",
+            ),
+        ];
+        for (policy, expected) in cases {
+            let statement = build_insert_batch("t", &columns, 2, &policy).unwrap();
+            assert_eq!(statement, expected);
+            assert!(validate_statement(&statement, 6).is_ok());
         }
+        assert_eq!(ConflictPolicy::default(), ConflictPolicy::Error);
+
+        // the identifiers are quoted
+        assert_eq!(
+            build_insert_batch("order", &["key`s"], 1, &ConflictPolicy::Error).unwrap(),
+            "INSERT INTO `order` (`key``s`) VALUES (?)"
+        );
+
+        // invalid batches are rejected before they're sent
+        for (columns, rows, policy) in [
+            (vec![], 1, ConflictPolicy::Error),
+            (vec!["id"], 0, ConflictPolicy::Error),
+            (vec!["id"], 1, ConflictPolicy::UpdateColumns(vec![])),
+            (vec!["id"], 1, ConflictPolicy::UpdateColumns(vec!["name"])),
+        ] {
+            assert!(matches!(
+                build_insert_batch("t", &columns, rows, &policy),
+                Err(BindError::InvalidBatch(_))
+            ));
+        }
+        assert_eq!(
+            build_insert_batch(
+                "t",
+                &["id"],
+                1,
+                &ConflictPolicy::UpdateColumns(vec!["name"])
+            )
+            .unwrap_err()
+            .to_string(),
+            "invalid batch insert: column name updated on conflict is not inserted"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_batch_row_width_mismatch() {
+        let mut conn = MysqlConn::from(mysql_desc::ConnectionOpts {
+            host: "localhost".to_string(),
+            ..Default::default()
+        });
+        let result = conn
+            .insert_batch(
+                "t",
+                &["id", "name"],
+                vec![
+                    vec![TypedValue::BigInt(1), TypedValue::String("a".to_string())],
+                    vec![TypedValue::BigInt(2)],
+                ],
+                &ConflictPolicy::Overwrite,
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            sqlx::Error::from(BindError::RowWidthMismatch {
+                index: 1,
+                columns: 2,
+                values: 1
+            })
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_validate_statement() {
+        assert!(validate_statement("select a, b from t where a = ? and b = ?", 2).is_ok());
+        assert!(validate_statement("select a, b from t", 0).is_ok());
+
+        assert_eq!(
+            validate_statement("insert into t (a, b, c) values (?, ?, ?)", 2),
+            Err(BindError::PlaceholderMismatch {
+                placeholders: 3,
+                arguments: 2
+            })
+        );
+        assert_eq!(
+            validate_statement("insert into t (a, b, c) values (?, ?, ?)", 2)
+                .unwrap_err()
+                .to_string(),
+            "statement has 3 placeholders, but 2 arguments are given"
+        );
+
+        // question marks in quoted strings and identifiers are not placeholders
+        assert!(validate_statement("select * from t where a = '?' and b = ?", 1).is_ok());
+        assert!(validate_statement(r#"select "what?", `col?` from t where b = ?"#, 1).is_ok());
+        assert!(validate_statement(r"select * from t where a = 'it\'s ?' and b = ?", 1).is_ok());
+        assert!(validate_statement("select * from t where a = 'it''s ?' and b = ?", 1).is_ok());
+
+        // the placeholders and quotes within comments are skipped
+        assert!(
+            validate_statement("select * from t where a = ? -- don't bind ?\n and b = ?", 2)
+                .is_ok()
+        );
+        assert!(
+            validate_statement("select * from t where a = ? # don't bind ?\n and b = ?", 2).is_ok()
+        );
+        assert!(validate_statement(
+            "select * from t /* don't bind ? */ where a = ? and b = ?",
+            2
+        )
+        .is_ok());
+        assert!(validate_statement("select * from t where a = ? -- trailing comment?", 1).is_ok());
+        assert!(validate_statement("select * from t where a = ? /* unterminated ?", 1).is_ok());
+        // `--` without a whitespace after it is two minus signs, and an executable comment is parsed by MySQL
+        assert!(validate_statement("select a--? from t where b = ?", 2).is_ok());
+        assert!(validate_statement("select * from t where a = ? /*!50000 and b = ? */", 2).is_ok());
+        assert!(validate_statement(
+            "select * from t where a = '-- ?' and b = '/* ?' and c = ?",
+            1
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_managed_connection_opts() {
+        let opts = mysql_desc::ConnectionOpts::rds(
+            "lightflus.cluster-abc.us-east-1.rds.amazonaws.com",
+            "iam_user",
+            "token",
+        )
+        .with_database("ci");
+        assert_eq!(
+            opts.host,
+            "lightflus.cluster-abc.us-east-1.rds.amazonaws.com"
+        );
+        assert_eq!(opts.port(), 3306);
+        assert_eq!(opts.ssl_mode(), SslMode::Required);
+        assert_eq!(opts.password, "token");
+        assert_eq!(opts.database, "ci");
+        assert!(matches!(
+            super::ssl_mode(opts.ssl_mode()),
+            MySqlSslMode::Required
+        ));
+
+        let opts = mysql_desc::ConnectionOpts::rds("127.0.0.1:13306", "iam_user", "token");
+        assert_eq!(opts.host, "127.0.0.1");
+        assert_eq!(opts.port(), 13306);
+
+        // an unbracketed IPv6 host has no port
+        let opts = mysql_desc::ConnectionOpts::rds("2001:db8::1", "iam_user", "token");
+        assert_eq!(opts.host, "2001:db8::1");
+        assert_eq!(opts.port(), 3306);
+
+        let opts = mysql_desc::ConnectionOpts::rds("[::1]:13306", "iam_user", "token");
+        assert_eq!(opts.host, "::1");
+        assert_eq!(opts.port(), 13306);
+
+        let opts = mysql_desc::ConnectionOpts::rds("[::1]", "iam_user", "token");
+        assert_eq!(opts.host, "::1");
+        assert_eq!(opts.port(), 3306);
+
+        let opts = mysql_desc::ConnectionOpts::planetscale("aws.connect.psdb.cloud", "u", "p");
+        assert_eq!(opts.ssl_mode(), SslMode::VerifyIdentity);
+        assert_eq!(opts.port(), 3306);
+
+        // the unset mode falls back to the default mode of MySQL clients
+        assert_eq!(
+            mysql_desc::ConnectionOpts::default().ssl_mode(),
+            SslMode::Preferred
+        );
+    }
+
+    #[test]
+    fn test_connect_options_port() {
+        let conn_of = |port| {
+            MysqlConn::from(mysql_desc::ConnectionOpts {
+                host: "localhost".to_string(),
+                port,
+                ..Default::default()
+            })
+        };
+
+        let conn = conn_of(3307);
+        let opts = super::connect_options(&conn.conn_opts).unwrap();
+        assert!(format!("{:?}", opts).contains("port: 3307"), "{:?}", opts);
+
+        // the unset port falls back to the default one
+        let conn = conn_of(0);
+        let opts = super::connect_options(&conn.conn_opts).unwrap();
+        assert!(format!("{:?}", opts).contains("port: 3306"), "{:?}", opts);
+
+        // 65536 would be truncated to 0
+        let conn = conn_of(65536);
+        let err = super::connect_options(&conn.conn_opts).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid mysql port 65536"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_connect_options_extra_params() {
+        let conn_opts = mysql_desc::ConnectionOpts::default()
+            .with_extra_param("sql_mode", "ANSI_QUOTES")
+            .with_extra_param("wait_timeout", "600");
+        assert!(super::connect_options(&conn_opts).is_ok());
+
+        // the names are formatted into the statements, so anything but a plain name is rejected
+        for name in [
+            "",
+            "sql_mode = 'x'; DROP TABLE t; --",
+            "@@sql_mode",
+            "sql mode",
+        ] {
+            let conn_opts = mysql_desc::ConnectionOpts::default().with_extra_param(name, "x");
+            let err = super::connect_options(&conn_opts).unwrap_err();
+            assert!(
+                err.to_string().contains("invalid mysql session variable"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conn_pool_is_lazy_and_shared() {
+        let closed_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let conn_opts = mysql_desc::ConnectionOpts {
+            host: "127.0.0.1".to_string(),
+            port: closed_port as u32,
+            ..Default::default()
+        };
+
+        // no connection is opened until the first query
+        let pool = MysqlConnPool::new(&conn_opts, 1).unwrap();
+        assert_eq!(pool.size(), 0);
+
+        // the invalid statements are rejected before a connection is acquired
+        let shared = pool.clone();
+        let result = tokio::spawn(async move { shared.execute("select ?", vec![]).await })
+            .await
+            .unwrap();
+        assert!(result.is_err());
+        assert_eq!(pool.size(), 0);
+
+        let conn_opts = mysql_desc::ConnectionOpts {
+            port: 65536,
+            ..conn_opts
+        };
+        assert!(MysqlConnPool::new(&conn_opts, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_stream_yields_errors() {
+        use futures_util::StreamExt;
+
+        let closed_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut conn = MysqlConn::from(mysql_desc::ConnectionOpts {
+            host: "127.0.0.1".to_string(),
+            port: closed_port as u32,
+            ..Default::default()
+        });
+
+        // the statement is validated before connecting
+        let rows = conn.fetch("select ?", vec![]).collect::<Vec<_>>().await;
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("1 placeholders, but 0 arguments"));
+
+        // the error of connecting ends the stream
+        let rows = conn.fetch("select 1", vec![]).collect::<Vec<_>>().await;
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].is_err());
+        assert_eq!(conn.pool_size(), 0);
+    }
+
+    /// write a MySQL packet: a 3-byte little-endian payload length, the sequence id and the payload
+    async fn write_packet<S: tokio::io::AsyncWrite + Unpin>(
+        stream: &mut S,
+        sequence_id: u8,
+        payload: &[u8],
+    ) {
+        use tokio::io::AsyncWriteExt;
+
+        let mut packet = (payload.len() as u32).to_le_bytes()[..3].to_vec();
+        packet.push(sequence_id);
+        packet.extend_from_slice(payload);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    /// read a MySQL packet, or None if the stream is closed
+    async fn read_packet<S: tokio::io::AsyncRead + Unpin>(stream: &mut S) -> Option<(u8, Vec<u8>)> {
+        use tokio::io::AsyncReadExt;
+
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await.ok()?;
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await.ok()?;
+        Some((header[3], payload))
+    }
+
+    /// send the initial handshake of a mock MySQL server, and read the handshake response.
+    /// It returns the sequence id and the user name of the response.
+    async fn mock_handshake<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(
+        stream: &mut S,
+    ) -> (u8, String) {
+        // CLIENT_LONG_PASSWORD | CLIENT_CONNECT_WITH_DB | CLIENT_PROTOCOL_41 | CLIENT_TRANSACTIONS | CLIENT_SECURE_CONNECTION
+        // | CLIENT_PLUGIN_AUTH | CLIENT_DEPRECATE_EOF
+        let capabilities: u32 = 0x1 | 0x8 | 0x200 | 0x2000 | 0x8000 | 0x80000 | 0x1000000;
+
+        let mut handshake = vec![0x0a];
+        handshake.extend_from_slice(b"8.0.0\0");
+        handshake.extend_from_slice(&1u32.to_le_bytes());
+        handshake.extend_from_slice(b"abcdefgh\0");
+        handshake.extend_from_slice(&(capabilities as u16).to_le_bytes());
+        // utf8mb4_general_ci
+        handshake.push(45);
+        // SERVER_STATUS_AUTOCOMMIT
+        handshake.extend_from_slice(&2u16.to_le_bytes());
+        handshake.extend_from_slice(&((capabilities >> 16) as u16).to_le_bytes());
+        handshake.push(21);
+        handshake.extend_from_slice(&[0; 10]);
+        handshake.extend_from_slice(b"ijklmnopqrst\0");
+        handshake.extend_from_slice(b"mysql_native_password\0");
+        write_packet(stream, 0, &handshake).await;
+
+        // capabilities, max packet size, collation and 23 reserved bytes precede the user name
+        let (sequence_id, response) = read_packet(stream).await.unwrap();
+        let username = response[32..]
+            .iter()
+            .take_while(|b| **b != 0)
+            .map(|b| *b as char)
+            .collect::<String>();
+        (sequence_id, username)
+    }
+
+    /// a mock MySQL server accepting any user without a password, and replying OK to every command.
+    /// It returns the user name of the handshake response.
+    async fn mock_mysql_server<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(
+        mut stream: S,
+    ) -> String {
+        const OK: [u8; 7] = [0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00];
+        let (sequence_id, username) = mock_handshake(&mut stream).await;
+        write_packet(&mut stream, sequence_id + 1, &OK).await;
+
+        // COM_QUIT
+        while let Some((sequence_id, command)) = read_packet(&mut stream).await {
+            if command.first() == Some(&0x01) {
+                break;
+            }
+            write_packet(&mut stream, sequence_id + 1, &OK).await;
+        }
+        username
+    }
+
+    /// a mock MySQL server denying the access of any user
+    async fn mock_access_denied<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin>(
+        mut stream: S,
+    ) {
+        let (sequence_id, username) = mock_handshake(&mut stream).await;
+        // ER_ACCESS_DENIED_ERROR with SQL state 28000
+        let mut err = vec![0xff];
+        err.extend_from_slice(&1045u16.to_le_bytes());
+        err.extend_from_slice(b"#28000");
+        err.extend_from_slice(format!("Access denied for user '{}'", username).as_bytes());
+        write_packet(&mut stream, sequence_id + 1, &err).await;
+    }
+
+    #[tokio::test]
+    async fn test_reopen_after_close() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            mock_mysql_server(socket).await
+        });
+
+        let conn_opts = mysql_desc::ConnectionOpts {
+            host: "127.0.0.1".to_string(),
+            port: port as u32,
+            username: "lightflus".to_string(),
+            password: "secret".to_string(),
+            ssl_mode: SslMode::Disabled as i32,
+            ..Default::default()
+        };
+        let mut conn = MysqlConn::from(conn_opts.clone());
+        conn.close();
+        assert_eq!(conn.conn_opts, mysql_desc::ConnectionOpts::default());
+
+        conn.reopen(conn_opts.clone());
+        assert_eq!(conn.conn_opts, conn_opts);
+        conn.connect().await.unwrap();
+        assert_eq!(conn.pool_size(), 1);
+
+        // reopening drops the live connection
+        conn.reopen(conn_opts);
+        assert_eq!(conn.pool_size(), 0);
+        assert_eq!(server.await.unwrap(), "lightflus");
+    }
+
+    #[tokio::test]
+    async fn test_connect_over_stream() {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(mock_mysql_server(server));
+
+        let mut conn = MysqlConn::from(mysql_desc::ConnectionOpts {
+            host: "mysql.lightflus.fake".to_string(),
+            port: 3306,
+            username: "lightflus".to_string(),
+            ssl_mode: SslMode::Preferred as i32,
+            ..Default::default()
+        });
+        conn.connect_over_stream(client).await.unwrap();
+        assert_eq!(conn.pool_size(), 1);
+
+        conn.close();
+        assert_eq!(server.await.unwrap(), "lightflus");
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let server_accepted = accepted.clone();
+        // the server isn't ready for the first 2 connections
+        let server = tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                if server_accepted.fetch_add(1, Ordering::SeqCst) >= 2 {
+                    return mock_mysql_server(socket).await;
+                }
+            }
+        });
+
+        let mut conn = MysqlConn::from(mysql_desc::ConnectionOpts {
+            host: "127.0.0.1".to_string(),
+            port: port as u32,
+            username: "lightflus".to_string(),
+            ssl_mode: SslMode::Disabled as i32,
+            ..Default::default()
+        });
+        let policy = RetryPolicy {
+            retry: 5,
+            base_delay_ms: 10,
+            multiplier: 2.0,
+            max_delay_ms: 100,
+            full_jitter: false,
+        };
+        conn.connect_with_retry(&policy).await.unwrap();
+        assert_eq!(conn.pool_size(), 1);
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+
+        conn.close();
+        assert_eq!(server.await.unwrap(), "lightflus");
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_access_denied() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let server_accepted = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                server_accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(mock_access_denied(socket));
+            }
+        });
+
+        let mut conn = MysqlConn::from(mysql_desc::ConnectionOpts {
+            host: "127.0.0.1".to_string(),
+            port: port as u32,
+            username: "lightflus".to_string(),
+            ssl_mode: SslMode::Disabled as i32,
+            ..Default::default()
+        });
+        let policy = RetryPolicy {
+            retry: 5,
+            base_delay_ms: 10,
+            ..Default::default()
+        };
+        let err = conn.connect_with_retry(&policy).await.unwrap_err();
+        assert!(!is_transient_connect_error(&err));
+        assert!(err.to_string().contains("Access denied"));
+        assert_eq!(conn.pool_size(), 0);
+        // the authentication failure isn't retried
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
     }
 }
@@ -0,0 +1,352 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_util::task::AtomicWaker;
+use tokio::sync::{
+    mpsc::error::{SendError, TrySendError},
+    Semaphore,
+};
+
+/// Create a bounded mpsc channel whose capacity can be changed after creation.
+///
+/// [tokio::sync::mpsc::channel] has a fixed capacity. A resizable channel has the same semantics as a bounded mpsc channel,
+/// but [ResizableReceiver::resize] can grow or shrink its capacity at runtime:
+/// - growing the capacity wakes up the senders waiting for a slot immediately;
+/// - shrinking the capacity never drops buffered messages. If the buffer holds more messages than the new capacity,
+///   the exceeded slots are withheld until the messages are received.
+///
+/// The intake of the channel can be closed temporarily by [ResizableReceiver::close_intake]: new sends are rejected,
+/// while the buffered messages can still be received.
+pub fn resizable_channel<T>(capacity: usize) -> (ResizableSender<T>, ResizableReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            capacity,
+            withheld: 0,
+        }),
+        permits: Semaphore::new(capacity),
+        rx_waker: AtomicWaker::new(),
+        senders: AtomicUsize::new(1),
+        intake_closed: AtomicBool::new(false),
+    });
+
+    (
+        ResizableSender {
+            shared: shared.clone(),
+        },
+        ResizableReceiver { shared },
+    )
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    /// number of slots which should be withheld on receiving after the capacity shrinks
+    withheld: usize,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// free slots of the channel
+    permits: Semaphore,
+    rx_waker: AtomicWaker,
+    senders: AtomicUsize,
+    /// whether new sends are rejected by [ResizableReceiver::close_intake]
+    intake_closed: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    fn push(&self, value: T) {
+        self.state.lock().unwrap().queue.push_back(value);
+        self.rx_waker.wake();
+    }
+
+    fn capacity(&self) -> usize {
+        self.state.lock().unwrap().capacity
+    }
+
+    fn is_intake_closed(&self) -> bool {
+        self.intake_closed.load(atomic::Ordering::SeqCst)
+    }
+}
+
+/// The sending-half of [resizable_channel]
+pub struct ResizableSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ResizableSender<T> {
+    /// Send a value, waiting until there is a free slot. An error is returned if the receiver has been dropped or the intake is closed.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.shared.is_intake_closed() {
+            return Err(SendError(value));
+        }
+        match self.shared.permits.acquire().await {
+            // the intake may be closed while waiting, the slot is given back then
+            Ok(_) if self.shared.is_intake_closed() => Err(SendError(value)),
+            Ok(permit) => {
+                permit.forget();
+                self.shared.push(value);
+                Ok(())
+            }
+            Err(_) => Err(SendError(value)),
+        }
+    }
+
+    /// Try to send a value immediately. [TrySendError::Closed] is returned if the intake is closed.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        if self.shared.is_intake_closed() {
+            return Err(TrySendError::Closed(value));
+        }
+        match self.shared.permits.try_acquire() {
+            Ok(permit) => {
+                permit.forget();
+                self.shared.push(value);
+                Ok(())
+            }
+            Err(tokio::sync::TryAcquireError::NoPermits) => Err(TrySendError::Full(value)),
+            Err(tokio::sync::TryAcquireError::Closed) => Err(TrySendError::Closed(value)),
+        }
+    }
+
+    /// current capacity of the channel
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.permits.is_closed()
+    }
+
+    /// Wrap this sender into an [InstrumentedSender] which records how long each send is blocked by a full channel
+    pub fn instrumented(self) -> InstrumentedSender<T> {
+        InstrumentedSender {
+            inner: self,
+            stats: Default::default(),
+        }
+    }
+}
+
+/// Statistics of the sends blocked by a full channel, i.e. the backpressure on the producer side
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SendBlockingStats {
+    /// number of sends which had to wait for a free slot
+    pub blocked_sends: u64,
+    /// total time the blocked sends waited
+    pub total_blocked: Duration,
+    /// the longest time a send waited
+    pub max_blocked: Duration,
+}
+
+/// A [ResizableSender] which records [SendBlockingStats]. The clones share the same stats.
+pub struct InstrumentedSender<T> {
+    inner: ResizableSender<T>,
+    stats: Arc<Mutex<SendBlockingStats>>,
+}
+
+impl<T> InstrumentedSender<T> {
+    /// Send a value, waiting until there is a free slot. The waiting time is recorded if the channel is full.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match self.inner.try_send(value) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Closed(value)) => Err(SendError(value)),
+            Err(TrySendError::Full(value)) => {
+                let start = tokio::time::Instant::now();
+                let result = self.inner.send(value).await;
+                let blocked = start.elapsed();
+
+                let mut stats = self.stats.lock().unwrap();
+                stats.blocked_sends += 1;
+                stats.total_blocked += blocked;
+                stats.max_blocked = stats.max_blocked.max(blocked);
+                result
+            }
+        }
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(value)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub fn stats(&self) -> SendBlockingStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+impl<T> Clone for InstrumentedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+impl<T> Clone for ResizableSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, atomic::Ordering::SeqCst);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for ResizableSender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            self.shared.rx_waker.wake();
+        }
+    }
+}
+
+/// The receiving-half of [resizable_channel]
+pub struct ResizableReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ResizableReceiver<T> {
+    /// Poll to receive the next value. [None] is returned if all senders have been dropped and the buffer is empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.shared.rx_waker.register(cx.waker());
+        let mut state = self.shared.state.lock().unwrap();
+        match state.queue.pop_front() {
+            Some(value) => {
+                if state.withheld > 0 {
+                    state.withheld -= 1;
+                } else {
+                    self.shared.permits.add_permits(1);
+                }
+                Poll::Ready(Some(value))
+            }
+            None if self.shared.senders.load(atomic::Ordering::SeqCst) == 0 => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Poll until a value is buffered, without receiving it. `false` is returned if all senders have been dropped and the buffer is empty.
+    pub fn poll_buffered(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        self.shared.rx_waker.register(cx.waker());
+        if !self.shared.state.lock().unwrap().queue.is_empty() {
+            Poll::Ready(true)
+        } else if self.shared.senders.load(atomic::Ordering::SeqCst) == 0 {
+            Poll::Ready(false)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Receive the next value
+    pub async fn recv(&mut self) -> Option<T> {
+        futures_util::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// number of buffered values
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reject new sends until [ResizableReceiver::reopen_intake] is called. The buffered values can still be received.
+    pub fn close_intake(&self) {
+        self.shared
+            .intake_closed
+            .store(true, atomic::Ordering::SeqCst);
+    }
+
+    pub fn reopen_intake(&self) {
+        self.shared
+            .intake_closed
+            .store(false, atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_intake_closed(&self) -> bool {
+        self.shared.is_intake_closed()
+    }
+
+    /// current capacity of the channel
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    /// Change the capacity of the channel
+    pub fn resize(&mut self, capacity: usize) {
+        let mut state = self.shared.state.lock().unwrap();
+        if capacity > state.capacity {
+            let grown = capacity - state.capacity;
+            let repaid = grown.min(state.withheld);
+            state.withheld -= repaid;
+            self.shared.permits.add_permits(grown - repaid);
+        } else {
+            let mut shrunk = state.capacity - capacity;
+            while shrunk > 0 {
+                match self.shared.permits.try_acquire() {
+                    Ok(permit) => {
+                        permit.forget();
+                        shrunk -= 1;
+                    }
+                    Err(_) => break,
+                }
+            }
+            state.withheld += shrunk;
+        }
+        state.capacity = capacity;
+    }
+}
+
+impl<T> Drop for ResizableReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.permits.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::error::TrySendError;
+
+    use super::resizable_channel;
+
+    #[tokio::test]
+    async fn test_resizable_channel() {
+        let (tx, mut rx) = resizable_channel(2);
+        assert!(tx.try_send(1).is_ok());
+        assert!(tx.try_send(2).is_ok());
+        assert!(matches!(tx.try_send(3), Err(TrySendError::Full(3))));
+
+        rx.resize(3);
+        assert_eq!(tx.capacity(), 3);
+        assert!(tx.try_send(3).is_ok());
+        assert_eq!(rx.len(), 3);
+
+        // shrinking never drops the buffered values
+        rx.resize(1);
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(rx.recv().await, Some(2));
+        assert!(tx.try_send(4).is_err());
+        assert_eq!(rx.recv().await, Some(3));
+        assert!(tx.try_send(4).is_ok());
+        assert!(tx.try_send(5).is_err());
+
+        drop(tx);
+        assert_eq!(rx.recv().await, Some(4));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -8,10 +8,12 @@ use futures_util::{future::Either, Future, FutureExt};
 
 use crate::collections::lang;
 
-pub fn join_all<'a, T, F: Fn(T)>(
+pub mod channel;
+
+pub fn join_all<'a, T, F: FnMut(T)>(
     cx: &mut Context<'_>,
     fut_list: &mut Vec<Pin<Box<dyn Future<Output = T> + Send + 'a>>>,
-    callback: F,
+    mut callback: F,
 ) {
     let mut ready_index = BTreeSet::default();
     if fut_list.is_empty() {
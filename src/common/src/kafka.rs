@@ -67,30 +67,49 @@ impl KafkaProducer {
         if payload.is_empty() {
             Ok(())
         } else {
-            let record = FutureRecord::to(self.topic.as_str())
-                .partition(self.partition)
-                .payload(payload)
-                .key(key);
-            self.producer
-                .send(record, Duration::from_secs(3))
-                .await
-                .map(|(partition, offset)| {
-                    tracing::debug!(
-                        "send message to partition {} with offset {}",
-                        partition,
-                        offset
-                    )
-                })
-                .map_err(|err| KafkaException { err: err.0 })
+            self.send_to(self.topic.as_str(), key, payload).await
         }
     }
 
+    /// send a message to `topic` instead of the topic of this producer
+    pub async fn send_to(
+        &self,
+        topic: &str,
+        key: &[u8],
+        payload: &[u8],
+    ) -> Result<(), KafkaException> {
+        let record = FutureRecord::to(topic)
+            .partition(self.partition)
+            .payload(payload)
+            .key(key);
+        self.producer
+            .send(record, Duration::from_secs(3))
+            .await
+            .map(|(partition, offset)| {
+                tracing::debug!(
+                    "send message to partition {} with offset {}",
+                    partition,
+                    offset
+                )
+            })
+            .map_err(|err| KafkaException { err: err.0 })
+    }
+
     pub fn close(&mut self) {
         self.topic.clear();
         drop(self.partition);
     }
 }
 
+#[tonic::async_trait]
+impl crate::net::gateway::queue::AckPublisher for KafkaProducer {
+    type Error = KafkaException;
+
+    async fn publish(&self, topic: &str, key: &[u8], payload: &[u8]) -> Result<(), Self::Error> {
+        self.send_to(topic, key, payload).await
+    }
+}
+
 pub struct KafkaConsumer {
     consumer: StreamConsumer,
 }
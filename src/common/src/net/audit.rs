@@ -0,0 +1,359 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+};
+
+use proto::common::{Heartbeat, HostAddr};
+
+/// Whether an audited heartbeat is sent or received by this node
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditDirection {
+    Sent,
+    Received,
+}
+
+/// A heartbeat recorded by an [AuditSink]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub direction: AuditDirection,
+    /// the receiver of a sent heartbeat, or the sender of a received one
+    pub host_addr: HostAddr,
+    pub heartbeat_id: u64,
+    /// the timestamp stamped on the heartbeat by its sender, in milliseconds since the epoch
+    pub timestamp: i64,
+    pub task_id: u32,
+}
+
+impl AuditRecord {
+    pub fn sent(host_addr: &HostAddr, heartbeat: &Heartbeat) -> Self {
+        Self::new(AuditDirection::Sent, host_addr, heartbeat)
+    }
+
+    pub fn received(host_addr: &HostAddr, heartbeat: &Heartbeat) -> Self {
+        Self::new(AuditDirection::Received, host_addr, heartbeat)
+    }
+
+    fn new(direction: AuditDirection, host_addr: &HostAddr, heartbeat: &Heartbeat) -> Self {
+        Self {
+            direction,
+            host_addr: host_addr.clone(),
+            heartbeat_id: heartbeat.heartbeat_id,
+            timestamp: heartbeat
+                .timestamp
+                .as_ref()
+                .map(|timestamp| timestamp.seconds * 1000 + timestamp.nanos as i64 / 1_000_000)
+                .unwrap_or_default(),
+            task_id: heartbeat.task_id,
+        }
+    }
+}
+
+/// [AuditSink] keeps a durable, append-only record of the heartbeats for compliance, which is set by [super::HeartbeatSender::with_audit_sink].
+///
+/// Unlike [super::HeartbeatStats], every heartbeat is recorded on its own. The records are appended by an [AuditWriter] on its own thread,
+/// so `append` may block. A failed append is logged and never retried, so a broken sink doesn't stop the heartbeats.
+pub trait AuditSink: Send + Sync {
+    fn append(&self, record: &AuditRecord) -> std::io::Result<()>;
+}
+
+/// [AuditWriter] appends the [AuditRecord]s to an [AuditSink] on a dedicated thread, so that a sink blocking on disk never blocks the async tasks writing to it.
+/// The records are appended in the order they're written. The thread exits once the writer and all its clones are dropped, after the pending records are appended.
+#[derive(Clone, Debug)]
+pub struct AuditWriter {
+    tx: mpsc::Sender<AuditRecord>,
+}
+
+impl AuditWriter {
+    pub fn spawn(sink: Arc<dyn AuditSink>) -> Self {
+        let (tx, rx) = mpsc::channel::<AuditRecord>();
+        std::thread::Builder::new()
+            .name("heartbeat-audit".to_string())
+            .spawn(move || {
+                rx.iter().for_each(|record| {
+                    if let Err(err) = sink.append(&record) {
+                        tracing::warn!(
+                            "failed to audit heartbeat {} [host_addr: {:?}, task_id: {}]: {}",
+                            record.heartbeat_id,
+                            &record.host_addr,
+                            record.task_id,
+                            err,
+                        );
+                    }
+                })
+            })
+            .expect("failed to spawn the heartbeat audit thread");
+        Self { tx }
+    }
+
+    /// Queue `record` to be appended without waiting for it
+    pub fn write(&self, record: AuditRecord) {
+        // the thread only exits after all the writers are dropped
+        let _ = self.tx.send(record);
+    }
+}
+
+/// [FileAuditSinkBuilder] is the config of a [FileAuditSink], e.g. in the config files of the coordinator and the task manager
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileAuditSinkBuilder {
+    /// the path of the audit file
+    pub path: String,
+    /// the max size of a file before it's rotated
+    pub max_bytes: u64,
+    /// the max number of rotated files kept
+    pub max_files: usize,
+}
+
+impl FileAuditSinkBuilder {
+    pub fn build(&self) -> std::io::Result<FileAuditSink> {
+        FileAuditSink::open(&self.path, self.max_bytes, self.max_files)
+    }
+
+    /// open the file sink, and start the [AuditWriter] of it
+    pub fn build_writer(&self) -> std::io::Result<AuditWriter> {
+        self.build().map(|sink| AuditWriter::spawn(Arc::new(sink)))
+    }
+}
+
+/// [FileAuditSink] appends the [AuditRecord]s to a file as json lines, and rotates the file by size.
+///
+/// Once appending a record would grow the file beyond `max_bytes`, the file is renamed to `<path>.1`, the older ones are shifted to `<path>.2` and so on,
+/// and a new file is started. At most `max_files` rotated files are kept, and the oldest one is removed.
+/// A record is never split across files, so a file may exceed `max_bytes` only if it holds a single record.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    /// the current file and its size
+    file: Mutex<(File, u64)>,
+}
+
+impl FileAuditSink {
+    /// Open the audit file at `path`, which is appended to if it exists already
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        max_bytes: u64,
+        max_files: usize,
+    ) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = Self::open_file(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn open_file(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// the path of the rotated file of `index`, from 1 for the latest one
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&self, file: &mut (File, u64)) -> std::io::Result<()> {
+        file.0.sync_all()?;
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for index in (1..self.max_files).rev() {
+                let rotated = self.rotated_path(index);
+                if rotated.exists() {
+                    std::fs::rename(&rotated, self.rotated_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        *file = (Self::open_file(&self.path)?, 0);
+        Ok(())
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn append(&self, record: &AuditRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap();
+        if file.1 > 0 && file.1 + line.len() as u64 > self.max_bytes {
+            self.rotate(&mut file)?;
+        }
+        file.0.write_all(&line)?;
+        file.0.sync_data()?;
+        file.1 += line.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use proto::common::{Heartbeat, HostAddr};
+
+    use crate::net::{gateway::MockRpcGateway, HeartbeatBuilder};
+
+    use super::{AuditDirection, AuditRecord, AuditSink, AuditWriter, FileAuditSink};
+
+    #[derive(Default)]
+    struct RecordingSink {
+        records: Mutex<Vec<AuditRecord>>,
+    }
+
+    impl AuditSink for RecordingSink {
+        fn append(&self, record: &AuditRecord) -> std::io::Result<()> {
+            self.records.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    impl RecordingSink {
+        /// the records appended by the writer thread, after `n` are appended or a second elapses
+        fn wait_for(&self, n: usize) -> Vec<AuditRecord> {
+            for _ in 0..100 {
+                if self.records.lock().unwrap().len() >= n {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            self.records.lock().unwrap().clone()
+        }
+    }
+
+    fn record_of(heartbeat_id: u64) -> AuditRecord {
+        AuditRecord::sent(
+            &HostAddr::default(),
+            &Heartbeat {
+                heartbeat_id,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeats_audited() {
+        let host_addr = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let (gateway, _, mut rx) = MockRpcGateway::with_host_addr(&host_addr, 10, 10);
+        // the failed heartbeats are dispatched, so they're audited as well
+        gateway.fail_next_heartbeats(1);
+        let sink = Arc::new(RecordingSink::default());
+        let mut heartbeat = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        }
+        .build(&host_addr, 7, |_, _, _| gateway.clone())
+        .with_audit_sink(sink.clone());
+
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(3500), &mut heartbeat).await;
+        assert!(result.is_err());
+
+        let records = sink.wait_for(4);
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.heartbeat_id)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(records.iter().all(|record| {
+            record.direction == AuditDirection::Sent
+                && record.host_addr == host_addr
+                && record.task_id == 7
+        }));
+        // the delivered ones are recorded with the timestamp they carry
+        let delivered = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+        assert_eq!(delivered.len(), 3);
+        delivered.iter().for_each(|heartbeat| {
+            assert_eq!(
+                records[heartbeat.heartbeat_id as usize],
+                AuditRecord::sent(&host_addr, heartbeat)
+            );
+            assert!(records[heartbeat.heartbeat_id as usize].timestamp > 0);
+        });
+    }
+
+    #[test]
+    fn test_audit_writer_appends_in_order() {
+        /// blocks on every append, like a file synced to disk
+        struct SlowSink(RecordingSink);
+
+        impl AuditSink for SlowSink {
+            fn append(&self, record: &AuditRecord) -> std::io::Result<()> {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                self.0.append(record)
+            }
+        }
+
+        let sink = Arc::new(SlowSink(RecordingSink::default()));
+        let writer = AuditWriter::spawn(sink.clone());
+        let start = std::time::Instant::now();
+        (0..5).for_each(|heartbeat_id| writer.write(record_of(heartbeat_id)));
+        // the writes never wait for the sink
+        assert!(start.elapsed() < std::time::Duration::from_millis(20));
+
+        let records = sink.0.wait_for(5);
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.heartbeat_id)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_file_audit_sink_rotates_by_size() {
+        let dir = std::env::temp_dir().join(format!("lightflus-audit-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("heartbeats.log");
+        let line_len = serde_json::to_vec(&record_of(0)).unwrap().len() as u64 + 1;
+
+        // each file holds 2 records
+        let sink = FileAuditSink::open(&path, line_len * 2, 2).unwrap();
+        (0..7).for_each(|heartbeat_id| sink.append(&record_of(heartbeat_id)).unwrap());
+
+        let read = |path: &std::path::Path| {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<AuditRecord>(line)
+                        .unwrap()
+                        .heartbeat_id
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(read(&path), vec![6]);
+        assert_eq!(read(&sink.rotated_path(1)), vec![4, 5]);
+        assert_eq!(read(&sink.rotated_path(2)), vec![2, 3]);
+        // the oldest file is removed
+        assert!(!sink.rotated_path(3).exists());
+
+        // a reopened sink appends to the existing file
+        drop(sink);
+        let sink = FileAuditSink::open(&path, line_len * 2, 2).unwrap();
+        sink.append(&record_of(7)).unwrap();
+        sink.append(&record_of(8)).unwrap();
+        assert_eq!(read(&path), vec![8]);
+        assert_eq!(read(&sink.rotated_path(1)), vec![6, 7]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+use std::{fmt::Display, time::Duration};
+
+use proto::common::HostAddr;
+
+use super::{
+    gateway::RpcGateway,
+    retry::{RetryPolicy, RetryingGateway},
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_RPC_TIMEOUT,
+};
+
+/// Error of [ClientConfigBuilder::build] when the config can't make a working client
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClientConfigError {
+    /// the host is empty or the port is zero
+    InvalidAddress(HostAddr),
+    /// every connection would time out before it's established
+    ZeroConnectTimeout,
+    /// every rpc call would time out before it's responded
+    ZeroRpcTimeout,
+}
+
+impl Display for ClientConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientConfigError::InvalidAddress(address) => f.write_fmt(format_args!(
+                "invalid address {:?}, it should have a host and a nonzero port",
+                address
+            )),
+            ClientConfigError::ZeroConnectTimeout => {
+                f.write_str("connect timeout should be nonzero")
+            }
+            ClientConfigError::ZeroRpcTimeout => f.write_str("rpc timeout should be nonzero"),
+        }
+    }
+}
+
+impl std::error::Error for ClientConfigError {}
+
+/// The validated config of an rpc client of a remote node, built by [ClientConfigBuilder]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientConfig {
+    address: HostAddr,
+    connect_timeout: Duration,
+    rpc_timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl ClientConfig {
+    pub fn builder(address: HostAddr) -> ClientConfigBuilder {
+        ClientConfigBuilder::new(address)
+    }
+
+    pub fn address(&self) -> &HostAddr {
+        &self.address
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        self.rpc_timeout
+    }
+
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Build the gateway of the remote node by `f` with the timeouts of this config, retrying its failed calls by the [RetryPolicy].
+    /// Each attempt is bounded by the rpc timeout.
+    pub fn build_gateway<F: FnOnce(&HostAddr, Duration, Duration) -> T, T: RpcGateway>(
+        &self,
+        f: F,
+    ) -> RetryingGateway<T> {
+        RetryingGateway::new(
+            f(&self.address, self.connect_timeout, self.rpc_timeout),
+            self.retry.clone(),
+        )
+        .with_attempt_timeout(self.rpc_timeout)
+    }
+}
+
+/// The builder of [ClientConfig]. The timeouts default to the ones of the other rpc clients, and the retries to [RetryPolicy::default].
+///
+/// ## Example
+/// ```
+/// use common::net::client::ClientConfigBuilder;
+/// use proto::common::HostAddr;
+/// use std::time::Duration;
+///
+/// let config = ClientConfigBuilder::new(HostAddr {
+///     host: "localhost".to_string(),
+///     port: 8970,
+/// })
+/// .rpc_timeout(Duration::from_secs(5))
+/// .build();
+/// assert!(config.is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClientConfigBuilder {
+    address: HostAddr,
+    connect_timeout: Duration,
+    rpc_timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl ClientConfigBuilder {
+    pub fn new(address: HostAddr) -> Self {
+        Self {
+            address,
+            connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            rpc_timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT),
+            retry: Default::default(),
+        }
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
+    /// Set the [RetryPolicy] of the failed calls. A policy of zero retries disables retrying.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn build(self) -> Result<ClientConfig, ClientConfigError> {
+        if !self.address.is_valid() {
+            return Err(ClientConfigError::InvalidAddress(self.address));
+        }
+        if self.connect_timeout.is_zero() {
+            return Err(ClientConfigError::ZeroConnectTimeout);
+        }
+        if self.rpc_timeout.is_zero() {
+            return Err(ClientConfigError::ZeroRpcTimeout);
+        }
+        Ok(ClientConfig {
+            address: self.address,
+            connect_timeout: self.connect_timeout,
+            rpc_timeout: self.rpc_timeout,
+            retry: self.retry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proto::common::HostAddr;
+
+    use crate::net::{
+        gateway::{MockRpcGateway, RpcGateway},
+        retry::RetryPolicy,
+        DEFAULT_CONNECT_TIMEOUT, DEFAULT_RPC_TIMEOUT,
+    };
+
+    use super::{ClientConfig, ClientConfigBuilder, ClientConfigError};
+
+    fn address() -> HostAddr {
+        HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        }
+    }
+
+    #[test]
+    fn test_client_config_build() {
+        let config = ClientConfig::builder(address()).build().unwrap();
+        assert_eq!(config.address(), &address());
+        assert_eq!(
+            config.connect_timeout(),
+            Duration::from_secs(DEFAULT_CONNECT_TIMEOUT)
+        );
+        assert_eq!(
+            config.rpc_timeout(),
+            Duration::from_secs(DEFAULT_RPC_TIMEOUT)
+        );
+        assert_eq!(config.retry(), &RetryPolicy::default());
+
+        let retry = RetryPolicy {
+            retry: 0,
+            ..Default::default()
+        };
+        let config = ClientConfigBuilder::new(address())
+            .connect_timeout(Duration::from_millis(500))
+            .rpc_timeout(Duration::from_secs(10))
+            .retry(retry.clone())
+            .build()
+            .unwrap();
+        assert_eq!(config.connect_timeout(), Duration::from_millis(500));
+        assert_eq!(config.rpc_timeout(), Duration::from_secs(10));
+        assert_eq!(config.retry(), &retry);
+
+        let gateway = config.build_gateway(|addr, connect_timeout, rpc_timeout| {
+            assert_eq!(connect_timeout, Duration::from_millis(500));
+            assert_eq!(rpc_timeout, Duration::from_secs(10));
+            MockRpcGateway::with_host_addr(addr, 10, 10).0
+        });
+        assert_eq!(gateway.get_host_addr(), &address());
+    }
+
+    #[test]
+    fn test_client_config_rejected() {
+        for invalid in [
+            HostAddr {
+                host: "".to_string(),
+                port: 8970,
+            },
+            HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 0,
+            },
+        ] {
+            assert_eq!(
+                ClientConfigBuilder::new(invalid.clone()).build(),
+                Err(ClientConfigError::InvalidAddress(invalid))
+            );
+        }
+
+        assert_eq!(
+            ClientConfigBuilder::new(address())
+                .connect_timeout(Duration::ZERO)
+                .build(),
+            Err(ClientConfigError::ZeroConnectTimeout)
+        );
+        assert_eq!(
+            ClientConfigBuilder::new(address())
+                .rpc_timeout(Duration::ZERO)
+                .build(),
+            Err(ClientConfigError::ZeroRpcTimeout)
+        );
+    }
+}
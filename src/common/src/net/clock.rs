@@ -0,0 +1,66 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// [Clock] is the source of time of the heartbeat and ack components.
+/// It can be replaced by a [ManualClock] to replay recorded traces deterministically.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// [TokioClock] reads the time of the tokio runtime, which can be paused and advanced in tests
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [ManualClock] stands still until it's advanced explicitly. The clones share the same time.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    origin: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed: Default::default(),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// move the clock to `elapsed` after its creation. The clock never goes backwards.
+    pub fn set_elapsed(&self, elapsed: Duration) {
+        let mut current = self.elapsed.lock().unwrap();
+        *current = (*current).max(elapsed);
+    }
+
+    /// the time elapsed since the creation of the clock
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.origin + self.elapsed()
+    }
+}
@@ -3,15 +3,16 @@ use crate::types;
 use crate::types::SingleKV;
 
 use proto::common::DataflowMeta;
-use proto::common::{Dataflow, HostAddr};
+use proto::common::{Dataflow, HealthStatus, Heartbeat, HostAddr};
 
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::hash::{Hash, Hasher};
 use std::time::Duration;
 use std::vec;
 
 use super::gateway::taskmanager::SafeTaskManagerRpcGateway;
+use super::gateway::HealthCheckRpcGateway;
 use super::DEFAULT_TASKMANAGER_PORT;
 
 #[derive(Clone, Eq, PartialEq, Debug, Copy)]
@@ -127,6 +128,26 @@ impl Cluster {
         });
     }
 
+    /// the [membership_digest] of all the workers of the cluster, regardless of their status
+    pub fn membership_digest(&self) -> u64 {
+        let members = self
+            .workers
+            .iter()
+            .map(|worker| worker.host_addr.clone())
+            .collect::<Vec<_>>();
+        membership_digest(&members)
+    }
+
+    /// the [HealthStatus] of the workers which are healthy right now, probed by [probe_healthy]
+    pub async fn probe_healthy_nodes(&self, timeout: Duration) -> HashMap<HostAddr, HealthStatus> {
+        let gateways = self
+            .workers
+            .iter()
+            .map(|worker| worker.get_gateway().clone())
+            .collect::<Vec<_>>();
+        probe_healthy(&gateways, timeout).await
+    }
+
     pub fn split_into_subdataflow(&self, dataflow: &Dataflow) -> HashMap<HostAddr, Dataflow> {
         let mut group = HashMap::<HostAddr, Vec<&DataflowMeta>>::new();
 
@@ -169,6 +190,104 @@ impl Cluster {
     }
 }
 
+/// FNV-1a offset basis and prime, which keep the digest stable across processes and builds, unlike [DefaultHasher]
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// An order-independent digest of the cluster members, which two nodes exchange to detect a split brain cheaply.
+/// The addresses are compared by [HostAddr::normalized] and deduplicated, so different spellings or orders of the same members have the same digest.
+/// The digest is never zero, which means no digest in [Heartbeat::membership_digest].
+pub fn membership_digest(members: &[HostAddr]) -> u64 {
+    let members = members
+        .iter()
+        .map(|member| member.normalized().to_string())
+        .collect::<BTreeSet<_>>();
+    let digest = members.iter().fold(FNV_OFFSET_BASIS, |digest, member| {
+        // the terminating zero separates the members
+        member
+            .bytes()
+            .chain(std::iter::once(0))
+            .fold(digest, |digest, byte| {
+                (digest ^ byte as u64).wrapping_mul(FNV_PRIME)
+            })
+    });
+    digest.max(1)
+}
+
+/// Whether two nodes agree on the cluster members, reported by [check_membership_agreement]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MembershipAgreement {
+    /// both nodes know the same members
+    Agreed,
+    /// the nodes know different members, which may be a split brain
+    Diverged { local: u64, peer: u64 },
+    /// the peer didn't send its digest
+    Unknown,
+}
+
+impl MembershipAgreement {
+    pub fn is_diverged(&self) -> bool {
+        matches!(self, Self::Diverged { .. })
+    }
+}
+
+/// Compare the members known by this node with the digest carried by a heartbeat of a peer. A divergence is logged as a warning.
+pub fn check_membership_agreement(
+    members: &[HostAddr],
+    heartbeat: &Heartbeat,
+) -> MembershipAgreement {
+    if heartbeat.membership_digest == 0 {
+        return MembershipAgreement::Unknown;
+    }
+    let local = membership_digest(members);
+    if local == heartbeat.membership_digest {
+        MembershipAgreement::Agreed
+    } else {
+        tracing::warn!(
+            "cluster membership diverged from peer [task_id: {}, local_digest: {:x}, peer_digest: {:x}]",
+            heartbeat.task_id,
+            local,
+            heartbeat.membership_digest
+        );
+        MembershipAgreement::Diverged {
+            local,
+            peer: heartbeat.membership_digest,
+        }
+    }
+}
+
+/// Probe all nodes concurrently by [HealthCheckRpcGateway::check_health], e.g. before routing work to them, and return the [HealthStatus] of the healthy ones.
+/// A node is healthy if it responds within `timeout` and reports itself alive. The unhealthy nodes are logged.
+pub async fn probe_healthy<T: HealthCheckRpcGateway>(
+    gateways: &[T],
+    timeout: Duration,
+) -> HashMap<HostAddr, HealthStatus> {
+    futures_util::future::join_all(gateways.iter().map(|gateway| async move {
+        let result = match tokio::time::timeout(timeout, gateway.check_health()).await {
+            Ok(result) => result,
+            Err(_) => Err(tonic::Status::deadline_exceeded(format!(
+                "health check not responded within {:?}",
+                timeout
+            ))),
+        };
+        (gateway.get_host_addr(), result)
+    }))
+    .await
+    .into_iter()
+    .filter_map(|(host_addr, result)| match result {
+        Ok(status) if status.alive => Some((host_addr.clone(), status)),
+        Ok(_) => {
+            tracing::warn!("node reports not alive [host_addr: {:?}]", host_addr);
+            None
+        }
+        Err(err) => {
+            tracing::warn!("health check failed [host_addr: {:?}]: {}", host_addr, err);
+            None
+        }
+    })
+    .collect()
+}
+
 #[derive(Clone, serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct NodeBuilder {
     pub host: String,
@@ -458,6 +577,139 @@ mod cluster_tests {
         assert!(node.is_available());
     }
 
+    #[test]
+    fn test_membership_digest() {
+        use super::membership_digest;
+
+        let node = |host: &str, port: u32| HostAddr {
+            host: host.to_string(),
+            port,
+        };
+        let members = vec![node("198.0.0.1", 8080), node("198.0.0.2", 8080)];
+
+        // the order and spelling of the members don't matter
+        let digest = membership_digest(&members);
+        assert_ne!(digest, 0);
+        assert_eq!(
+            digest,
+            membership_digest(&[node("198.0.0.2", 8080), node("198.0.0.1", 8080)])
+        );
+        assert_eq!(
+            digest,
+            membership_digest(&[
+                node(" 198.0.0.2", 8080),
+                node("198.0.0.1", 8080),
+                node("198.0.0.2", 8080)
+            ])
+        );
+
+        // a missing, an extra or a different member diverges
+        assert_ne!(digest, membership_digest(&members[..1]));
+        assert_ne!(
+            digest,
+            membership_digest(&[members.clone(), vec![node("198.0.0.3", 8080)]].concat())
+        );
+        assert_ne!(
+            digest,
+            membership_digest(&[node("198.0.0.1", 8080), node("198.0.0.2", 8081)])
+        );
+        assert_ne!(membership_digest(&[]), 0);
+        assert_ne!(membership_digest(&[]), digest);
+    }
+
+    #[tokio::test]
+    async fn test_check_membership_agreement() {
+        use proto::common::Heartbeat;
+
+        use super::{check_membership_agreement, membership_digest, MembershipAgreement};
+
+        let node = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8080,
+        };
+        let members = vec![node("198.0.0.1"), node("198.0.0.2")];
+        let heartbeat_of = |members: &[HostAddr]| Heartbeat {
+            membership_digest: membership_digest(members),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            check_membership_agreement(
+                &members,
+                &heartbeat_of(&[node("198.0.0.2"), node("198.0.0.1")])
+            ),
+            MembershipAgreement::Agreed
+        );
+        let agreement = check_membership_agreement(&members, &heartbeat_of(&[node("198.0.0.1")]));
+        assert!(agreement.is_diverged());
+        assert_eq!(
+            agreement,
+            MembershipAgreement::Diverged {
+                local: membership_digest(&members),
+                peer: membership_digest(&[node("198.0.0.1")]),
+            }
+        );
+        assert_eq!(
+            check_membership_agreement(&members, &Heartbeat::default()),
+            MembershipAgreement::Unknown
+        );
+
+        let cluster = super::ClusterBuilder {
+            nodes: "198.0.0.2:8080,198.0.0.1:8080".to_string(),
+            rpc_timeout: 3,
+            connect_timeout: 3,
+        }
+        .build();
+        assert_eq!(cluster.membership_digest(), membership_digest(&members));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_probe_healthy() {
+        use std::time::Duration;
+
+        use proto::common::HealthStatus;
+
+        use crate::net::gateway::MockRpcGateway;
+
+        use super::probe_healthy;
+
+        let node = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8080,
+        };
+        let gateways = ["198.0.0.1", "198.0.0.2", "198.0.0.3", "198.0.0.4"]
+            .iter()
+            .map(|host| MockRpcGateway::with_host_addr(&node(host), 10, 10).0)
+            .collect::<Vec<_>>();
+        gateways[0].set_health(Ok(HealthStatus {
+            alive: true,
+            running_subdataflows: 3,
+            ..Default::default()
+        }));
+        gateways[1].set_health(Err(tonic::Status::unavailable("connection refused")));
+        gateways[2].set_health(Ok(HealthStatus {
+            alive: false,
+            ..Default::default()
+        }));
+
+        let healthy = probe_healthy(&gateways, Duration::from_secs(1)).await;
+        assert_eq!(healthy.len(), 2);
+        assert_eq!(healthy[&node("198.0.0.1")].running_subdataflows, 3);
+        assert!(healthy.contains_key(&node("198.0.0.4")));
+
+        // a node recovers
+        gateways[1].set_health(Ok(HealthStatus {
+            alive: true,
+            ..Default::default()
+        }));
+        let healthy = probe_healthy(&gateways, Duration::from_secs(1)).await;
+        assert_eq!(healthy.len(), 3);
+        assert!(healthy.contains_key(&node("198.0.0.2")));
+        assert!(probe_healthy::<MockRpcGateway>(&[], Duration::from_secs(1))
+            .await
+            .is_empty());
+    }
+
     #[tokio::test]
     async fn test_cluster_build() {
         let builder = super::ClusterBuilder {
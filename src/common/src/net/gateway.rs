@@ -1,9 +1,28 @@
-use proto::common::{Ack, Heartbeat, HostAddr, Response};
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use proto::common::{Ack, AckBatchResponse, HealthStatus, Heartbeat, HostAddr, Response};
 use tokio::sync::mpsc;
-use tonic::async_trait;
+use tonic::{
+    async_trait,
+    body::BoxBody,
+    codegen::http,
+    transport::{Body, Channel, ClientTlsConfig},
+};
+use tower::{util::BoxCloneService, ServiceExt};
+
+use super::{
+    reconnect::ReconnectCoordinator, resolver, resolver::Resolver, tenant, trace, KeepaliveOptions,
+};
 
 /// Rpc Gateway trait. All Rpc clients should implement this trait
-pub trait RpcGateway: Unpin {
+pub trait RpcGateway: Unpin + Send + Sync {
     fn get_host_addr(&self) -> &HostAddr;
 }
 
@@ -11,18 +30,223 @@ pub trait RpcGateway: Unpin {
 #[async_trait]
 pub trait ReceiveAckRpcGateway: RpcGateway {
     async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status>;
+
+    /// Send a batch of acks in a single call if the gateway supports it. The result of each ack is returned in the same order as `reqs`.
+    /// By default, the acks are sent one by one by [ReceiveAckRpcGateway::receive_ack], and every ack is tried even if the previous ones failed.
+    async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+        let mut results = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            results.push(self.receive_ack(req).await);
+        }
+        results
+    }
 }
 
 /// Trait for [RpcGateway] that must implements receive_heartbeat rpc call
 #[async_trait]
 pub trait ReceiveHeartbeatRpcGateway: RpcGateway {
     async fn receive_heartbeat(&self, request: Heartbeat) -> Result<Response, tonic::Status>;
+
+    /// How long [ReceiveHeartbeatRpcGateway::receive_heartbeat] may take if a single call is bounded by `rpc_timeout`,
+    /// which the sender awaits the heartbeat for. A gateway retrying the heartbeats extends it to cover all the attempts and the backoffs between them.
+    fn heartbeat_budget(&self, rpc_timeout: Duration) -> Duration {
+        rpc_timeout
+    }
+}
+
+/// Trait for [RpcGateway] that must implements check_health rpc call, which asks the remote node whether it's alive right now without any heartbeat
+#[async_trait]
+pub trait HealthCheckRpcGateway: RpcGateway {
+    async fn check_health(&self) -> Result<HealthStatus, tonic::Status>;
+}
+
+/// Trait for [RpcGateway] that can establish its connection eagerly instead of on the first rpc call
+#[async_trait]
+pub trait ConnectRpcGateway: RpcGateway {
+    async fn connect(&self) -> Result<(), tonic::transport::Error>;
+}
+
+/// create the [tonic::transport::Endpoint] of a remote node with the connect timeout, the optional http2 keepalive settings and the optional TLS config.
+/// The endpoint of a node connected over TLS has the `https` scheme.
+pub(crate) fn endpoint(
+    host_addr: &HostAddr,
+    connect_timeout: std::time::Duration,
+    keepalive: Option<&KeepaliveOptions>,
+    tls: Option<&ClientTlsConfig>,
+) -> Result<tonic::transport::Endpoint, tonic::transport::Error> {
+    let uri = if tls.is_some() {
+        host_addr.as_secure_uri()
+    } else {
+        host_addr.as_uri()
+    };
+    let mut endpoint = tonic::transport::Endpoint::new(uri)?.connect_timeout(connect_timeout);
+    if let Some(keepalive) = keepalive {
+        endpoint = keepalive.apply(endpoint);
+    }
+    if let Some(tls) = tls {
+        endpoint = endpoint.tls_config(tls.clone())?;
+    }
+    Ok(endpoint)
+}
+
+/// create a lazy [tonic::transport::Channel] which connects to the remote node on the first rpc call.
+/// The host is resolved by the custom resolver if there's one, otherwise by the system resolver.
+pub(crate) fn lazy_channel(
+    host_addr: &HostAddr,
+    connect_timeout: std::time::Duration,
+    keepalive: Option<&KeepaliveOptions>,
+    tls: Option<&ClientTlsConfig>,
+    resolver: Option<&Arc<dyn Resolver>>,
+) -> tonic::transport::Channel {
+    let endpoint =
+        endpoint(host_addr, connect_timeout, keepalive, tls).expect("parse endpoint failed");
+    match resolver {
+        Some(resolver) => {
+            endpoint.connect_with_connector_lazy(resolver::connector(resolver.clone()))
+        }
+        None => endpoint.connect_lazy(),
+    }
+}
+
+/// connect to the endpoint eagerly. The host is resolved by the custom resolver if there's one, otherwise by the system resolver.
+pub(crate) async fn connect_channel(
+    endpoint: tonic::transport::Endpoint,
+    resolver: Option<&Arc<dyn Resolver>>,
+) -> Result<tonic::transport::Channel, tonic::transport::Error> {
+    match resolver {
+        Some(resolver) => {
+            endpoint
+                .connect_with_connector(resolver::connector(resolver.clone()))
+                .await
+        }
+        None => endpoint.connect().await,
+    }
+}
+
+/// The channel the rpc clients of the gateways are built on: the [Channel] to the remote node, optionally wrapped by tower layers.
+/// The errors of the layers are reported as [tonic::Status], like the ones of tonic interceptors.
+pub type GatewayChannel =
+    BoxCloneService<http::Request<BoxBody>, http::Response<Body>, tonic::Status>;
+
+/// [ChannelFactory] wraps the [Channel] to a remote node before a gateway builds its rpc client on it,
+/// e.g. to insert tower layers like rate limiting, auth interceptors or metrics.
+///
+/// It's given the address of the remote node and the channel configured by the gateway, i.e. with its timeouts, keepalive, TLS and resolver.
+#[derive(Clone)]
+pub struct ChannelFactory(Arc<ChannelFactoryFn>);
+
+type ChannelFactoryFn = dyn Fn(&HostAddr, Channel) -> GatewayChannel + Send + Sync;
+
+impl ChannelFactory {
+    pub fn new<F: Fn(&HostAddr, Channel) -> GatewayChannel + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl Debug for ChannelFactory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ChannelFactory")
+    }
+}
+
+/// box the channel into a [GatewayChannel] without any layer
+pub fn box_channel(channel: Channel) -> GatewayChannel {
+    BoxCloneService::new(channel.map_err(|err| tonic::Status::from_error(Box::new(err))))
+}
+
+/// wrap the channel by the factory if there's one, otherwise box it as it is
+pub(crate) fn gateway_channel(
+    host_addr: &HostAddr,
+    channel: Channel,
+    factory: Option<&ChannelFactory>,
+) -> GatewayChannel {
+    match factory {
+        Some(factory) => (factory.0)(host_addr, channel),
+        None => box_channel(channel),
+    }
+}
+
+/// Whether the rpc call failed to reach the remote node, e.g. the connection is refused or reset, rather than being answered with an error
+pub fn is_connection_error(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unavailable
+        || std::error::Error::source(status)
+            .map(|source| source.is::<tonic::transport::Error>())
+            .unwrap_or_default()
+}
+
+/// The cached rpc client of a gateway, created by `new_client` if there's none. The client is cloned out of the lock,
+/// so that an rpc call doesn't hold the lock while it's awaited: the clones share the same channel, and the calls go on concurrently.
+///
+/// If the gateway shares a [ReconnectCoordinator], a missing client is only (re)created once a slot is acquired from it.
+/// The lock is held while waiting for the slot, so the concurrent calls of the gateway take a single slot and reuse the new client.
+pub(crate) async fn cached_client<C: Clone>(
+    client: &tokio::sync::Mutex<Option<C>>,
+    reconnect: Option<&ReconnectCoordinator>,
+    new_client: impl FnOnce() -> C,
+) -> C {
+    let mut client = client.lock().await;
+    if client.is_none() {
+        if let Some(reconnect) = reconnect {
+            reconnect.acquire().await;
+        }
+    }
+    client.get_or_insert_with(new_client).clone()
+}
+
+/// Drop the cached rpc client of a gateway if `result` failed by a connection error, so that the next call reconnects on a new channel.
+/// Otherwise the client, and the connection under it, is reused by the following calls. The lock is only taken if the client is dropped.
+// the result of the rpc call is passed through as is
+#[allow(clippy::result_large_err)]
+pub(crate) async fn release_on_connection_error<C, R>(
+    client: &tokio::sync::Mutex<Option<C>>,
+    host_addr: &HostAddr,
+    result: Result<R, tonic::Status>,
+) -> Result<R, tonic::Status> {
+    if let Err(status) = result.as_ref() {
+        if is_connection_error(status) && client.lock().await.take().is_some() {
+            tracing::debug!(
+                "connection to {:?} is dropped, reconnect on the next rpc call: {}",
+                host_addr,
+                status
+            );
+        }
+    }
+    result
+}
+
+/// the result of each of the `len` acks sent by a batch call. If the call fails as a whole, every ack fails by its status.
+// the acks fail by the same tonic::Status as the rpc calls of the gateways
+#[allow(clippy::result_large_err)]
+pub(crate) fn ack_batch_results(
+    len: usize,
+    result: Result<AckBatchResponse, tonic::Status>,
+) -> Vec<Result<Response, tonic::Status>> {
+    match result {
+        Ok(response) => response.into_results(len),
+        Err(status) => (0..len)
+            .map(|_| Err(tonic::Status::new(status.code(), status.message())))
+            .collect(),
+    }
+}
+
+/// create an outgoing [tonic::Request] carrying the active trace context and cluster id
+pub(crate) fn new_request<T>(message: T) -> tonic::Request<T> {
+    let mut request = trace::new_request(message);
+    tenant::inject(request.metadata_mut());
+    request
 }
 
 #[derive(Clone)]
 pub struct MockRpcGateway {
     ack_channel: mpsc::Sender<Ack>,
     heartbeat_channel: mpsc::Sender<Heartbeat>,
+    host_addr: HostAddr,
+    /// number of the upcoming heartbeats to fail, shared by the clones
+    heartbeat_failures: Arc<AtomicUsize>,
+    /// the size of each batch received by [ReceiveAckRpcGateway::receive_acks], shared by the clones
+    ack_batches: Arc<Mutex<Vec<usize>>>,
+    /// the result of [HealthCheckRpcGateway::check_health], shared by the clones
+    health: Arc<Mutex<Result<HealthStatus, tonic::Status>>>,
 }
 
 unsafe impl Send for MockRpcGateway {}
@@ -37,17 +261,33 @@ impl ReceiveAckRpcGateway for MockRpcGateway {
             .map(|_| Response::ok())
             .map_err(|err| tonic::Status::data_loss(err.to_string()))
     }
+
+    async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+        self.ack_batches.lock().unwrap().push(reqs.len());
+        let mut results = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            results.push(self.receive_ack(req).await);
+        }
+        results
+    }
 }
 
 impl RpcGateway for MockRpcGateway {
     fn get_host_addr(&self) -> &HostAddr {
-        todo!()
+        &self.host_addr
     }
 }
 
 #[async_trait]
 impl ReceiveHeartbeatRpcGateway for MockRpcGateway {
     async fn receive_heartbeat(&self, request: Heartbeat) -> Result<Response, tonic::Status> {
+        let failing = self
+            .heartbeat_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            return Err(tonic::Status::unavailable("mock heartbeat failure"));
+        }
         self.heartbeat_channel
             .send(request)
             .await
@@ -56,10 +296,33 @@ impl ReceiveHeartbeatRpcGateway for MockRpcGateway {
     }
 }
 
+#[async_trait]
+impl HealthCheckRpcGateway for MockRpcGateway {
+    async fn check_health(&self) -> Result<HealthStatus, tonic::Status> {
+        self.health.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ConnectRpcGateway for MockRpcGateway {
+    async fn connect(&self) -> Result<(), tonic::transport::Error> {
+        Ok(())
+    }
+}
+
 impl MockRpcGateway {
     pub fn new(
         ack_buf_size: usize,
         heartbeat_buf_size: usize,
+    ) -> (Self, mpsc::Receiver<Ack>, mpsc::Receiver<Heartbeat>) {
+        Self::with_host_addr(&HostAddr::default(), ack_buf_size, heartbeat_buf_size)
+    }
+
+    /// create a [MockRpcGateway] which reports `host_addr` as its remote address
+    pub fn with_host_addr(
+        host_addr: &HostAddr,
+        ack_buf_size: usize,
+        heartbeat_buf_size: usize,
     ) -> (Self, mpsc::Receiver<Ack>, mpsc::Receiver<Heartbeat>) {
         let (ack_tx, ack_rx) = mpsc::channel(ack_buf_size);
         let (heartbeat_tx, heartbeat_rx) = mpsc::channel(heartbeat_buf_size);
@@ -67,11 +330,99 @@ impl MockRpcGateway {
             Self {
                 ack_channel: ack_tx,
                 heartbeat_channel: heartbeat_tx,
+                host_addr: host_addr.clone(),
+                heartbeat_failures: Default::default(),
+                ack_batches: Default::default(),
+                health: Arc::new(Mutex::new(Ok(HealthStatus {
+                    alive: true,
+                    ..Default::default()
+                }))),
             },
             ack_rx,
             heartbeat_rx,
         )
     }
+
+    /// fail the next `n` heartbeats received by this gateway or its clones, then deliver the following ones
+    pub fn fail_next_heartbeats(&self, n: usize) {
+        self.heartbeat_failures.store(n, Ordering::SeqCst)
+    }
+
+    /// the size of each batch of acks received by this gateway or its clones, in the order they're received
+    pub fn ack_batches(&self) -> Vec<usize> {
+        self.ack_batches.lock().unwrap().clone()
+    }
+
+    /// answer the health checks of this gateway or its clones by `health` from now on. The node is alive by default.
+    pub fn set_health(&self, health: Result<HealthStatus, tonic::Status>) {
+        *self.health.lock().unwrap() = health
+    }
+}
+
+/// Gateways which deliver acks over a message queue, e.g. Kafka, instead of direct rpc calls
+pub mod queue {
+    use prost::Message;
+    use proto::common::{Ack, HostAddr, Response};
+    use tonic::async_trait;
+
+    use super::{ReceiveAckRpcGateway, RpcGateway};
+
+    /// A client of message queue which [QueueAckGateway] publishes acks by
+    #[async_trait]
+    pub trait AckPublisher: Unpin + Send + Sync {
+        type Error: std::fmt::Display;
+
+        async fn publish(&self, topic: &str, key: &[u8], payload: &[u8])
+            -> Result<(), Self::Error>;
+    }
+
+    /// [QueueAckGateway] publishes each ack to a configured topic instead of calling the receive_ack rpc of the remote node.
+    ///
+    /// The payload is the protobuf-encoded [Ack], and the key is the protobuf-encoded execution id of the ack,
+    /// so that acks of the same execution keep their order in one partition.
+    /// `host_addr` is the logical receiver of the acks, which the consumer of the topic should act for.
+    #[derive(Clone, Debug)]
+    pub struct QueueAckGateway<P: AckPublisher> {
+        publisher: P,
+        topic: String,
+        host_addr: HostAddr,
+    }
+
+    impl<P: AckPublisher> QueueAckGateway<P> {
+        pub fn new(host_addr: &HostAddr, topic: &str, publisher: P) -> Self {
+            Self {
+                publisher,
+                topic: topic.to_string(),
+                host_addr: host_addr.clone(),
+            }
+        }
+
+        pub fn topic(&self) -> &str {
+            &self.topic
+        }
+    }
+
+    impl<P: AckPublisher> RpcGateway for QueueAckGateway<P> {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    #[async_trait]
+    impl<P: AckPublisher> ReceiveAckRpcGateway for QueueAckGateway<P> {
+        async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+            let key = req
+                .execution_id
+                .as_ref()
+                .map(|execution_id| execution_id.encode_to_vec())
+                .unwrap_or_default();
+            self.publisher
+                .publish(&self.topic, &key, &req.encode_to_vec())
+                .await
+                .map(|_| Response::ok())
+                .map_err(|err| tonic::Status::unavailable(err.to_string()))
+        }
+    }
 }
 
 pub mod taskmanager {
@@ -80,8 +431,8 @@ pub mod taskmanager {
     use prost::Message;
     use proto::{
         common::{
-            Ack, Heartbeat, HostAddr, KeyedDataEvent, KeyedEventSet, ResourceId, Response,
-            SubDataflowStates,
+            Ack, AckBatch, HealthCheckRequest, HealthStatus, Heartbeat, HostAddr, KeyedDataEvent,
+            KeyedEventSet, ResourceId, Response, SubDataflowStates,
         },
         taskmanager::{
             task_manager_api_client::TaskManagerApiClient, BatchSendEventsToOperatorResponse,
@@ -90,24 +441,46 @@ pub mod taskmanager {
         },
     };
     use tokio::sync::Mutex;
-    use tonic::{async_trait, transport::Channel};
-
-    use crate::net::DEFAULT_RPC_TIMEOUT;
+    use tonic::{async_trait, transport::ClientTlsConfig};
+
+    use crate::net::{
+        cert::{CertError, CertExpiryCheck, CertExpiryPolicy, CertStatus},
+        reconnect::ReconnectCoordinator,
+        resolver::Resolver,
+        retry::{is_undelivered, RetryPolicy},
+        KeepaliveOptions, TlsOptions, DEFAULT_RPC_TIMEOUT,
+    };
 
     use super::{
-        super::DEFAULT_CONNECT_TIMEOUT, ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway,
-        RpcGateway,
+        super::DEFAULT_CONNECT_TIMEOUT, ack_batch_results, cached_client, connect_channel,
+        endpoint, gateway_channel, lazy_channel, new_request, release_on_connection_error,
+        ChannelFactory, ConnectRpcGateway, GatewayChannel, HealthCheckRpcGateway,
+        ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway,
     };
 
     /// A thread-safe RpcGateway wrapper for [`TaskManagerApiClient`]. It's also reponsible for concurrency control of client-side gRPC.
-    /// [`SafeTaskWorkerRpcGateway`] ensures only one thread can call [`TaskManagerApiClient`] at the same time. Requests have to be sent FIFO, without any fault tolerance.
+    /// Concurrent calls of [`SafeTaskWorkerRpcGateway`] share the same [`TaskManagerApiClient`] without waiting for each other, as the client is cloned out of its lock for each call.
     /// [`SafeTaskWorkerRpcGateway`] can be shared in different threads safely.
+    ///
+    /// The client is created on the first rpc call, or by [ConnectRpcGateway::connect], and reused by the following calls.
+    /// It's dropped once a call fails by a connection error, and the next call reconnects within the connect timeout,
+    /// after a slot is acquired from the [ReconnectCoordinator] set by [Self::with_reconnect_coordinator].
+    /// Each call is bounded by the rpc timeout.
     #[derive(Debug, Clone)]
     pub struct SafeTaskManagerRpcGateway {
-        inner: Arc<Mutex<Option<TaskManagerApiClient<Channel>>>>,
+        inner: Arc<Mutex<Option<TaskManagerApiClient<GatewayChannel>>>>,
         host_addr: HostAddr,
         connect_timeout: Duration,
         rpc_timeout: Duration,
+        keepalive: Option<KeepaliveOptions>,
+        tls: Option<ClientTlsConfig>,
+        /// the SNI of [TlsOptions::sni_override], which the certificate probe of [CertExpiryCheck] sends as well
+        sni_override: Option<String>,
+        resolver: Option<Arc<dyn Resolver>>,
+        channel_factory: Option<ChannelFactory>,
+        cert_expiry: Option<CertExpiryCheck>,
+        retry: Option<RetryPolicy>,
+        reconnect: Option<ReconnectCoordinator>,
     }
 
     unsafe impl Send for SafeTaskManagerRpcGateway {}
@@ -124,51 +497,153 @@ pub mod taskmanager {
     #[async_trait]
     impl ReceiveAckRpcGateway for SafeTaskManagerRpcGateway {
         async fn receive_ack(&self, request: Ack) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-                )
-            });
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_with(|| self.try_receive_ack(request.clone()), is_undelivered)
+                        .await
+                }
+                None => self.try_receive_ack(request).await,
+            }
+        }
 
-            inner
-                .receive_ack(tonic::Request::new(request))
-                .await
-                .map(|resp| resp.into_inner())
+        async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_acks(reqs, |acks| self.try_receive_acks(acks))
+                        .await
+                }
+                None => self.try_receive_acks(reqs).await,
+            }
         }
     }
 
     #[async_trait]
     impl ReceiveHeartbeatRpcGateway for SafeTaskManagerRpcGateway {
         async fn receive_heartbeat(&self, request: Heartbeat) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-                )
-            });
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_within(self.rpc_timeout, || {
+                            self.try_receive_heartbeat(request.clone())
+                        })
+                        .await
+                }
+                None => self.try_receive_heartbeat(request).await,
+            }
+        }
 
-            inner
-                .receive_heartbeat(tonic::Request::new(request))
+        fn heartbeat_budget(&self, rpc_timeout: Duration) -> Duration {
+            match self.retry.as_ref() {
+                Some(policy) => policy.budget(self.rpc_timeout),
+                None => rpc_timeout,
+            }
+        }
+    }
+
+    impl SafeTaskManagerRpcGateway {
+        async fn try_receive_ack(&self, request: Ack) -> Result<Response, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+
+            let mut request = new_request(request);
+            request.set_timeout(self.rpc_timeout);
+
+            let result = inner
+                .receive_ack(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
+        }
+
+        async fn try_receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+            let len = reqs.len();
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+
+            let mut request = new_request(AckBatch { acks: reqs });
+            request.set_timeout(self.rpc_timeout);
+
+            let result = inner
+                .receive_acks(request)
+                .await
+                .map(|resp| resp.into_inner());
+            ack_batch_results(
+                len,
+                release_on_connection_error(&self.inner, &self.host_addr, result).await,
+            )
+        }
+
+        async fn try_receive_heartbeat(
+            &self,
+            request: Heartbeat,
+        ) -> Result<Response, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+
+            let mut request = new_request(request);
+            request.set_timeout(self.rpc_timeout);
+
+            let result = inner
+                .receive_heartbeat(request)
+                .await
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
+        }
+    }
+
+    #[async_trait]
+    impl HealthCheckRpcGateway for SafeTaskManagerRpcGateway {
+        async fn check_health(&self) -> Result<HealthStatus, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+
+            let mut request = new_request(HealthCheckRequest {});
+            request.set_timeout(self.rpc_timeout);
+
+            let result = inner
+                .check_health(request)
+                .await
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
+        }
+    }
+
+    #[async_trait]
+    impl ConnectRpcGateway for SafeTaskManagerRpcGateway {
+        async fn connect(&self) -> Result<(), tonic::transport::Error> {
+            let channel = connect_channel(
+                endpoint(
+                    &self.host_addr,
+                    self.connect_timeout,
+                    self.keepalive.as_ref(),
+                    self.tls.as_ref(),
+                )?,
+                self.resolver.as_ref(),
+            )
+            .await?;
+            let client = TaskManagerApiClient::new(gateway_channel(
+                &self.host_addr,
+                channel,
+                self.channel_factory.as_ref(),
+            ));
+            *self.inner.lock().await = Some(client);
+            if self.tls.is_some() && self.cert_expiry.is_some() {
+                // the result is logged and kept by cert_status, the callers refusing the node check it by check_cert_expiry
+                let _ = self.check_cert_expiry().await;
+            }
+            Ok(())
         }
     }
 
     impl SafeTaskManagerRpcGateway {
         pub fn new(host_addr: &HostAddr) -> Self {
-            let client = TaskManagerApiClient::with_connection_timeout(
-                host_addr.as_uri(),
+            Self::with_timeout(
+                host_addr,
                 Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-            );
-            Self {
-                inner: Arc::new(tokio::sync::Mutex::new(Some(client))),
-                host_addr: host_addr.clone(),
-                connect_timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-                rpc_timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT),
-            }
+                Duration::from_secs(DEFAULT_RPC_TIMEOUT),
+            )
         }
 
         pub fn with_timeout(
@@ -176,69 +651,154 @@ pub mod taskmanager {
             connect_timeout: Duration,
             rpc_timeout: Duration,
         ) -> Self {
-            let client =
-                TaskManagerApiClient::with_connection_timeout(host_addr.as_uri(), connect_timeout);
             Self {
-                inner: Arc::new(tokio::sync::Mutex::new(Some(client))),
+                inner: Default::default(),
                 host_addr: host_addr.clone(),
                 connect_timeout,
                 rpc_timeout,
+                keepalive: None,
+                tls: None,
+                sni_override: None,
+                resolver: None,
+                channel_factory: None,
+                cert_expiry: None,
+                retry: None,
+                reconnect: None,
             }
         }
 
+        /// Send http2 keepalive pings on the channel, so that a half-open connection is detected before the next rpc call
+        pub fn with_keepalive(mut self, keepalive: KeepaliveOptions) -> Self {
+            self.keepalive = Some(keepalive);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Connect to the remote node over TLS. The certificate expiry is probed with the host of the remote node as SNI,
+        /// use [Self::with_tls_options] to probe with [TlsOptions::sni_override] as well.
+        pub fn with_tls(mut self, tls: ClientTlsConfig) -> Self {
+            self.tls = Some(tls);
+            self.sni_override = None;
+            self.inner = Default::default();
+            self
+        }
+
+        /// Connect to the remote node over TLS by `options`. It fails if the CA certificate can't be read.
+        pub fn with_tls_options(mut self, options: &TlsOptions) -> std::io::Result<Self> {
+            self = self.with_tls(options.client_tls_config()?);
+            self.sni_override = options.sni_override.clone();
+            Ok(self)
+        }
+
+        /// Retry the failed heartbeats and acks by `policy`. The acks are only retried if they're undelivered by [is_undelivered], so they're never duplicated.
+        pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+            self.retry = Some(policy);
+            self
+        }
+
+        /// Stagger the reconnects of the gateway with the other gateways sharing `coordinator`, see [ReconnectCoordinator]
+        pub fn with_reconnect_coordinator(mut self, coordinator: ReconnectCoordinator) -> Self {
+            self.reconnect = Some(coordinator);
+            self
+        }
+
+        /// Resolve the host of the remote node by a custom resolver instead of the system resolver
+        pub fn with_resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+            self.resolver = Some(resolver);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Build the rpc client on the channel wrapped by `factory`, e.g. to insert tower layers
+        pub fn with_channel_factory(mut self, factory: ChannelFactory) -> Self {
+            self.channel_factory = Some(factory);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Check the certificate expiry of the remote node by `policy` when the gateway is connected by [ConnectRpcGateway::connect]
+        pub fn with_cert_expiry_check(mut self, policy: CertExpiryPolicy) -> Self {
+            self.cert_expiry = Some(CertExpiryCheck::new(policy));
+            self
+        }
+
+        /// The certificate expiry of the remote node by the latest check. It's [None] if the gateway isn't connected over TLS or the certificate hasn't been checked.
+        pub fn cert_status(&self) -> Option<CertStatus> {
+            self.tls.as_ref()?;
+            self.cert_expiry.as_ref().and_then(CertExpiryCheck::status)
+        }
+
+        /// Check the certificate expiry of the remote node now, by the default [CertExpiryPolicy] if none is set by [Self::with_cert_expiry_check]
+        pub async fn check_cert_expiry(&self) -> Result<CertStatus, CertError> {
+            if self.tls.is_none() {
+                return Err(CertError::NotTls);
+            }
+            self.cert_expiry
+                .clone()
+                .unwrap_or_default()
+                .check(
+                    &self.host_addr,
+                    self.sni_override.as_deref(),
+                    self.resolver.as_ref(),
+                    self.connect_timeout,
+                )
+                .await
+        }
+
+        fn new_client(&self) -> TaskManagerApiClient<GatewayChannel> {
+            TaskManagerApiClient::new(gateway_channel(
+                &self.host_addr,
+                lazy_channel(
+                    &self.host_addr,
+                    self.connect_timeout,
+                    self.keepalive.as_ref(),
+                    self.tls.as_ref(),
+                    self.resolver.as_ref(),
+                ),
+                self.channel_factory.as_ref(),
+            ))
+        }
+
         pub async fn send_event_to_operator(
             &self,
             event: KeyedDataEvent,
         ) -> Result<SendEventToOperatorResponse, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    self.connect_timeout,
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(event);
             request.set_timeout(self.rpc_timeout);
 
-            inner
+            let result = inner
                 .send_event_to_operator(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub async fn stop_dataflow(
             &self,
             job_id: ResourceId,
         ) -> Result<StopDataflowResponse, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    self.connect_timeout,
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(job_id);
             request.set_timeout(self.rpc_timeout);
 
-            inner
+            let result = inner
                 .stop_dataflow(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub async fn create_sub_dataflow(
             &self,
             req: CreateSubDataflowRequest,
         ) -> Result<CreateSubDataflowResponse, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    self.connect_timeout,
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(req);
             request.set_timeout(self.rpc_timeout);
@@ -247,9 +807,7 @@ pub mod taskmanager {
                 .create_sub_dataflow(request)
                 .await
                 .map(|resp| resp.into_inner());
-
-            drop(guard);
-            result
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub fn close(&mut self) {
@@ -261,42 +819,34 @@ pub mod taskmanager {
             &self,
             req: KeyedEventSet,
         ) -> Result<BatchSendEventsToOperatorResponse, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    self.connect_timeout,
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(req);
             request.set_timeout(self.rpc_timeout);
 
-            inner
+            let result = inner
                 .batch_send_events_to_operator(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub async fn get_sub_dataflow(
             &self,
             req: ResourceId,
         ) -> Result<SubDataflowStates, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                TaskManagerApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    self.connect_timeout,
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(req);
             request.set_timeout(self.rpc_timeout);
 
-            inner
+            let result = inner
                 .get_sub_dataflow(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
     }
 
@@ -376,26 +926,49 @@ pub mod coordinator {
     use std::{sync::Arc, time::Duration};
 
     use tokio::sync::Mutex;
-    use tonic::async_trait;
+    use tonic::{async_trait, transport::ClientTlsConfig};
 
     use proto::{
-        common::{Ack, Dataflow, DataflowStates, Heartbeat, HostAddr, ResourceId, Response},
+        common::{
+            Ack, AckBatch, Dataflow, DataflowStates, Heartbeat, HostAddr, ResourceId, Response,
+        },
         coordinator::{coordinator_api_client::CoordinatorApiClient, GetDataflowRequest},
     };
 
-    use crate::net::{DEFAULT_CONNECT_TIMEOUT, DEFAULT_RPC_TIMEOUT};
+    use crate::net::{
+        cert::{CertError, CertExpiryCheck, CertExpiryPolicy, CertStatus},
+        reconnect::ReconnectCoordinator,
+        resolver::Resolver,
+        retry::{is_undelivered, RetryPolicy},
+        KeepaliveOptions, TlsOptions, DEFAULT_CONNECT_TIMEOUT, DEFAULT_RPC_TIMEOUT,
+    };
 
-    use super::{ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway};
+    use super::{
+        ack_batch_results, cached_client, connect_channel, endpoint, gateway_channel, lazy_channel,
+        new_request, release_on_connection_error, ChannelFactory, ConnectRpcGateway,
+        GatewayChannel, ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway,
+    };
 
     /// A thread-safe RpcGateway wrapper for [`CoordinatorApiClient`]. It's also reponsible for concurrency control of client-side gRPC.
-    /// [`SafeCoordinatorRpcGateway`] ensures only one thread can call [`CoordinatorApiClient`] at the same time. Requests have to be sent FIFO, without any fault tolerance.
+    /// Concurrent calls of [`SafeCoordinatorRpcGateway`] share the same [`CoordinatorApiClient`] without waiting for each other, as the client is cloned out of its lock for each call.
     /// [`SafeCoordinatorRpcGateway`] can be shared in different threads safely.
+    ///
+    /// Like [`super::taskmanager::SafeTaskManagerRpcGateway`], the client is reused until a call fails by a connection error.
     #[derive(Debug, Clone)]
     pub struct SafeCoordinatorRpcGateway {
-        inner: Arc<Mutex<Option<CoordinatorApiClient<tonic::transport::Channel>>>>,
+        inner: Arc<Mutex<Option<CoordinatorApiClient<GatewayChannel>>>>,
         host_addr: HostAddr,
         rpc_timeout: u64,
         connect_timeout: u64,
+        keepalive: Option<KeepaliveOptions>,
+        tls: Option<ClientTlsConfig>,
+        /// the SNI of [TlsOptions::sni_override], which the certificate probe of [CertExpiryCheck] sends as well
+        sni_override: Option<String>,
+        resolver: Option<Arc<dyn Resolver>>,
+        channel_factory: Option<ChannelFactory>,
+        cert_expiry: Option<CertExpiryCheck>,
+        retry: Option<RetryPolicy>,
+        reconnect: Option<ReconnectCoordinator>,
     }
 
     impl RpcGateway for SafeCoordinatorRpcGateway {
@@ -408,113 +981,279 @@ pub mod coordinator {
     #[async_trait]
     impl ReceiveHeartbeatRpcGateway for SafeCoordinatorRpcGateway {
         async fn receive_heartbeat(&self, request: Heartbeat) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                CoordinatorApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-                )
-            });
-
-            let mut request = tonic::Request::new(request);
-            request.set_timeout(Duration::from_secs(self.rpc_timeout));
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_within(Duration::from_secs(self.rpc_timeout), || {
+                            self.try_receive_heartbeat(request.clone())
+                        })
+                        .await
+                }
+                None => self.try_receive_heartbeat(request).await,
+            }
+        }
 
-            inner
-                .receive_heartbeat(request)
-                .await
-                .map(|resp| resp.into_inner())
+        fn heartbeat_budget(&self, rpc_timeout: Duration) -> Duration {
+            match self.retry.as_ref() {
+                Some(policy) => policy.budget(Duration::from_secs(self.rpc_timeout)),
+                None => rpc_timeout,
+            }
         }
     }
 
     #[async_trait]
     impl ReceiveAckRpcGateway for SafeCoordinatorRpcGateway {
         async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                CoordinatorApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-                )
-            });
-            let mut request = tonic::Request::new(req);
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_with(|| self.try_receive_ack(req.clone()), is_undelivered)
+                        .await
+                }
+                None => self.try_receive_ack(req).await,
+            }
+        }
+
+        async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+            match self.retry.as_ref() {
+                Some(policy) => {
+                    policy
+                        .run_acks(reqs, |acks| self.try_receive_acks(acks))
+                        .await
+                }
+                None => self.try_receive_acks(reqs).await,
+            }
+        }
+    }
+
+    impl SafeCoordinatorRpcGateway {
+        async fn try_receive_heartbeat(
+            &self,
+            request: Heartbeat,
+        ) -> Result<Response, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+
+            let mut request = new_request(request);
             request.set_timeout(Duration::from_secs(self.rpc_timeout));
 
-            inner
+            let result = inner
+                .receive_heartbeat(request)
+                .await
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
+        }
+
+        async fn try_receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+            let mut request = new_request(req);
+            request.set_timeout(Duration::from_secs(self.rpc_timeout));
+
+            let result = inner
                 .receive_ack(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
+        }
+
+        async fn try_receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+            let len = reqs.len();
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
+            let mut request = new_request(AckBatch { acks: reqs });
+            request.set_timeout(Duration::from_secs(self.rpc_timeout));
+
+            let result = inner
+                .receive_acks(request)
+                .await
+                .map(|resp| resp.into_inner());
+            ack_batch_results(
+                len,
+                release_on_connection_error(&self.inner, &self.host_addr, result).await,
+            )
+        }
+    }
+
+    #[async_trait]
+    impl ConnectRpcGateway for SafeCoordinatorRpcGateway {
+        async fn connect(&self) -> Result<(), tonic::transport::Error> {
+            let channel = connect_channel(
+                endpoint(
+                    &self.host_addr,
+                    Duration::from_secs(self.connect_timeout),
+                    self.keepalive.as_ref(),
+                    self.tls.as_ref(),
+                )?,
+                self.resolver.as_ref(),
+            )
+            .await?;
+            let client = CoordinatorApiClient::new(gateway_channel(
+                &self.host_addr,
+                channel,
+                self.channel_factory.as_ref(),
+            ));
+            *self.inner.lock().await = Some(client);
+            if self.tls.is_some() && self.cert_expiry.is_some() {
+                // the result is logged and kept by cert_status, the callers refusing the node check it by check_cert_expiry
+                let _ = self.check_cert_expiry().await;
+            }
+            Ok(())
         }
     }
 
     impl SafeCoordinatorRpcGateway {
         pub async fn new(host_addr: &HostAddr) -> Self {
-            let client = CoordinatorApiClient::connect_with_timeout(
-                host_addr.as_uri(),
-                Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
-            )
-            .await;
-            Self {
-                inner: Arc::new(tokio::sync::Mutex::new(client.ok())),
+            let gateway = Self {
+                inner: Default::default(),
                 host_addr: host_addr.clone(),
                 rpc_timeout: DEFAULT_RPC_TIMEOUT,
                 connect_timeout: DEFAULT_CONNECT_TIMEOUT,
-            }
+                keepalive: None,
+                tls: None,
+                sni_override: None,
+                resolver: None,
+                channel_factory: None,
+                cert_expiry: None,
+                retry: None,
+                reconnect: None,
+            };
+            // the client is created on the first rpc call if the connection fails
+            let _ = gateway.connect().await;
+            gateway
         }
 
-        pub async fn create_dataflow(&self, dataflow: Dataflow) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                CoordinatorApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
+        /// Send http2 keepalive pings on the channel, so that a half-open connection is detected before the next rpc call
+        pub fn with_keepalive(mut self, keepalive: KeepaliveOptions) -> Self {
+            self.keepalive = Some(keepalive);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Connect to the remote node over TLS. The certificate expiry is probed with the host of the remote node as SNI,
+        /// use [Self::with_tls_options] to probe with [TlsOptions::sni_override] as well.
+        pub fn with_tls(mut self, tls: ClientTlsConfig) -> Self {
+            self.tls = Some(tls);
+            self.sni_override = None;
+            self.inner = Default::default();
+            self
+        }
+
+        /// Connect to the remote node over TLS by `options`. It fails if the CA certificate can't be read.
+        pub fn with_tls_options(mut self, options: &TlsOptions) -> std::io::Result<Self> {
+            self = self.with_tls(options.client_tls_config()?);
+            self.sni_override = options.sni_override.clone();
+            Ok(self)
+        }
+
+        /// Retry the failed heartbeats and acks by `policy`, like [super::taskmanager::SafeTaskManagerRpcGateway::with_retry]
+        pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+            self.retry = Some(policy);
+            self
+        }
+
+        /// Stagger the reconnects of the gateway with the other gateways sharing `coordinator`, see [ReconnectCoordinator]
+        pub fn with_reconnect_coordinator(mut self, coordinator: ReconnectCoordinator) -> Self {
+            self.reconnect = Some(coordinator);
+            self
+        }
+
+        /// Resolve the host of the remote node by a custom resolver instead of the system resolver
+        pub fn with_resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+            self.resolver = Some(resolver);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Build the rpc client on the channel wrapped by `factory`, e.g. to insert tower layers
+        pub fn with_channel_factory(mut self, factory: ChannelFactory) -> Self {
+            self.channel_factory = Some(factory);
+            self.inner = Default::default();
+            self
+        }
+
+        /// Check the certificate expiry of the remote node by `policy` when the gateway is connected by [ConnectRpcGateway::connect]
+        pub fn with_cert_expiry_check(mut self, policy: CertExpiryPolicy) -> Self {
+            self.cert_expiry = Some(CertExpiryCheck::new(policy));
+            self
+        }
+
+        /// The certificate expiry of the remote node by the latest check. It's [None] if the gateway isn't connected over TLS or the certificate hasn't been checked.
+        pub fn cert_status(&self) -> Option<CertStatus> {
+            self.tls.as_ref()?;
+            self.cert_expiry.as_ref().and_then(CertExpiryCheck::status)
+        }
+
+        /// Check the certificate expiry of the remote node now, by the default [CertExpiryPolicy] if none is set by [Self::with_cert_expiry_check]
+        pub async fn check_cert_expiry(&self) -> Result<CertStatus, CertError> {
+            if self.tls.is_none() {
+                return Err(CertError::NotTls);
+            }
+            self.cert_expiry
+                .clone()
+                .unwrap_or_default()
+                .check(
+                    &self.host_addr,
+                    self.sni_override.as_deref(),
+                    self.resolver.as_ref(),
                     Duration::from_secs(self.connect_timeout),
                 )
-            });
+                .await
+        }
+
+        fn new_client(&self) -> CoordinatorApiClient<GatewayChannel> {
+            CoordinatorApiClient::new(gateway_channel(
+                &self.host_addr,
+                lazy_channel(
+                    &self.host_addr,
+                    Duration::from_secs(self.connect_timeout),
+                    self.keepalive.as_ref(),
+                    self.tls.as_ref(),
+                    self.resolver.as_ref(),
+                ),
+                self.channel_factory.as_ref(),
+            ))
+        }
+
+        pub async fn create_dataflow(&self, dataflow: Dataflow) -> Result<Response, tonic::Status> {
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let result = inner
                 .create_dataflow(tonic::Request::new(dataflow))
                 .await
                 .map(|resp| resp.into_inner());
-
-            drop(guard);
-            result
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub async fn terminate_dataflow(&self, req: ResourceId) -> Result<Response, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                CoordinatorApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(self.connect_timeout),
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
             let mut request = tonic::Request::new(req);
             request.set_timeout(Duration::from_secs(self.rpc_timeout));
 
-            inner
+            let result = inner
                 .terminate_dataflow(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
 
         pub async fn get_dataflow(
             &self,
             req: GetDataflowRequest,
         ) -> Result<DataflowStates, tonic::Status> {
-            let mut guard = self.inner.lock().await;
-            let inner = guard.get_or_insert_with(|| {
-                CoordinatorApiClient::with_connection_timeout(
-                    self.host_addr.as_uri(),
-                    Duration::from_secs(self.connect_timeout),
-                )
-            });
+            let mut inner =
+                cached_client(&self.inner, self.reconnect.as_ref(), || self.new_client()).await;
 
             let mut request = tonic::Request::new(req);
             request.set_timeout(Duration::from_secs(self.rpc_timeout));
 
-            inner
+            let result = inner
                 .get_dataflow(request)
                 .await
-                .map(|resp| resp.into_inner())
+                .map(|resp| resp.into_inner());
+            release_on_connection_error(&self.inner, &self.host_addr, result).await
         }
     }
 }
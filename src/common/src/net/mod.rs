@@ -1,55 +1,219 @@
 use std::{
-    net::UdpSocket,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
     pin::Pin,
-    sync::atomic::{self, AtomicU64},
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc, Mutex,
+    },
     task::{self, Poll},
     time::Duration,
 };
 
-use futures_util::{ready, Future, FutureExt};
-use proto::common::{Ack, Heartbeat, HostAddr, NodeType, SubDataflowId};
-use tokio::sync::mpsc;
+use futures_util::{ready, stream::FuturesUnordered, Future, FutureExt, StreamExt};
+use proto::common::{Ack, Heartbeat, HostAddr, NodeType, Response, SubDataflowId};
+use tokio::sync::{
+    broadcast, mpsc,
+    mpsc::error::{SendError, TrySendError},
+    oneshot,
+};
 
-use crate::{futures::join_all, types::ExecutorId, utils};
+use crate::{
+    futures::channel::{resizable_channel, InstrumentedSender, ResizableReceiver, ResizableSender},
+    types::ExecutorId,
+    utils,
+};
 
-use self::gateway::{ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway};
+use self::{
+    gateway::{ConnectRpcGateway, ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway},
+    rtt::{HistogramSnapshot, RttTracker},
+    subnet::SubnetProfile,
+};
 
 pub(crate) const DEFAULT_RPC_TIMEOUT: u64 = 3;
 pub(crate) const DEFAULT_CONNECT_TIMEOUT: u64 = 3;
 pub(crate) const DEFAULT_TASKMANAGER_PORT: u16 = 8792;
+pub(crate) const DEFAULT_ACK_DELAY: u64 = 1;
+pub(crate) const DEFAULT_ACK_BUF_SIZE: usize = 500;
+pub(crate) const DEFAULT_HEARTBEAT_PERIOD: u64 = 3;
+pub(crate) const DEFAULT_CONNECT_CONCURRENCY: usize = 16;
+/// the heartbeat period and ack delay which zero is clamped to, in seconds
+pub const MIN_TICK_SECS: u64 = 1;
+/// prefix of the environment variables which toggle the features of the builders
+pub const FEATURE_TOGGLE_PREFIX: &str = "LIGHTFLUS_";
+pub mod audit;
+pub mod cert;
+pub mod client;
+pub mod clock;
 pub mod cluster;
 #[cfg(not(tarpaulin_include))]
 pub mod gateway;
+pub mod monitor;
+pub mod reconnect;
+pub mod reload;
+pub mod replay;
+pub mod resolver;
+pub mod retry;
+pub mod rtt;
+pub mod selftest;
+pub mod subnet;
+pub mod tenant;
+pub mod trace;
+
+/// The host of local node if neither [hostname] nor [local_ip] can be determined
+pub const LOOPBACK_HOST: &str = "127.0.0.1";
 
+/// The address of local node. The host falls back to [local_ip] if [hostname] fails, and to [LOOPBACK_HOST] as the last resort,
+/// so the address never has an empty host.
 pub fn local(port: usize) -> HostAddr {
+    local_with(port, hostname, local_ip)
+}
+
+fn local_with<H: FnOnce() -> Option<String>, I: FnOnce() -> Option<String>>(
+    port: usize,
+    hostname: H,
+    local_ip: I,
+) -> HostAddr {
+    let host = hostname()
+        .filter(|host| !host.is_empty())
+        .or_else(|| {
+            tracing::warn!("fail to get hostname, fall back to local ip");
+            local_ip().filter(|ip| !ip.is_empty())
+        })
+        .unwrap_or_else(|| {
+            tracing::warn!("fail to get local ip, fall back to {}", LOOPBACK_HOST);
+            LOOPBACK_HOST.to_string()
+        });
+
     HostAddr {
-        host: hostname().unwrap_or_default(),
+        host,
         port: port as u32,
     }
 }
 
+/// [Memoized] caches the result of a lookup which doesn't change during the lifetime of the node, until it's invalidated.
+/// A failed lookup isn't cached, so it's tried again by the next call.
+struct Memoized {
+    value: Mutex<Option<String>>,
+}
+
+impl Memoized {
+    const fn new() -> Self {
+        Self {
+            value: Mutex::new(None),
+        }
+    }
+
+    fn get_or_lookup<F: FnOnce() -> Option<String>>(&self, lookup: F) -> Option<String> {
+        let mut value = self.value.lock().unwrap();
+        if value.is_none() {
+            *value = lookup();
+        }
+        value.clone()
+    }
+
+    fn invalidate(&self) {
+        self.value.lock().unwrap().take();
+    }
+}
+
+static HOSTNAME: Memoized = Memoized::new();
+static LOCAL_IP: Memoized = Memoized::new();
+
+/// the hostname of the local node. It's looked up once and cached until [refresh] is called.
 pub fn hostname() -> Option<String> {
-    use std::process::Command;
-    if cfg!(unix) || cfg!(windows) {
-        let output = match Command::new("hostname").output() {
-            Ok(o) => o,
-            Err(_) => return None,
-        };
-        let mut s = String::from_utf8(output.stdout).unwrap();
-        s.pop(); // pop '\n'
-        Some(s)
-    } else {
-        None
+    HOSTNAME.get_or_lookup(lookup_hostname)
+}
+
+/// Invalidate the cached results of [hostname] and [local_ip], e.g. after a network reconfiguration.
+/// The next calls look them up again.
+pub fn refresh() {
+    HOSTNAME.invalidate();
+    LOCAL_IP.invalidate();
+}
+
+/// the hostname of the local node by `gethostname(2)`. The trailing whitespaces and NULs are trimmed.
+/// It's [None] if the call fails or the name isn't valid UTF-8.
+#[cfg(unix)]
+fn lookup_hostname() -> Option<String> {
+    // the names are limited to 255 bytes by POSIX, one more byte for the terminating NUL
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if ret != 0 {
+        return None;
     }
+    // the name may be truncated without the terminating NUL
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    std::str::from_utf8(&buf[..len])
+        .ok()
+        .map(|host| host.trim_end().to_string())
+}
+
+/// the hostname of the local node by the `COMPUTERNAME` environment variable
+#[cfg(windows)]
+fn lookup_hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .map(|host| host.trim_end().to_string())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lookup_hostname() -> Option<String> {
+    None
+}
+
+/// Socket options of the UDP socket that [local_ip] binds to discover the local ip.
+/// Some restrictive environments reject the bind unless address or port reuse is allowed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiscoverySocketOptions {
+    /// set `SO_REUSEADDR` before binding
+    pub reuse_addr: bool,
+    /// set `SO_REUSEPORT` before binding. It's ignored on platforms that don't support it.
+    pub reuse_port: bool,
 }
 
+/// The target which [local_ip] probes to discover the local IPv4 address. No packet is sent to it.
+pub const DISCOVERY_TARGET_V4: &str = "8.8.8.8:80";
+/// The target which [local_ip_v6] probes to discover the local IPv6 address. No packet is sent to it.
+pub const DISCOVERY_TARGET_V6: &str = "[2001:4860:4860::8888]:80";
+
+/// The local IPv4 address. It's discovered once and cached until [refresh] is called.
 pub fn local_ip() -> Option<String> {
-    let socket = match UdpSocket::bind("0.0.0.0:0") {
+    LOCAL_IP.get_or_lookup(|| local_ip_with_options(&Default::default()))
+}
+
+/// The local IPv6 address. It's [None] in IPv4-only networks.
+pub fn local_ip_v6() -> Option<String> {
+    local_ip_to(DISCOVERY_TARGET_V6)
+}
+
+/// The local address which routes to `target`, e.g. `10.0.0.1:80` or `[fd00::1]:80`.
+/// Probing a host of a private or air-gapped network discovers the address the node is reachable by there.
+pub fn local_ip_to(target: &str) -> Option<String> {
+    local_ip_to_with_options(target, &Default::default())
+}
+
+pub fn local_ip_with_options(opts: &DiscoverySocketOptions) -> Option<String> {
+    local_ip_to_with_options(DISCOVERY_TARGET_V4, opts)
+}
+
+/// The local address is discovered by connecting a UDP socket to `target`, which only picks the route and sends nothing
+pub fn local_ip_to_with_options(target: &str, opts: &DiscoverySocketOptions) -> Option<String> {
+    let target = match target.to_socket_addrs() {
+        Ok(mut addrs) => addrs.next()?,
+        Err(_) => return None,
+    };
+    let unspecified = match target {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let socket = match bind_discovery_socket(&unspecified, opts) {
         Ok(s) => s,
         Err(_) => return None,
     };
 
-    match socket.connect("8.8.8.8:80") {
+    match socket.connect(target) {
         Ok(()) => (),
         Err(_) => return None,
     };
@@ -57,6 +221,151 @@ pub fn local_ip() -> Option<String> {
     socket.local_addr().ok().map(|addr| addr.ip().to_string())
 }
 
+fn bind_discovery_socket(
+    addr: &SocketAddr,
+    opts: &DiscoverySocketOptions,
+) -> std::io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(*addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if opts.reuse_addr {
+        socket.set_reuse_address(true)?;
+    }
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    if opts.reuse_port {
+        socket.set_reuse_port(true)?;
+    }
+    socket.bind(&(*addr).into())?;
+
+    Ok(socket.into())
+}
+
+/// HTTP/2 keepalive settings of the gRPC channels to remote nodes.
+///
+/// Without keepalive, a connection silently dropped by a NAT or load balancer is only detected by the next rpc call.
+/// With keepalive, a PING frame is sent every `interval` seconds and the connection is closed if it's not acknowledged within `timeout` seconds.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KeepaliveOptions {
+    /// seconds between two keepalive pings
+    pub interval: u64,
+    /// seconds to wait for the acknowledgement of a keepalive ping
+    pub timeout: u64,
+    /// whether pings are sent even if there's no in-flight rpc on the channel
+    #[serde(default)]
+    pub permit_without_stream: bool,
+}
+
+impl KeepaliveOptions {
+    /// apply the keepalive settings to a channel endpoint
+    pub fn apply<E: KeepaliveTarget>(&self, endpoint: E) -> E {
+        endpoint
+            .http2_keep_alive_interval(Duration::from_secs(self.interval))
+            .keep_alive_timeout(Duration::from_secs(self.timeout))
+            .keep_alive_while_idle(self.permit_without_stream)
+    }
+}
+
+/// A channel builder which accepts HTTP/2 keepalive settings, e.g. [tonic::transport::Endpoint]
+pub trait KeepaliveTarget: Sized {
+    fn http2_keep_alive_interval(self, interval: Duration) -> Self;
+    fn keep_alive_timeout(self, timeout: Duration) -> Self;
+    fn keep_alive_while_idle(self, enabled: bool) -> Self;
+}
+
+impl KeepaliveTarget for tonic::transport::Endpoint {
+    fn http2_keep_alive_interval(self, interval: Duration) -> Self {
+        tonic::transport::Endpoint::http2_keep_alive_interval(self, interval)
+    }
+
+    fn keep_alive_timeout(self, timeout: Duration) -> Self {
+        tonic::transport::Endpoint::keep_alive_timeout(self, timeout)
+    }
+
+    fn keep_alive_while_idle(self, enabled: bool) -> Self {
+        tonic::transport::Endpoint::keep_alive_while_idle(self, enabled)
+    }
+}
+
+/// TLS settings of the gRPC channels to remote nodes
+#[derive(serde::Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    /// path of the PEM-encoded CA certificate which the certificate of remote node is verified against
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// the hostname sent as SNI and which the certificate of remote node is verified against.
+    /// By default it's the host of the connect address, which doesn't match a hostname-bound certificate when connecting by IP or through a load balancer.
+    #[serde(default)]
+    pub sni_override: Option<String>,
+}
+
+impl TlsOptions {
+    /// create the [tonic::transport::ClientTlsConfig] of the gateways. It fails if the CA certificate can't be read.
+    pub fn client_tls_config(&self) -> std::io::Result<tonic::transport::ClientTlsConfig> {
+        let mut config = tonic::transport::ClientTlsConfig::new();
+        if let Some(path) = self.ca_cert_path.as_ref() {
+            let pem = std::fs::read(path)?;
+            config = config.ca_certificate(tonic::transport::Certificate::from_pem(pem));
+        }
+        if let Some(sni) = self.sni_override.as_ref() {
+            config = config.domain_name(sni);
+        }
+        Ok(config)
+    }
+}
+
+/// The tick duration configured by whole seconds `secs`, or by `millis` if it's set.
+/// A zero duration makes an interval fire continuously and spin the CPU, so it's clamped to [MIN_TICK_SECS].
+fn clamp_tick(name: &str, secs: u64, millis: Option<u64>) -> Duration {
+    let tick = millis
+        .map(Duration::from_millis)
+        .unwrap_or_else(|| Duration::from_secs(secs));
+    if tick.is_zero() {
+        tracing::warn!("{} is zero, clamped to {}s", name, MIN_TICK_SECS);
+        Duration::from_secs(MIN_TICK_SECS)
+    } else {
+        tick
+    }
+}
+
+/// The dispatch mode of an [AckResponder] of `nodes` gateways. The quorum of [DispatchMode::QuorumOf] is clamped into `[1, nodes]`:
+/// a quorum of zero would succeed without sending anything, and a quorum more than the nodes would fail before any call.
+fn clamp_quorum(mode: DispatchMode, nodes: usize) -> DispatchMode {
+    match mode {
+        DispatchMode::QuorumOf(quorum) if nodes > 0 && (quorum == 0 || quorum > nodes) => {
+            let clamped = quorum.clamp(1, nodes);
+            tracing::warn!(
+                "ack quorum of {} is out of the {} nodes, clamped to {}",
+                quorum,
+                nodes,
+                clamped
+            );
+            DispatchMode::QuorumOf(clamped)
+        }
+        mode => mode,
+    }
+}
+
+/// read the feature toggle `LIGHTFLUS_<name>` from the environment if `read_env` is set.
+/// `true`, `1`, `on` and `yes` enable the feature; `false`, `0`, `off` and `no` disable it.
+/// The configured value is used if the toggle is unset or malformed.
+fn feature_toggle(read_env: bool, name: &str, configured: bool) -> bool {
+    if !read_env {
+        return configured;
+    }
+    let key = format!("{}{}", FEATURE_TOGGLE_PREFIX, name);
+    match std::env::var(&key) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" | "on" | "yes" => true,
+            "false" | "0" | "off" | "no" => false,
+            _ => {
+                tracing::warn!("malformed feature toggle {}={}, ignored", key, value);
+                configured
+            }
+        },
+        Err(_) => configured,
+    }
+}
+
 /// Heartbeat Builder
 ///
 /// How to build heartbeat sender
@@ -81,6 +390,7 @@ pub fn local_ip() -> Option<String> {
 ///         period: 3,
 ///         connect_timeout: 3,
 ///         rpc_timeout: 3,
+///         ..Default::default()
 ///     };
 ///     
 ///     let ref addr = HostAddr {
@@ -107,6 +417,7 @@ pub fn local_ip() -> Option<String> {
 ///         period: 3,
 ///         connect_timeout: 3,
 ///         rpc_timeout: 3,
+///         ..Default::default()
 ///     };
 ///     
 ///     let ref addr = HostAddr {
@@ -118,326 +429,5493 @@ pub fn local_ip() -> Option<String> {
 ///     let _ = tokio::time::timeout(Duration::from_secs(1), heartbeat);
 /// }
 /// ```
-#[derive(serde::Deserialize, Clone, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct HeartbeatBuilder {
-    /// period of heartbeat, in seconds
+    /// period of heartbeat, in seconds. Zero is clamped to [MIN_TICK_SECS].
     pub period: u64,
+    /// period of heartbeat, in milliseconds. It overrides `period` if it's set, which enables sub-second heartbeats.
+    #[serde(default)]
+    pub period_ms: Option<u64>,
     /// timeout of heartbeat rpc connection, in seconds
     pub connect_timeout: u64,
-    /// timeout of heartbeat rpc request, in seconds
+    /// timeout of heartbeat rpc request, in seconds. A heartbeat not responded within it fails, so a hung receiver can't stall the sender.
+    /// If the gateway retries the heartbeats, each attempt is bounded by it instead, and the heartbeat by the budget of all the attempts,
+    /// see [gateway::ReceiveHeartbeatRpcGateway::heartbeat_budget].
     pub rpc_timeout: u64,
+    /// id of the cluster which heartbeats are stamped with
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    /// skip the missed ticks instead of bursting them when a tick takes longer than the period, so that the sender can catch up
+    #[serde(default)]
+    pub skip_missed_ticks: bool,
+    /// go through all the sending logic but record the heartbeats in a [DryRunLog] instead of sending them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// read feature toggles from the environment on building, which override the configured ones:
+    /// `LIGHTFLUS_HEARTBEAT_SKIP_MISSED_TICKS`, `LIGHTFLUS_HEARTBEAT_DRY_RUN` and `LIGHTFLUS_HEARTBEAT_JITTER`.
+    /// The jitter turned on by the toggle is `jitter_ms`, or a tenth of the period if `jitter_ms` is zero.
+    #[serde(default)]
+    pub env_toggles: bool,
+    /// the periods of the nodes which are heartbeated at their own cadence instead of `period`
+    #[serde(default)]
+    pub period_overrides: Vec<PeriodOverride>,
+    /// the hook applied to every heartbeat before it's sent. It can't be configured by serde.
+    #[serde(skip)]
+    pub transform: Option<HeartbeatTransform>,
+    /// aggregate the failures of heartbeats into [FailureBurst] events. Disabled if it's unset.
+    #[serde(default)]
+    pub failure_burst: Option<FailureBurstConfig>,
+    /// what to send before an execution id is assigned by [HeartbeatSender::update_execution_id]
+    #[serde(default)]
+    pub pre_execution: PreExecutionPolicy,
+    /// how the health of the heartbeated nodes is derived from their consecutive failures
+    #[serde(default)]
+    pub node_health: NodeHealthConfig,
+    /// the max random deviation of each heartbeat period, in milliseconds, so that the senders started at once don't heartbeat in lockstep.
+    /// Each tick is scheduled a fresh random delay within `period ± jitter_ms` after the previous one, and the first tick within `jitter_ms`.
+    /// It's capped at half of the period, and the missed ticks are delayed rather than skipped or bursted. Zero disables the jitter.
+    #[serde(default)]
+    pub jitter_ms: u64,
+    /// the settings of the nodes within each subnet, which override the ones above. The first subnet containing a node applies.
+    #[serde(default)]
+    pub subnets: Vec<SubnetProfile>,
+}
+
+/// How [NodeHealthTracker] marks a node by its consecutive heartbeat failures
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct NodeHealthConfig {
+    /// the consecutive failures to mark a node [NodeHealth::Suspect]
+    pub suspect_after: u32,
+    /// the consecutive failures to mark a node [NodeHealth::Down]
+    pub down_after: u32,
+    /// while a node is down, it's heartbeated once per backoff instead of every tick, in milliseconds.
+    /// Zero disables the backoff.
+    #[serde(default)]
+    pub down_backoff_ms: u64,
+}
+
+impl Default for NodeHealthConfig {
+    fn default() -> Self {
+        Self {
+            suspect_after: 1,
+            down_after: 3,
+            down_backoff_ms: 0,
+        }
+    }
+}
+
+/// What a [HeartbeatSender] does while its execution id is unset
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PreExecutionPolicy {
+    /// send heartbeats without an execution id
+    #[default]
+    SendAnyway,
+    /// send nothing until an execution id is assigned
+    SuppressUntilSet,
+    /// send heartbeats with the placeholder as the execution id
+    UsePlaceholder(SubDataflowId),
+}
+
+/// How the failures of heartbeats are aggregated into [FailureBurst] events
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct FailureBurstConfig {
+    /// the window which failures are aggregated over, in milliseconds. It starts at the first failure.
+    pub window_ms: u64,
+    /// the minimum number of failures within a window to emit a [FailureBurst]. Fewer failures are a brief blip and are not emitted.
+    pub min_failures: u32,
+}
+
+/// [HeartbeatTransform] modifies every outgoing heartbeat before it's sent, e.g. to adjust the node type or stamp metadata.
+/// A hook that leaves the heartbeat untouched keeps the defaults.
+#[derive(Clone)]
+pub struct HeartbeatTransform(Arc<dyn Fn(&mut Heartbeat) + Send + Sync>);
+
+impl HeartbeatTransform {
+    pub fn new<F: Fn(&mut Heartbeat) + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+
+    fn apply(&self, heartbeat: &mut Heartbeat) {
+        (self.0)(heartbeat)
+    }
+}
+
+impl std::fmt::Debug for HeartbeatTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HeartbeatTransform")
+    }
+}
+
+/// The heartbeat period of a single node, in milliseconds. Zero is clamped to [MIN_TICK_SECS].
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PeriodOverride {
+    pub host_addr: HostAddr,
+    pub period_ms: u64,
+}
+
+impl Default for HeartbeatBuilder {
+    fn default() -> Self {
+        Self {
+            period: DEFAULT_HEARTBEAT_PERIOD,
+            period_ms: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            cluster_id: None,
+            skip_missed_ticks: false,
+            dry_run: false,
+            env_toggles: false,
+            period_overrides: vec![],
+            transform: None,
+            failure_burst: None,
+            pre_execution: Default::default(),
+            node_health: Default::default(),
+            jitter_ms: 0,
+            subnets: vec![],
+        }
+    }
 }
 
 impl HeartbeatBuilder {
+    /// Set the hook applied to every heartbeat before it's sent
+    pub fn with_transform<F: Fn(&mut Heartbeat) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.transform = Some(HeartbeatTransform::new(f));
+        self
+    }
+
+    /// the heartbeat period of the node, the overridden one if there is
+    pub fn period_of(&self, host_addr: &HostAddr) -> Duration {
+        let period_ms = self
+            .period_overrides
+            .iter()
+            .find(|period| &period.host_addr == host_addr)
+            .map(|period| period.period_ms)
+            .or(self.period_ms);
+        clamp_tick("heartbeat period", self.period, period_ms)
+    }
+
+    /// the [SubnetProfile] of the node, if any subnet contains it
+    pub fn subnet_of(&self, host_addr: &HostAddr) -> Option<&SubnetProfile> {
+        self.subnets
+            .iter()
+            .find(|profile| profile.contains(host_addr))
+    }
+
     pub fn build<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveHeartbeatRpcGateway>(
         &self,
         host_addr: &HostAddr,
         task_id: ExecutorId,
         f: F,
     ) -> HeartbeatSender<T> {
-        HeartbeatSender {
-            gateway: f(
+        self.build_with_results(host_addr, task_id, f).0
+    }
+
+    /// the max jitter of the heartbeats of `period`, which is capped at half of the period
+    fn max_jitter(&self, period: Duration) -> Duration {
+        let jitter = Duration::from_millis(self.jitter_ms);
+        let enabled = feature_toggle(self.env_toggles, "HEARTBEAT_JITTER", !jitter.is_zero());
+        match enabled {
+            true if jitter.is_zero() => period / 10,
+            true => jitter.min(period / 2),
+            false => Duration::ZERO,
+        }
+    }
+
+    /// Same as [HeartbeatBuilder::build], but the [HeartbeatResult] of every heartbeat sent is reported to the returned receiver.
+    /// A result is dropped if the receiver lags behind by [HEARTBEAT_RESULT_CHANNEL_SIZE] results.
+    pub fn build_with_results<
+        F: Fn(&HostAddr, Duration, Duration) -> T,
+        T: ReceiveHeartbeatRpcGateway,
+    >(
+        &self,
+        host_addr: &HostAddr,
+        task_id: ExecutorId,
+        f: F,
+    ) -> (HeartbeatSender<T>, mpsc::Receiver<HeartbeatResult>) {
+        let (results_tx, results_rx) = mpsc::channel(HEARTBEAT_RESULT_CHANNEL_SIZE);
+        let subnet = self.subnet_of(host_addr);
+        let period = self.period_of(host_addr);
+        let max_jitter = self.max_jitter(period);
+        let ticker = if max_jitter.is_zero() {
+            let mut interval = tokio::time::interval(period);
+            let skip_missed_ticks = feature_toggle(
+                self.env_toggles,
+                "HEARTBEAT_SKIP_MISSED_TICKS",
+                self.skip_missed_ticks,
+            );
+            if skip_missed_ticks {
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            }
+            HeartbeatTicker::Fixed(interval)
+        } else {
+            HeartbeatTicker::Jittered {
+                period,
+                max_jitter,
+                sleep: Box::pin(tokio::time::sleep(random_duration(max_jitter))),
+            }
+        };
+        let rpc_timeout = Duration::from_secs(
+            subnet
+                .and_then(|profile| profile.rpc_timeout)
+                .unwrap_or(self.rpc_timeout),
+        );
+        let sender = HeartbeatSender {
+            gateway: Arc::new(f(
                 host_addr,
-                Duration::from_secs(self.connect_timeout),
-                Duration::from_secs(self.rpc_timeout),
-            ),
-            interval: tokio::time::interval(Duration::from_secs(self.period)),
+                Duration::from_secs(
+                    subnet
+                        .and_then(|profile| profile.connect_timeout)
+                        .unwrap_or(self.connect_timeout),
+                ),
+                rpc_timeout,
+            )),
+            rpc_timeout,
+            next_tick: ticker.deadline(),
+            ticker,
             execution_id: None,
-            current_heartbeat_id: AtomicU64::default(),
+            current_heartbeat_id: Default::default(),
             task_id,
-        }
+            cluster_id: self.cluster_id.clone(),
+            stats: Default::default(),
+            dry_run_log: feature_toggle(self.env_toggles, "HEARTBEAT_DRY_RUN", self.dry_run)
+                .then(Default::default),
+            coalesced: None,
+            transform: self.transform.clone(),
+            failure_bursts: broadcast::channel(FAILURE_BURST_CHANNEL_SIZE).0,
+            burst_aggregator: self.failure_burst.clone().map(FailureBurstAggregator::new),
+            pre_execution: self.pre_execution.clone(),
+            node_health: Default::default(),
+            node_health_config: subnet
+                .and_then(|profile| profile.node_health.clone())
+                .unwrap_or_else(|| self.node_health.clone()),
+            subnet: subnet.map(|profile| profile.name.clone()),
+            rtt: Default::default(),
+            in_flight: None,
+            results: Some(results_tx),
+            shutdown: None,
+            membership_digest: 0,
+            audit_writer: None,
+            trace_context: trace::TraceContext::current(),
+        };
+        (sender, results_rx)
+    }
+}
+
+/// the capacity of the channel of [HeartbeatResult]s
+pub const HEARTBEAT_RESULT_CHANNEL_SIZE: usize = 64;
+
+/// The outcome of a heartbeat sent by [HeartbeatSender], reported by [HeartbeatBuilder::build_with_results]
+#[derive(Clone, Debug)]
+pub struct HeartbeatResult {
+    pub host_addr: HostAddr,
+    pub heartbeat_id: u64,
+    /// the response of the receiver, or the error of the rpc call
+    pub result: Result<Response, tonic::Status>,
+}
+
+impl HeartbeatResult {
+    pub fn is_success(&self) -> bool {
+        self.result.is_ok()
     }
 }
 
 pub struct HeartbeatSender<T: ReceiveHeartbeatRpcGateway> {
-    gateway: T,
-    interval: tokio::time::Interval,
+    gateway: Arc<T>,
+    /// how long a single call of a heartbeat is awaited. The heartbeat counts as failed after the [gateway::ReceiveHeartbeatRpcGateway::heartbeat_budget] of it.
+    rpc_timeout: Duration,
+    ticker: HeartbeatTicker,
+    /// when the ticker fires next time
+    next_tick: tokio::time::Instant,
     execution_id: Option<SubDataflowId>,
-    current_heartbeat_id: AtomicU64,
+    /// the id of the next heartbeat, which may be shared by several senders
+    current_heartbeat_id: Arc<AtomicU64>,
     task_id: ExecutorId,
+    cluster_id: Option<String>,
+    stats: Arc<Mutex<HeartbeatStats>>,
+    dry_run_log: Option<DryRunLog<Heartbeat>>,
+    /// the execution ids sharing this sender if it's built by [HeartbeatCoalescer]
+    coalesced: Option<Arc<Mutex<Vec<SubDataflowId>>>>,
+    transform: Option<HeartbeatTransform>,
+    failure_bursts: broadcast::Sender<FailureBurst>,
+    burst_aggregator: Option<FailureBurstAggregator>,
+    pre_execution: PreExecutionPolicy,
+    node_health: NodeHealthTracker,
+    node_health_config: NodeHealthConfig,
+    /// the name of the subnet of the node
+    subnet: Option<String>,
+    rtt: RttTracker,
+    in_flight: Option<InFlightHeartbeat>,
+    /// where the [HeartbeatResult]s are reported. It's [None] once the receiver is closed.
+    results: Option<mpsc::Sender<HeartbeatResult>>,
+    /// the signal to stop the sender gracefully, set by [HeartbeatSender::with_shutdown]
+    shutdown: Option<oneshot::Receiver<()>>,
+    /// the [cluster::membership_digest] which heartbeats are stamped with. Zero if it's not set.
+    membership_digest: u64,
+    /// where the dispatched heartbeats are recorded, set by [HeartbeatSender::with_audit_sink]
+    audit_writer: Option<audit::AuditWriter>,
+    /// the trace context of the span which built the sender, carried by the heartbeats it sends
+    trace_context: Option<trace::TraceContext>,
 }
 impl<T: ReceiveHeartbeatRpcGateway> HeartbeatSender<T> {
-    pub fn update_execution_id(&mut self, execution_id: SubDataflowId) {
-        self.execution_id = Some(execution_id)
+    /// Subscribe the [FailureBurst] events of this sender, which are emitted if [HeartbeatBuilder::failure_burst] is set.
+    /// It should be called before the sender is spawned.
+    pub fn subscribe_failure_bursts(&self) -> broadcast::Receiver<FailureBurst> {
+        self.failure_bursts.subscribe()
     }
-}
 
-impl<T: ReceiveHeartbeatRpcGateway> Future for HeartbeatSender<T> {
-    type Output = ();
+    /// The shared [NodeHealthTracker] of this sender. It can be read after the sender is spawned.
+    pub fn node_health(&self) -> NodeHealthTracker {
+        self.node_health.clone()
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        let this = self.get_mut();
-        ready!(Pin::new(&mut this.interval).poll_tick(cx));
-        let now = utils::times::now();
-        tracing::debug!("heartbeat sent at time {:?}", now);
-        let future = this.gateway.receive_heartbeat(Heartbeat {
-            heartbeat_id: this
-                .current_heartbeat_id
-                .fetch_add(1, atomic::Ordering::SeqCst),
-            timestamp: Some(prost_types::Timestamp {
-                seconds: now.timestamp(),
-                nanos: now.timestamp_subsec_nanos() as i32,
-            }),
-            node_type: NodeType::JobManager as i32,
-            subdataflow_id: this.execution_id.clone(),
-            task_id: this.task_id,
-        });
-        join_all(cx, &mut vec![future], |r| match r {
-            Ok(_) => tracing::info!(
-                "heartbeat sent success  [execution_id: {:?}, task_id: {}]",
-                &this.execution_id,
-                this.task_id,
-            ),
-            Err(err) => tracing::error!(
-                "heartbeat sent failed, [execution_id: {:?}, task_id: {}], err: {}",
-                &this.execution_id,
-                this.task_id,
-                err,
-            ),
-        });
-        Poll::Pending
+    /// Share `tracker` with this sender, so that the health of the nodes of many senders can be read in one place
+    pub fn with_node_health_tracker(mut self, tracker: NodeHealthTracker) -> Self {
+        self.node_health = tracker;
+        self
     }
-}
 
-/// The builder of [AckResponder] which is also the configuration of ACK
-///
-/// AckResponderBuilder::build has three arguments:
-/// - First Arg: the host address of remote node
-/// - Second Arg: rpc connection timeout
-/// - Third Arg: rpc request timeout
-///
-/// It will return two values:
-/// - a new [AckResponder]
-/// - a [mpsc::Sender] channel for [Ack] messages. Users can trigger ack by send an [Ack] message into it.
-///
-/// [AckResponder] implements [Future]. Users can run an [AckResponder] by:
-/// - Tokio spawn
-/// - async/await
-///
-/// # Example of Tokio spawn
-/// ```
-/// use common::net::{AckResponderBuilder, gateway::taskmanager::SafeTaskManagerRpcGateway};
-/// use proto::common::HostAddr;
-///
-/// #[tokio::main]
-/// async fn main() {
-///     let builder = AckResponderBuilder {
-///         delay: 3,
-///         buf_size: 10,
-///         connect_timeout: 3,
-///         rpc_timeout: 3
-///     };
-///     
-///     let ref addr = HostAddr {
-///         host: "localhost".to_string(),
-///         port: 8080
-///     };
-/// 
-///     let (responder, _) = builder.build(addr, |addr, connect_timeout, rpc_timeout| SafeTaskManagerRpcGateway::with_timeout(addr, connect_timeout, rpc_timeout));
-///     let _ = tokio::spawn(responder);
-/// }
-/// ```
-///
-/// # Example of Tokio spwan
-/// ```
-/// use common::net::{AckResponderBuilder, gateway::taskmanager::SafeTaskManagerRpcGateway};
-/// use std::time::Duration;
-/// use proto::common::HostAddr;
-///
-/// #[tokio::main]
-/// async fn main() {
-///     let builder = AckResponderBuilder {
-///         delay: 3,
-///         buf_size: 10,
-///         connect_timeout: 3,
-///         rpc_timeout: 3
-///     };
-/// 
-///     let ref addr = HostAddr {
-///         host: "localhost".to_string(),
-///         port: 8080
-///     };
-///     
-///     let (responder, _) = builder.build(addr, |addr, connect_timeout, rpc_timeout| SafeTaskManagerRpcGateway::with_timeout(addr, connect_timeout, rpc_timeout));
-///     let _ = tokio::time::timeout(Duration::from_secs(1), responder);
-/// }
-/// ```
-#[derive(serde::Deserialize, Clone, Debug)]
-pub struct AckResponderBuilder {
-    // deplay duration, in seconds
-    pub delay: u64,
-    // buffer ack queue size
-    pub buf_size: usize,
-    /// timeout of ack rpc connection, in seconds
-    pub connect_timeout: u64,
-    /// timeout of ack rpc request, in seconds
-    pub rpc_timeout: u64,
-}
+    /// The shared [RttTracker] of this sender. It can be read after the sender is spawned.
+    pub fn rtt_tracker(&self) -> RttTracker {
+        self.rtt.clone()
+    }
 
-impl AckResponderBuilder {
-    pub fn build<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
-        &self,
-        host_addr: &HostAddr,
-        f: F,
-    ) -> (AckResponder<T>, mpsc::Sender<Ack>) {
-        let (tx, rx) = mpsc::channel(self.buf_size);
-        (
-            AckResponder {
-                delay_interval: tokio::time::interval(Duration::from_secs(self.delay)),
-                recv: rx,
-                gateway: f(
-                    host_addr,
-                    Duration::from_secs(self.connect_timeout),
-                    Duration::from_secs(self.rpc_timeout),
-                ),
-            },
-            tx,
-        )
+    /// Share `tracker` with this sender, so that the round-trip times of many senders can be read cluster-wide by [RttTracker::cluster_snapshot]
+    pub fn with_rtt_tracker(mut self, tracker: RttTracker) -> Self {
+        self.rtt = tracker;
+        self
     }
-}
 
-pub struct AckResponder<T: ReceiveAckRpcGateway> {
-    delay_interval: tokio::time::Interval,
-    recv: mpsc::Receiver<Ack>,
-    gateway: T,
-}
+    /// Share the heartbeat id sequence `ids` with this sender, so that the ids keep increasing when the senders of a node are rebuilt or added
+    pub fn with_heartbeat_ids(mut self, ids: Arc<AtomicU64>) -> Self {
+        self.current_heartbeat_id = ids;
+        self
+    }
 
-impl<T: ReceiveAckRpcGateway> Future for AckResponder<T> {
-    type Output = ();
+    /// Stop the sender gracefully once `shutdown` is signalled: the in-flight heartbeat is awaited, then the sender resolves without sending more.
+    /// Unlike aborting its task, a heartbeat is never interrupted halfway. Dropping the sending half of `shutdown` doesn't stop the sender.
+    pub fn with_shutdown(mut self, shutdown: oneshot::Receiver<()>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
 
-    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
-        let this = self.get_mut();
-        ready!(Pin::new(&mut this.delay_interval).poll_tick(cx));
-        let mut all_ack_futures = vec![];
+    /// Stamp the heartbeats with the [cluster::membership_digest] of `members`, so that the receiver can check whether it agrees on the members
+    /// by [cluster::check_membership_agreement]
+    pub fn with_membership(mut self, members: &[HostAddr]) -> Self {
+        self.membership_digest = cluster::membership_digest(members);
+        self
+    }
 
-        loop {
-            match this.recv.poll_recv(cx) {
-                Poll::Ready(Some(ack)) => {
-                    let future = this.gateway.receive_ack(ack.clone());
-                    all_ack_futures.push(future);
+    /// Record every dispatched heartbeat to `sink` before it's sent, whether it's delivered or not. The heartbeats of a dry run are not recorded.
+    /// The records are appended by an [audit::AuditWriter] on its own thread, so the sender never blocks on the sink.
+    pub fn with_audit_sink(self, sink: Arc<dyn audit::AuditSink>) -> Self {
+        self.with_audit_writer(audit::AuditWriter::spawn(sink))
+    }
+
+    /// Same as [HeartbeatSender::with_audit_sink], sharing the `writer` with the other senders
+    pub fn with_audit_writer(mut self, writer: audit::AuditWriter) -> Self {
+        self.audit_writer = Some(writer);
+        self
+    }
+
+    /// The distribution of the round-trip times of the successful heartbeats to the node. It's [None] if none has succeeded.
+    pub fn rtt_histogram(&self, host_addr: &HostAddr) -> Option<HistogramSnapshot> {
+        self.rtt.snapshot(host_addr)
+    }
+
+    pub fn update_execution_id(&mut self, execution_id: SubDataflowId) {
+        self.execution_id = Some(execution_id)
+    }
+
+    /// The shared [HeartbeatStats] of this sender. It can be read after the sender is spawned.
+    pub fn stats(&self) -> Arc<Mutex<HeartbeatStats>> {
+        self.stats.clone()
+    }
+
+    /// The fraction of successful heartbeat deliveries within the last `window`. See [HeartbeatStats::success_rate].
+    pub fn success_rate(&self, window: Duration) -> f64 {
+        self.stats.lock().unwrap().success_rate(window)
+    }
+
+    /// The [DryRunLog] of the heartbeats that would have been sent. It's [None] if the sender is not in dry-run mode.
+    pub fn dry_run_log(&self) -> Option<DryRunLog<Heartbeat>> {
+        self.dry_run_log.clone()
+    }
+
+    /// The remaining time until the next heartbeat tick. It's zero if the tick is due.
+    pub fn next_tick_in(&self) -> Duration {
+        self.next_tick
+            .saturating_duration_since(tokio::time::Instant::now())
+    }
+
+    /// schedule the next tick the same way as [tokio::time::Interval::poll_tick] does
+    fn schedule_next_tick(&mut self, tick: tokio::time::Instant) {
+        if let HeartbeatTicker::Jittered { .. } = self.ticker {
+            self.next_tick = self.ticker.deadline();
+            return;
+        }
+        let now = tokio::time::Instant::now();
+        let period = self.ticker.period();
+        self.next_tick = if now > tick + Duration::from_millis(5) {
+            match self.ticker.missed_tick_behavior() {
+                tokio::time::MissedTickBehavior::Burst => tick + period,
+                tokio::time::MissedTickBehavior::Delay => now + period,
+                tokio::time::MissedTickBehavior::Skip => {
+                    now + period
+                        - Duration::from_nanos(((now - tick).as_nanos() % period.as_nanos()) as u64)
+                }
+            }
+        } else {
+            tick + period
+        };
+    }
+}
+
+/// How clustered the next heartbeat ticks of several senders are, reported by [tick_alignment]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AlignmentMetric {
+    pub senders: usize,
+    /// the mean resultant length of the phases of the next ticks within their periods, from 0 if they're spread evenly to 1 if they're all at once.
+    /// A value close to 1 means the senders heartbeat in lockstep, i.e. a thundering herd which [HeartbeatBuilder::jitter_ms] spreads out.
+    pub alignment: f64,
+    /// the time between the earliest and the latest next tick
+    pub spread: Duration,
+}
+
+/// Measure how aligned the next ticks of `senders` are, e.g. the senders of a node to all the nodes of a cluster.
+/// Each next tick is taken as a phase within the period of its sender, so the ticks a whole period apart are aligned as well.
+/// The alignment is 1 for a single sender and 0 for none.
+pub fn tick_alignment<T: ReceiveHeartbeatRpcGateway>(
+    senders: &[&HeartbeatSender<T>],
+) -> AlignmentMetric {
+    let earliest = match senders.iter().map(|sender| sender.next_tick).min() {
+        Some(earliest) => earliest,
+        None => {
+            return AlignmentMetric {
+                senders: 0,
+                alignment: 0.0,
+                spread: Duration::ZERO,
+            }
+        }
+    };
+    let latest = senders
+        .iter()
+        .map(|sender| sender.next_tick)
+        .max()
+        .unwrap_or(earliest);
+    let (cos, sin) = senders.iter().fold((0.0, 0.0), |(cos, sin), sender| {
+        let period = sender.ticker.period().as_nanos().max(1);
+        let offset = (sender.next_tick - earliest).as_nanos() % period;
+        let angle = std::f64::consts::TAU * offset as f64 / period as f64;
+        (cos + angle.cos(), sin + angle.sin())
+    });
+    AlignmentMetric {
+        senders: senders.len(),
+        alignment: (cos * cos + sin * sin).sqrt() / senders.len() as f64,
+        spread: latest - earliest,
+    }
+}
+
+/// [HeartbeatTicker] fires the ticks of a [HeartbeatSender]
+enum HeartbeatTicker {
+    /// ticks at a fixed period
+    Fixed(tokio::time::Interval),
+    /// each tick is scheduled a fresh random delay within `period ± max_jitter` after the previous one fires
+    Jittered {
+        period: Duration,
+        max_jitter: Duration,
+        sleep: Pin<Box<tokio::time::Sleep>>,
+    },
+}
+
+impl HeartbeatTicker {
+    fn poll_tick(&mut self, cx: &mut task::Context<'_>) -> Poll<tokio::time::Instant> {
+        match self {
+            Self::Fixed(interval) => interval.poll_tick(cx),
+            Self::Jittered {
+                period,
+                max_jitter,
+                sleep,
+            } => {
+                ready!(sleep.as_mut().poll(cx));
+                let tick = sleep.deadline();
+                let delay = *period - *max_jitter + random_duration(*max_jitter * 2);
+                sleep.as_mut().reset(tokio::time::Instant::now() + delay);
+                Poll::Ready(tick)
+            }
+        }
+    }
+
+    /// the nominal period between ticks
+    fn period(&self) -> Duration {
+        match self {
+            Self::Fixed(interval) => interval.period(),
+            Self::Jittered { period, .. } => *period,
+        }
+    }
+
+    /// A jittered ticker schedules each tick after the previous one fires, so the missed ticks are always delayed
+    fn missed_tick_behavior(&self) -> tokio::time::MissedTickBehavior {
+        match self {
+            Self::Fixed(interval) => interval.missed_tick_behavior(),
+            Self::Jittered { .. } => tokio::time::MissedTickBehavior::Delay,
+        }
+    }
+
+    /// when the ticker fires next time, if it's not polled yet
+    fn deadline(&self) -> tokio::time::Instant {
+        match self {
+            Self::Fixed(_) => tokio::time::Instant::now(),
+            Self::Jittered { sleep, .. } => sleep.deadline(),
+        }
+    }
+}
+
+/// Whether the shutdown signal is received. A closed signal, whose sending half is dropped without sending, is cleared and never stops the owner.
+fn shutdown_signalled(
+    shutdown: &mut Option<oneshot::Receiver<()>>,
+    cx: &mut task::Context<'_>,
+) -> bool {
+    match shutdown.as_mut().map(|rx| rx.poll_unpin(cx)) {
+        Some(Poll::Ready(Ok(()))) => true,
+        Some(Poll::Ready(Err(_))) => {
+            *shutdown = None;
+            false
+        }
+        Some(Poll::Pending) | None => false,
+    }
+}
+
+/// a random duration within `[0, max]`
+pub(crate) fn random_duration(max: Duration) -> Duration {
+    if max.is_zero() {
+        Duration::ZERO
+    } else {
+        let random = uuid::Uuid::new_v4().as_u128() as u64;
+        Duration::from_nanos(random % (max.as_nanos() as u64 + 1))
+    }
+}
+
+/// [HeartbeatCoalescer] shares one [HeartbeatSender] per remote node among all the jobs heartbeating the node.
+///
+/// When a JobManager runs many jobs, each job heartbeating the same node on its own sends N heartbeats per tick.
+/// With [HeartbeatCoalescer], the node receives a single heartbeat per tick, which carries the execution ids of all the active jobs:
+/// the first one in `subdataflow_id` and the others in `coalesced_subdataflow_ids`.
+/// The sender of a node stops once all of its jobs are deregistered.
+pub struct HeartbeatCoalescer {
+    builder: HeartbeatBuilder,
+    task_id: ExecutorId,
+    nodes: HashMap<HostAddr, Arc<Mutex<Vec<SubDataflowId>>>>,
+}
+
+impl HeartbeatCoalescer {
+    pub fn new(builder: &HeartbeatBuilder, task_id: ExecutorId) -> Self {
+        Self {
+            builder: builder.clone(),
+            task_id,
+            nodes: Default::default(),
+        }
+    }
+
+    /// Register a job heartbeating `host_addr`. If it's the first job of the node, a new [HeartbeatSender] is built by `f` and returned,
+    /// which should be spawned by the caller. Otherwise the job joins the heartbeat of the existing sender and [None] is returned.
+    pub fn register<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveHeartbeatRpcGateway>(
+        &mut self,
+        host_addr: &HostAddr,
+        execution_id: SubDataflowId,
+        f: F,
+    ) -> Option<HeartbeatSender<T>> {
+        match self.nodes.get(host_addr) {
+            Some(execution_ids) => {
+                let mut execution_ids = execution_ids.lock().unwrap();
+                if !execution_ids.contains(&execution_id) {
+                    execution_ids.push(execution_id);
                 }
-                Poll::Ready(None) => continue,
-                _ => {
-                    join_all(cx, &mut all_ack_futures, |r| match r {
-                        Ok(_) => tracing::info!("ack success"),
-                        Err(status) => tracing::error!("ack failed: {}", status),
+                None
+            }
+            None => {
+                let execution_ids = Arc::new(Mutex::new(vec![execution_id.clone()]));
+                self.nodes.insert(host_addr.clone(), execution_ids.clone());
+                let mut sender = self.builder.build(host_addr, self.task_id, f);
+                sender.execution_id = Some(execution_id);
+                sender.coalesced = Some(execution_ids);
+                Some(sender)
+            }
+        }
+    }
+
+    /// Deregister a job heartbeating `host_addr`. The sender of the node stops on its next tick if no job is left.
+    pub fn deregister(&mut self, host_addr: &HostAddr, execution_id: &SubDataflowId) {
+        let drained = match self.nodes.get(host_addr) {
+            Some(execution_ids) => {
+                let mut execution_ids = execution_ids.lock().unwrap();
+                execution_ids.retain(|id| id != execution_id);
+                execution_ids.is_empty()
+            }
+            None => false,
+        };
+        if drained {
+            self.nodes.remove(host_addr);
+        }
+    }
+
+    /// the execution ids of the jobs heartbeating `host_addr`
+    pub fn execution_ids(&self, host_addr: &HostAddr) -> Vec<SubDataflowId> {
+        self.nodes
+            .get(host_addr)
+            .map(|execution_ids| execution_ids.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// The health of a heartbeated node, derived from its consecutive heartbeat failures by [NodeHealthTracker]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NodeHealth {
+    /// the latest heartbeat succeeded, or the node hasn't failed enough to be suspected
+    #[default]
+    Up,
+    /// the node failed [NodeHealthConfig::suspect_after] consecutive heartbeats
+    Suspect,
+    /// the node failed [NodeHealthConfig::down_after] consecutive heartbeats
+    Down,
+}
+
+/// The health record of a node kept by [NodeHealthTracker]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeHealthRecord {
+    pub health: NodeHealth,
+    pub consecutive_failures: u32,
+    /// when a down node is heartbeated next time, if it's backed off
+    pub next_attempt: Option<tokio::time::Instant>,
+    /// the name of the [SubnetProfile] of the node
+    pub subnet: Option<String>,
+}
+
+/// The number of nodes of each [NodeHealth] within a subnet
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubnetHealth {
+    pub up: usize,
+    pub suspect: usize,
+    pub down: usize,
+}
+
+/// [NodeHealthTracker] counts the consecutive heartbeat failures of each node and marks it up, suspect or down
+/// by the [NodeHealthConfig] of the node. A node is up again after a successful heartbeat. The clones share the same records.
+#[derive(Clone, Debug, Default)]
+pub struct NodeHealthTracker {
+    records: Arc<Mutex<HashMap<HostAddr, NodeHealthRecord>>>,
+}
+
+impl NodeHealthTracker {
+    /// the health of the node. A node never heartbeated is [None].
+    pub fn status(&self, host_addr: &HostAddr) -> Option<NodeHealth> {
+        self.get(host_addr).map(|record| record.health)
+    }
+
+    pub fn get(&self, host_addr: &HostAddr) -> Option<NodeHealthRecord> {
+        self.records.lock().unwrap().get(host_addr).cloned()
+    }
+
+    /// the health of all the heartbeated nodes
+    pub fn statuses(&self) -> HashMap<HostAddr, NodeHealth> {
+        self.records
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(host_addr, record)| (host_addr.clone(), record.health))
+            .collect()
+    }
+
+    /// the health of the nodes grouped by the name of their subnets. The nodes in no subnet are grouped under [None].
+    pub fn health_by_subnet(&self) -> BTreeMap<Option<String>, SubnetHealth> {
+        let mut subnets = BTreeMap::<Option<String>, SubnetHealth>::new();
+        self.records.lock().unwrap().values().for_each(|record| {
+            let subnet = subnets.entry(record.subnet.clone()).or_default();
+            match record.health {
+                NodeHealth::Up => subnet.up += 1,
+                NodeHealth::Suspect => subnet.suspect += 1,
+                NodeHealth::Down => subnet.down += 1,
+            }
+        });
+        subnets
+    }
+
+    /// whether the node should be heartbeated now. A down node isn't until its backoff elapses.
+    fn is_due(&self, host_addr: &HostAddr) -> bool {
+        self.get(host_addr)
+            .and_then(|record| record.next_attempt)
+            .map(|next_attempt| tokio::time::Instant::now() >= next_attempt)
+            .unwrap_or(true)
+    }
+
+    fn observe(
+        &self,
+        host_addr: &HostAddr,
+        subnet: Option<&String>,
+        config: &NodeHealthConfig,
+        success: bool,
+    ) {
+        let mut records = self.records.lock().unwrap();
+        let record = records.entry(host_addr.clone()).or_default();
+        let previous = record.health;
+        if success {
+            *record = NodeHealthRecord::default();
+        } else {
+            record.consecutive_failures += 1;
+            record.health = if record.consecutive_failures >= config.down_after.max(1) {
+                NodeHealth::Down
+            } else if record.consecutive_failures >= config.suspect_after.max(1) {
+                NodeHealth::Suspect
+            } else {
+                NodeHealth::Up
+            };
+            record.next_attempt = (record.health == NodeHealth::Down && config.down_backoff_ms > 0)
+                .then(|| {
+                    tokio::time::Instant::now() + Duration::from_millis(config.down_backoff_ms)
+                });
+        }
+        record.subnet = subnet.cloned();
+        if record.health != previous {
+            tracing::info!(
+                "node health changes from {:?} to {:?} [host_addr: {:?}, consecutive_failures: {}]",
+                previous,
+                record.health,
+                host_addr,
+                record.consecutive_failures,
+            );
+        }
+    }
+}
+
+/// the capacity of the channel of [FailureBurst] events. A lagging subscriber misses the oldest events.
+const FAILURE_BURST_CHANNEL_SIZE: usize = 16;
+
+/// The summary of the heartbeat failures of a node clustered within a window, emitted once per window instead of once per failure
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailureBurst {
+    pub addr: HostAddr,
+    pub failure_count: u32,
+    pub window: Duration,
+}
+
+/// [FailureBurstAggregator] counts the failures within a window which starts at the first failure.
+/// Once the window elapses, the failures are summarized into a [FailureBurst] if there are at least `min_failures` of them.
+/// A sustained problem emits a burst per window.
+#[derive(Debug)]
+struct FailureBurstAggregator {
+    window: Duration,
+    min_failures: u32,
+    window_start: Option<tokio::time::Instant>,
+    failure_count: u32,
+}
+
+impl FailureBurstAggregator {
+    fn new(config: FailureBurstConfig) -> Self {
+        Self {
+            window: Duration::from_millis(config.window_ms),
+            min_failures: config.min_failures.max(1),
+            window_start: None,
+            failure_count: 0,
+        }
+    }
+
+    /// observe the outcome of a heartbeat. The burst of the previous window is returned once the window elapses.
+    fn observe(&mut self, addr: &HostAddr, success: bool) -> Option<FailureBurst> {
+        let now = tokio::time::Instant::now();
+        let mut burst = None;
+        if let Some(window_start) = self.window_start {
+            if now.duration_since(window_start) >= self.window {
+                if self.failure_count >= self.min_failures {
+                    burst = Some(FailureBurst {
+                        addr: addr.clone(),
+                        failure_count: self.failure_count,
+                        window: self.window,
                     });
-                    return Poll::Pending;
                 }
+                self.window_start = None;
+                self.failure_count = 0;
+            }
+        }
+        if !success {
+            self.window_start.get_or_insert(now);
+            self.failure_count += 1;
+        }
+        burst
+    }
+}
+
+/// Statistics of the fan-out of each heartbeat tick
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeartbeatStats {
+    /// number of ticks sent
+    pub ticks: u64,
+    /// number of heartbeats sent to the node, excluding the suppressed, backed-off and dry-run ones
+    pub sent: u64,
+    /// number of heartbeats failed or timed out
+    pub failures: u64,
+    /// how long the latest tick took to send heartbeats to all gateways
+    pub last_fanout_duration: Duration,
+    /// the longest fan-out duration of all ticks
+    pub max_fanout_duration: Duration,
+    /// number of ticks whose fan-out took longer than the period
+    pub overrun_ticks: u64,
+    /// when each of the recent heartbeats was delivered, and whether it succeeded
+    deliveries: VecDeque<(tokio::time::Instant, bool)>,
+}
+
+/// max number of recent delivery outcomes kept by [HeartbeatStats]
+const MAX_DELIVERY_OUTCOMES: usize = 1024;
+
+impl HeartbeatStats {
+    fn record_delivery(&mut self, success: bool) {
+        if self.deliveries.len() == MAX_DELIVERY_OUTCOMES {
+            self.deliveries.pop_front();
+        }
+        self.deliveries
+            .push_back((tokio::time::Instant::now(), success));
+    }
+
+    /// The fraction of successful heartbeat deliveries within the last `window`, from 0.0 to 1.0.
+    /// It's 1.0 if nothing was delivered within the window. At most [MAX_DELIVERY_OUTCOMES] recent deliveries are counted.
+    pub fn success_rate(&self, window: Duration) -> f64 {
+        let now = tokio::time::Instant::now();
+        let (total, succeeded) = self
+            .deliveries
+            .iter()
+            .rev()
+            .take_while(|(delivered_at, _)| now.duration_since(*delivered_at) <= window)
+            .fold((0u64, 0u64), |(total, succeeded), (_, success)| {
+                (total + 1, succeeded + u64::from(*success))
+            });
+        if total == 0 {
+            1.0
+        } else {
+            succeeded as f64 / total as f64
+        }
+    }
+}
+
+/// a heartbeat waiting for the response of its receiver
+struct InFlightHeartbeat {
+    response: Pin<Box<dyn Future<Output = Result<Response, tonic::Status>> + Send>>,
+    heartbeat_id: u64,
+    /// when the heartbeat is sent
+    start: std::time::Instant,
+}
+
+impl<T: ReceiveHeartbeatRpcGateway + 'static> Future for HeartbeatSender<T> {
+    type Output = ();
+
+    /// Each tick sends a single heartbeat, whose response is awaited before the next tick is polled.
+    /// A failed heartbeat is logged and not retried until the next tick.
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let cluster_id = this.cluster_id.clone();
+        loop {
+            if let Some(in_flight) = this.in_flight.as_mut() {
+                let result = ready!(tenant::sync_scope(cluster_id.as_ref(), || in_flight
+                    .response
+                    .as_mut()
+                    .poll(cx)));
+                let (heartbeat_id, start) = (in_flight.heartbeat_id, in_flight.start);
+                this.in_flight = None;
+                this.complete_heartbeat(heartbeat_id, start, Some(result));
+            }
+
+            if shutdown_signalled(&mut this.shutdown, cx) {
+                tracing::info!(
+                    "heartbeat stops on shutdown [host_addr: {:?}, task_id: {}]",
+                    this.gateway.get_host_addr(),
+                    this.task_id,
+                );
+                return Poll::Ready(());
+            }
+
+            let tick = ready!(this.ticker.poll_tick(cx));
+            this.schedule_next_tick(tick);
+            if let Some(coalesced) = this.coalesced.as_ref() {
+                if coalesced.lock().unwrap().is_empty() {
+                    tracing::info!(
+                        "all jobs deregistered, coalesced heartbeat stops [host_addr: {:?}]",
+                        this.gateway.get_host_addr()
+                    );
+                    return Poll::Ready(());
+                }
+            }
+            this.send_heartbeat();
+        }
+    }
+}
+
+impl<T: ReceiveHeartbeatRpcGateway + 'static> HeartbeatSender<T> {
+    /// send the heartbeat of a tick. The response is awaited by [HeartbeatSender::poll] unless it's a dry run.
+    fn send_heartbeat(&mut self) {
+        let start = std::time::Instant::now();
+        let now = utils::times::now();
+        tracing::debug!("heartbeat sent at time {:?}", now);
+        let coalesced_subdataflow_ids = match self.coalesced.as_ref() {
+            Some(coalesced) => {
+                let execution_ids = coalesced.lock().unwrap();
+                self.execution_id = execution_ids.first().cloned();
+                execution_ids.iter().skip(1).cloned().collect()
+            }
+            None => vec![],
+        };
+        let subdataflow_id = match (&self.execution_id, &self.pre_execution) {
+            (Some(execution_id), _) => Some(execution_id.clone()),
+            (None, PreExecutionPolicy::SendAnyway) => None,
+            (None, PreExecutionPolicy::SuppressUntilSet) => {
+                tracing::debug!(
+                    "heartbeat suppressed until the execution id is set [host_addr: {:?}, task_id: {}]",
+                    self.gateway.get_host_addr(),
+                    self.task_id,
+                );
+                return;
+            }
+            (None, PreExecutionPolicy::UsePlaceholder(placeholder)) => Some(placeholder.clone()),
+        };
+        if self.dry_run_log.is_none() && !self.node_health.is_due(self.gateway.get_host_addr()) {
+            tracing::debug!(
+                "heartbeat backed off, the node is down [host_addr: {:?}, task_id: {}]",
+                self.gateway.get_host_addr(),
+                self.task_id,
+            );
+            return;
+        }
+        let mut heartbeat = Heartbeat {
+            heartbeat_id: self
+                .current_heartbeat_id
+                .fetch_add(1, atomic::Ordering::SeqCst),
+            timestamp: Some(prost_types::Timestamp {
+                seconds: now.timestamp(),
+                nanos: now.timestamp_subsec_nanos() as i32,
+            }),
+            node_type: NodeType::JobManager as i32,
+            subdataflow_id,
+            task_id: self.task_id,
+            coalesced_subdataflow_ids,
+            membership_digest: self.membership_digest,
+        };
+        if let Some(transform) = self.transform.as_ref() {
+            transform.apply(&mut heartbeat);
+        }
+        let heartbeat_id = heartbeat.heartbeat_id;
+        match self.dry_run_log.as_ref() {
+            Some(log) => {
+                tracing::info!(
+                    "heartbeat dry run [host_addr: {:?}, execution_id: {:?}, task_id: {}]",
+                    self.gateway.get_host_addr(),
+                    &self.execution_id,
+                    self.task_id,
+                );
+                log.record(self.gateway.get_host_addr(), heartbeat);
+                self.complete_heartbeat(heartbeat_id, start, None);
+            }
+            None => {
+                if let Some(writer) = self.audit_writer.as_ref() {
+                    writer.write(audit::AuditRecord::sent(
+                        self.gateway.get_host_addr(),
+                        &heartbeat,
+                    ));
+                }
+                let gateway = self.gateway.clone();
+                let budget = self.gateway.heartbeat_budget(self.rpc_timeout);
+                self.stats.lock().unwrap().sent += 1;
+                self.in_flight = Some(InFlightHeartbeat {
+                    response: Box::pin(trace::within(self.trace_context, async move {
+                        match tokio::time::timeout(budget, gateway.receive_heartbeat(heartbeat))
+                            .await
+                        {
+                            Ok(result) => result,
+                            Err(_) => Err(tonic::Status::deadline_exceeded(format!(
+                                "heartbeat not responded within {:?}",
+                                budget
+                            ))),
+                        }
+                    })),
+                    heartbeat_id,
+                    start,
+                });
+            }
+        }
+    }
+
+    /// record the outcome of a heartbeat. `result` is [None] if it's a dry run.
+    fn complete_heartbeat(
+        &mut self,
+        heartbeat_id: u64,
+        start: std::time::Instant,
+        result: Option<Result<Response, tonic::Status>>,
+    ) {
+        let delivered = result.as_ref().map(Result::is_ok);
+        if delivered == Some(true) {
+            self.rtt
+                .record(self.gateway.get_host_addr(), start.elapsed());
+        }
+        match &result {
+            Some(Ok(_)) => tracing::info!(
+                "heartbeat sent success  [execution_id: {:?}, task_id: {}]",
+                &self.execution_id,
+                self.task_id,
+            ),
+            Some(Err(err)) => tracing::error!(
+                "heartbeat sent failed, [execution_id: {:?}, task_id: {}], err: {}",
+                &self.execution_id,
+                self.task_id,
+                err,
+            ),
+            None => {}
+        }
+
+        if let (Some(results), Some(result)) = (self.results.as_ref(), result) {
+            let result = HeartbeatResult {
+                host_addr: self.gateway.get_host_addr().clone(),
+                heartbeat_id,
+                result,
+            };
+            match results.try_send(result) {
+                Ok(_) => {}
+                Err(TrySendError::Full(result)) => tracing::warn!(
+                    "heartbeat result dropped, the receiver lags behind [host_addr: {:?}, heartbeat_id: {}]",
+                    &result.host_addr,
+                    result.heartbeat_id,
+                ),
+                Err(TrySendError::Closed(_)) => self.results = None,
+            }
+        }
+
+        if let Some(success) = delivered {
+            self.node_health.observe(
+                self.gateway.get_host_addr(),
+                self.subnet.as_ref(),
+                &self.node_health_config,
+                success,
+            );
+        }
+
+        if let (Some(aggregator), Some(success)) = (self.burst_aggregator.as_mut(), delivered) {
+            if let Some(burst) = aggregator.observe(self.gateway.get_host_addr(), success) {
+                tracing::warn!(
+                    "{} heartbeats failed within {:?} [host_addr: {:?}, execution_id: {:?}, task_id: {}]",
+                    burst.failure_count,
+                    burst.window,
+                    &burst.addr,
+                    &self.execution_id,
+                    self.task_id,
+                );
+                let _ = self.failure_bursts.send(burst);
+            }
+        }
+
+        let fanout_duration = start.elapsed();
+        let period = self.ticker.period();
+        let mut stats = self.stats.lock().unwrap();
+        stats.ticks += 1;
+        if let Some(success) = delivered {
+            stats.record_delivery(success);
+            if !success {
+                stats.failures += 1;
             }
         }
+        stats.last_fanout_duration = fanout_duration;
+        stats.max_fanout_duration = stats.max_fanout_duration.max(fanout_duration);
+        if fanout_duration > period {
+            stats.overrun_ticks += 1;
+            tracing::warn!(
+                "heartbeat fan-out took {:?}, longer than the period {:?} [execution_id: {:?}, task_id: {}]",
+                fanout_duration,
+                period,
+                &self.execution_id,
+                self.task_id,
+            );
+        }
+    }
+}
+
+/// [DryRunLog] records the messages which a sender in dry-run mode would have sent, with the address of their receivers.
+/// It's shared by the sender and its readers, so it can be read after the sender is spawned.
+#[derive(Debug)]
+pub struct DryRunLog<M> {
+    entries: Arc<Mutex<Vec<(HostAddr, M)>>>,
+}
+
+impl<M> Default for DryRunLog<M> {
+    fn default() -> Self {
+        Self {
+            entries: Default::default(),
+        }
+    }
+}
+
+impl<M> Clone for DryRunLog<M> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<M: Clone> DryRunLog<M> {
+    pub fn record(&self, host_addr: &HostAddr, message: M) {
+        self.entries
+            .lock()
+            .unwrap()
+            .push((host_addr.clone(), message))
+    }
+
+    /// all the recorded messages in the order they would have been sent
+    pub fn entries(&self) -> Vec<(HostAddr, M)> {
+        self.entries.lock().unwrap().clone()
+    }
+}
+
+/// The builder of [AckResponder] which is also the configuration of ACK
+///
+/// AckResponderBuilder::build has three arguments:
+/// - First Arg: the host address of remote node
+/// - Second Arg: rpc connection timeout
+/// - Third Arg: rpc request timeout
+///
+/// It will return two values:
+/// - a new [AckResponder]
+/// - a [mpsc::Sender] channel for [Ack] messages. Users can trigger ack by send an [Ack] message into it.
+///   If [AckResponderBuilder::adaptive_buffer] is set, build it by [AckResponderBuilder::build_resizable] instead, which returns a [ResizableSender].
+///
+/// [AckResponder] implements [Future]. Users can run an [AckResponder] by:
+/// - Tokio spawn
+/// - async/await
+///
+/// # Example of Tokio spawn
+/// ```
+/// use common::net::{AckResponderBuilder, gateway::taskmanager::SafeTaskManagerRpcGateway};
+/// use proto::common::HostAddr;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let builder = AckResponderBuilder {
+///         delay: 3,
+///         buf_size: 10,
+///         connect_timeout: 3,
+///         rpc_timeout: 3,
+///         ..Default::default()
+///     };
+///     
+///     let ref addr = HostAddr {
+///         host: "localhost".to_string(),
+///         port: 8080
+///     };
+///
+///     let (responder, _) = builder.build(addr, |addr, connect_timeout, rpc_timeout| SafeTaskManagerRpcGateway::with_timeout(addr, connect_timeout, rpc_timeout));
+///     let _ = tokio::spawn(responder);
+/// }
+/// ```
+///
+/// # Example of Tokio spwan
+/// ```
+/// use common::net::{AckResponderBuilder, gateway::taskmanager::SafeTaskManagerRpcGateway};
+/// use std::time::Duration;
+/// use proto::common::HostAddr;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let builder = AckResponderBuilder {
+///         delay: 3,
+///         buf_size: 10,
+///         connect_timeout: 3,
+///         rpc_timeout: 3,
+///         ..Default::default()
+///     };
+///
+///     let ref addr = HostAddr {
+///         host: "localhost".to_string(),
+///         port: 8080
+///     };
+///     
+///     let (responder, _) = builder.build(addr, |addr, connect_timeout, rpc_timeout| SafeTaskManagerRpcGateway::with_timeout(addr, connect_timeout, rpc_timeout));
+///     let _ = tokio::time::timeout(Duration::from_secs(1), responder);
+/// }
+/// ```
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct AckResponderBuilder {
+    /// how long an ack is delayed before it's dispatched, in seconds, so that the acks enqueued meanwhile are dispatched in the same batch.
+    /// The delay starts when the first ack of a batch is enqueued, so no ack waits longer than it however long the previous dispatch took.
+    /// Zero is clamped to [MIN_TICK_SECS].
+    pub delay: u64,
+    /// delay duration, in milliseconds. It overrides `delay` if it's set.
+    #[serde(default)]
+    pub delay_ms: Option<u64>,
+    // buffer ack queue size
+    pub buf_size: usize,
+    /// timeout of ack rpc connection, in seconds
+    pub connect_timeout: u64,
+    /// timeout of ack rpc request, in seconds
+    pub rpc_timeout: u64,
+    /// how acks are dispatched to the remote nodes
+    #[serde(default)]
+    pub dispatch_mode: DispatchMode,
+    /// id of the cluster which acks are stamped with
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    /// if it's set, the ack queue starts with `buf_size` and adapts its size to the observed load
+    #[serde(default)]
+    pub adaptive_buffer: Option<AdaptiveBufferBuilder>,
+    /// max number of connections established simultaneously by [AckResponder::warm_up]
+    #[serde(default = "default_connect_concurrency")]
+    pub connect_concurrency: usize,
+    /// go through all the dispatching logic but record the acks in a [DryRunLog] instead of sending them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// read feature toggles from the environment on building, which override the configured ones: `LIGHTFLUS_ACK_DRY_RUN` and `LIGHTFLUS_ACK_BATCHING`
+    #[serde(default)]
+    pub env_toggles: bool,
+    /// dispatch the acks of a batch by their priority, from the highest to the lowest, instead of FIFO
+    #[serde(default)]
+    pub prioritized: bool,
+    /// sort the acks of a batch by the key before they're dispatched. If [AckResponderBuilder::prioritized] is also set,
+    /// the acks are ordered by their priority first, and by the key within the same priority.
+    #[serde(default)]
+    pub sort_key: Option<AckSortKey>,
+    /// keep the duplicated node addresses, which get one gateway each. By default, the addresses are deduplicated by [HostAddr::normalized].
+    #[serde(default)]
+    pub preserve_duplicate_nodes: bool,
+    /// dispatch the acks enqueued within `delay` in one call. If it's disabled, every ack is dispatched in a call of its own as soon as it's enqueued.
+    #[serde(default = "default_batching")]
+    pub batching: bool,
+}
+
+fn default_connect_concurrency() -> usize {
+    DEFAULT_CONNECT_CONCURRENCY
+}
+
+fn default_batching() -> bool {
+    true
+}
+
+impl Default for AckResponderBuilder {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_ACK_DELAY,
+            delay_ms: None,
+            buf_size: DEFAULT_ACK_BUF_SIZE,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+            dispatch_mode: Default::default(),
+            cluster_id: None,
+            adaptive_buffer: None,
+            connect_concurrency: DEFAULT_CONNECT_CONCURRENCY,
+            dry_run: false,
+            env_toggles: false,
+            prioritized: false,
+            sort_key: None,
+            preserve_duplicate_nodes: false,
+            batching: true,
+        }
+    }
+}
+
+impl AckResponderBuilder {
+    pub fn build<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
+        &self,
+        host_addr: &HostAddr,
+        f: F,
+    ) -> (AckResponder<T>, mpsc::Sender<Ack>) {
+        self.build_with_nodes(std::slice::from_ref(host_addr), f)
+    }
+
+    /// Same as [AckResponderBuilder::build], but the ack queue is a [resizable_channel],
+    /// which adapts its size to the load if [AckResponderBuilder::adaptive_buffer] is set
+    pub fn build_resizable<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
+        &self,
+        host_addr: &HostAddr,
+        f: F,
+    ) -> (AckResponder<T>, ResizableSender<Ack>) {
+        self.build_resizable_with_nodes(std::slice::from_ref(host_addr), f)
+    }
+
+    /// Same as [AckResponderBuilder::build_resizable], but the returned sender records how long the producers are blocked by a full ack queue
+    pub fn build_instrumented<
+        F: Fn(&HostAddr, Duration, Duration) -> T,
+        T: ReceiveAckRpcGateway,
+    >(
+        &self,
+        host_addr: &HostAddr,
+        f: F,
+    ) -> (AckResponder<T>, InstrumentedSender<Ack>) {
+        let (responder, tx) = self.build_resizable(host_addr, f);
+        (responder, tx.instrumented())
+    }
+
+    /// Build an [AckResponder] which dispatches acks to several remote nodes according to [AckResponderBuilder::dispatch_mode]
+    pub fn build_with_nodes<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
+        &self,
+        host_addrs: &[HostAddr],
+        f: F,
+    ) -> (AckResponder<T>, mpsc::Sender<Ack>) {
+        if self.adaptive_buffer.is_some() {
+            tracing::warn!(
+                "adaptive ack buffer is ignored by a fixed-size ack queue, build the responder by build_resizable instead"
+            );
+        }
+        let (tx, rx) = mpsc::channel(self.queue_capacity());
+        (
+            self.build_responder(host_addrs, f, AckQueue::fixed(rx, &tx)),
+            tx,
+        )
+    }
+
+    /// Same as [AckResponderBuilder::build_with_nodes], but the ack queue is a [resizable_channel]
+    pub fn build_resizable_with_nodes<
+        F: Fn(&HostAddr, Duration, Duration) -> T,
+        T: ReceiveAckRpcGateway,
+    >(
+        &self,
+        host_addrs: &[HostAddr],
+        f: F,
+    ) -> (AckResponder<T>, ResizableSender<Ack>) {
+        let (tx, rx) = resizable_channel(self.queue_capacity());
+        (
+            self.build_responder(host_addrs, f, AckQueue::Resizable(rx)),
+            tx,
+        )
+    }
+
+    /// the capacity of the ack queue. Zero is clamped to one, as no ack could ever be enqueued into an empty queue.
+    fn queue_capacity(&self) -> usize {
+        if self.buf_size == 0 {
+            tracing::warn!("ack buf_size is zero, clamped to 1");
+            1
+        } else {
+            self.buf_size
+        }
+    }
+
+    fn build_responder<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
+        &self,
+        host_addrs: &[HostAddr],
+        f: F,
+        recv: AckQueue,
+    ) -> AckResponder<T> {
+        let host_addrs = if self.preserve_duplicate_nodes {
+            host_addrs.to_vec()
+        } else {
+            dedup_host_addrs(host_addrs)
+        };
+        let delay = clamp_tick("ack delay", self.delay, self.delay_ms);
+        // only a resizable queue can adapt its size
+        let adaptive_buffer = self
+            .adaptive_buffer
+            .as_ref()
+            .filter(|_| matches!(recv, AckQueue::Resizable(_)));
+        AckResponder {
+            delay,
+            flush_at: None,
+            resize_tick: adaptive_buffer.map(|_| tokio::time::interval(delay)),
+            buffer_sizer: adaptive_buffer.map(|adaptive| adaptive.build(recv.capacity())),
+            recv,
+            gateways: Arc::new(
+                host_addrs
+                    .iter()
+                    .map(|host_addr| {
+                        f(
+                            host_addr,
+                            Duration::from_secs(self.connect_timeout),
+                            Duration::from_secs(self.rpc_timeout),
+                        )
+                    })
+                    .collect(),
+            ),
+            in_flight: Default::default(),
+            dispatch_mode: clamp_quorum(self.dispatch_mode, host_addrs.len()),
+            failures: Default::default(),
+            cluster_id: self.cluster_id.clone(),
+            connect_concurrency: self.connect_concurrency,
+            dry_run_log: feature_toggle(self.env_toggles, "ACK_DRY_RUN", self.dry_run)
+                .then(Default::default),
+            prioritized: self.prioritized,
+            sort_key: self.sort_key,
+            dispatch_winners: broadcast::channel(DISPATCH_WINNER_CHANNEL_SIZE).0,
+            delivered: Default::default(),
+            rpc_timeout: Duration::from_secs(self.rpc_timeout),
+            shutdown: None,
+            flush_on_shutdown: false,
+            stopping: false,
+            trace_context: trace::TraceContext::current(),
+            router: None,
+            batching: feature_toggle(self.env_toggles, "ACK_BATCHING", self.batching),
+        }
+    }
+
+    /// Build a [ShardedAckResponder] of `shards` [AckResponder]s, each with its own ack queue and its own gateways built by `f`
+    pub fn build_sharded<F: Fn(&HostAddr, Duration, Duration) -> T, T: ReceiveAckRpcGateway>(
+        &self,
+        host_addrs: &[HostAddr],
+        shards: usize,
+        f: F,
+    ) -> (ShardedAckResponder<T>, ShardedAckSender) {
+        let (responders, senders) = (0..shards.max(1))
+            .map(|_| self.build_resizable_with_nodes(host_addrs, &f))
+            .unzip();
+        (
+            ShardedAckResponder { responders },
+            ShardedAckSender { senders },
+        )
+    }
+}
+
+/// remove the duplicated addresses by [HostAddr::normalized]. The first spelling of each node is kept.
+fn dedup_host_addrs(host_addrs: &[HostAddr]) -> Vec<HostAddr> {
+    let mut seen = HashSet::new();
+    host_addrs
+        .iter()
+        .filter(|host_addr| {
+            let unique = seen.insert(host_addr.normalized());
+            if !unique {
+                tracing::warn!(
+                    "duplicated node address is ignored [host_addr: {:?}]",
+                    host_addr
+                );
+            }
+            unique
+        })
+        .cloned()
+        .collect()
+}
+
+/// [NetConfig] is a serializable dump of the net configuration of a node for diagnostics, e.g. support bundles.
+///
+/// Besides the configured builders, it carries the effective values which the senders are actually built with:
+/// the ticks after clamping and overriding, and the feature toggles after reading the environment.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct NetConfig {
+    pub heartbeat: HeartbeatBuilder,
+    pub ack: AckResponderBuilder,
+    /// the remote nodes of the node
+    pub nodes: Vec<HostAddr>,
+    /// the effective heartbeat period of every remote node, in milliseconds
+    pub heartbeat_periods_ms: Vec<NodePeriod>,
+    /// the effective ack delay, in milliseconds
+    pub ack_delay_ms: u64,
+    /// the effective feature toggles, by the name of their environment variables without [FEATURE_TOGGLE_PREFIX]
+    pub feature_toggles: BTreeMap<&'static str, bool>,
+}
+
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct NodePeriod {
+    pub host_addr: HostAddr,
+    pub period_ms: u64,
+}
+
+impl NetConfig {
+    pub fn new(
+        heartbeat: &HeartbeatBuilder,
+        ack: &AckResponderBuilder,
+        nodes: &[HostAddr],
+    ) -> Self {
+        let feature_toggles = BTreeMap::from([
+            (
+                "HEARTBEAT_SKIP_MISSED_TICKS",
+                feature_toggle(
+                    heartbeat.env_toggles,
+                    "HEARTBEAT_SKIP_MISSED_TICKS",
+                    heartbeat.skip_missed_ticks,
+                ),
+            ),
+            (
+                "HEARTBEAT_DRY_RUN",
+                feature_toggle(
+                    heartbeat.env_toggles,
+                    "HEARTBEAT_DRY_RUN",
+                    heartbeat.dry_run,
+                ),
+            ),
+            (
+                "HEARTBEAT_JITTER",
+                feature_toggle(
+                    heartbeat.env_toggles,
+                    "HEARTBEAT_JITTER",
+                    heartbeat.jitter_ms > 0,
+                ),
+            ),
+            (
+                "ACK_DRY_RUN",
+                feature_toggle(ack.env_toggles, "ACK_DRY_RUN", ack.dry_run),
+            ),
+            (
+                "ACK_BATCHING",
+                feature_toggle(ack.env_toggles, "ACK_BATCHING", ack.batching),
+            ),
+        ]);
+
+        Self {
+            heartbeat: heartbeat.clone(),
+            ack: ack.clone(),
+            nodes: nodes.to_vec(),
+            heartbeat_periods_ms: nodes
+                .iter()
+                .map(|host_addr| NodePeriod {
+                    host_addr: host_addr.clone(),
+                    period_ms: heartbeat.period_of(host_addr).as_millis() as u64,
+                })
+                .collect(),
+            ack_delay_ms: clamp_tick("ack delay", ack.delay, ack.delay_ms).as_millis() as u64,
+            feature_toggles,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// [ShardedAckResponder] scales the ack throughput by several [AckResponder]s, i.e. the shards, which can be run by different tasks.
+///
+/// A single [AckResponder] becomes a bottleneck at very high ack rates, because all acks funnel through one poll loop.
+/// [ShardedAckSender] hashes acks by their execution id across the shards, so the acks of the same execution are always dispatched by the same shard in order.
+pub struct ShardedAckResponder<T: ReceiveAckRpcGateway> {
+    responders: Vec<AckResponder<T>>,
+}
+
+impl<T: ReceiveAckRpcGateway> ShardedAckResponder<T> {
+    pub fn num_shards(&self) -> usize {
+        self.responders.len()
+    }
+
+    pub fn into_shards(self) -> Vec<AckResponder<T>> {
+        self.responders
+    }
+}
+
+impl<T: ReceiveAckRpcGateway + 'static> ShardedAckResponder<T> {
+    /// spawn a tokio task for each shard
+    pub fn spawn(self) -> Vec<tokio::task::JoinHandle<()>> {
+        self.responders.into_iter().map(tokio::spawn).collect()
+    }
+}
+
+/// The sending-half of the ack queue of [AckResponder] or [ShardedAckResponder].
+/// [AckSender::try_ack] never waits, so it can be called within the poll of an operator's own future, where `.await` isn't possible.
+pub trait AckSender {
+    /// Send an ack without waiting. If the queue is full, the ack is returned by [TrySendError::Full] immediately,
+    /// so that the caller decides whether to drop it, buffer it or retry it later.
+    fn try_ack(&self, ack: Ack) -> Result<(), TrySendError<Ack>>;
+}
+
+impl AckSender for mpsc::Sender<Ack> {
+    fn try_ack(&self, ack: Ack) -> Result<(), TrySendError<Ack>> {
+        self.try_send(ack)
+    }
+}
+
+impl AckSender for ResizableSender<Ack> {
+    fn try_ack(&self, ack: Ack) -> Result<(), TrySendError<Ack>> {
+        self.try_send(ack)
+    }
+}
+
+impl AckSender for InstrumentedSender<Ack> {
+    fn try_ack(&self, ack: Ack) -> Result<(), TrySendError<Ack>> {
+        self.try_send(ack)
+    }
+}
+
+impl AckSender for ShardedAckSender {
+    fn try_ack(&self, ack: Ack) -> Result<(), TrySendError<Ack>> {
+        self.try_send(ack)
+    }
+}
+
+/// The sending-half of [ShardedAckResponder]. The acks without execution id are sent to the first shard.
+#[derive(Clone)]
+pub struct ShardedAckSender {
+    senders: Vec<ResizableSender<Ack>>,
+}
+
+impl ShardedAckSender {
+    /// the index of the shard which dispatches the ack
+    pub fn shard_of(&self, ack: &Ack) -> usize {
+        match ack.execution_id.as_ref() {
+            Some(execution_id) => {
+                let mut hasher = DefaultHasher::new();
+                execution_id.hash(&mut hasher);
+                (hasher.finish() % self.senders.len() as u64) as usize
+            }
+            None => 0,
+        }
+    }
+
+    pub async fn send(&self, ack: Ack) -> Result<(), SendError<Ack>> {
+        self.senders[self.shard_of(&ack)].send(ack).await
+    }
+
+    pub fn try_send(&self, ack: Ack) -> Result<(), TrySendError<Ack>> {
+        self.senders[self.shard_of(&ack)].try_send(ack)
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.senders.len()
+    }
+}
+
+/// The configuration of adaptive ack queue
+///
+/// The queue doubles its capacity, up to `max_buf_size`, once it has been full on `grow_after` consecutive ticks of [AckResponder].
+/// It halves its capacity, down to the baseline `buf_size` of [AckResponderBuilder], once it has been quiet, i.e. at most a quarter full,
+/// on `shrink_after` consecutive ticks.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveBufferBuilder {
+    /// the upper bound of the queue size
+    pub max_buf_size: usize,
+    /// how many consecutive full ticks trigger growing
+    #[serde(default = "default_grow_after")]
+    pub grow_after: u32,
+    /// how many consecutive quiet ticks trigger shrinking
+    #[serde(default = "default_shrink_after")]
+    pub shrink_after: u32,
+}
+
+fn default_grow_after() -> u32 {
+    3
+}
+
+fn default_shrink_after() -> u32 {
+    10
+}
+
+impl AdaptiveBufferBuilder {
+    pub fn build(&self, baseline: usize) -> BufferSizer {
+        BufferSizer {
+            baseline,
+            max: self.max_buf_size.max(baseline),
+            grow_after: self.grow_after,
+            shrink_after: self.shrink_after,
+            full_ticks: 0,
+            quiet_ticks: 0,
+        }
+    }
+}
+
+/// [BufferSizer] decides the capacity of an adaptive queue by the backlog observed on each tick
+#[derive(Clone, Debug)]
+pub struct BufferSizer {
+    baseline: usize,
+    max: usize,
+    grow_after: u32,
+    shrink_after: u32,
+    full_ticks: u32,
+    quiet_ticks: u32,
+}
+
+impl BufferSizer {
+    /// Observe the backlog of the queue. A new capacity is returned if the queue should be resized.
+    pub fn observe(&mut self, backlog: usize, capacity: usize) -> Option<usize> {
+        if backlog >= capacity {
+            self.quiet_ticks = 0;
+            self.full_ticks += 1;
+            if self.full_ticks >= self.grow_after && capacity < self.max {
+                self.full_ticks = 0;
+                return Some(capacity.saturating_mul(2).clamp(self.baseline, self.max));
+            }
+        } else if backlog <= capacity / 4 {
+            self.full_ticks = 0;
+            self.quiet_ticks += 1;
+            if self.quiet_ticks >= self.shrink_after && capacity > self.baseline {
+                self.quiet_ticks = 0;
+                return Some((capacity / 2).clamp(self.baseline, self.max));
+            }
+        } else {
+            self.full_ticks = 0;
+            self.quiet_ticks = 0;
+        }
+        None
+    }
+}
+
+/// The way a message is dispatched to the gateways of a sender
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DispatchMode {
+    /// send the message to every gateway
+    #[default]
+    Broadcast,
+    /// try gateways one by one, ordered by least-recent-failure, and stop at the first success
+    AnyOfFirstSuccess,
+    /// send the message to every gateway concurrently, and stop once `n` of them succeed
+    QuorumOf(usize),
+}
+
+/// The key which the acks of a batch are sorted by before they're dispatched, see [AckResponderBuilder::sort_key]
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AckSortKey {
+    /// the ascending request id, e.g. the heartbeat id. The acks without request id go last.
+    RequestId,
+    /// the ascending timestamp. The acks without timestamp go last.
+    Timestamp,
+}
+
+impl AckSortKey {
+    /// sort the acks by the key. The sort is stable, so the acks of the same key keep the FIFO order.
+    pub fn sort(&self, acks: &mut [Ack]) {
+        match self {
+            Self::RequestId => acks.sort_by_key(|ack| {
+                let request_id = ack.request_id.as_ref().map(|request_id| match request_id {
+                    proto::common::ack::RequestId::HeartbeatId(heartbeat_id) => *heartbeat_id,
+                });
+                (request_id.is_none(), request_id)
+            }),
+            Self::Timestamp => acks.sort_by_key(|ack| {
+                let timestamp = ack
+                    .timestamp
+                    .as_ref()
+                    .map(|timestamp| (timestamp.seconds, timestamp.nanos));
+                (timestamp.is_none(), timestamp)
+            }),
+        }
+    }
+}
+
+/// Whether the dispatching of a message is complete, see [DispatchMode::completion]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Completion {
+    /// more responses are needed
+    Pending,
+    /// the completion condition of the mode is met
+    Succeeded,
+    /// the completion condition can't be met anymore
+    Failed,
+}
+
+impl DispatchMode {
+    /// Evaluate the completion condition of the mode after `successes` and `failures` of `total` gateways respond:
+    /// [DispatchMode::Broadcast] needs all the gateways to succeed, [DispatchMode::AnyOfFirstSuccess] needs one,
+    /// and [DispatchMode::QuorumOf] needs `n`.
+    pub fn completion(&self, successes: usize, failures: usize, total: usize) -> Completion {
+        let required = match self {
+            DispatchMode::Broadcast => total,
+            DispatchMode::AnyOfFirstSuccess => total.min(1),
+            DispatchMode::QuorumOf(n) => *n,
+        };
+        if successes >= required {
+            Completion::Succeeded
+        } else if successes + total.saturating_sub(successes + failures) < required {
+            Completion::Failed
+        } else {
+            Completion::Pending
+        }
+    }
+}
+
+/// The failure record of a remote node
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FailureRecord {
+    /// how many times the node has failed
+    pub failure_count: u64,
+    /// when the node failed last time
+    pub last_failure: Option<tokio::time::Instant>,
+}
+
+/// [GatewayFailures] records failures of each remote node and orders gateways from the healthiest to the least healthy
+#[derive(Clone, Debug, Default)]
+pub struct GatewayFailures {
+    records: HashMap<HostAddr, FailureRecord>,
+}
+
+impl GatewayFailures {
+    pub fn record_failure(&mut self, host_addr: &HostAddr) {
+        let record = self.records.entry(host_addr.clone()).or_default();
+        record.failure_count += 1;
+        record.last_failure = Some(tokio::time::Instant::now());
+    }
+
+    pub fn get(&self, host_addr: &HostAddr) -> FailureRecord {
+        self.records.get(host_addr).copied().unwrap_or_default()
+    }
+
+    /// Order gateways by least-recent-failure. Gateways that never failed come first, and ties are broken by the lowest failure count.
+    /// The sort is stable, so gateways with the same record keep their configured order.
+    pub fn order<'a, T: RpcGateway>(&self, gateways: &'a [T]) -> Vec<&'a T> {
+        let mut ordered = gateways.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|gateway| {
+            let record = self.get(gateway.get_host_addr());
+            (record.last_failure, record.failure_count)
+        });
+        ordered
+    }
+}
+
+/// Send a batch of acks to `gateway`, and return the result of each ack. Every ack fails if the call is not responded within `rpc_timeout`,
+/// so that a gateway which never responds can't block the dispatch to the other nodes.
+async fn receive_acks_within<T: ReceiveAckRpcGateway>(
+    gateway: &T,
+    acks: Vec<Ack>,
+    rpc_timeout: Duration,
+) -> Vec<Result<Response, tonic::Status>> {
+    let len = acks.len();
+    match tokio::time::timeout(rpc_timeout, gateway.receive_acks(acks)).await {
+        Ok(results) => results,
+        Err(_) => gateway::ack_batch_results(
+            len,
+            Err(tonic::Status::deadline_exceeded(format!(
+                "acks not responded within {:?} [host_addr: {:?}]",
+                rpc_timeout,
+                gateway.get_host_addr()
+            ))),
+        ),
+    }
+}
+
+/// Establish connections of all gateways in bounded waves, so that a large cluster won't overwhelm the local socket table.
+/// At most `connect_concurrency` connections are in progress at the same time. The results are in the same order as the gateways.
+pub async fn connect_all<T: ConnectRpcGateway>(
+    gateways: &[T],
+    connect_concurrency: usize,
+) -> Vec<(&HostAddr, Result<(), tonic::transport::Error>)> {
+    let permits = tokio::sync::Semaphore::new(connect_concurrency.max(1));
+    let permits = &permits;
+    futures_util::future::join_all(gateways.iter().map(|gateway| async move {
+        let _permit = permits.acquire().await;
+        (gateway.get_host_addr(), gateway.connect().await)
+    }))
+    .await
+}
+
+/// [AckRouter] resolves the destination node of an [Ack]
+pub trait AckRouter {
+    /// return the address of the destination node, or [None] if the ack can not be routed
+    fn route(&self, ack: &Ack) -> Option<HostAddr>;
+}
+
+impl<F: Fn(&Ack) -> Option<HostAddr>> AckRouter for F {
+    fn route(&self, ack: &Ack) -> Option<HostAddr> {
+        self(ack)
+    }
+}
+
+/// Group acks by their resolved destination so that each node can receive all of its acks in one batch.
+/// Acks are kept in their original order within each group.
+/// Acks that can not be routed are grouped under [HostAddr::default()], which is never a valid address.
+pub fn partition_acks_by_target<R: AckRouter + ?Sized>(
+    acks: Vec<Ack>,
+    router: &R,
+) -> HashMap<HostAddr, Vec<Ack>> {
+    let mut partitions = HashMap::<HostAddr, Vec<Ack>>::new();
+    acks.into_iter().for_each(|ack| {
+        let target = router.route(&ack).unwrap_or_default();
+        partitions.entry(target).or_default().push(ack)
+    });
+    partitions
+}
+
+/// the result of each ack dispatched to each remote node by a batch
+type AckResults = Vec<(HostAddr, Vec<Result<Response, tonic::Status>>)>;
+
+/// [AckResponder] dispatches the acks in batches. A batch starts with the first ack enqueued into the empty queue,
+/// and it's dispatched with all the acks enqueued meanwhile once [AckResponderBuilder::delay] elapses since then.
+/// The next batch isn't dispatched until the calls of the previous one are responded, or time out after `rpc_timeout`.
+/// An idle responder doesn't wake up until the next ack is enqueued, and it resolves once all the senders are dropped with no ack left.
+pub struct AckResponder<T: ReceiveAckRpcGateway> {
+    /// the debounce delay of each batch
+    delay: Duration,
+    /// when the pending batch is dispatched. It's [None] if no ack is pending.
+    flush_at: Option<Pin<Box<tokio::time::Sleep>>>,
+    /// the steady tick on which the queue is resized, if the buffer is adaptive. It's independent of the dispatching of batches.
+    resize_tick: Option<tokio::time::Interval>,
+    recv: AckQueue,
+    /// shared with the calls of the dispatched batches
+    gateways: Arc<Vec<T>>,
+    /// the calls of the dispatched batches, which are awaited by [AckResponder::poll]
+    in_flight: FuturesUnordered<Pin<Box<dyn Future<Output = AckResults> + Send>>>,
+    dispatch_mode: DispatchMode,
+    failures: GatewayFailures,
+    cluster_id: Option<String>,
+    buffer_sizer: Option<BufferSizer>,
+    connect_concurrency: usize,
+    dry_run_log: Option<DryRunLog<Ack>>,
+    prioritized: bool,
+    sort_key: Option<AckSortKey>,
+    dispatch_winners: broadcast::Sender<DispatchWinner>,
+    delivered: AckDeliveryCounter,
+    /// how long the response of a gateway is awaited before the acks to it are counted as failed
+    rpc_timeout: Duration,
+    /// the signal to stop the responder gracefully, set by [AckResponder::with_shutdown]
+    shutdown: Option<oneshot::Receiver<()>>,
+    /// dispatch the queued acks before the responder stops on shutdown
+    flush_on_shutdown: bool,
+    /// the shutdown is signalled, and the responder stops once the calls in flight are responded
+    stopping: bool,
+    /// the trace context of the span which built the responder, carried by the acks it dispatches
+    trace_context: Option<trace::TraceContext>,
+    /// the router which the acks are sent to their destination nodes by, set by [AckResponder::with_router]
+    router: Option<Arc<dyn AckRouter + Send + Sync>>,
+    /// dispatch the acks enqueued within `delay` in one call, see [AckResponderBuilder::batching]
+    batching: bool,
+}
+
+/// The receiving half of the ack queue of [AckResponder]
+enum AckQueue {
+    /// a [mpsc] channel built by [AckResponderBuilder::build]
+    Fixed {
+        recv: mpsc::Receiver<Ack>,
+        /// counts the buffered acks without keeping the channel open
+        sender: mpsc::WeakSender<Ack>,
+        capacity: usize,
+        /// the ack received by [AckQueue::poll_buffered], which is the next one to be received
+        peeked: Option<Ack>,
+        intake_closed: bool,
+    },
+    /// a [resizable_channel] built by [AckResponderBuilder::build_resizable]
+    Resizable(ResizableReceiver<Ack>),
+}
+
+impl AckQueue {
+    fn fixed(recv: mpsc::Receiver<Ack>, sender: &mpsc::Sender<Ack>) -> Self {
+        Self::Fixed {
+            recv,
+            sender: sender.downgrade(),
+            capacity: sender.max_capacity(),
+            peeked: None,
+            intake_closed: false,
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut task::Context<'_>) -> Poll<Option<Ack>> {
+        match self {
+            Self::Fixed { recv, peeked, .. } => match peeked.take() {
+                Some(ack) => Poll::Ready(Some(ack)),
+                None => recv.poll_recv(cx),
+            },
+            Self::Resizable(recv) => recv.poll_recv(cx),
+        }
+    }
+
+    fn poll_buffered(&mut self, cx: &mut task::Context<'_>) -> Poll<bool> {
+        match self {
+            Self::Fixed { recv, peeked, .. } => {
+                if peeked.is_none() {
+                    *peeked = ready!(recv.poll_recv(cx));
+                }
+                Poll::Ready(peeked.is_some())
+            }
+            Self::Resizable(recv) => recv.poll_buffered(cx),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Fixed {
+                sender,
+                capacity,
+                peeked,
+                ..
+            } => {
+                let queued = sender
+                    .upgrade()
+                    .map(|sender| capacity.saturating_sub(sender.capacity()))
+                    .unwrap_or_default();
+                queued + usize::from(peeked.is_some())
+            }
+            Self::Resizable(recv) => recv.len(),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match self {
+            Self::Fixed { capacity, .. } => *capacity,
+            Self::Resizable(recv) => recv.capacity(),
+        }
+    }
+
+    /// Reject new sends, while the buffered acks can still be received. A fixed-size queue can't be reopened then.
+    fn close_intake(&mut self) {
+        match self {
+            Self::Fixed {
+                recv,
+                intake_closed,
+                ..
+            } => {
+                recv.close();
+                *intake_closed = true;
+            }
+            Self::Resizable(recv) => recv.close_intake(),
+        }
+    }
+
+    fn reopen_intake(&mut self) {
+        match self {
+            Self::Fixed { .. } => {
+                tracing::warn!("a fixed-size ack queue can't be reopened, build the responder by build_resizable to resume it")
+            }
+            Self::Resizable(recv) => recv.reopen_intake(),
+        }
+    }
+
+    fn is_intake_closed(&self) -> bool {
+        match self {
+            Self::Fixed { intake_closed, .. } => *intake_closed,
+            Self::Resizable(recv) => recv.is_intake_closed(),
+        }
+    }
+}
+
+/// the capacity of the channel of [DispatchWinner] events. A lagging subscriber misses the oldest events.
+const DISPATCH_WINNER_CHANNEL_SIZE: usize = 64;
+
+/// The gateway which accepted an ack dispatched in [DispatchMode::AnyOfFirstSuccess] mode
+#[derive(Clone, Debug, PartialEq)]
+pub struct DispatchWinner {
+    pub host_addr: HostAddr,
+    pub ack: Ack,
+    /// number of the gateways which were tried before the winner and failed
+    pub failed_attempts: usize,
+}
+
+/// The outcomes of the acks dispatched to a remote node, counted by [AckDeliveryCounter]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AckNodeStats {
+    /// number of acks accepted by the node
+    pub delivered: u64,
+    /// number of acks failed to be delivered to the node. In [DispatchMode::AnyOfFirstSuccess] mode, they may be delivered to another node.
+    pub dropped: u64,
+}
+
+/// [AckDeliveryCounter] counts the outcomes of the acks dispatched to each remote node, for the whole lifetime of an [AckResponder].
+/// The clones share the same counts, so they can be read after the responder is spawned.
+#[derive(Clone, Debug, Default)]
+pub struct AckDeliveryCounter {
+    counts: Arc<Mutex<HashMap<HostAddr, AckNodeStats>>>,
+}
+
+impl AckDeliveryCounter {
+    pub fn record(&self, host_addr: &HostAddr, delivered: u64) {
+        self.counts
+            .lock()
+            .unwrap()
+            .entry(host_addr.clone())
+            .or_default()
+            .delivered += delivered
+    }
+
+    pub fn record_dropped(&self, host_addr: &HostAddr, dropped: u64) {
+        self.counts
+            .lock()
+            .unwrap()
+            .entry(host_addr.clone())
+            .or_default()
+            .dropped += dropped
+    }
+
+    /// the number of acks delivered to each node. The nodes which never accepted an ack are absent.
+    pub fn per_node_counts(&self) -> HashMap<HostAddr, u64> {
+        self.counts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, stats)| stats.delivered > 0)
+            .map(|(host_addr, stats)| (host_addr.clone(), stats.delivered))
+            .collect()
+    }
+
+    /// the [AckNodeStats] of each node which any ack has been dispatched to
+    pub fn stats(&self) -> HashMap<HostAddr, AckNodeStats> {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+impl<T: ReceiveAckRpcGateway + ConnectRpcGateway> AckResponder<T> {
+    /// Establish connections to all remote nodes before the first ack is sent.
+    /// At most [AckResponderBuilder::connect_concurrency] connections are established simultaneously.
+    pub async fn warm_up(&self) -> Vec<(&HostAddr, Result<(), tonic::transport::Error>)> {
+        connect_all(self.gateways.as_slice(), self.connect_concurrency).await
+    }
+}
+
+impl<T: ReceiveAckRpcGateway + 'static> Future for AckResponder<T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let cluster_id = this.cluster_id.clone();
+        if shutdown_signalled(&mut this.shutdown, cx) {
+            this.shutdown = None;
+            this.stopping = true;
+            this.recv.close_intake();
+            if this.flush_on_shutdown {
+                tracing::info!(
+                    "ack responder stops on shutdown, {} acks to flush",
+                    this.recv.len()
+                );
+                tenant::sync_scope(cluster_id.as_ref(), || this.send_acks(cx));
+            } else {
+                tracing::info!(
+                    "ack responder stops on shutdown, {} acks discarded",
+                    this.recv.len()
+                );
+            }
+        }
+        if this.stopping {
+            ready!(tenant::sync_scope(cluster_id.as_ref(), || this.poll_in_flight(cx)));
+            return Poll::Ready(());
+        }
+        while let Some(Poll::Ready(_)) = this
+            .resize_tick
+            .as_mut()
+            .map(|tick| Pin::new(tick).poll_tick(cx))
+        {
+            this.resize_buffer();
+        }
+        loop {
+            // the calls are polled within the scope of the cluster id, which is tagged on the requests
+            ready!(tenant::sync_scope(cluster_id.as_ref(), || this.poll_in_flight(cx)));
+            match this.flush_at.as_mut() {
+                Some(flush_at) => {
+                    ready!(flush_at.as_mut().poll(cx));
+                    this.flush_at = None;
+                    tenant::sync_scope(cluster_id.as_ref(), || this.send_acks(cx));
+                }
+                None => {
+                    // the senders are all dropped with no ack left, there will be nothing to dispatch
+                    if !ready!(this.recv.poll_buffered(cx)) {
+                        return Poll::Ready(());
+                    }
+                    let delay = if this.batching {
+                        this.delay
+                    } else {
+                        Duration::ZERO
+                    };
+                    this.flush_at = Some(Box::pin(tokio::time::sleep(delay)));
+                }
+            }
+        }
+    }
+}
+
+impl<T: ReceiveAckRpcGateway + 'static> AckResponder<T> {
+    /// Stop accepting new acks without stopping the responder, e.g. during a rolling config change.
+    /// New sends are rejected, while the buffered acks are still dispatched. Call [AckResponder::unquiesce] to resume,
+    /// which only works if the responder is built by [AckResponderBuilder::build_resizable]: a [mpsc] channel can't be reopened once closed.
+    pub fn quiesce(&mut self) {
+        tracing::info!("ack responder quiesced, {} acks to drain", self.recv.len());
+        self.recv.close_intake()
+    }
+
+    pub fn unquiesce(&mut self) {
+        tracing::info!("ack responder resumed");
+        self.recv.reopen_intake()
+    }
+
+    pub fn is_quiesced(&self) -> bool {
+        self.recv.is_intake_closed()
+    }
+
+    /// Stop the responder gracefully once `shutdown` is signalled, e.g. before the node exits: new acks are rejected,
+    /// and if `flush` is set, all the queued acks are dispatched before the responder resolves. Otherwise, they're discarded.
+    /// Dropping the sending half of `shutdown` doesn't stop the responder.
+    pub fn with_shutdown(mut self, shutdown: oneshot::Receiver<()>, flush: bool) -> Self {
+        self.shutdown = Some(shutdown);
+        self.flush_on_shutdown = flush;
+        self
+    }
+
+    /// Send each ack only to the node resolved by `router`: the acks of a batch are grouped by [partition_acks_by_target],
+    /// and each node gets its own acks in one call. The acks which can't be routed, or are routed to a node without gateway,
+    /// are dispatched by [AckResponderBuilder::dispatch_mode] as usual.
+    pub fn with_router<R: AckRouter + Send + Sync + 'static>(mut self, router: R) -> Self {
+        self.router = Some(Arc::new(router));
+        self
+    }
+
+    /// Subscribe the [DispatchWinner] of every ack dispatched in [DispatchMode::AnyOfFirstSuccess] mode,
+    /// which tells which node accepted the ack. It should be called before the responder is spawned.
+    pub fn subscribe_dispatch_winners(&self) -> broadcast::Receiver<DispatchWinner> {
+        self.dispatch_winners.subscribe()
+    }
+
+    /// The [DryRunLog] of the acks that would have been sent. It's [None] if the responder is not in dry-run mode.
+    pub fn dry_run_log(&self) -> Option<DryRunLog<Ack>> {
+        self.dry_run_log.clone()
+    }
+
+    /// The [AckDeliveryCounter] shared by this responder. It should be taken before the responder is spawned.
+    pub fn delivery_counter(&self) -> AckDeliveryCounter {
+        self.delivered.clone()
+    }
+
+    /// the number of acks delivered to each node successfully since the responder is built
+    pub fn per_node_counts(&self) -> HashMap<HostAddr, u64> {
+        self.delivered.per_node_counts()
+    }
+
+    /// the number of acks delivered and dropped by each node since the responder is built
+    pub fn stats(&self) -> HashMap<HostAddr, AckNodeStats> {
+        self.delivered.stats()
+    }
+
+    /// the gateways an ack is dispatched to. In [DispatchMode::AnyOfFirstSuccess] mode, the first gateway is assumed to succeed.
+    fn dry_run_targets(&self) -> Vec<&T> {
+        match self.dispatch_mode {
+            DispatchMode::Broadcast | DispatchMode::QuorumOf(_) => self.gateways.iter().collect(),
+            DispatchMode::AnyOfFirstSuccess => self
+                .failures
+                .order(&self.gateways)
+                .into_iter()
+                .take(1)
+                .collect(),
+        }
+    }
+
+    fn resize_buffer(&mut self) {
+        if let (Some(sizer), AckQueue::Resizable(recv)) =
+            (self.buffer_sizer.as_mut(), &mut self.recv)
+        {
+            if let Some(capacity) = sizer.observe(recv.len(), recv.capacity()) {
+                tracing::info!("resize ack queue from {} to {}", recv.capacity(), capacity);
+                recv.resize(capacity);
+            }
+        }
+    }
+
+    /// dispatch all the queued acks in one batch
+    fn send_acks(&mut self, cx: &mut task::Context<'_>) {
+        let mut acks = vec![];
+        // drain the queue until it's empty, or closed with all the senders dropped
+        while let Poll::Ready(Some(ack)) = self.recv.poll_recv(cx) {
+            acks.push(ack);
+        }
+        if let Some(sort_key) = self.sort_key {
+            sort_key.sort(&mut acks);
+        }
+        if self.prioritized {
+            // the sort is stable, so acks of the same priority keep the FIFO order
+            acks.sort_by_key(|ack| std::cmp::Reverse(ack.priority));
+        }
+
+        if let Some(log) = self.dry_run_log.as_ref() {
+            acks.iter().for_each(|ack| {
+                self.dry_run_targets().into_iter().for_each(|gateway| {
+                    tracing::info!("ack dry run [host_addr: {:?}]", gateway.get_host_addr());
+                    log.record(gateway.get_host_addr(), ack.clone())
+                })
+            });
+            return;
+        }
+        if acks.is_empty() {
+            return;
+        }
+
+        // all the acks drained are sent to a gateway in one batch, unless batching is disabled
+        let rpc_timeout = self.rpc_timeout;
+        let trace_context = self.trace_context;
+        // without batching, every ack is dispatched in a call of its own
+        let batches = if self.batching {
+            vec![acks]
+        } else {
+            acks.into_iter().map(|ack| vec![ack]).collect()
+        };
+        for acks in batches {
+            let acks = match self.router.clone() {
+                Some(router) => {
+                    let mut partitions = partition_acks_by_target(acks, router.as_ref());
+                    for index in 0..self.gateways.len() {
+                        if let Some(routed) =
+                            partitions.remove(self.gateways[index].get_host_addr())
+                        {
+                            self.dispatch_to(index, routed);
+                        }
+                    }
+                    // the unroutable acks are dispatched by the dispatch mode, in their order within each partition
+                    partitions.into_values().flatten().collect()
+                }
+                None => acks,
+            };
+            match self.dispatch_mode {
+                _ if acks.is_empty() => {}
+                DispatchMode::Broadcast => {
+                    for index in 0..self.gateways.len() {
+                        self.dispatch_to(index, acks.clone());
+                    }
+                }
+                DispatchMode::AnyOfFirstSuccess => {
+                    let gateways = self.gateways.clone();
+                    let failures = self.failures.clone();
+                    let dispatch_winners = self.dispatch_winners.clone();
+                    let future = async move {
+                        let mut results = vec![];
+                        // only the acks not delivered yet move on to the next gateway, with the number of gateways they failed on
+                        let mut undelivered =
+                            acks.into_iter().map(|ack| (ack, 0)).collect::<Vec<_>>();
+                        for gateway in failures.order(&gateways) {
+                            if undelivered.is_empty() {
+                                break;
+                            }
+                            let batch = undelivered.iter().map(|(ack, _)| ack.clone()).collect();
+                            let r = receive_acks_within(gateway, batch, rpc_timeout).await;
+                            let mut accepted = 0;
+                            undelivered = undelivered
+                                .into_iter()
+                                .zip(r.iter())
+                                .filter_map(|((ack, failed_attempts), result)| match result {
+                                    Ok(_) => {
+                                        accepted += 1;
+                                        let _ = dispatch_winners.send(DispatchWinner {
+                                            host_addr: gateway.get_host_addr().clone(),
+                                            ack,
+                                            failed_attempts,
+                                        });
+                                        None
+                                    }
+                                    Err(_) => Some((ack, failed_attempts + 1)),
+                                })
+                                .collect();
+                            if accepted > 0 {
+                                tracing::debug!(
+                                    "{} acks accepted [host_addr: {:?}, failed_attempts: {}]",
+                                    accepted,
+                                    gateway.get_host_addr(),
+                                    results.len()
+                                );
+                            }
+                            results.push((gateway.get_host_addr().clone(), r));
+                        }
+                        results
+                    };
+                    self.in_flight
+                        .push(trace::within(trace_context, future).boxed());
+                }
+                DispatchMode::QuorumOf(quorum) => {
+                    let mode = self.dispatch_mode;
+                    let gateways = self.gateways.clone();
+                    let future = async move {
+                        let total = gateways.len();
+                        let mut calls = gateways
+                            .iter()
+                            .map(|gateway| {
+                                receive_acks_within(gateway, acks.clone(), rpc_timeout)
+                                    .map(move |r| (gateway.get_host_addr(), r))
+                            })
+                            .collect::<FuturesUnordered<_>>();
+                        let mut results = vec![];
+                        let (mut successes, mut failures) = (0, 0);
+                        let mut completion = mode.completion(successes, failures, total);
+                        while completion == Completion::Pending {
+                            match calls.next().await {
+                                Some((host_addr, r)) => {
+                                    // a node counts toward the quorum only if it accepted every ack of the batch
+                                    if r.iter().all(Result::is_ok) {
+                                        successes += 1
+                                    } else {
+                                        failures += 1
+                                    }
+                                    results.push((host_addr.clone(), r));
+                                    completion = mode.completion(successes, failures, total);
+                                }
+                                None => break,
+                            }
+                        }
+                        if completion != Completion::Succeeded {
+                            tracing::error!(
+                                "ack quorum not reached, {} of {} succeeded, {} required",
+                                successes,
+                                total,
+                                quorum
+                            );
+                        }
+                        results
+                    };
+                    self.in_flight
+                        .push(trace::within(trace_context, future).boxed());
+                }
+            }
+        }
+    }
+
+    /// dispatch `acks` to the gateway at `index` in one call
+    fn dispatch_to(&mut self, index: usize, acks: Vec<Ack>) {
+        let gateways = self.gateways.clone();
+        let rpc_timeout = self.rpc_timeout;
+        let future = async move {
+            let gateway = &gateways[index];
+            let r = receive_acks_within(gateway, acks, rpc_timeout).await;
+            vec![(gateway.get_host_addr().clone(), r)]
+        };
+        self.in_flight
+            .push(trace::within(self.trace_context, future).boxed());
+    }
+
+    /// record the results of the calls responded so far. It's ready once there's no call in flight.
+    fn poll_in_flight(&mut self, cx: &mut task::Context<'_>) -> Poll<()> {
+        while let Poll::Ready(Some(results)) = self.in_flight.poll_next_unpin(cx) {
+            self.record_results(results)
+        }
+        if self.in_flight.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn record_results(&mut self, results: AckResults) {
+        results.into_iter().for_each(|(host_addr, r)| {
+            let succeeded = r.iter().filter(|result| result.is_ok()).count() as u64;
+            if succeeded > 0 {
+                self.delivered.record(&host_addr, succeeded);
+                tracing::info!("{} acks success [host_addr: {:?}]", succeeded, host_addr)
+            }
+            let dropped = r.len() as u64 - succeeded;
+            if let Some(status) = r.into_iter().find_map(Result::err) {
+                self.failures.record_failure(&host_addr);
+                self.delivered.record_dropped(&host_addr, dropped);
+                tracing::error!(
+                    "{} acks failed [host_addr: {:?}]: {}",
+                    dropped,
+                    host_addr,
+                    status
+                )
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::{Duration, Timelike};
+    use proto::common::{ack::AckType, Ack, HostAddr, NodeType, ResourceId, SubDataflowId};
+
+    use crate::net::gateway::MockRpcGateway;
+
+    use super::HeartbeatBuilder;
+
+    #[test]
+    pub fn test_local_ip() {
+        use super::local_ip;
+        let option = local_ip();
+        assert!(option.is_some());
+        println!("{}", option.unwrap())
+    }
+
+    #[test]
+    pub fn test_local_ip_to() {
+        use super::{local_ip_to, local_ip_to_with_options, DiscoverySocketOptions};
+
+        // the probe never sends anything, so the target doesn't have to accept it
+        let target = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = target.local_addr().unwrap().to_string();
+        assert_eq!(local_ip_to(&target), Some("127.0.0.1".to_string()));
+        let opts = DiscoverySocketOptions {
+            reuse_addr: true,
+            reuse_port: false,
+        };
+        assert_eq!(
+            local_ip_to_with_options(&target, &opts),
+            Some("127.0.0.1".to_string())
+        );
+
+        // skipped if the host has no IPv6 loopback
+        if let Ok(target) = std::net::UdpSocket::bind("[::1]:0") {
+            let target = target.local_addr().unwrap().to_string();
+            assert_eq!(local_ip_to(&target), Some("::1".to_string()));
+        }
+
+        assert!(local_ip_to("127.0.0.1").is_none());
+        assert!(local_ip_to("not an address").is_none());
+        assert!(local_ip_to("").is_none());
+    }
+
+    #[test]
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    pub fn test_bind_discovery_socket_with_reuse_options() {
+        use super::{bind_discovery_socket, DiscoverySocketOptions};
+
+        let opts = DiscoverySocketOptions {
+            reuse_addr: true,
+            reuse_port: true,
+        };
+        let socket = bind_discovery_socket(&"127.0.0.1:0".parse().unwrap(), &opts);
+        assert!(socket.is_ok());
+
+        let socket = socket2::Socket::from(socket.unwrap());
+        assert!(socket.reuse_address().unwrap());
+        assert!(socket.reuse_port().unwrap());
+    }
+
+    #[test]
+    pub fn test_local_fallback() {
+        use super::{local_with, LOOPBACK_HOST};
+
+        let addr = local_with(8080, || Some("node-1".to_string()), || None);
+        assert_eq!(addr.host, "node-1");
+        assert_eq!(addr.port, 8080);
+
+        let addr = local_with(8080, || None, || Some("10.0.0.1".to_string()));
+        assert_eq!(addr.host, "10.0.0.1");
+
+        let addr = local_with(
+            8080,
+            || Some("".to_string()),
+            || Some("10.0.0.1".to_string()),
+        );
+        assert_eq!(addr.host, "10.0.0.1");
+
+        let addr = local_with(8080, || None, || None);
+        assert_eq!(addr.host, LOOPBACK_HOST);
+        assert!(addr.is_valid());
+    }
+
+    #[test]
+    pub fn test_host_addr_from_str() {
+        use proto::common_impl::HostAddrParseError;
+
+        for (addr, host, port) in [
+            ("10.0.0.1:8080", "10.0.0.1", 8080),
+            ("[::1]:8080", "::1", 8080),
+            ("[fd12:3456::1]:65535", "fd12:3456::1", 65535),
+            (
+                "taskmanager-1.lightflus.svc:8970",
+                "taskmanager-1.lightflus.svc",
+                8970,
+            ),
+        ] {
+            let host_addr = addr.parse::<HostAddr>().unwrap();
+            assert_eq!(
+                host_addr,
+                HostAddr {
+                    host: host.to_string(),
+                    port,
+                }
+            );
+            assert_eq!(host_addr.to_string(), addr);
+        }
+
+        let err = |addr: &str| addr.parse::<HostAddr>().unwrap_err();
+        assert_eq!(
+            err(":8080"),
+            HostAddrParseError::EmptyHost(":8080".to_string())
+        );
+        assert_eq!(
+            err("[]:8080"),
+            HostAddrParseError::EmptyHost("[]:8080".to_string())
+        );
+        assert_eq!(
+            err("localhost:0"),
+            HostAddrParseError::InvalidPort("localhost:0".to_string())
+        );
+        assert!(matches!(
+            err("localhost:65536"),
+            HostAddrParseError::InvalidPort(_)
+        ));
+        assert!(matches!(
+            err("localhost:http"),
+            HostAddrParseError::InvalidPort(_)
+        ));
+        assert!(matches!(
+            err("localhost:"),
+            HostAddrParseError::InvalidPort(_)
+        ));
+        assert!(matches!(
+            err("localhost"),
+            HostAddrParseError::MissingPort(_)
+        ));
+        assert!(matches!(err("[::1]"), HostAddrParseError::MissingPort(_)));
+        assert!(matches!(
+            err("::1:8080"),
+            HostAddrParseError::InvalidHost(_)
+        ));
+        assert!(matches!(
+            err("[::1:8080"),
+            HostAddrParseError::InvalidHost(_)
+        ));
+        assert_eq!(
+            err("localhost").to_string(),
+            "missing port in address \"localhost\", it should be host:port"
+        );
+    }
+
+    #[test]
+    pub fn test_host_addr_uri() {
+        let host_addr = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8080,
+        };
+        for (host, http, https) in [
+            ("10.0.0.1", "http://10.0.0.1:8080", "https://10.0.0.1:8080"),
+            (
+                "node-1.lightflus.io",
+                "http://node-1.lightflus.io:8080",
+                "https://node-1.lightflus.io:8080",
+            ),
+            ("::1", "http://[::1]:8080", "https://[::1]:8080"),
+            (
+                "[fd12::1]",
+                "http://[fd12::1]:8080",
+                "https://[fd12::1]:8080",
+            ),
+        ] {
+            assert_eq!(host_addr(host).as_uri(), http);
+            assert_eq!(host_addr(host).as_secure_uri(), https);
+            assert!(http.parse::<tonic::codegen::http::Uri>().is_ok());
+        }
+        assert_eq!(
+            host_addr("::1").as_uri_with_scheme("grpc"),
+            "grpc://[::1]:8080"
+        );
+
+        // the gateways connected over TLS use the https scheme
+        let tls = tonic::transport::ClientTlsConfig::new();
+        let endpoint = |host, tls| {
+            super::gateway::endpoint(
+                &host_addr(host),
+                std::time::Duration::from_secs(1),
+                None,
+                tls,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            endpoint("::1", None).uri().to_string(),
+            "http://[::1]:8080/"
+        );
+        assert_eq!(
+            endpoint("node-1.lightflus.io", Some(&tls))
+                .uri()
+                .to_string(),
+            "https://node-1.lightflus.io:8080/"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_options_applied() {
+        use super::{KeepaliveOptions, KeepaliveTarget};
+
+        #[derive(Default, Debug, PartialEq)]
+        struct RecordingEndpoint {
+            interval: Option<std::time::Duration>,
+            timeout: Option<std::time::Duration>,
+            while_idle: Option<bool>,
+        }
+
+        impl KeepaliveTarget for RecordingEndpoint {
+            fn http2_keep_alive_interval(self, interval: std::time::Duration) -> Self {
+                Self {
+                    interval: Some(interval),
+                    ..self
+                }
+            }
+
+            fn keep_alive_timeout(self, timeout: std::time::Duration) -> Self {
+                Self {
+                    timeout: Some(timeout),
+                    ..self
+                }
+            }
+
+            fn keep_alive_while_idle(self, enabled: bool) -> Self {
+                Self {
+                    while_idle: Some(enabled),
+                    ..self
+                }
+            }
+        }
+
+        let opts: KeepaliveOptions =
+            serde_json::from_str(r#"{"interval": 10, "timeout": 5}"#).unwrap();
+        assert!(!opts.permit_without_stream);
+
+        let opts = KeepaliveOptions {
+            permit_without_stream: true,
+            ..opts
+        };
+        assert_eq!(
+            opts.apply(RecordingEndpoint::default()),
+            RecordingEndpoint {
+                interval: Some(std::time::Duration::from_secs(10)),
+                timeout: Some(std::time::Duration::from_secs(5)),
+                while_idle: Some(true),
+            }
+        );
+
+        let gateway = super::gateway::taskmanager::SafeTaskManagerRpcGateway::new(&HostAddr {
+            host: "localhost".to_string(),
+            port: 8792,
+        })
+        .with_keepalive(opts);
+        assert!(format!("{:?}", gateway).contains("permit_without_stream: true"));
+    }
+
+    #[tokio::test]
+    async fn test_tls_sni_override() {
+        use super::TlsOptions;
+
+        let opts: TlsOptions =
+            serde_json::from_str(r#"{"sni_override": "node-1.lightflus.io"}"#).unwrap();
+        let config = opts.client_tls_config().unwrap();
+        assert!(format!("{:?}", config).contains(r#"domain: Some("node-1.lightflus.io")"#));
+        assert!(
+            format!("{:?}", TlsOptions::default().client_tls_config().unwrap())
+                .contains("domain: None")
+        );
+        assert!(TlsOptions {
+            ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+            ..Default::default()
+        }
+        .client_tls_config()
+        .is_err());
+
+        // the certificate is verified against the override, while the channel still connects by ip
+        let gateway = super::gateway::taskmanager::SafeTaskManagerRpcGateway::new(&HostAddr {
+            host: "10.0.0.1".to_string(),
+            port: 8792,
+        })
+        .with_tls(config);
+        let debug = format!("{:?}", gateway);
+        assert!(debug.contains(r#"domain: Some("node-1.lightflus.io")"#));
+        assert!(debug.contains(r#"host: "10.0.0.1""#));
+    }
+
+    #[tokio::test]
+    async fn test_gateway_channel_factory() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use proto::common::Heartbeat;
+
+        use super::gateway::{
+            taskmanager::SafeTaskManagerRpcGateway, ChannelFactory, GatewayChannel,
+            ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway,
+        };
+
+        let closed_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let host_addr = HostAddr {
+            host: "127.0.0.1".to_string(),
+            port: closed_port as u32,
+        };
+
+        // count the heartbeat rpc calls passing through the channel
+        let heartbeats = Arc::new(AtomicUsize::new(0));
+        let counter = heartbeats.clone();
+        let factory = ChannelFactory::new(move |addr, channel| {
+            assert_eq!(addr.port, closed_port as u32);
+            let counter = counter.clone();
+            GatewayChannel::new(
+                tower::ServiceBuilder::new()
+                    .map_request(move |request: tonic::codegen::http::Request<_>| {
+                        if request.uri().path().ends_with("/ReceiveHeartbeat") {
+                            counter.fetch_add(1, Ordering::SeqCst);
+                        }
+                        request
+                    })
+                    .service(super::gateway::box_channel(channel)),
+            )
+        });
+        let gateway = SafeTaskManagerRpcGateway::with_timeout(
+            &host_addr,
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(1),
+        )
+        .with_channel_factory(factory);
+
+        for heartbeat_id in 0..3 {
+            // the interceptor observes the call even though the node is unreachable
+            let result = gateway
+                .receive_heartbeat(Heartbeat {
+                    heartbeat_id,
+                    ..Default::default()
+                })
+                .await;
+            assert!(result.is_err());
+            assert_eq!(heartbeats.load(Ordering::SeqCst), heartbeat_id as usize + 1);
+        }
+        assert!(gateway.receive_ack(Ack::default()).await.is_err());
+        assert_eq!(heartbeats.load(Ordering::SeqCst), 3);
+        assert!(format!("{:?}", gateway).contains("channel_factory: Some(ChannelFactory)"));
+    }
+
+    #[tokio::test]
+    async fn test_gateway_calls_not_serialized() {
+        use std::sync::Arc;
+
+        use prost::Message;
+        use proto::common::{Ack, Heartbeat, Response};
+        use tonic::{codegen::http, transport::Body};
+
+        use super::gateway::{
+            taskmanager::SafeTaskManagerRpcGateway, ChannelFactory, GatewayChannel,
+            ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway,
+        };
+
+        // the heartbeats are answered once a permit is released, while the acks are answered at once
+        let heartbeat_permits = Arc::new(tokio::sync::Semaphore::new(0));
+        let factory = {
+            let heartbeat_permits = heartbeat_permits.clone();
+            ChannelFactory::new(move |_, _| {
+                let heartbeat_permits = heartbeat_permits.clone();
+                GatewayChannel::new(tower::service_fn(move |request: http::Request<_>| {
+                    let heartbeat_permits = heartbeat_permits.clone();
+                    let is_heartbeat = request.uri().path().ends_with("ReceiveHeartbeat");
+                    async move {
+                        if is_heartbeat {
+                            heartbeat_permits.acquire().await.unwrap().forget();
+                        }
+                        // a length-prefixed message followed by the grpc-status trailer
+                        let message = Response::ok().encode_to_vec();
+                        let mut frame = vec![0];
+                        frame.extend((message.len() as u32).to_be_bytes());
+                        frame.extend(message);
+                        let (mut tx, body) = Body::channel();
+                        tokio::spawn(async move {
+                            let _ = tx.send_data(frame.into()).await;
+                            let mut trailers = http::HeaderMap::new();
+                            trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+                            let _ = tx.send_trailers(trailers).await;
+                        });
+                        Ok::<_, tonic::Status>(
+                            http::Response::builder()
+                                .header("content-type", "application/grpc")
+                                .body(body)
+                                .unwrap(),
+                        )
+                    }
+                }))
+            })
+        };
+        let gateway = SafeTaskManagerRpcGateway::with_timeout(
+            &HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 8792,
+            },
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(5),
+        )
+        .with_channel_factory(factory);
+
+        let heartbeat = {
+            let gateway = gateway.clone();
+            tokio::spawn(async move { gateway.receive_heartbeat(Heartbeat::default()).await })
+        };
+        tokio::task::yield_now().await;
+
+        // the pending heartbeat doesn't hold the client
+        let ack = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            gateway.receive_ack(Ack::default()),
+        )
+        .await;
+        assert!(matches!(ack, Ok(Ok(_))));
+        assert!(!heartbeat.is_finished());
+
+        heartbeat_permits.add_permits(1);
+        assert!(heartbeat.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_gateway_reconnect_on_connection_error() {
+        use std::sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use prost::Message;
+        use proto::common::{Ack, Heartbeat, Response};
+        use tonic::{codegen::http, transport::Body};
+
+        use super::gateway::{
+            is_connection_error, taskmanager::SafeTaskManagerRpcGateway, ChannelFactory,
+            GatewayChannel, ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway,
+        };
+
+        /// a transport answering every rpc call with [Response::ok] until its connections are broken by `disconnect`
+        #[derive(Clone, Default)]
+        struct MockTransport {
+            /// number of connections established so far
+            connections: Arc<AtomicUsize>,
+            /// the connections up to this one are broken
+            broken: Arc<AtomicUsize>,
+            /// whether the remote node rejects the calls with an invalid argument
+            rejecting: Arc<AtomicBool>,
+        }
+
+        impl MockTransport {
+            fn factory(&self) -> ChannelFactory {
+                let transport = self.clone();
+                ChannelFactory::new(move |_, _| {
+                    let connection = transport.connections.fetch_add(1, Ordering::SeqCst) + 1;
+                    let transport = transport.clone();
+                    GatewayChannel::new(tower::service_fn(move |_: http::Request<_>| {
+                        let alive = transport.broken.load(Ordering::SeqCst) < connection;
+                        let rejecting = transport.rejecting.load(Ordering::SeqCst);
+                        async move {
+                            if !alive {
+                                return Err(tonic::Status::unavailable("connection reset by peer"));
+                            }
+                            if rejecting {
+                                return Ok(http::Response::builder()
+                                    .header("content-type", "application/grpc")
+                                    .header("grpc-status", "3")
+                                    .body(Body::empty())
+                                    .unwrap());
+                            }
+                            // a length-prefixed message followed by the grpc-status trailer
+                            let message = Response::ok().encode_to_vec();
+                            let mut frame = vec![0];
+                            frame.extend((message.len() as u32).to_be_bytes());
+                            frame.extend(message);
+                            let (mut tx, body) = Body::channel();
+                            tokio::spawn(async move {
+                                let _ = tx.send_data(frame.into()).await;
+                                let mut trailers = http::HeaderMap::new();
+                                trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+                                let _ = tx.send_trailers(trailers).await;
+                            });
+                            Ok(http::Response::builder()
+                                .header("content-type", "application/grpc")
+                                .body(body)
+                                .unwrap())
+                        }
+                    }))
+                })
+            }
+
+            fn disconnect(&self) {
+                self.broken
+                    .store(self.connections.load(Ordering::SeqCst), Ordering::SeqCst)
+            }
+        }
+
+        let transport = MockTransport::default();
+        let gateway = SafeTaskManagerRpcGateway::with_timeout(
+            &HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 8792,
+            },
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(1),
+        )
+        .with_channel_factory(transport.factory());
+
+        // the connection is established lazily, and reused by the following calls
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 0);
+        assert!(gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .is_ok());
+        assert!(gateway.receive_ack(Ack::default()).await.is_ok());
+        assert!(gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .is_ok());
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 1);
+
+        // the call on the broken connection fails, and the next one reconnects
+        transport.disconnect();
+        let err = gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .unwrap_err();
+        assert!(is_connection_error(&err));
+        assert!(gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .is_ok());
+        assert!(gateway.receive_ack(Ack::default()).await.is_ok());
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 2);
+
+        // an error answered by the node keeps the connection
+        transport.rejecting.store(true, Ordering::SeqCst);
+        let err = gateway.receive_ack(Ack::default()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(!is_connection_error(&err));
+        transport.rejecting.store(false, Ordering::SeqCst);
+        assert!(gateway.receive_ack(Ack::default()).await.is_ok());
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    pub fn test_hostname() {
+        let host = super::hostname().unwrap();
+        assert!(!host.is_empty());
+        assert_eq!(host, host.trim_end());
+
+        // the same as what the kernel reports
+        if let Ok(expected) = std::fs::read_to_string("/proc/sys/kernel/hostname") {
+            assert_eq!(host, expected.trim_end());
+        }
+
+        super::refresh();
+        assert_eq!(super::hostname(), Some(host.clone()));
+        assert_eq!(super::hostname(), Some(host));
+    }
+
+    #[test]
+    fn test_memoized_lookup() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use super::Memoized;
+
+        let lookups = AtomicUsize::new(0);
+        let lookup = |value: Option<&str>| {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            value.map(|v| v.to_string())
+        };
+        let memoized = Memoized::new();
+
+        // the failed lookup is tried again
+        assert_eq!(memoized.get_or_lookup(|| lookup(None)), None);
+        assert_eq!(
+            memoized.get_or_lookup(|| lookup(Some("node-1"))),
+            Some("node-1".to_string())
+        );
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        // the second call is served by the cache
+        assert_eq!(
+            memoized.get_or_lookup(|| lookup(Some("node-2"))),
+            Some("node-1".to_string())
+        );
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+
+        memoized.invalidate();
+        assert_eq!(
+            memoized.get_or_lookup(|| lookup(Some("node-2"))),
+            Some("node-2".to_string())
+        );
+        assert_eq!(lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_drains_burst() {
+        use proto::common::ack::RequestId;
+
+        use super::AckResponderBuilder;
+
+        let builder = AckResponderBuilder {
+            delay: 3,
+            buf_size: 10,
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(builder.buf_size, 10);
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let handler = tokio::spawn(responder);
+        // the first tick fires immediately with nothing queued
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+        let start = tokio::time::Instant::now();
+        for heartbeat_id in 0..5 {
+            assert!(tx
+                .send(Ack {
+                    ack_type: AckType::Heartbeat as i32,
+                    request_id: Some(RequestId::HeartbeatId(heartbeat_id)),
+                    ..Default::default()
+                })
+                .await
+                .is_ok());
+        }
+        // all the queued acks are sent in the same batch, rather than one per delay
+        for heartbeat_id in 0..5 {
+            let ack = rx.recv().await.unwrap();
+            assert_eq!(ack.request_id, Some(RequestId::HeartbeatId(heartbeat_id)));
+        }
+        assert!(start.elapsed() <= std::time::Duration::from_secs(3));
+
+        // the responder resolves once all the senders are dropped, as there's nothing left to dispatch
+        drop(tx);
+        assert!(handler.await.is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_success() {
+        use super::AckResponderBuilder;
+
+        let builder = AckResponderBuilder {
+            delay: 3,
+            buf_size: 10,
+            connect_timeout: 3,
+            rpc_timeout: 3,
+            ..Default::default()
+        };
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(builder.buf_size, 10);
+
+        let (responder, tx) = builder.build(
+            &HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 8970,
+            },
+            |_, _, _| gateway.clone(),
+        );
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+
+        let handler = tokio::spawn(responder);
+        // every ack is dispatched the delay after it's enqueued, however long ago the previous one was dispatched
+        for _ in 0..2 {
+            let result = tx.send(ack.clone()).await;
+            let start = tokio::time::Instant::now();
+            assert!(result.is_ok());
+
+            let result = rx.recv().await;
+            assert_eq!(result, Some(ack.clone()));
+            assert_eq!(start.elapsed(), std::time::Duration::from_secs(3));
+        }
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_delay_from_enqueue() {
+        use super::AckResponderBuilder;
+
+        let builder = AckResponderBuilder {
+            delay: 3,
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let start = tokio::time::Instant::now();
+        let handler = tokio::spawn(responder);
+
+        // an idle responder dispatches nothing
+        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(gateway.ack_batches().is_empty());
+
+        // the acks enqueued within the delay of the first one are dispatched with it in one batch
+        assert!(tx.send(ack_of(1)).await.is_ok());
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        assert!(tx.send(ack_of(2)).await.is_ok());
+        for sub_id in [1, 2] {
+            assert_eq!(
+                rx.recv().await.unwrap().execution_id.unwrap().sub_id,
+                sub_id
+            );
+        }
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(13));
+        assert_eq!(gateway.ack_batches(), vec![2]);
+
+        // the next ack starts a new batch, which isn't aligned to the previous dispatch
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(tx.send(ack_of(3)).await.is_ok());
+        assert_eq!(rx.recv().await.unwrap().execution_id.unwrap().sub_id, 3);
+        assert_eq!(start.elapsed(), std::time::Duration::from_millis(16500));
+        assert_eq!(gateway.ack_batches(), vec![2, 1]);
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_batching_disabled() {
+        use super::AckResponderBuilder;
+
+        let builder = AckResponderBuilder {
+            delay: 3,
+            batching: false,
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let start = tokio::time::Instant::now();
+        let handler = tokio::spawn(responder);
+
+        // every ack is dispatched in a call of its own without waiting for the delay
+        for sub_id in [1, 2, 3] {
+            assert!(tx
+                .send(Ack {
+                    execution_id: Some(SubDataflowId {
+                        job_id: None,
+                        sub_id,
+                    }),
+                    ..Default::default()
+                })
+                .await
+                .is_ok());
+        }
+        for sub_id in [1, 2, 3] {
+            assert_eq!(
+                rx.recv().await.unwrap().execution_id.unwrap().sub_id,
+                sub_id
+            );
+        }
+        assert!(start.elapsed() < std::time::Duration::from_secs(3));
+        assert_eq!(gateway.ack_batches(), vec![1, 1, 1]);
+
+        handler.abort();
+    }
+
+    #[tokio::test]
+    async fn test_ack_published_to_queue() {
+        use std::sync::{Arc, Mutex};
+
+        use prost::Message;
+        use tonic::async_trait;
+
+        use super::{
+            gateway::queue::{AckPublisher, QueueAckGateway},
+            AckResponderBuilder,
+        };
+
+        /// (topic, key, payload) of a published message
+        type Published = (String, Vec<u8>, Vec<u8>);
+
+        #[derive(Clone, Default)]
+        struct RecordingPublisher {
+            published: Arc<Mutex<Vec<Published>>>,
+        }
+
+        #[async_trait]
+        impl AckPublisher for RecordingPublisher {
+            type Error = String;
+
+            async fn publish(&self, topic: &str, key: &[u8], payload: &[u8]) -> Result<(), String> {
+                self.published.lock().unwrap().push((
+                    topic.to_string(),
+                    key.to_vec(),
+                    payload.to_vec(),
+                ));
+                Ok(())
+            }
+        }
+
+        let publisher = RecordingPublisher::default();
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 10,
+            ..Default::default()
+        };
+        let (responder, tx) = builder.build(
+            &HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 8970,
+            },
+            |addr, _, _| QueueAckGateway::new(addr, "lightflus-acks", publisher.clone()),
+        );
+        let handler = tokio::spawn(responder);
+
+        let execution_id = SubDataflowId {
+            job_id: Some(ResourceId {
+                resource_id: "resource_id".to_string(),
+                namespace_id: "namespace_id".to_string(),
+            }),
+            sub_id: 1,
+        };
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: Some(execution_id.clone()),
+            request_id: None,
+            priority: 0,
+        };
+        assert!(tx.send(ack.clone()).await.is_ok());
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+        let published = publisher.published.lock().unwrap().clone();
+        assert_eq!(
+            published,
+            vec![(
+                "lightflus-acks".to_string(),
+                execution_id.encode_to_vec(),
+                ack.encode_to_vec()
+            )]
+        );
+        handler.abort();
+    }
+
+    struct SlowAckGateway {
+        shard: usize,
+        host_addr: HostAddr,
+        delivered: std::sync::Arc<std::sync::Mutex<Vec<(usize, Ack)>>>,
+    }
+
+    impl crate::net::gateway::RpcGateway for SlowAckGateway {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    #[tonic::async_trait]
+    impl crate::net::gateway::ReceiveAckRpcGateway for SlowAckGateway {
+        async fn receive_ack(&self, req: Ack) -> Result<proto::common::Response, tonic::Status> {
+            // the dispatching of a shard is blocked by a slow gateway
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            self.delivered.lock().unwrap().push((self.shard, req));
+            Ok(proto::common::Response::ok())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_ack_sharded_responder() {
+        use super::AckResponderBuilder;
+        use proto::common::ack::RequestId;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let builder = AckResponderBuilder {
+            delay_ms: Some(10),
+            buf_size: 1000,
+            ..Default::default()
+        };
+        let ack_of = |sub_id: u32, heartbeat_id: u64| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            request_id: Some(RequestId::HeartbeatId(heartbeat_id)),
+            ..Default::default()
+        };
+
+        let mut elapsed = vec![];
+        for shards in [1, 4] {
+            let delivered = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+            let next_shard = AtomicUsize::new(0);
+            let (responder, tx) =
+                builder.build_sharded(&[HostAddr::default()], shards, |host_addr, _, _| {
+                    SlowAckGateway {
+                        shard: next_shard.fetch_add(1, Ordering::SeqCst),
+                        host_addr: host_addr.clone(),
+                        delivered: delivered.clone(),
+                    }
+                });
+            assert_eq!(responder.num_shards(), shards);
+            assert_eq!(tx.num_shards(), shards);
+
+            for heartbeat_id in 0..4 {
+                for sub_id in 0..16 {
+                    assert!(tx.send(ack_of(sub_id, heartbeat_id)).await.is_ok());
+                }
+            }
+            let start = std::time::Instant::now();
+            let handlers = responder.spawn();
+            while delivered.lock().unwrap().len() < 64 {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            }
+            elapsed.push(start.elapsed());
+            handlers.iter().for_each(|handler| handler.abort());
+
+            // the acks of the same execution always land on the same shard, in order
+            let delivered = delivered.lock().unwrap().clone();
+            for sub_id in 0..16 {
+                let acks = delivered
+                    .iter()
+                    .filter(|(_, ack)| ack.execution_id.as_ref().unwrap().sub_id == sub_id)
+                    .collect::<Vec<_>>();
+                assert!(acks.iter().all(|(shard, ack)| *shard == tx.shard_of(ack)));
+                assert_eq!(
+                    acks.iter()
+                        .map(|(_, ack)| ack.request_id.clone())
+                        .collect::<Vec<_>>(),
+                    (0..4)
+                        .map(|heartbeat_id| Some(RequestId::HeartbeatId(heartbeat_id)))
+                        .collect::<Vec<_>>()
+                );
+            }
+            if shards > 1 {
+                let used = delivered
+                    .iter()
+                    .map(|(shard, _)| *shard)
+                    .collect::<std::collections::HashSet<_>>();
+                assert!(used.len() > 1);
+            }
+        }
+
+        // the shards dispatch in parallel
+        assert!(elapsed[1] * 2 < elapsed[0], "{:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_prioritized_dispatch() {
+        use super::AckResponderBuilder;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let ack_of = |sub_id: u32, priority: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            priority,
+            ..Default::default()
+        };
+
+        for (prioritized, expected) in [(true, [2, 1, 3]), (false, [1, 2, 3])] {
+            let (mut responder, tx) = AckResponderBuilder {
+                prioritized,
+                ..Default::default()
+            }
+            .build(&HostAddr::default(), |_, _, _| gateway.clone());
+
+            // a low-priority ack is queued before a high-priority one
+            assert!(tx.send(ack_of(1, 0)).await.is_ok());
+            assert!(tx.send(ack_of(2, 10)).await.is_ok());
+            assert!(tx.send(ack_of(3, 0)).await.is_ok());
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+            assert!(result.is_err());
+
+            let mut dispatched = vec![];
+            for _ in 0..3 {
+                dispatched.push(rx.recv().await.unwrap().execution_id.unwrap().sub_id);
+            }
+            assert_eq!(dispatched, expected);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_sorted_dispatch() {
+        use proto::common::ack::RequestId;
+
+        use super::{AckResponderBuilder, AckSortKey};
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let ack_of = |heartbeat_id: Option<u64>| Ack {
+            request_id: heartbeat_id.map(RequestId::HeartbeatId),
+            ..Default::default()
+        };
+
+        for (sort_key, expected) in [
+            (
+                Some(AckSortKey::RequestId),
+                [Some(1), Some(2), Some(3), None],
+            ),
+            (None, [Some(3), None, Some(1), Some(2)]),
+        ] {
+            let (mut responder, tx) = AckResponderBuilder {
+                sort_key,
+                ..Default::default()
+            }
+            .build(&HostAddr::default(), |_, _, _| gateway.clone());
+
+            // the acks are queued out of order
+            for heartbeat_id in [Some(3), None, Some(1), Some(2)] {
+                assert!(tx.send(ack_of(heartbeat_id)).await.is_ok());
+            }
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+            assert!(result.is_err());
+
+            let mut dispatched = vec![];
+            for _ in 0..4 {
+                dispatched.push(rx.recv().await.unwrap().request_id);
+            }
+            assert_eq!(
+                dispatched,
+                expected.map(|heartbeat_id| heartbeat_id.map(RequestId::HeartbeatId))
+            );
+        }
+
+        let builder: AckResponderBuilder =
+            serde_json::from_str(r#"{"delay": 3, "buf_size": 10, "connect_timeout": 3, "rpc_timeout": 3, "sort_key": "RequestId"}"#)
+                .unwrap();
+        assert_eq!(builder.sort_key, Some(AckSortKey::RequestId));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_batched_dispatch() {
+        use proto::common::ack::RequestId;
+
+        use super::AckResponderBuilder;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (mut responder, tx) =
+            AckResponderBuilder::default().build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let ack_of = |heartbeat_id: u64| Ack {
+            request_id: Some(RequestId::HeartbeatId(heartbeat_id)),
+            ..Default::default()
+        };
+
+        for heartbeat_id in 0..5 {
+            assert!(tx.send(ack_of(heartbeat_id)).await.is_ok());
+        }
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+
+        // the queued acks are sent in a single call, in the order they're queued
+        for heartbeat_id in 0..5 {
+            assert_eq!(rx.recv().await, Some(ack_of(heartbeat_id)));
+        }
+        assert_eq!(gateway.ack_batches(), vec![5]);
+
+        // a tick without acks sends nothing
+        let result = tokio::time::timeout(std::time::Duration::from_secs(4), &mut responder).await;
+        assert!(result.is_err());
+        assert_eq!(gateway.ack_batches(), vec![5]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_quiesce() {
+        use super::AckResponderBuilder;
+        use tokio::sync::mpsc::error::TrySendError;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let (mut responder, tx) = AckResponderBuilder::default()
+            .build_resizable(&HostAddr::default(), |_, _, _| gateway.clone());
+
+        assert!(tx.send(ack_of(1)).await.is_ok());
+        assert!(tx.send(ack_of(2)).await.is_ok());
+        responder.quiesce();
+        assert!(responder.is_quiesced());
+        assert!(tx.send(ack_of(3)).await.is_err());
+        assert!(matches!(
+            tx.try_send(ack_of(4)),
+            Err(TrySendError::Closed(_))
+        ));
+
+        // the buffered acks are still dispatched
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        for sub_id in [1, 2] {
+            assert_eq!(
+                rx.recv().await.unwrap().execution_id.unwrap().sub_id,
+                sub_id
+            );
+        }
+        assert!(rx.try_recv().is_err());
+
+        responder.unquiesce();
+        assert!(!responder.is_quiesced());
+        assert!(tx.send(ack_of(5)).await.is_ok());
+        let result = tokio::time::timeout(std::time::Duration::from_secs(4), &mut responder).await;
+        assert!(result.is_err());
+        assert_eq!(rx.recv().await.unwrap().execution_id.unwrap().sub_id, 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_quiesce_fixed_queue() {
+        use super::AckResponderBuilder;
+        use tokio::sync::mpsc::error::TrySendError;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let (mut responder, tx) =
+            AckResponderBuilder::default().build(&HostAddr::default(), |_, _, _| gateway.clone());
+
+        assert!(tx.send(ack_of(1)).await.is_ok());
+        responder.quiesce();
+        assert!(responder.is_quiesced());
+        assert!(tx.send(ack_of(2)).await.is_err());
+        assert!(matches!(
+            tx.try_send(ack_of(3)),
+            Err(TrySendError::Closed(_))
+        ));
+
+        // the buffered acks are still dispatched, and the responder resolves as its queue is closed for good
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_ok());
+        assert_eq!(rx.recv().await.unwrap().execution_id.unwrap().sub_id, 1);
+        assert!(rx.try_recv().is_err());
+
+        // a mpsc channel can't be reopened
+        responder.unquiesce();
+        assert!(responder.is_quiesced());
+        assert!(tx.send(ack_of(4)).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_zero_buf_size_clamped() {
+        use super::AckResponderBuilder;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let builder = AckResponderBuilder {
+            buf_size: 0,
+            ..Default::default()
+        };
+
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert_eq!(tx.max_capacity(), 1);
+        let handler = tokio::spawn(responder);
+        assert!(tx.send(Ack::default()).await.is_ok());
+        assert!(rx.recv().await.is_some());
+        handler.abort();
+
+        let (responder, tx) =
+            builder.build_resizable(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert_eq!(tx.capacity(), 1);
+        let handler = tokio::spawn(responder);
+        let send = tokio::time::timeout(std::time::Duration::from_secs(1), tx.send(Ack::default()));
+        assert!(send.await.unwrap().is_ok());
+        assert!(rx.recv().await.is_some());
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_shutdown_flushes_queued_acks() {
+        use super::AckResponderBuilder;
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let builder = AckResponderBuilder {
+            delay: 3,
+            ..Default::default()
+        };
+
+        // the queued acks are dispatched before the responder stops
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let mut responder = responder.with_shutdown(shutdown_rx, true);
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1), &mut responder).await;
+        assert!(result.is_err());
+        for sub_id in [1, 2, 3] {
+            assert!(tx.send(ack_of(sub_id)).await.is_ok());
+        }
+        assert!(shutdown_tx.send(()).is_ok());
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1), &mut responder).await;
+        assert!(result.is_ok());
+        for sub_id in [1, 2, 3] {
+            assert_eq!(
+                rx.recv().await.unwrap().execution_id.unwrap().sub_id,
+                sub_id
+            );
+        }
+        assert!(tx.send(ack_of(4)).await.is_err());
+
+        // without flushing, the queued acks are discarded
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let mut responder = responder.with_shutdown(shutdown_rx, false);
+        assert!(tx.send(ack_of(5)).await.is_ok());
+        assert!(shutdown_tx.send(()).is_ok());
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1), &mut responder).await;
+        assert!(result.is_ok());
+        assert!(rx.try_recv().is_err());
+
+        // dropping the signal doesn't stop the responder
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        let (responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let mut responder = responder.with_shutdown(shutdown_rx, true);
+        drop(shutdown_tx);
+        assert!(tx.send(ack_of(6)).await.is_ok());
+        let result = tokio::time::timeout(std::time::Duration::from_secs(4), &mut responder).await;
+        assert!(result.is_err());
+        assert_eq!(rx.recv().await.unwrap().execution_id.unwrap().sub_id, 6);
+    }
+
+    #[tokio::test]
+    async fn test_try_ack_saturated() {
+        use tokio::sync::mpsc::error::TrySendError;
+
+        use super::{AckResponderBuilder, AckSender};
+
+        let builder = AckResponderBuilder {
+            buf_size: 2,
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (mut responder, tx) = builder.build(&HostAddr::default(), |_, _, _| gateway.clone());
+        let ack_of = |sub_id| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+
+        // the acks are sent within the poll of a future, which returns without yielding
+        let result = futures_util::future::poll_fn(|_| {
+            std::task::Poll::Ready(
+                (1..=3)
+                    .map(|sub_id| tx.try_ack(ack_of(sub_id)))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .await;
+        assert!(result[0].is_ok());
+        assert!(result[1].is_ok());
+        match &result[2] {
+            Err(TrySendError::Full(ack)) => assert_eq!(ack, &ack_of(3)),
+            other => panic!("{:?}", other),
+        }
+
+        // the queue accepts acks again once the responder drains it
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        assert_eq!(rx.recv().await, Some(ack_of(1)));
+        assert_eq!(rx.recv().await, Some(ack_of(2)));
+        assert!(tx.try_ack(ack_of(3)).is_ok());
+
+        // the instrumented and sharded senders report the full queue the same way
+        let (_responder, tx) =
+            builder.build_instrumented(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert!(tx.try_ack(ack_of(1)).is_ok());
+        assert!(tx.try_ack(ack_of(2)).is_ok());
+        assert!(matches!(tx.try_ack(ack_of(3)), Err(TrySendError::Full(_))));
+        let (_responder, tx) =
+            builder.build_sharded(&[HostAddr::default()], 1, |_, _, _| gateway.clone());
+        assert!(tx.try_ack(ack_of(1)).is_ok());
+        assert!(tx.try_ack(ack_of(2)).is_ok());
+        assert!(matches!(tx.try_ack(ack_of(3)), Err(TrySendError::Full(_))));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_send_blocking_instrumented() {
+        use super::AckResponderBuilder;
+
+        let builder = AckResponderBuilder {
+            buf_size: 1,
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (mut responder, tx) =
+            builder.build_instrumented(&HostAddr::default(), |_, _, _| gateway.clone());
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+
+        // the buffer is filled without blocking
+        assert!(tx.send(ack.clone()).await.is_ok());
+        assert_eq!(tx.stats(), Default::default());
+
+        let blocked_tx = tx.clone();
+        let blocked_ack = ack.clone();
+        let handler = tokio::spawn(async move { blocked_tx.send(blocked_ack).await });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!handler.is_finished());
+
+        // the responder drains the buffer and unblocks the producer
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        assert!(handler.await.unwrap().is_ok());
+        assert_eq!(rx.recv().await, Some(ack));
+
+        let stats = tx.stats();
+        assert_eq!(stats.blocked_sends, 1);
+        assert!(stats.total_blocked >= std::time::Duration::from_millis(200));
+        assert_eq!(stats.max_blocked, stats.total_blocked);
+    }
+
+    #[tokio::test]
+    async fn test_ack_dedup_node_addresses() {
+        use super::AckResponderBuilder;
+
+        let node_of = |host: &str, port| HostAddr {
+            host: host.to_string(),
+            port,
+        };
+        let addrs = [
+            node_of("node-1.lightflus", 8970),
+            node_of("Node-1.Lightflus.", 8970),
+            node_of("node-1.lightflus", 8971),
+        ];
+        let (gateway, _, _) = MockRpcGateway::new(10, 10);
+
+        let built = std::cell::RefCell::new(vec![]);
+        let _ = AckResponderBuilder::default().build_with_nodes(&addrs, |addr, _, _| {
+            built.borrow_mut().push(addr.clone());
+            gateway.clone()
+        });
+        assert_eq!(built.take(), vec![addrs[0].clone(), addrs[2].clone()]);
+
+        let _ = AckResponderBuilder {
+            preserve_duplicate_nodes: true,
+            ..Default::default()
+        }
+        .build_with_nodes(&addrs, |addr, _, _| {
+            built.borrow_mut().push(addr.clone());
+            gateway.clone()
+        });
+        assert_eq!(built.take(), addrs.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_ack_any_of_prefers_least_recently_failed() {
+        use super::{AckResponderBuilder, DispatchMode};
+
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 10,
+            dispatch_mode: DispatchMode::AnyOfFirstSuccess,
+            ..Default::default()
+        };
+        let unhealthy_addr = &HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let healthy_addr = &HostAddr {
+            host: "198.0.0.2".to_string(),
+            port: 8970,
+        };
+
+        let (unhealthy, mut unhealthy_rx, _) =
+            MockRpcGateway::with_host_addr(unhealthy_addr, builder.buf_size, 10);
+        let (healthy, mut healthy_rx, _) =
+            MockRpcGateway::with_host_addr(healthy_addr, builder.buf_size, 10);
+
+        let (mut responder, tx) = builder.build_with_nodes(
+            &[unhealthy_addr.clone(), healthy_addr.clone()],
+            |addr, _, _| {
+                if addr == unhealthy_addr {
+                    unhealthy.clone()
+                } else {
+                    healthy.clone()
+                }
+            },
+        );
+        responder.failures.record_failure(unhealthy_addr);
+        let handler = tokio::spawn(responder);
+
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+        assert!(tx.send(ack.clone()).await.is_ok());
+        assert_eq!(healthy_rx.recv().await, Some(ack));
+        assert!(unhealthy_rx.try_recv().is_err());
+
+        handler.abort();
+    }
+
+    #[tokio::test]
+    async fn test_ack_any_of_reports_winner() {
+        use super::{AckResponderBuilder, DispatchMode, DispatchWinner};
+
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 10,
+            dispatch_mode: DispatchMode::AnyOfFirstSuccess,
+            ..Default::default()
+        };
+        let addrs = (1..=4)
+            .map(|i| HostAddr {
+                host: format!("198.0.0.{}", i),
+                port: 8970,
+            })
+            .collect::<Vec<_>>();
+        let (gateways, mut ack_rxs): (Vec<_>, Vec<_>) = addrs
+            .iter()
+            .map(|addr| {
+                let (gateway, ack_rx, _) =
+                    MockRpcGateway::with_host_addr(addr, builder.buf_size, 10);
+                (gateway, Some(ack_rx))
+            })
+            .unzip();
+        // the receivers of the first two nodes are dropped, so they fail every ack
+        ack_rxs[0].take();
+        ack_rxs[1].take();
+
+        let (responder, tx) = builder.build_with_nodes(&addrs, |addr, _, _| {
+            let index = addrs.iter().position(|a| a == addr).unwrap();
+            gateways[index].clone()
+        });
+        let mut winners = responder.subscribe_dispatch_winners();
+        let handler = tokio::spawn(responder);
+
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+        assert!(tx.send(ack.clone()).await.is_ok());
+        assert_eq!(
+            winners.recv().await.unwrap(),
+            DispatchWinner {
+                host_addr: addrs[2].clone(),
+                ack: ack.clone(),
+                failed_attempts: 2,
+            }
+        );
+        assert_eq!(ack_rxs[2].as_mut().unwrap().recv().await, Some(ack));
+        assert!(ack_rxs[3].as_mut().unwrap().try_recv().is_err());
+
+        handler.abort();
+    }
+
+    #[tokio::test]
+    async fn test_ack_any_of_redispatches_undelivered_acks() {
+        use proto::common::{ack::RequestId, Response};
+        use tonic::async_trait;
+
+        use super::{
+            gateway::{ReceiveAckRpcGateway, RpcGateway},
+            AckNodeStats, AckResponderBuilder, DispatchMode,
+        };
+
+        /// rejects the acks of priority 1 if it's picky, and delivers the others to the mock gateway one by one
+        #[derive(Clone)]
+        struct PickyGateway {
+            mock: MockRpcGateway,
+            picky: bool,
+        }
+
+        impl RpcGateway for PickyGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                self.mock.get_host_addr()
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveAckRpcGateway for PickyGateway {
+            async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+                if self.picky && req.priority == 1 {
+                    Err(tonic::Status::invalid_argument("rejected"))
+                } else {
+                    self.mock.receive_ack(req).await
+                }
+            }
+        }
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971)];
+        let (picky, mut picky_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        let (fallback, mut fallback_rx, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+        let (mut responder, tx) = AckResponderBuilder {
+            dispatch_mode: DispatchMode::AnyOfFirstSuccess,
+            ..Default::default()
+        }
+        .build_with_nodes(&addrs, |addr, _, _| PickyGateway {
+            mock: if addr == &addrs[0] {
+                picky.clone()
+            } else {
+                fallback.clone()
+            },
+            picky: addr == &addrs[0],
+        });
+        let mut winners = responder.subscribe_dispatch_winners();
+
+        let ack_of = |heartbeat_id, priority| Ack {
+            request_id: Some(RequestId::HeartbeatId(heartbeat_id)),
+            priority,
+            ..Default::default()
+        };
+        for (heartbeat_id, priority) in [(0, 0), (1, 1), (2, 0), (3, 1)] {
+            assert!(tx.send(ack_of(heartbeat_id, priority)).await.is_ok());
+        }
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+
+        // every ack is tried on the first node, and only the rejected ones move on to the next one
+        let received = |rx: &mut tokio::sync::mpsc::Receiver<Ack>| {
+            std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>()
+        };
+        assert_eq!(received(&mut picky_rx), vec![ack_of(0, 0), ack_of(2, 0)]);
+        assert_eq!(received(&mut fallback_rx), vec![ack_of(1, 1), ack_of(3, 1)]);
+        let winners = std::iter::from_fn(|| winners.try_recv().ok())
+            .map(|winner| (winner.host_addr, winner.failed_attempts))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            winners,
+            vec![
+                (addrs[0].clone(), 0),
+                (addrs[0].clone(), 0),
+                (addrs[1].clone(), 1),
+                (addrs[1].clone(), 1)
+            ]
+        );
+        // the acks are counted one by one
+        assert_eq!(
+            responder.stats(),
+            [
+                (
+                    addrs[0].clone(),
+                    AckNodeStats {
+                        delivered: 2,
+                        dropped: 2
+                    }
+                ),
+                (
+                    addrs[1].clone(),
+                    AckNodeStats {
+                        delivered: 2,
+                        dropped: 0
+                    }
+                )
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ack_per_node_counts() {
+        use super::{AckResponderBuilder, DispatchMode};
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971), addr_of(8972)];
+        let (first, mut first_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        let (second, second_rx, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+        // the receiver of the failing gateway is dropped, so no ack is delivered to it
+        let (failing, _, _) = MockRpcGateway::with_host_addr(&addrs[2], 10, 10);
+        let gateway_of = |addr: &HostAddr| match addr.port {
+            8970 => first.clone(),
+            8971 => second.clone(),
+            _ => failing.clone(),
+        };
+
+        let (mut responder, tx) = AckResponderBuilder {
+            dispatch_mode: DispatchMode::Broadcast,
+            ..Default::default()
+        }
+        .build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+        let counter = responder.delivery_counter();
+        assert!(responder.per_node_counts().is_empty());
+
+        // 3 acks are delivered to both healthy nodes
+        for _ in 0..3 {
+            assert!(tx.send(Ack::default()).await.is_ok());
+        }
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        (0..3).for_each(|_| assert!(first_rx.try_recv().is_ok()));
+
+        // 2 more acks are delivered to the first node only
+        drop(second_rx);
+        for _ in 0..2 {
+            assert!(tx.send(Ack::default()).await.is_ok());
+        }
+        let handler = tokio::spawn(responder);
+        for _ in 0..2 {
+            assert!(first_rx.recv().await.is_some());
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        handler.abort();
+
+        assert_eq!(
+            counter.per_node_counts(),
+            [(addrs[0].clone(), 5), (addrs[1].clone(), 3)]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ack_stats() {
+        use super::{AckNodeStats, AckResponderBuilder, DispatchMode};
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971)];
+        let (healthy, mut healthy_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        // the receiver of the failing gateway is dropped, so every ack to it is dropped
+        let (failing, _, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+
+        let (mut responder, tx) = AckResponderBuilder {
+            dispatch_mode: DispatchMode::Broadcast,
+            ..Default::default()
+        }
+        .build_with_nodes(&addrs, |addr, _, _| {
+            if addr == &addrs[0] {
+                healthy.clone()
+            } else {
+                failing.clone()
+            }
+        });
+        for _ in 0..3 {
+            assert!(tx.send(Ack::default()).await.is_ok());
+        }
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        (0..3).for_each(|_| assert!(healthy_rx.try_recv().is_ok()));
+
+        assert_eq!(
+            responder.stats(),
+            [
+                (
+                    addrs[0].clone(),
+                    AckNodeStats {
+                        delivered: 3,
+                        dropped: 0
+                    }
+                ),
+                (
+                    addrs[1].clone(),
+                    AckNodeStats {
+                        delivered: 0,
+                        dropped: 3
+                    }
+                )
+            ]
+            .into_iter()
+            .collect()
+        );
+        // the nodes which never accepted an ack are absent in the delivered counts
+        assert_eq!(
+            responder.per_node_counts(),
+            [(addrs[0].clone(), 3)].into_iter().collect()
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_hung_gateway_times_out() {
+        use proto::common::Response;
+        use tonic::async_trait;
+
+        use super::{
+            gateway::{ReceiveAckRpcGateway, RpcGateway},
+            AckResponderBuilder, DispatchMode,
+        };
+
+        /// never responds if it's hung, otherwise delivers the acks to the mock gateway
+        #[derive(Clone)]
+        struct MaybeHungGateway {
+            mock: MockRpcGateway,
+            hung: bool,
+        }
+
+        impl RpcGateway for MaybeHungGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                self.mock.get_host_addr()
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveAckRpcGateway for MaybeHungGateway {
+            async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+                self.receive_acks(vec![req]).await.remove(0)
+            }
+
+            async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+                if self.hung {
+                    futures_util::future::pending::<()>().await;
+                }
+                self.mock.receive_acks(reqs).await
+            }
+        }
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971)];
+        let (hung, _hung_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        let (healthy, mut healthy_rx, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+        let gateway_of = |addr: &HostAddr| MaybeHungGateway {
+            mock: if addr == &addrs[0] {
+                hung.clone()
+            } else {
+                healthy.clone()
+            },
+            hung: addr == &addrs[0],
+        };
+
+        for dispatch_mode in [DispatchMode::Broadcast, DispatchMode::AnyOfFirstSuccess] {
+            let (mut responder, tx) = AckResponderBuilder {
+                rpc_timeout: 1,
+                dispatch_mode,
+                ..Default::default()
+            }
+            .build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+            assert!(tx.send(Ack::default()).await.is_ok());
+
+            // the batch is dispatched after the ack delay, and the hung call times out after the rpc timeout
+            let start = tokio::time::Instant::now();
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(2500), &mut responder).await;
+            assert!(result.is_err());
+            assert!(start.elapsed() >= std::time::Duration::from_secs(2));
+
+            // the hung gateway fails, and the ack is delivered to the healthy one
+            assert_eq!(responder.failures.get(&addrs[0]).failure_count, 1);
+            assert_eq!(healthy_rx.try_recv().ok(), Some(Ack::default()));
+            assert_eq!(
+                responder.per_node_counts(),
+                [(addrs[1].clone(), 1)].into_iter().collect()
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_slow_gateway_awaited_without_spinning() {
+        use proto::common::Response;
+        use tonic::async_trait;
+
+        use super::{
+            gateway::{ReceiveAckRpcGateway, RpcGateway},
+            AckResponderBuilder,
+        };
+
+        /// responds once its timer fires, which needs the responder to yield to the runtime
+        #[derive(Clone)]
+        struct SlowGateway(MockRpcGateway);
+
+        impl RpcGateway for SlowGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                self.0.get_host_addr()
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveAckRpcGateway for SlowGateway {
+            async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+                self.receive_acks(vec![req]).await.remove(0)
+            }
+
+            async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                self.0.receive_acks(reqs).await
+            }
+        }
+
+        let (gateway, mut rx, _) = MockRpcGateway::new(10, 10);
+        let (responder, tx) = AckResponderBuilder {
+            rpc_timeout: 1,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), |_, _, _| SlowGateway(gateway.clone()));
+        let counter = responder.delivery_counter();
+        let handler = tokio::spawn(responder);
+
+        // the current-thread runtime keeps driving the timers while the call is in flight
+        let start = tokio::time::Instant::now();
+        assert!(tx.send(Ack::default()).await.is_ok());
+        assert_eq!(rx.recv().await, Some(Ack::default()));
+        assert_eq!(start.elapsed(), std::time::Duration::from_millis(1500));
+        tokio::task::yield_now().await;
+        let stats = counter.stats();
+        let stats = stats.values().next().unwrap();
+        assert_eq!((stats.delivered, stats.dropped), (1, 0));
+
+        handler.abort();
+    }
+
+    #[test]
+    fn test_dispatch_mode_completion() {
+        use super::{Completion, DispatchMode};
+
+        // (successes, failures) of 3 gateways
+        let cases = [
+            (
+                DispatchMode::Broadcast,
+                [
+                    ((2, 0), Completion::Pending),
+                    ((3, 0), Completion::Succeeded),
+                    ((0, 1), Completion::Failed),
+                ],
+            ),
+            (
+                DispatchMode::AnyOfFirstSuccess,
+                [
+                    ((0, 2), Completion::Pending),
+                    ((1, 1), Completion::Succeeded),
+                    ((0, 3), Completion::Failed),
+                ],
+            ),
+            (
+                DispatchMode::QuorumOf(2),
+                [
+                    ((1, 1), Completion::Pending),
+                    ((2, 0), Completion::Succeeded),
+                    ((1, 2), Completion::Failed),
+                ],
+            ),
+        ];
+        for (mode, expectations) in cases {
+            for ((successes, failures), expected) in expectations {
+                assert_eq!(
+                    mode.completion(successes, failures, 3),
+                    expected,
+                    "{:?} with {} successes and {} failures",
+                    mode,
+                    successes,
+                    failures
+                );
+            }
+        }
+        // a quorum larger than the number of gateways is never reached
+        assert_eq!(
+            DispatchMode::QuorumOf(4).completion(0, 0, 3),
+            Completion::Failed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_quorum_dispatch() {
+        use super::{AckResponderBuilder, DispatchMode};
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971), addr_of(8972)];
+        let (ok_1, mut ok_1_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        let (ok_2, mut ok_2_rx, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+        // the receiver of the failing gateway is dropped, so every ack to it fails
+        let (failing, _, _) = MockRpcGateway::with_host_addr(&addrs[2], 10, 10);
+        let gateway_of = |addr: &HostAddr| match addr.port {
+            8970 => ok_1.clone(),
+            8971 => ok_2.clone(),
+            _ => failing.clone(),
+        };
+        let ack = Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id: 1,
+            }),
+            ..Default::default()
+        };
+
+        for (quorum, failing_called) in [(2, 0), (3, 1)] {
+            let (mut responder, tx) = AckResponderBuilder {
+                dispatch_mode: DispatchMode::QuorumOf(quorum),
+                ..Default::default()
+            }
+            .build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+            assert!(tx.send(ack.clone()).await.is_ok());
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+            assert!(result.is_err());
+
+            // both healthy gateways are needed to reach any quorum of 2 or more
+            assert_eq!(ok_1_rx.try_recv().ok(), Some(ack.clone()));
+            assert_eq!(ok_2_rx.try_recv().ok(), Some(ack.clone()));
+            assert!(responder.failures.get(&addrs[2]).failure_count <= failing_called);
+            if quorum == 3 {
+                // the quorum of 3 can't be reached without the response of the failing gateway
+                assert_eq!(responder.failures.get(&addrs[2]).failure_count, 1);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ack_quorum_clamped() {
+        use super::{AckResponderBuilder, DispatchMode};
+
+        let addr_of = |port| HostAddr {
+            host: "198.0.0.1".to_string(),
+            port,
+        };
+        let addrs = [addr_of(8970), addr_of(8971)];
+        let (first, mut first_rx, _) = MockRpcGateway::with_host_addr(&addrs[0], 10, 10);
+        let (second, mut second_rx, _) = MockRpcGateway::with_host_addr(&addrs[1], 10, 10);
+        let gateway_of = |addr: &HostAddr| {
+            if addr == &addrs[0] {
+                first.clone()
+            } else {
+                second.clone()
+            }
+        };
+
+        for (quorum, clamped) in [(0, 1), (3, 2)] {
+            let (mut responder, tx) = AckResponderBuilder {
+                dispatch_mode: DispatchMode::QuorumOf(quorum),
+                ..Default::default()
+            }
+            .build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+            assert_eq!(responder.dispatch_mode, DispatchMode::QuorumOf(clamped));
+
+            assert!(tx.send(Ack::default()).await.is_ok());
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+            assert!(result.is_err());
+            // the ack is delivered to the quorum instead of succeeding or failing without any call
+            let delivered = [first_rx.try_recv().is_ok(), second_rx.try_recv().is_ok()];
+            assert!(delivered.iter().filter(|delivered| **delivered).count() >= clamped);
+        }
+
+        // a quorum within the nodes is kept
+        let (responder, _) = AckResponderBuilder {
+            dispatch_mode: DispatchMode::QuorumOf(2),
+            ..Default::default()
+        }
+        .build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+        assert_eq!(responder.dispatch_mode, DispatchMode::QuorumOf(2));
+    }
+
+    #[tokio::test]
+    async fn test_zero_period_and_delay_clamped() {
+        use super::{AckResponderBuilder, MIN_TICK_SECS};
+
+        let (gateway, _, _) = MockRpcGateway::new(10, 10);
+        let heartbeat = HeartbeatBuilder {
+            period: 0,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(
+            heartbeat.ticker.period(),
+            std::time::Duration::from_secs(MIN_TICK_SECS)
+        );
+
+        let (responder, _) = AckResponderBuilder {
+            delay: 0,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert_eq!(
+            responder.delay,
+            std::time::Duration::from_secs(MIN_TICK_SECS)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_env_feature_toggles() {
+        use super::AckResponderBuilder;
+
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_SKIP_MISSED_TICKS", "true");
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_DRY_RUN", "1");
+        std::env::set_var("LIGHTFLUS_ACK_DRY_RUN", "on");
+        std::env::set_var("LIGHTFLUS_ACK_BATCHING", "off");
+
+        let (gateway, _, _) = MockRpcGateway::new(10, 10);
+        let heartbeat = HeartbeatBuilder {
+            env_toggles: true,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(
+            heartbeat.ticker.missed_tick_behavior(),
+            tokio::time::MissedTickBehavior::Skip
+        );
+        assert!(heartbeat.dry_run_log().is_some());
+
+        // the jitter defaults to a tenth of the period if it's not configured
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_JITTER", "on");
+        let heartbeat = HeartbeatBuilder {
+            env_toggles: true,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert!(matches!(
+            heartbeat.ticker,
+            super::HeartbeatTicker::Jittered { max_jitter, .. } if max_jitter == heartbeat.ticker.period() / 10
+        ));
+
+        let (responder, _) = AckResponderBuilder {
+            env_toggles: true,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert!(responder.dry_run_log().is_some());
+        assert!(!responder.batching);
+
+        // the environment is ignored unless the builder opts in
+        let heartbeat =
+            HeartbeatBuilder::default().build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(
+            heartbeat.ticker.missed_tick_behavior(),
+            tokio::time::MissedTickBehavior::Burst
+        );
+        assert!(heartbeat.dry_run_log().is_none());
+        assert!(matches!(heartbeat.ticker, super::HeartbeatTicker::Fixed(_)));
+        let (responder, _) =
+            AckResponderBuilder::default().build(&HostAddr::default(), |_, _, _| gateway.clone());
+        assert!(responder.batching);
+
+        // toggles can disable a configured feature, malformed ones are ignored
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_DRY_RUN", "off");
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_SKIP_MISSED_TICKS", "maybe");
+        std::env::set_var("LIGHTFLUS_HEARTBEAT_JITTER", "off");
+        let heartbeat = HeartbeatBuilder {
+            skip_missed_ticks: true,
+            dry_run: true,
+            env_toggles: true,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(
+            heartbeat.ticker.missed_tick_behavior(),
+            tokio::time::MissedTickBehavior::Skip
+        );
+        assert!(heartbeat.dry_run_log().is_none());
+
+        let heartbeat = HeartbeatBuilder {
+            jitter_ms: 100,
+            env_toggles: true,
+            ..Default::default()
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert!(matches!(heartbeat.ticker, super::HeartbeatTicker::Fixed(_)));
+
+        std::env::remove_var("LIGHTFLUS_HEARTBEAT_SKIP_MISSED_TICKS");
+        std::env::remove_var("LIGHTFLUS_HEARTBEAT_DRY_RUN");
+        std::env::remove_var("LIGHTFLUS_ACK_DRY_RUN");
+        std::env::remove_var("LIGHTFLUS_HEARTBEAT_JITTER");
+        std::env::remove_var("LIGHTFLUS_ACK_BATCHING");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_coalesced_for_same_node() {
+        use super::HeartbeatCoalescer;
+
+        let host_addr = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let (gateway, _, mut rx) = MockRpcGateway::with_host_addr(&host_addr, 10, 10);
+        let execution_id = |sub_id| SubDataflowId {
+            job_id: Some(ResourceId {
+                resource_id: format!("job-{}", sub_id),
+                namespace_id: "default".to_string(),
+            }),
+            sub_id,
+        };
+        let mut coalescer = HeartbeatCoalescer::new(&HeartbeatBuilder::default(), 0);
+
+        let sender = coalescer.register(&host_addr, execution_id(1), |_, _, _| gateway.clone());
+        assert!(sender.is_some());
+        for sub_id in [2, 3] {
+            assert!(coalescer
+                .register(&host_addr, execution_id(sub_id), |_, _, _| gateway.clone())
+                .is_none());
+        }
+        let handler = tokio::spawn(sender.unwrap());
+
+        // one heartbeat per tick carries all the three jobs
+        let heartbeat = rx.recv().await.unwrap();
+        assert_eq!(
+            heartbeat
+                .split_coalesced()
+                .into_iter()
+                .map(|heartbeat| heartbeat.subdataflow_id.unwrap())
+                .collect::<Vec<_>>(),
+            vec![execution_id(1), execution_id(2), execution_id(3)]
+        );
+        assert!(rx.try_recv().is_err());
+
+        coalescer.deregister(&host_addr, &execution_id(1));
+        let heartbeat = rx.recv().await.unwrap();
+        assert_eq!(heartbeat.subdataflow_id, Some(execution_id(2)));
+        assert_eq!(heartbeat.coalesced_subdataflow_ids, vec![execution_id(3)]);
+
+        // the sender stops once all the jobs are deregistered
+        coalescer.deregister(&host_addr, &execution_id(2));
+        coalescer.deregister(&host_addr, &execution_id(3));
+        assert!(coalescer.execution_ids(&host_addr).is_empty());
+        assert!(handler.await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_millisecond_period() {
+        let builder: HeartbeatBuilder = serde_json::from_str(
+            r#"{"period": 3, "connect_timeout": 3, "rpc_timeout": 3, "period_ms": 500}"#,
+        )
+        .unwrap();
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 10);
+        let heartbeat = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        let handler = tokio::spawn(heartbeat);
+
+        let start = tokio::time::Instant::now();
+        let mut elapsed = vec![];
+        for _ in 0..4 {
+            assert!(rx.recv().await.is_some());
+            elapsed.push(start.elapsed().as_millis());
+        }
+        assert_eq!(elapsed, vec![0, 500, 1000, 1500]);
+
+        // second-based configs are still supported
+        let builder: HeartbeatBuilder =
+            serde_json::from_str(r#"{"period": 2, "connect_timeout": 3, "rpc_timeout": 3}"#)
+                .unwrap();
+        assert_eq!(builder.period_ms, None);
+        let heartbeat = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(heartbeat.ticker.period(), std::time::Duration::from_secs(2));
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_transform() {
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        }
+        .with_transform(|heartbeat| {
+            heartbeat.node_type = NodeType::TaskWorker as i32;
+            heartbeat.task_id = 42;
+        });
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let handler =
+            tokio::spawn(builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone()));
+
+        for heartbeat_id in 0..3 {
+            let heartbeat = rx.recv().await.unwrap();
+            assert_eq!(heartbeat.node_type(), NodeType::TaskWorker);
+            assert_eq!(heartbeat.task_id, 42);
+            // the fields untouched by the hook keep their defaults
+            assert_eq!(heartbeat.heartbeat_id, heartbeat_id);
+            assert!(heartbeat.timestamp.is_some());
+        }
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_failure_burst() {
+        use super::{gateway::RpcGateway, FailureBurst, FailureBurstConfig};
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            failure_burst: Some(FailureBurstConfig {
+                window_ms: 5000,
+                min_failures: 3,
+            }),
+            ..Default::default()
+        };
+        // the heartbeat receiver is dropped, so every heartbeat fails
+        let (gateway, _, _) = MockRpcGateway::new(10, 10);
+        let host_addr = gateway.get_host_addr().clone();
+        let sender = builder.build(&host_addr, 0, |_, _, _| gateway.clone());
+        let mut bursts = sender.subscribe_failure_bursts();
+        let handler = tokio::spawn(sender);
+
+        // 5 failures within the first window are summarized into a single event once the window elapses
+        tokio::time::sleep(std::time::Duration::from_millis(4500)).await;
+        assert!(bursts.try_recv().is_err());
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let burst = FailureBurst {
+            addr: host_addr,
+            failure_count: 5,
+            window: std::time::Duration::from_secs(5),
+        };
+        assert_eq!(bursts.try_recv().ok(), Some(burst.clone()));
+        assert!(bursts.try_recv().is_err());
+
+        // the sustained failures emit a burst per window
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        assert_eq!(bursts.try_recv().ok(), Some(burst));
+        assert!(bursts.try_recv().is_err());
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_pre_execution_policy() {
+        use super::PreExecutionPolicy;
+
+        let execution_id = SubDataflowId {
+            job_id: Some(ResourceId {
+                resource_id: "job".to_string(),
+                namespace_id: "default".to_string(),
+            }),
+            sub_id: 1,
+        };
+        let placeholder = SubDataflowId {
+            job_id: None,
+            sub_id: u32::MAX,
+        };
+        let builder_of = |pre_execution: PreExecutionPolicy| HeartbeatBuilder {
+            period: 1,
+            pre_execution,
+            ..Default::default()
+        };
+
+        // heartbeats are sent without an execution id by default
+        assert_eq!(
+            HeartbeatBuilder::default().pre_execution,
+            PreExecutionPolicy::SendAnyway
+        );
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let handler = tokio::spawn(builder_of(PreExecutionPolicy::SendAnyway).build(
+            &HostAddr::default(),
+            0,
+            |_, _, _| gateway.clone(),
+        ));
+        let heartbeat = rx.recv().await.unwrap();
+        assert_eq!(heartbeat.subdataflow_id, None);
+        handler.abort();
+
+        // the placeholder stands in for the execution id
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let handler = tokio::spawn(
+            builder_of(PreExecutionPolicy::UsePlaceholder(placeholder.clone())).build(
+                &HostAddr::default(),
+                0,
+                |_, _, _| gateway.clone(),
+            ),
+        );
+        let heartbeat = rx.recv().await.unwrap();
+        assert_eq!(heartbeat.subdataflow_id, Some(placeholder.clone()));
+        handler.abort();
+
+        // nothing is sent until an execution id is assigned
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let sender = builder_of(PreExecutionPolicy::SuppressUntilSet).build(
+            &HostAddr::default(),
+            0,
+            |_, _, _| gateway.clone(),
+        );
+        let stats = sender.stats();
+        let handler = tokio::spawn(sender);
+        tokio::time::sleep(std::time::Duration::from_millis(3500)).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(stats.lock().unwrap().ticks, 0);
+        handler.abort();
+
+        // an assigned execution id overrides every policy
+        for policy in [
+            PreExecutionPolicy::SendAnyway,
+            PreExecutionPolicy::SuppressUntilSet,
+            PreExecutionPolicy::UsePlaceholder(placeholder.clone()),
+        ] {
+            let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+            let mut sender =
+                builder_of(policy).build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+            sender.update_execution_id(execution_id.clone());
+            let handler = tokio::spawn(sender);
+            let heartbeat = rx.recv().await.unwrap();
+            assert_eq!(heartbeat.subdataflow_id, Some(execution_id.clone()));
+            assert_eq!(heartbeat.heartbeat_id, 0);
+            handler.abort();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_node_health() {
+        use super::{gateway::RpcGateway, NodeHealth, NodeHealthConfig, NodeHealthTracker};
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            node_health: NodeHealthConfig {
+                suspect_after: 1,
+                down_after: 3,
+                down_backoff_ms: 5000,
+            },
+            ..Default::default()
+        };
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let host_addr = gateway.get_host_addr().clone();
+        gateway.fail_next_heartbeats(4);
+        let tracker = NodeHealthTracker::default();
+        let sender = builder
+            .build(&host_addr, 0, |_, _, _| gateway.clone())
+            .with_node_health_tracker(tracker.clone());
+        let stats = sender.stats();
+        let handler = tokio::spawn(sender);
+        let consecutive_failures = || tracker.get(&host_addr).unwrap().consecutive_failures;
+
+        // the heartbeats at 0s and 1s fail
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        assert_eq!(tracker.status(&host_addr), Some(NodeHealth::Suspect));
+        assert_eq!(consecutive_failures(), 2);
+
+        // the node is down after the third failure at 2s, and isn't heartbeated until 7s
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(tracker.status(&host_addr), Some(NodeHealth::Down));
+        tokio::time::sleep(std::time::Duration::from_secs(4)).await;
+        assert_eq!(consecutive_failures(), 3);
+        assert_eq!(stats.lock().unwrap().ticks, 3);
+
+        // the probe at 7s fails again and backs off until 12s
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(tracker.status(&host_addr), Some(NodeHealth::Down));
+        assert_eq!(consecutive_failures(), 4);
+        assert!(rx.try_recv().is_err());
+
+        // the probe at 12s succeeds, and the node is up again
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        assert_eq!(tracker.status(&host_addr), Some(NodeHealth::Up));
+        assert_eq!(consecutive_failures(), 0);
+        assert!(rx.try_recv().is_ok());
+        assert_eq!(
+            tracker.statuses(),
+            [(host_addr.clone(), NodeHealth::Up)].into_iter().collect()
+        );
+
+        handler.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_subnets() {
+        use std::sync::{Arc, Mutex};
+
+        use super::{
+            subnet::SubnetProfile, NodeHealth, NodeHealthConfig, NodeHealthTracker, SubnetHealth,
+        };
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            subnets: vec![
+                SubnetProfile {
+                    name: "local".to_string(),
+                    cidr: "10.0.1.0/24".to_string(),
+                    connect_timeout: Some(1),
+                    rpc_timeout: Some(1),
+                    node_health: Some(NodeHealthConfig {
+                        suspect_after: 1,
+                        down_after: 2,
+                        down_backoff_ms: 0,
+                    }),
+                },
+                SubnetProfile {
+                    name: "remote".to_string(),
+                    cidr: "10.0.2.0/24".to_string(),
+                    connect_timeout: Some(5),
+                    rpc_timeout: Some(10),
+                    node_health: Some(NodeHealthConfig {
+                        suspect_after: 2,
+                        down_after: 5,
+                        down_backoff_ms: 0,
+                    }),
+                },
+            ],
+            ..Default::default()
+        };
+        let host_addr = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        };
+        let local = host_addr("10.0.1.7");
+        let remote = host_addr("10.0.2.7");
+        let other = host_addr("10.0.3.7");
+        assert_eq!(builder.subnet_of(&local).unwrap().name, "local");
+        assert_eq!(builder.subnet_of(&remote).unwrap().name, "remote");
+        assert!(builder.subnet_of(&other).is_none());
+
+        let timeouts = Arc::new(Mutex::new(vec![]));
+        let tracker = NodeHealthTracker::default();
+        let (handlers, _receivers): (Vec<_>, Vec<_>) = [&local, &remote, &other]
+            .into_iter()
+            .map(|host_addr| {
+                let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+                gateway.fail_next_heartbeats(2);
+                let sender = builder
+                    .build(host_addr, 0, |host_addr, connect_timeout, rpc_timeout| {
+                        timeouts.lock().unwrap().push((
+                            host_addr.host.clone(),
+                            connect_timeout.as_secs(),
+                            rpc_timeout.as_secs(),
+                        ));
+                        gateway.clone()
+                    })
+                    .with_node_health_tracker(tracker.clone());
+                (tokio::spawn(sender), rx)
+            })
+            .unzip();
+
+        // the subnets override the timeouts of the builder
+        assert_eq!(
+            *timeouts.lock().unwrap(),
+            vec![
+                ("10.0.1.7".to_string(), 1, 1),
+                ("10.0.2.7".to_string(), 5, 10),
+                (
+                    "10.0.3.7".to_string(),
+                    builder.connect_timeout,
+                    builder.rpc_timeout
+                ),
+            ]
+        );
+
+        // the heartbeats at 0s and 1s fail: it's enough to mark a local node down, but not a remote one
+        tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+        assert_eq!(tracker.status(&local), Some(NodeHealth::Down));
+        assert_eq!(tracker.status(&remote), Some(NodeHealth::Suspect));
+        assert_eq!(tracker.status(&other), Some(NodeHealth::Suspect));
+        assert_eq!(
+            tracker.get(&remote).unwrap().subnet,
+            Some("remote".to_string())
+        );
+        assert_eq!(
+            tracker.health_by_subnet(),
+            [
+                (
+                    None,
+                    SubnetHealth {
+                        suspect: 1,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    Some("local".to_string()),
+                    SubnetHealth {
+                        down: 1,
+                        ..Default::default()
+                    }
+                ),
+                (
+                    Some("remote".to_string()),
+                    SubnetHealth {
+                        suspect: 1,
+                        ..Default::default()
+                    }
+                ),
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        // the heartbeats at 2s succeed, and the nodes stay in their subnets
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let health = tracker.health_by_subnet();
+        assert_eq!(health.len(), 3);
+        assert!(health.values().all(|subnet| *subnet
+            == SubnetHealth {
+                up: 1,
+                ..Default::default()
+            }));
+
+        handlers.iter().for_each(|handler| handler.abort());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_results() {
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        let host_addrs = ["198.0.0.1", "198.0.0.2", "198.0.0.3"].map(|host| HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        });
+        let mut handlers = vec![];
+        let mut receivers = vec![];
+        let mut results = vec![];
+        for host_addr in &host_addrs {
+            let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+            let (sender, results_rx) =
+                builder.build_with_results(host_addr, 0, |_, _, _| gateway.clone());
+            handlers.push(tokio::spawn(sender));
+            receivers.push(rx);
+            results.push(results_rx);
+        }
+
+        // the first round at 0s succeeds
+        for (host_addr, results) in host_addrs.iter().zip(results.iter_mut()) {
+            let result = results.recv().await.unwrap();
+            assert_eq!(&result.host_addr, host_addr);
+            assert_eq!(result.heartbeat_id, 0);
+            assert!(result.is_success());
+            assert!(results.try_recv().is_err());
+        }
+        handlers.iter().for_each(|handler| handler.abort());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_rtt_histogram() {
+        use super::rtt::RttTracker;
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        let host_addrs = ["198.0.0.1", "198.0.0.2"].map(|host| HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        });
+        let tracker = RttTracker::default();
+        let mut handlers = vec![];
+        let mut receivers = vec![];
+        let mut results = vec![];
+        for (host_addr, failures) in host_addrs.iter().zip([0, 10]) {
+            let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+            gateway.fail_next_heartbeats(failures);
+            let (sender, results_rx) =
+                builder.build_with_results(host_addr, 0, |_, _, _| gateway.clone());
+            let sender = sender.with_rtt_tracker(tracker.clone());
+            assert!(sender.rtt_histogram(host_addr).is_none());
+            handlers.push(tokio::spawn(sender));
+            receivers.push(rx);
+            results.push(results_rx);
+        }
+
+        for _ in 0..3 {
+            for results in results.iter_mut() {
+                results.recv().await.unwrap();
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+        handlers.iter().for_each(|handler| handler.abort());
+
+        // only the successful heartbeats are measured
+        let rtt = tracker.snapshot(&host_addrs[0]).unwrap();
+        assert!(rtt.count >= 3);
+        assert!(rtt.min <= rtt.p50 && rtt.p50 <= rtt.p99 && rtt.p99 <= rtt.max);
+        assert!(tracker.snapshot(&host_addrs[1]).is_none());
+        assert_eq!(tracker.cluster_snapshot().unwrap().count, rtt.count);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_jitter() {
+        let builder = HeartbeatBuilder {
+            period_ms: Some(1000),
+            jitter_ms: 300,
+            ..Default::default()
+        };
+        assert_eq!(HeartbeatBuilder::default().jitter_ms, 0);
+
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        let start = tokio::time::Instant::now();
+        let sender = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert!(sender.next_tick_in() <= std::time::Duration::from_millis(300));
+        let handler = tokio::spawn(sender);
+
+        let mut ticks = vec![];
+        for _ in 0..8 {
+            assert!(rx.recv().await.is_some());
+            ticks.push(tokio::time::Instant::now());
+        }
+        handler.abort();
+
+        // the first tick is within the jitter, and each following one within the period ± the jitter
+        assert!(ticks[0] - start <= std::time::Duration::from_millis(300));
+        let gaps = ticks
+            .windows(2)
+            .map(|pair| pair[1] - pair[0])
+            .collect::<Vec<_>>();
+        gaps.iter().for_each(|gap| {
+            assert!(*gap >= std::time::Duration::from_millis(700), "{:?}", gap);
+            assert!(*gap <= std::time::Duration::from_millis(1300), "{:?}", gap);
+        });
+        // they're not perfectly periodic
+        assert!(gaps.iter().any(|gap| *gap != gaps[0]), "{:?}", gaps);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_period_overrides() {
+        let builder: HeartbeatBuilder = serde_json::from_str(
+            r#"{
+                "period": 5,
+                "connect_timeout": 3,
+                "rpc_timeout": 3,
+                "period_overrides": [{"host_addr": {"host": "coordinator", "port": 8791}, "period_ms": 1000}]
+            }"#,
+        )
+        .unwrap();
+        let coordinator = HostAddr {
+            host: "coordinator".to_string(),
+            port: 8791,
+        };
+        let worker = HostAddr {
+            host: "worker".to_string(),
+            port: 8792,
+        };
+        assert_eq!(
+            builder.period_of(&coordinator),
+            std::time::Duration::from_secs(1)
+        );
+        assert_eq!(
+            builder.period_of(&worker),
+            std::time::Duration::from_secs(5)
+        );
+
+        let (coordinator_gateway, _, mut coordinator_rx) = MockRpcGateway::new(10, 100);
+        let (worker_gateway, _, mut worker_rx) = MockRpcGateway::new(10, 10);
+        let handlers = [
+            tokio::spawn(builder.build(&coordinator, 0, |_, _, _| coordinator_gateway.clone())),
+            tokio::spawn(builder.build(&worker, 0, |_, _, _| worker_gateway.clone())),
+        ];
+
+        // each node receives heartbeats at its own cadence
+        let start = tokio::time::Instant::now();
+        let mut elapsed = vec![];
+        for _ in 0..3 {
+            assert!(worker_rx.recv().await.is_some());
+            elapsed.push(start.elapsed().as_millis());
+        }
+        assert_eq!(elapsed, vec![0, 5000, 10000]);
+
+        // the coordinator is beaten every second in the meantime
+        let mut heartbeat_ids = vec![];
+        while let Ok(heartbeat) = coordinator_rx.try_recv() {
+            heartbeat_ids.push(heartbeat.heartbeat_id);
+        }
+        assert_eq!(heartbeat_ids, (0..=10).collect::<Vec<_>>());
+
+        handlers.iter().for_each(|handler| handler.abort());
+    }
+
+    #[test]
+    fn test_export_net_config() {
+        use super::{AckResponderBuilder, DispatchMode, NetConfig, PeriodOverride};
+
+        let coordinator = HostAddr {
+            host: "coordinator".to_string(),
+            port: 8791,
+        };
+        let worker = HostAddr {
+            host: "worker".to_string(),
+            port: 8792,
+        };
+        let heartbeat = HeartbeatBuilder {
+            period: 5,
+            connect_timeout: 2,
+            rpc_timeout: 4,
+            cluster_id: Some("cluster-1".to_string()),
+            skip_missed_ticks: true,
+            period_overrides: vec![PeriodOverride {
+                host_addr: coordinator.clone(),
+                period_ms: 1000,
+            }],
+            ..Default::default()
+        };
+        let ack = AckResponderBuilder {
+            delay: 0,
+            buf_size: 64,
+            dispatch_mode: DispatchMode::AnyOfFirstSuccess,
+            dry_run: true,
+            ..Default::default()
+        };
+
+        let config = NetConfig::new(&heartbeat, &ack, &[coordinator, worker]).to_json();
+        assert_eq!(config["heartbeat"]["period"], 5);
+        assert_eq!(config["heartbeat"]["connect_timeout"], 2);
+        assert_eq!(config["heartbeat"]["rpc_timeout"], 4);
+        assert_eq!(config["heartbeat"]["cluster_id"], "cluster-1");
+        assert_eq!(config["ack"]["buf_size"], 64);
+        assert_eq!(config["ack"]["dispatch_mode"], "AnyOfFirstSuccess");
+        assert_eq!(
+            config["nodes"],
+            serde_json::json!([
+                {"host": "coordinator", "port": 8791},
+                {"host": "worker", "port": 8792}
+            ])
+        );
+        assert_eq!(
+            config["heartbeat_periods_ms"],
+            serde_json::json!([
+                {"host_addr": {"host": "coordinator", "port": 8791}, "period_ms": 1000},
+                {"host_addr": {"host": "worker", "port": 8792}, "period_ms": 5000}
+            ])
+        );
+        // the zero delay is clamped
+        assert_eq!(config["ack_delay_ms"], super::MIN_TICK_SECS * 1000);
+        assert_eq!(
+            config["feature_toggles"],
+            serde_json::json!({
+                "ACK_BATCHING": true,
+                "ACK_DRY_RUN": true,
+                "HEARTBEAT_DRY_RUN": false,
+                "HEARTBEAT_JITTER": false,
+                "HEARTBEAT_SKIP_MISSED_TICKS": true
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_records_without_sending() {
+        use super::{gateway::RpcGateway, AckResponderBuilder};
+
+        let addrs = (1..=3)
+            .map(|index| HostAddr {
+                host: format!("198.0.0.{}", index),
+                port: 8970,
+            })
+            .collect::<Vec<_>>();
+        let gateways = addrs
+            .iter()
+            .map(|addr| MockRpcGateway::with_host_addr(addr, 10, 10))
+            .collect::<Vec<_>>();
+        let gateway_of = |addr: &HostAddr| {
+            gateways
+                .iter()
+                .find(|(gateway, _, _)| gateway.get_host_addr() == addr)
+                .map(|(gateway, _, _)| gateway.clone())
+                .unwrap()
+        };
+
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 10,
+            dry_run: true,
+            ..Default::default()
+        };
+        let (mut responder, tx) = builder.build_with_nodes(&addrs, |addr, _, _| gateway_of(addr));
+        let ack_log = responder.dry_run_log().unwrap();
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+        assert!(tx.try_send(ack.clone()).is_ok());
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut responder).await;
+        assert!(result.is_err());
+        assert_eq!(
+            ack_log.entries(),
+            addrs
+                .iter()
+                .map(|addr| (addr.clone(), ack.clone()))
+                .collect::<Vec<_>>()
+        );
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            dry_run: true,
+            ..Default::default()
+        };
+        let mut heartbeat = builder.build(&addrs[0], 0, |addr, _, _| gateway_of(addr));
+        let heartbeat_log = heartbeat.dry_run_log().unwrap();
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_err());
+        let entries = heartbeat_log.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, addrs[0]);
+        assert_eq!(entries[0].1.heartbeat_id, 0);
+
+        // no gateway rpc is invoked
+        drop(responder);
+        drop(heartbeat);
+        gateways
+            .into_iter()
+            .for_each(|(_, mut ack_rx, mut heartbeat_rx)| {
+                assert!(ack_rx.try_recv().is_err());
+                assert!(heartbeat_rx.try_recv().is_err());
+            });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_adaptive_buffer_grows_under_sustained_overload() {
+        use super::{AckResponderBuilder, AdaptiveBufferBuilder};
+
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 2,
+            adaptive_buffer: Some(AdaptiveBufferBuilder {
+                max_buf_size: 8,
+                grow_after: 2,
+                shrink_after: 2,
+            }),
+            ..Default::default()
+        };
+        let (gateway, mut rx, _) = MockRpcGateway::new(1000, 10);
+        let (responder, tx) =
+            builder.build_resizable(&HostAddr::default(), |_, _, _| gateway.clone());
+        let ack = Ack {
+            timestamp: None,
+            ack_type: AckType::Heartbeat as i32,
+            node_type: NodeType::JobManager as i32,
+            execution_id: None,
+            request_id: None,
+            priority: 0,
+        };
+
+        // keep the queue full
+        let producer = {
+            let tx = tx.clone();
+            let ack = ack.clone();
+            tokio::spawn(async move { while tx.send(ack.clone()).await.is_ok() {} })
+        };
+        let handler = tokio::spawn(responder);
+
+        let mut capacities = vec![tx.capacity()];
+        for _ in 0..10 {
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            capacities.push(tx.capacity());
+        }
+        assert!(capacities.iter().all(|capacity| (2..=8).contains(capacity)));
+        assert!(capacities.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(capacities.last(), Some(&8));
+        assert!(rx.try_recv().is_ok());
+
+        // shrink back to the baseline once the load is gone
+        producer.abort();
+        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        assert_eq!(tx.capacity(), 2);
+
+        handler.abort();
     }
-}
 
-#[cfg(test)]
-mod tests {
+    #[tokio::test(start_paused = true)]
+    async fn test_connect_all_bounded_concurrency() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
 
-    use chrono::{Duration, Timelike};
-    use proto::common::{ack::AckType, Ack, HostAddr, NodeType, ResourceId, SubDataflowId};
+        use tonic::async_trait;
 
-    use crate::net::gateway::MockRpcGateway;
+        use super::{
+            connect_all,
+            gateway::{ConnectRpcGateway, RpcGateway},
+        };
 
-    use super::HeartbeatBuilder;
+        struct SlowConnectGateway {
+            host_addr: HostAddr,
+            in_progress: Arc<AtomicUsize>,
+            max_in_progress: Arc<AtomicUsize>,
+        }
 
-    #[test]
-    pub fn test_local_ip() {
-        use super::local_ip;
-        let option = local_ip();
-        assert!(option.is_some());
-        println!("{}", option.unwrap())
-    }
+        impl RpcGateway for SlowConnectGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
 
-    #[test]
-    pub fn test_hostname() {
-        let host = super::hostname();
-        assert!(host.is_some());
+        #[async_trait]
+        impl ConnectRpcGateway for SlowConnectGateway {
+            async fn connect(&self) -> Result<(), tonic::transport::Error> {
+                let in_progress = self.in_progress.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_in_progress
+                    .fetch_max(in_progress, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                self.in_progress.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let in_progress = Arc::new(AtomicUsize::default());
+        let max_in_progress = Arc::new(AtomicUsize::default());
+        let gateways = (0..50)
+            .map(|port| SlowConnectGateway {
+                host_addr: HostAddr {
+                    host: "198.0.0.1".to_string(),
+                    port,
+                },
+                in_progress: in_progress.clone(),
+                max_in_progress: max_in_progress.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let results = connect_all(&gateways, 4).await;
+        assert_eq!(results.len(), 50);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(results[10].0.port, 10);
+        assert_eq!(max_in_progress.load(Ordering::SeqCst), 4);
+        assert_eq!(in_progress.load(Ordering::SeqCst), 0);
     }
 
-    #[tokio::test]
-    async fn test_ack_success() {
-        use super::AckResponderBuilder;
+    #[test]
+    fn test_partition_acks_by_target() {
+        use super::partition_acks_by_target;
 
-        let builder = AckResponderBuilder {
-            delay: 3,
-            buf_size: 10,
-            connect_timeout: 3,
-            rpc_timeout: 3,
+        let node_1 = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let node_2 = HostAddr {
+            host: "198.0.0.2".to_string(),
+            port: 8970,
+        };
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let router = |ack: &Ack| match ack.get_execution_id().map(|id| id.sub_id) {
+            Some(1) => Some(node_1.clone()),
+            Some(2) => Some(node_2.clone()),
+            _ => None,
         };
 
-        let (gateway, mut rx, _) = MockRpcGateway::new(builder.buf_size, 10);
-
-        let (responder, tx) = builder.build(
-            &HostAddr {
-                host: "198.0.0.1".to_string(),
-                port: 8970,
-            },
-            |_, _, _| gateway.clone(),
+        let partitions = partition_acks_by_target(
+            vec![ack_of(1), ack_of(2), ack_of(1), ack_of(3), Ack::default()],
+            &router,
         );
 
-        let handler = tokio::spawn(responder);
-        // send first time
-        {
-            let result = tx
-                .send(Ack {
-                    timestamp: None,
-                    ack_type: AckType::Heartbeat as i32,
-                    node_type: NodeType::JobManager as i32,
-                    execution_id: None,
-                    request_id: None,
-                })
-                .await;
-            let start = chrono::Utc::now();
-            assert!(result.is_ok());
-
-            let result = rx.recv().await;
-            let end = chrono::Utc::now();
-            assert_eq!(
-                result,
-                Some(Ack {
-                    timestamp: None,
-                    ack_type: AckType::Heartbeat as i32,
-                    node_type: NodeType::JobManager as i32,
-                    execution_id: None,
-                    request_id: None,
-                })
-            );
-
-            let duration = end - start;
-            assert!(duration <= Duration::seconds(1));
-        }
+        assert_eq!(partitions.len(), 3);
+        assert_eq!(partitions.get(&node_1), Some(&vec![ack_of(1), ack_of(1)]));
+        assert_eq!(partitions.get(&node_2), Some(&vec![ack_of(2)]));
+        assert_eq!(
+            partitions.get(&HostAddr::default()),
+            Some(&vec![ack_of(3), Ack::default()])
+        );
+    }
 
-        // send second time
-        {
-            let result = tx
-                .send(Ack {
-                    timestamp: None,
-                    ack_type: AckType::Heartbeat as i32,
-                    node_type: NodeType::JobManager as i32,
-                    execution_id: None,
-                    request_id: None,
-                })
-                .await;
-            assert!(result.is_ok());
-            let start = chrono::Utc::now();
+    #[tokio::test(start_paused = true)]
+    async fn test_ack_routed_to_target_nodes() {
+        use super::AckResponderBuilder;
 
-            let result = rx.recv().await;
-            let end = chrono::Utc::now();
-            assert_eq!(
-                result,
-                Some(Ack {
-                    timestamp: None,
-                    ack_type: AckType::Heartbeat as i32,
-                    node_type: NodeType::JobManager as i32,
-                    execution_id: None,
-                    request_id: None,
-                })
-            );
+        let node_1 = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let node_2 = HostAddr {
+            host: "198.0.0.2".to_string(),
+            port: 8970,
+        };
+        let ack_of = |sub_id: u32| Ack {
+            execution_id: Some(SubDataflowId {
+                job_id: None,
+                sub_id,
+            }),
+            ..Default::default()
+        };
+        let router = {
+            let (node_1, node_2) = (node_1.clone(), node_2.clone());
+            move |ack: &Ack| match ack.get_execution_id().map(|id| id.sub_id) {
+                Some(1) => Some(node_1.clone()),
+                Some(2) => Some(node_2.clone()),
+                _ => None,
+            }
+        };
+        let (gateway_1, mut rx_1, _) = MockRpcGateway::with_host_addr(&node_1, 10, 10);
+        let (gateway_2, mut rx_2, _) = MockRpcGateway::with_host_addr(&node_2, 10, 10);
 
-            let duration = end.second() - start.second();
-            assert!(duration == 3)
+        let (responder, tx) = AckResponderBuilder::default().build_with_nodes(
+            &[node_1.clone(), node_2.clone()],
+            |addr, _, _| {
+                if addr == &node_1 {
+                    gateway_1.clone()
+                } else {
+                    gateway_2.clone()
+                }
+            },
+        );
+        let handler = tokio::spawn(responder.with_router(router));
+        for sub_id in [1, 2, 1, 3] {
+            assert!(tx.send(ack_of(sub_id)).await.is_ok());
         }
-
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
         handler.abort();
+
+        // each node gets its own acks in one batch, and the unroutable ack is broadcast
+        let received = |rx: &mut tokio::sync::mpsc::Receiver<Ack>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|ack| ack.execution_id.unwrap().sub_id)
+                .collect::<Vec<_>>()
+        };
+        let (mut received_1, mut received_2) = (received(&mut rx_1), received(&mut rx_2));
+        received_1.sort();
+        received_2.sort();
+        assert_eq!(received_1, vec![1, 1, 3]);
+        assert_eq!(received_2, vec![2, 3]);
+        assert_eq!(gateway_1.ack_batches(), vec![2, 1]);
+        assert_eq!(gateway_2.ack_batches(), vec![1, 1]);
     }
 
     #[tokio::test]
@@ -446,6 +5924,7 @@ mod tests {
             period: 3,
             connect_timeout: 3,
             rpc_timeout: 3,
+            ..Default::default()
         };
 
         let (gateway, _, mut rx) = MockRpcGateway::new(10, 10);
@@ -480,12 +5959,525 @@ mod tests {
         handler.abort()
     }
 
+    #[tokio::test]
+    async fn test_heartbeat_fanout_overrun() {
+        use proto::common::{Heartbeat, Response};
+        use tonic::async_trait;
+
+        use super::gateway::{ReceiveHeartbeatRpcGateway, RpcGateway};
+
+        struct SlowGateway {
+            host_addr: HostAddr,
+        }
+
+        impl RpcGateway for SlowGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveHeartbeatRpcGateway for SlowGateway {
+            async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+                std::thread::sleep(std::time::Duration::from_millis(1100));
+                Ok(Response::ok())
+            }
+        }
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+
+        let mut heartbeat = builder.build(&HostAddr::default(), 0, |addr, _, _| SlowGateway {
+            host_addr: addr.clone(),
+        });
+        let stats = heartbeat.stats();
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_err());
+        {
+            let stats = stats.lock().unwrap();
+            assert!(stats.ticks >= 1);
+            assert_eq!(stats.overrun_ticks, stats.ticks);
+            assert!(stats.last_fanout_duration >= std::time::Duration::from_millis(1100));
+            assert!(stats.max_fanout_duration >= stats.last_fanout_duration);
+        }
+
+        let (gateway, _rx, _) = MockRpcGateway::new(10, 10);
+        let mut heartbeat = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        let stats = heartbeat.stats();
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_err());
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.ticks, 1);
+        assert_eq!(stats.overrun_ticks, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_failure_waits_for_next_tick() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        use proto::common::{Heartbeat, Response};
+        use tonic::async_trait;
+
+        use super::gateway::{ReceiveHeartbeatRpcGateway, RpcGateway};
+
+        /// fails every heartbeat after `latency`
+        struct UnreachableGateway {
+            host_addr: HostAddr,
+            latency: std::time::Duration,
+            calls: Arc<AtomicU64>,
+        }
+
+        impl RpcGateway for UnreachableGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveHeartbeatRpcGateway for UnreachableGateway {
+            async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(self.latency).await;
+                Err(tonic::Status::unavailable("unreachable"))
+            }
+        }
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        for latency in [0, 300] {
+            let calls = Arc::new(AtomicU64::default());
+            let mut heartbeat =
+                builder.build(&HostAddr::default(), 0, |addr, _, _| UnreachableGateway {
+                    host_addr: addr.clone(),
+                    latency: std::time::Duration::from_millis(latency),
+                    calls: calls.clone(),
+                });
+
+            // a single heartbeat is sent at each of 0s, 1s, ..., 5s, whether the failure is immediate or not
+            let result =
+                tokio::time::timeout(std::time::Duration::from_millis(5500), &mut heartbeat).await;
+            assert!(result.is_err());
+            assert_eq!(calls.load(Ordering::SeqCst), 6, "latency {}ms", latency);
+            assert_eq!(
+                heartbeat.success_rate(std::time::Duration::from_secs(10)),
+                0.0
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_shutdown() {
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        // the second heartbeat is in flight until the first one is taken from the channel
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 1);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
+        let mut heartbeat = builder
+            .build(&HostAddr::default(), 0, |_, _, _| gateway.clone())
+            .with_shutdown(shutdown_rx);
+
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1500), &mut heartbeat).await;
+        assert!(result.is_err());
+        assert!(shutdown_tx.send(()).is_ok());
+
+        // the in-flight heartbeat is awaited on shutdown
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_err());
+        assert_eq!(rx.recv().await.unwrap().heartbeat_id, 0);
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_ok());
+        assert_eq!(rx.recv().await.unwrap().heartbeat_id, 1);
+
+        // no more heartbeat is sent after the sender stops
+        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(heartbeat.stats().lock().unwrap().sent, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_membership_digest() {
+        use super::cluster::{check_membership_agreement, MembershipAgreement};
+
+        let members = vec![
+            HostAddr {
+                host: "198.0.0.1".to_string(),
+                port: 8080,
+            },
+            HostAddr {
+                host: "198.0.0.2".to_string(),
+                port: 8080,
+            },
+        ];
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 10);
+        let mut heartbeat = HeartbeatBuilder::default()
+            .build(&members[0], 0, |_, _, _| gateway.clone())
+            .with_membership(&members);
+
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(100), &mut heartbeat).await;
+        assert!(result.is_err());
+        let received = rx.recv().await.unwrap();
+        assert_eq!(
+            check_membership_agreement(&members, &received),
+            MembershipAgreement::Agreed
+        );
+        assert!(check_membership_agreement(&members[..1], &received).is_diverged());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_rpc_timeout() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        use proto::common::{Heartbeat, Response};
+        use tonic::async_trait;
+
+        use super::gateway::{ReceiveHeartbeatRpcGateway, RpcGateway};
+
+        /// never responds to the first heartbeat, and responds to the following ones immediately
+        struct HungGateway {
+            host_addr: HostAddr,
+            calls: Arc<AtomicU64>,
+        }
+
+        impl RpcGateway for HungGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveHeartbeatRpcGateway for HungGateway {
+            async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+                if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    futures_util::future::pending::<()>().await;
+                }
+                Ok(Response::ok())
+            }
+        }
+
+        let builder = HeartbeatBuilder {
+            period: 5,
+            rpc_timeout: 2,
+            ..Default::default()
+        };
+        let calls = Arc::new(AtomicU64::default());
+        let (mut heartbeat, mut results) =
+            builder.build_with_results(&HostAddr::default(), 0, |addr, _, rpc_timeout| {
+                assert_eq!(rpc_timeout, std::time::Duration::from_secs(2));
+                HungGateway {
+                    host_addr: addr.clone(),
+                    calls: calls.clone(),
+                }
+            });
+
+        // the hung heartbeat fails at 2s, and the next one is sent at 5s
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(5500), &mut heartbeat).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let first = results.try_recv().unwrap();
+        assert_eq!(first.heartbeat_id, 0);
+        assert_eq!(
+            first.result.unwrap_err().code(),
+            tonic::Code::DeadlineExceeded
+        );
+        let second = results.try_recv().unwrap();
+        assert_eq!(second.heartbeat_id, 1);
+        assert!(second.is_success());
+        assert_eq!(
+            heartbeat.success_rate(std::time::Duration::from_secs(10)),
+            0.5
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_retried_within_budget() {
+        use std::sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        };
+
+        use proto::common::{Heartbeat, Response};
+        use tonic::async_trait;
+
+        use super::{
+            gateway::{ReceiveHeartbeatRpcGateway, RpcGateway},
+            retry::{RetryPolicy, RetryingGateway},
+        };
+
+        /// never responds to the first 2 calls, and responds to the following ones immediately
+        struct HungGateway {
+            host_addr: HostAddr,
+            calls: Arc<AtomicU64>,
+        }
+
+        impl RpcGateway for HungGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveHeartbeatRpcGateway for HungGateway {
+            async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+                if self.calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    futures_util::future::pending::<()>().await;
+                }
+                Ok(Response::ok())
+            }
+        }
+
+        let builder = HeartbeatBuilder {
+            period: 10,
+            rpc_timeout: 2,
+            ..Default::default()
+        };
+        let policy = RetryPolicy {
+            retry: 3,
+            base_delay_ms: 100,
+            multiplier: 2.0,
+            max_delay_ms: 1000,
+            full_jitter: false,
+        };
+        let calls = Arc::new(AtomicU64::default());
+        let (mut heartbeat, mut results) =
+            builder.build_with_results(&HostAddr::default(), 0, |addr, _, rpc_timeout| {
+                RetryingGateway::new(
+                    HungGateway {
+                        host_addr: addr.clone(),
+                        calls: calls.clone(),
+                    },
+                    policy.clone(),
+                )
+                .with_attempt_timeout(rpc_timeout)
+            });
+        // 4 attempts of 2s, and the backoffs of 100ms, 200ms and 400ms
+        assert_eq!(
+            heartbeat
+                .gateway
+                .heartbeat_budget(std::time::Duration::from_secs(2)),
+            std::time::Duration::from_millis(8700)
+        );
+
+        // the first 2 attempts time out at 2s and 4.1s, the third one is responded at 4.3s, beyond the rpc timeout of the sender
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), &mut heartbeat).await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let first = results.try_recv().unwrap();
+        assert_eq!(first.heartbeat_id, 0);
+        assert!(first.is_success());
+        assert!(results.try_recv().is_err());
+        assert_eq!(
+            heartbeat.success_rate(std::time::Duration::from_secs(10)),
+            1.0
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_success_rate() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        use proto::common::{Heartbeat, Response};
+        use tonic::async_trait;
+
+        use super::gateway::{ReceiveHeartbeatRpcGateway, RpcGateway};
+
+        /// fails every fourth heartbeat
+        struct FlakyGateway {
+            host_addr: HostAddr,
+            calls: AtomicU64,
+        }
+
+        impl RpcGateway for FlakyGateway {
+            fn get_host_addr(&self) -> &HostAddr {
+                &self.host_addr
+            }
+        }
+
+        #[async_trait]
+        impl ReceiveHeartbeatRpcGateway for FlakyGateway {
+            async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+                if self.calls.fetch_add(1, Ordering::SeqCst) % 4 == 3 {
+                    Err(tonic::Status::unavailable("partitioned"))
+                } else {
+                    Ok(Response::ok())
+                }
+            }
+        }
+
+        let builder = HeartbeatBuilder {
+            period_ms: Some(100),
+            ..Default::default()
+        };
+        let mut heartbeat = builder.build(&HostAddr::default(), 0, |addr, _, _| FlakyGateway {
+            host_addr: addr.clone(),
+            calls: Default::default(),
+        });
+        assert_eq!(
+            heartbeat.success_rate(std::time::Duration::from_secs(1)),
+            1.0
+        );
+
+        // 40 heartbeats are sent at 0ms, 100ms, ..., 3900ms
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(3950), &mut heartbeat).await;
+        assert!(result.is_err());
+        assert_eq!(heartbeat.stats().lock().unwrap().ticks, 40);
+
+        let rate = heartbeat.success_rate(std::time::Duration::from_secs(10));
+        assert!((rate - 0.75).abs() < 1e-9, "{}", rate);
+        // the latest 8 heartbeats, i.e. heartbeat 32 to 39, contain 2 failures
+        let rate = heartbeat.success_rate(std::time::Duration::from_millis(750));
+        assert!((rate - 0.75).abs() < 1e-9, "{}", rate);
+
+        tokio::time::advance(std::time::Duration::from_secs(20)).await;
+        assert_eq!(
+            heartbeat.success_rate(std::time::Duration::from_secs(10)),
+            1.0
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_tick_alignment() {
+        use super::tick_alignment;
+
+        let (gateway, _rx, _) = MockRpcGateway::new(10, 10);
+        let node_of = |i| HostAddr {
+            host: format!("198.0.0.{}", i),
+            port: 8970,
+        };
+
+        // the senders built at once without jitter tick in lockstep
+        let builder = HeartbeatBuilder {
+            period: 10,
+            ..Default::default()
+        };
+        let mut aligned = (1..=50)
+            .map(|i| builder.build(&node_of(i), 0, |_, _, _| gateway.clone()))
+            .collect::<Vec<_>>();
+        let metric = tick_alignment(&aligned.iter().collect::<Vec<_>>());
+        assert_eq!(metric.senders, 50);
+        assert!((metric.alignment - 1.0).abs() < 1e-9, "{:?}", metric);
+        assert_eq!(metric.spread, std::time::Duration::ZERO);
+
+        // the ticks a whole period apart are aligned, and the ones half a period apart cancel out
+        let now = tokio::time::Instant::now();
+        aligned[1].next_tick = now + std::time::Duration::from_secs(10);
+        let metric = tick_alignment(&[&aligned[0], &aligned[1]]);
+        assert!((metric.alignment - 1.0).abs() < 1e-9, "{:?}", metric);
+        assert_eq!(metric.spread, std::time::Duration::from_secs(10));
+        aligned[1].next_tick = now + std::time::Duration::from_secs(5);
+        let metric = tick_alignment(&[&aligned[0], &aligned[1]]);
+        assert!(metric.alignment < 1e-9, "{:?}", metric);
+
+        // the first ticks of the jittered senders are spread within the first half of the period
+        let builder = HeartbeatBuilder {
+            period: 10,
+            jitter_ms: 5000,
+            ..Default::default()
+        };
+        let jittered = (1..=50)
+            .map(|i| builder.build(&node_of(i), 0, |_, _, _| gateway.clone()))
+            .collect::<Vec<_>>();
+        let metric = tick_alignment(&jittered.iter().collect::<Vec<_>>());
+        assert_eq!(metric.senders, 50);
+        assert!(metric.alignment < 0.9, "{:?}", metric);
+        assert!(
+            metric.spread > std::time::Duration::from_secs(1),
+            "{:?}",
+            metric
+        );
+
+        let metric = tick_alignment::<MockRpcGateway>(&[]);
+        assert_eq!(metric.senders, 0);
+        assert_eq!(metric.alignment, 0.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_stats_counters() {
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        let (gateway, _, mut rx) = MockRpcGateway::new(10, 100);
+        gateway.fail_next_heartbeats(2);
+        let mut sender = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        let stats = sender.stats();
+
+        // 5 heartbeats are sent at 0s, 1s, ..., 4s, and the first 2 fail
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(4500), &mut sender).await;
+        assert!(result.is_err());
+        {
+            let stats = stats.lock().unwrap();
+            assert_eq!(stats.ticks, 5);
+            assert_eq!(stats.sent, 5);
+            assert_eq!(stats.failures, 2);
+        }
+        (0..3).for_each(|_| assert!(rx.try_recv().is_ok()));
+
+        // nothing is sent in a dry run
+        let mut sender = HeartbeatBuilder {
+            dry_run: true,
+            ..builder
+        }
+        .build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(2500), &mut sender).await;
+        assert!(result.is_err());
+        let stats = sender.stats();
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.ticks, 3);
+        assert_eq!(stats.sent, 0);
+        assert_eq!(stats.failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_next_tick_in() {
+        let builder = HeartbeatBuilder {
+            period: 3,
+            ..Default::default()
+        };
+        let (gateway, _rx, _) = MockRpcGateway::new(10, 10);
+        let mut heartbeat = builder.build(&HostAddr::default(), 0, |_, _, _| gateway.clone());
+        assert_eq!(heartbeat.next_tick_in(), std::time::Duration::ZERO);
+
+        let result =
+            tokio::time::timeout(std::time::Duration::from_millis(1), &mut heartbeat).await;
+        assert!(result.is_err());
+        tokio::time::advance(std::time::Duration::from_secs(1)).await;
+
+        let remaining = heartbeat.next_tick_in();
+        assert!(remaining <= std::time::Duration::from_secs(2));
+        assert!(remaining >= std::time::Duration::from_millis(1990));
+    }
+
     #[tokio::test]
     async fn test_heartbeat_update_execution_id() {
         let builder = HeartbeatBuilder {
             period: 3,
             connect_timeout: 3,
             rpc_timeout: 3,
+            ..Default::default()
         };
 
         let (gateway, _, _) = MockRpcGateway::new(10, 10);
@@ -0,0 +1,655 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use proto::common::{Heartbeat, HostAddr};
+use tokio::{sync::broadcast, time::Instant};
+
+use crate::utils::times;
+
+use super::clock::{Clock, TokioClock};
+
+/// the default threshold of the clock skew between a sender and the receiver
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+/// the capacity of the channel of [NodeStateChange] events. A lagging subscriber misses the oldest events.
+const STATE_CHANGE_CHANNEL_SIZE: usize = 64;
+
+/// How a received heartbeat is ordered against the previous ones of the same node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeartbeatOrder {
+    /// the heartbeat id is greater than any previously seen id
+    InOrder,
+    /// the heartbeat id is less than or equal to a previously seen id, the heartbeat is reordered or duplicated
+    Reordered,
+}
+
+/// The heartbeat state of a remote node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeHeartbeatState {
+    /// the highest heartbeat id received from the node
+    pub highest_heartbeat_id: u64,
+    /// when the latest heartbeat was received
+    pub last_seen: Instant,
+    /// how many reordered or duplicated heartbeats were received
+    pub reordered_count: u64,
+    /// number of failures since the latest success
+    pub consecutive_failures: u32,
+    /// number of successes since the latest failure
+    pub consecutive_successes: u32,
+    /// whether the node is reported healthy
+    pub healthy: bool,
+    /// the clock skew measured by the latest timestamped heartbeat in milliseconds, positive if the clock of the sender is ahead
+    pub clock_skew_ms: i64,
+    /// whether the latest measured clock skew exceeds the threshold
+    pub clock_skewed: bool,
+    /// how many heartbeats were received with a clock skew exceeding the threshold
+    pub skewed_count: u64,
+    /// how many checks by [HeartbeatMonitor::check_missed] found no heartbeat since the previous check
+    pub missed_beats: u32,
+    /// whether the node has been declared dead by [HeartbeatMonitor::check_missed]
+    pub declared_dead: bool,
+}
+
+impl NodeHeartbeatState {
+    fn new(heartbeat_id: u64, now: Instant) -> Self {
+        Self {
+            highest_heartbeat_id: heartbeat_id,
+            last_seen: now,
+            reordered_count: 0,
+            consecutive_failures: 0,
+            consecutive_successes: 1,
+            healthy: true,
+            clock_skew_ms: 0,
+            clock_skewed: false,
+            skewed_count: 0,
+            missed_beats: 0,
+            declared_dead: false,
+        }
+    }
+}
+
+/// A node is declared dead or alive again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeStateChange {
+    pub host_addr: HostAddr,
+    /// false if the node is declared dead, true if it's alive again
+    pub alive: bool,
+    /// when the latest heartbeat of the node was received
+    pub last_seen: Instant,
+    /// the missed beats of the node when it's declared dead, see [NodeHeartbeatState::missed_beats]
+    pub missed_beats: u32,
+}
+
+/// How long ago a node was last seen, reported by [HeartbeatMonitor::staleness_report], e.g. to render `last seen 4s ago` in a CLI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeStaleness {
+    pub host_addr: HostAddr,
+    /// when the latest heartbeat of the node was received
+    pub last_seen: Instant,
+    /// the time elapsed since `last_seen` when the report is made
+    pub age: Duration,
+    pub healthy: bool,
+}
+
+/// [HeartbeatMonitor] is the receiver side of [super::HeartbeatSender].
+///
+/// It tracks the liveness of every remote node: a node is alive if a heartbeat of it has been received within the timeout.
+/// It also tracks the highest heartbeat id of every node. Any heartbeat whose id is less than or equal to a previously seen one
+/// is counted as reordered or duplicated, which may indicate network issues or a misbehaving sender.
+/// A reordered heartbeat still proves the node is alive.
+///
+/// Besides liveness, [HeartbeatMonitor] reports the health of every node with hysteresis, so that transient packet loss won't trigger alerts:
+/// a healthy node becomes unhealthy only after `failure_threshold` consecutive failures,
+/// and an unhealthy node becomes healthy again only after `recovery_threshold` consecutive successes.
+/// Each received heartbeat is a success, and each missed heartbeat found by [HeartbeatMonitor::check_missed] is a failure.
+///
+/// The timestamp stamped by the sender is compared against the local time when the heartbeat is received. A node whose clock skew
+/// exceeds the threshold is flagged and a warning is logged. Liveness only depends on the local receive time, so a skewed node is still alive.
+///
+/// The moment a node is declared dead by [HeartbeatMonitor::check_missed], and the moment it's alive again, are published as [NodeStateChange]
+/// events to the subscribers of [HeartbeatMonitor::subscribe], so that alerting doesn't need to poll [HeartbeatMonitor::is_alive].
+#[derive(Clone, Debug)]
+pub struct HeartbeatMonitor {
+    timeout: Duration,
+    failure_threshold: u32,
+    recovery_threshold: u32,
+    max_clock_skew: Duration,
+    nodes: HashMap<HostAddr, NodeHeartbeatState>,
+    clock: Arc<dyn Clock>,
+    /// when [HeartbeatMonitor::check_missed] was called last time
+    last_check: Option<Instant>,
+    state_changes: broadcast::Sender<NodeStateChange>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            failure_threshold: 1,
+            recovery_threshold: 1,
+            max_clock_skew: DEFAULT_MAX_CLOCK_SKEW,
+            nodes: Default::default(),
+            clock: Arc::new(TokioClock),
+            last_check: None,
+            state_changes: broadcast::channel(STATE_CHANGE_CHANNEL_SIZE).0,
+        }
+    }
+
+    /// Subscribe the [NodeStateChange] events published after the subscription
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeStateChange> {
+        self.state_changes.subscribe()
+    }
+
+    /// Read the time from `clock` instead of the tokio runtime
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set how many consecutive failures flip a node to unhealthy, and how many consecutive successes flip it back
+    pub fn with_thresholds(mut self, failure_threshold: u32, recovery_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self.recovery_threshold = recovery_threshold.max(1);
+        self
+    }
+
+    /// Set the threshold of the clock skew between a sender and the receiver, [DEFAULT_MAX_CLOCK_SKEW] by default
+    pub fn with_max_clock_skew(mut self, max_clock_skew: Duration) -> Self {
+        self.max_clock_skew = max_clock_skew;
+        self
+    }
+
+    /// Record a heartbeat received from `host_addr`
+    pub fn receive(&mut self, host_addr: &HostAddr, heartbeat: &Heartbeat) -> HeartbeatOrder {
+        let now = self.clock.now();
+        let recovery_threshold = self.recovery_threshold;
+        let max_clock_skew = self.max_clock_skew;
+        match self.nodes.get_mut(host_addr) {
+            Some(state) => {
+                state.last_seen = now;
+                state.missed_beats = 0;
+                if state.declared_dead {
+                    state.declared_dead = false;
+                    tracing::info!("node is alive again [host_addr: {:?}]", host_addr);
+                    let _ = self.state_changes.send(NodeStateChange {
+                        host_addr: host_addr.clone(),
+                        alive: true,
+                        last_seen: now,
+                        missed_beats: 0,
+                    });
+                }
+                Self::on_success(host_addr, state, recovery_threshold);
+                Self::measure_clock_skew(host_addr, state, heartbeat, max_clock_skew);
+                if heartbeat.heartbeat_id <= state.highest_heartbeat_id {
+                    state.reordered_count += 1;
+                    tracing::warn!(
+                        "reordered or duplicated heartbeat [host_addr: {:?}, heartbeat_id: {}, highest_heartbeat_id: {}]",
+                        host_addr,
+                        heartbeat.heartbeat_id,
+                        state.highest_heartbeat_id
+                    );
+                    HeartbeatOrder::Reordered
+                } else {
+                    state.highest_heartbeat_id = heartbeat.heartbeat_id;
+                    HeartbeatOrder::InOrder
+                }
+            }
+            None => {
+                let mut state = NodeHeartbeatState::new(heartbeat.heartbeat_id, now);
+                Self::measure_clock_skew(host_addr, &mut state, heartbeat, max_clock_skew);
+                self.nodes.insert(host_addr.clone(), state);
+                HeartbeatOrder::InOrder
+            }
+        }
+    }
+
+    /// whether a heartbeat of the node has been received within the timeout
+    pub fn is_alive(&self, host_addr: &HostAddr) -> bool {
+        self.nodes
+            .get(host_addr)
+            .map(|state| self.clock.now() - state.last_seen <= self.timeout)
+            .unwrap_or(false)
+    }
+
+    /// how many reordered or duplicated heartbeats were received from the node
+    pub fn reordered_count(&self, host_addr: &HostAddr) -> u64 {
+        self.nodes
+            .get(host_addr)
+            .map(|state| state.reordered_count)
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, host_addr: &HostAddr) -> Option<&NodeHeartbeatState> {
+        self.nodes.get(host_addr)
+    }
+
+    /// all nodes whose heartbeat has ever been received
+    pub fn nodes(&self) -> impl Iterator<Item = (&HostAddr, &NodeHeartbeatState)> {
+        self.nodes.iter()
+    }
+
+    /// The staleness of every node whose heartbeat has ever been received, the stalest first.
+    /// The ages are measured against the time of the call.
+    pub fn staleness_report(&self) -> Vec<NodeStaleness> {
+        let now = self.clock.now();
+        let mut report = self
+            .nodes
+            .iter()
+            .map(|(host_addr, state)| NodeStaleness {
+                host_addr: host_addr.clone(),
+                last_seen: state.last_seen,
+                age: now.saturating_duration_since(state.last_seen),
+                healthy: state.healthy,
+            })
+            .collect::<Vec<_>>();
+        report.sort_by(|a, b| {
+            b.age
+                .cmp(&a.age)
+                .then_with(|| a.host_addr.host.cmp(&b.host_addr.host))
+                .then_with(|| a.host_addr.port.cmp(&b.host_addr.port))
+        });
+        report
+    }
+
+    /// whether the clock skew of the node exceeds the threshold. Unknown nodes are not skewed.
+    pub fn is_clock_skewed(&self, host_addr: &HostAddr) -> bool {
+        self.nodes
+            .get(host_addr)
+            .map(|state| state.clock_skewed)
+            .unwrap_or(false)
+    }
+
+    /// whether the node is reported healthy. Unknown nodes are unhealthy.
+    pub fn is_healthy(&self, host_addr: &HostAddr) -> bool {
+        self.nodes
+            .get(host_addr)
+            .map(|state| state.healthy)
+            .unwrap_or(false)
+    }
+
+    /// Record a failure of every node whose heartbeat is missed, i.e. not received within the timeout, and declare it dead.
+    /// It should be called periodically, e.g. once per heartbeat period.
+    pub fn check_missed(&mut self) {
+        let timeout = self.timeout;
+        let failure_threshold = self.failure_threshold;
+        let now = self.clock.now();
+        let previous_check = self.last_check.replace(now);
+        let state_changes = &self.state_changes;
+        self.nodes.iter_mut().for_each(|(host_addr, state)| {
+            if previous_check
+                .map(|checked_at| state.last_seen <= checked_at)
+                .unwrap_or(false)
+            {
+                state.missed_beats = state.missed_beats.saturating_add(1);
+            }
+            if now - state.last_seen <= timeout {
+                return;
+            }
+            Self::on_failure(host_addr, state, failure_threshold);
+            if !state.declared_dead {
+                state.declared_dead = true;
+                tracing::warn!(
+                    "node is declared dead after {} missed beats [host_addr: {:?}]",
+                    state.missed_beats,
+                    host_addr
+                );
+                let _ = state_changes.send(NodeStateChange {
+                    host_addr: host_addr.clone(),
+                    alive: false,
+                    last_seen: state.last_seen,
+                    missed_beats: state.missed_beats,
+                });
+            }
+        });
+    }
+
+    /// Record a failure of the node, e.g. a failed rpc call to it
+    pub fn record_failure(&mut self, host_addr: &HostAddr) {
+        let failure_threshold = self.failure_threshold;
+        if let Some(state) = self.nodes.get_mut(host_addr) {
+            Self::on_failure(host_addr, state, failure_threshold)
+        }
+    }
+
+    /// Heartbeats without timestamp leave the measured skew untouched
+    fn measure_clock_skew(
+        host_addr: &HostAddr,
+        state: &mut NodeHeartbeatState,
+        heartbeat: &Heartbeat,
+        max_clock_skew: Duration,
+    ) {
+        let sent_at = match heartbeat.timestamp.as_ref() {
+            Some(timestamp) => times::from_prost_timestamp_to_utc_chrono(timestamp),
+            None => return,
+        };
+        let skew = sent_at - times::now();
+        state.clock_skew_ms = skew.num_milliseconds();
+        state.clock_skewed = state.clock_skew_ms.unsigned_abs() > max_clock_skew.as_millis() as u64;
+        if state.clock_skewed {
+            state.skewed_count += 1;
+            tracing::warn!(
+                "clock skew of heartbeat exceeds {:?} [host_addr: {:?}, heartbeat_id: {}, clock_skew_ms: {}]",
+                max_clock_skew,
+                host_addr,
+                heartbeat.heartbeat_id,
+                state.clock_skew_ms
+            );
+        }
+    }
+
+    fn on_failure(host_addr: &HostAddr, state: &mut NodeHeartbeatState, failure_threshold: u32) {
+        state.consecutive_successes = 0;
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.healthy && state.consecutive_failures >= failure_threshold {
+            state.healthy = false;
+            tracing::warn!(
+                "node becomes unhealthy after {} consecutive failures [host_addr: {:?}]",
+                state.consecutive_failures,
+                host_addr
+            );
+        }
+    }
+
+    fn on_success(host_addr: &HostAddr, state: &mut NodeHeartbeatState, recovery_threshold: u32) {
+        state.consecutive_failures = 0;
+        state.consecutive_successes = state.consecutive_successes.saturating_add(1);
+        if !state.healthy && state.consecutive_successes >= recovery_threshold {
+            state.healthy = true;
+            tracing::info!(
+                "node recovers after {} consecutive successes [host_addr: {:?}]",
+                state.consecutive_successes,
+                host_addr
+            );
+        }
+    }
+}
+
+/// The worst-case time for a dead node to be detected, if it's heartbeated every `period` and declared dead after `missed_threshold` missed beats.
+///
+/// The node may die right after a heartbeat is sent, which takes up to `rtt` to arrive. The following `missed_threshold` beats are missed,
+/// and since the missed beats are checked once per period, the last one may be found up to another period later.
+pub fn detection_time(period: Duration, missed_threshold: u32, rtt: Duration) -> Duration {
+    period.saturating_mul(missed_threshold.max(1).saturating_add(1)) + rtt
+}
+
+/// The longest heartbeat period, in whole milliseconds, whose [detection_time] is within `detection_sla`,
+/// e.g. the period to detect a dead node within 10 seconds after 3 missed beats.
+///
+/// A zero `missed_threshold` is treated as 1. If the SLA can't be satisfied at all, i.e. it's no longer than a millisecond
+/// per beat plus `rtt`, [Duration::ZERO] is returned and the caller should either relax the SLA or reduce the threshold.
+pub fn recommended_period(
+    detection_sla: Duration,
+    missed_threshold: u32,
+    rtt: Duration,
+) -> Duration {
+    let beats = missed_threshold.max(1).saturating_add(1);
+    let period_ms = detection_sla.saturating_sub(rtt).as_millis() / beats as u128;
+    Duration::from_millis(period_ms as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proto::common::{Heartbeat, HostAddr};
+
+    use crate::utils::times;
+
+    use super::{
+        detection_time, recommended_period, HeartbeatMonitor, HeartbeatOrder, NodeStaleness,
+        NodeStateChange,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_reorder_detection() {
+        let mut monitor = HeartbeatMonitor::new(Duration::from_secs(3));
+        let host_addr = &HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let heartbeat = |heartbeat_id| Heartbeat {
+            heartbeat_id,
+            ..Default::default()
+        };
+
+        let mut orders = vec![];
+        for heartbeat_id in [0, 1, 3, 2, 3, 4] {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            orders.push(monitor.receive(host_addr, &heartbeat(heartbeat_id)));
+        }
+        assert_eq!(
+            orders,
+            vec![
+                HeartbeatOrder::InOrder,
+                HeartbeatOrder::InOrder,
+                HeartbeatOrder::InOrder,
+                HeartbeatOrder::Reordered,
+                HeartbeatOrder::Reordered,
+                HeartbeatOrder::InOrder,
+            ]
+        );
+        assert_eq!(monitor.reordered_count(host_addr), 2);
+        assert_eq!(monitor.get(host_addr).unwrap().highest_heartbeat_id, 4);
+        assert!(monitor.is_alive(host_addr));
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert!(!monitor.is_alive(host_addr));
+        assert!(!monitor.is_alive(&HostAddr::default()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_health_thresholds() {
+        let mut monitor = HeartbeatMonitor::new(Duration::from_secs(3)).with_thresholds(3, 2);
+        let host_addr = &HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let mut heartbeat_id = 0;
+        let mut beat = |monitor: &mut HeartbeatMonitor| {
+            heartbeat_id += 1;
+            monitor.receive(
+                host_addr,
+                &Heartbeat {
+                    heartbeat_id,
+                    ..Default::default()
+                },
+            );
+        };
+        beat(&mut monitor);
+        assert!(monitor.is_healthy(host_addr));
+
+        // intermittent failures below the threshold never flip the node
+        for _ in 0..3 {
+            monitor.record_failure(host_addr);
+            monitor.record_failure(host_addr);
+            assert!(monitor.is_healthy(host_addr));
+            beat(&mut monitor);
+            assert!(monitor.is_healthy(host_addr));
+        }
+
+        // missed heartbeats reach the threshold
+        tokio::time::advance(Duration::from_secs(4)).await;
+        monitor.check_missed();
+        monitor.check_missed();
+        assert!(monitor.is_healthy(host_addr));
+        monitor.check_missed();
+        assert!(!monitor.is_healthy(host_addr));
+        assert_eq!(monitor.get(host_addr).unwrap().consecutive_failures, 3);
+
+        // one success is not enough to recover
+        beat(&mut monitor);
+        assert!(!monitor.is_healthy(host_addr));
+        monitor.record_failure(host_addr);
+        beat(&mut monitor);
+        assert!(!monitor.is_healthy(host_addr));
+        beat(&mut monitor);
+        assert!(monitor.is_healthy(host_addr));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_clock_skew_detection() {
+        let mut monitor = HeartbeatMonitor::new(Duration::from_secs(3))
+            .with_max_clock_skew(Duration::from_secs(5));
+        let host_addr = &HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let heartbeat = |heartbeat_id, skew: chrono::Duration| Heartbeat {
+            heartbeat_id,
+            timestamp: Some(times::from_utc_chrono_to_prost_timestamp(
+                &(times::now() + skew),
+            )),
+            ..Default::default()
+        };
+
+        monitor.receive(host_addr, &heartbeat(1, chrono::Duration::zero()));
+        assert!(!monitor.is_clock_skewed(host_addr));
+
+        // a heartbeat from far future flags the node, but the node is still alive
+        monitor.receive(host_addr, &heartbeat(2, chrono::Duration::hours(1)));
+        assert!(monitor.is_clock_skewed(host_addr));
+        assert!(monitor.is_alive(host_addr));
+        assert!(monitor.is_healthy(host_addr));
+        let state = monitor.get(host_addr).unwrap();
+        assert_eq!(state.skewed_count, 1);
+        assert!(state.clock_skew_ms > 3_590_000);
+
+        // heartbeats without timestamp keep the flag
+        monitor.receive(
+            host_addr,
+            &Heartbeat {
+                heartbeat_id: 3,
+                ..Default::default()
+            },
+        );
+        assert!(monitor.is_clock_skewed(host_addr));
+
+        monitor.receive(host_addr, &heartbeat(4, chrono::Duration::seconds(-1)));
+        assert!(!monitor.is_clock_skewed(host_addr));
+        assert_eq!(monitor.get(host_addr).unwrap().skewed_count, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_node_declared_dead_event() {
+        let mut monitor = HeartbeatMonitor::new(Duration::from_secs(3));
+        let mut state_changes = monitor.subscribe();
+        let host_addr = &HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let heartbeat = |heartbeat_id| Heartbeat {
+            heartbeat_id,
+            ..Default::default()
+        };
+
+        monitor.receive(host_addr, &heartbeat(1));
+        let last_seen = tokio::time::Instant::now();
+
+        // the node misses its beats, and is declared dead once the timeout elapses
+        for _ in 0..3 {
+            tokio::time::advance(Duration::from_secs(1)).await;
+            monitor.check_missed();
+            assert!(state_changes.try_recv().is_err());
+        }
+        tokio::time::advance(Duration::from_secs(1)).await;
+        monitor.check_missed();
+        assert_eq!(
+            state_changes.try_recv().unwrap(),
+            NodeStateChange {
+                host_addr: host_addr.clone(),
+                alive: false,
+                last_seen,
+                missed_beats: 3,
+            }
+        );
+
+        // a dead node is declared only once
+        tokio::time::advance(Duration::from_secs(1)).await;
+        monitor.check_missed();
+        assert!(state_changes.try_recv().is_err());
+
+        monitor.receive(host_addr, &heartbeat(2));
+        let change = state_changes.try_recv().unwrap();
+        assert!(change.alive);
+        assert_eq!(change.last_seen, tokio::time::Instant::now());
+        assert_eq!(monitor.get(host_addr).unwrap().missed_beats, 0);
+    }
+
+    #[test]
+    fn test_staleness_report() {
+        use std::sync::Arc;
+
+        use crate::net::clock::ManualClock;
+
+        let clock = ManualClock::new();
+        let mut monitor = HeartbeatMonitor::new(Duration::from_secs(3))
+            .with_clock(Arc::new(clock.clone()))
+            .with_thresholds(1, 1);
+        let host_addr = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        };
+        let (node_1, node_2) = (host_addr("198.0.0.1"), host_addr("198.0.0.2"));
+        assert!(monitor.staleness_report().is_empty());
+
+        monitor.receive(&node_1, &Heartbeat::default());
+        clock.advance(Duration::from_secs(4));
+        monitor.receive(&node_2, &Heartbeat::default());
+        monitor.check_missed();
+        clock.advance(Duration::from_millis(1500));
+
+        // node 1 was last seen 5.5s ago and is unhealthy after a missed check, node 2 was seen 1.5s ago
+        let report = monitor.staleness_report();
+        assert_eq!(
+            report,
+            vec![
+                NodeStaleness {
+                    host_addr: node_1.clone(),
+                    last_seen: monitor.get(&node_1).unwrap().last_seen,
+                    age: Duration::from_millis(5500),
+                    healthy: false,
+                },
+                NodeStaleness {
+                    host_addr: node_2.clone(),
+                    last_seen: monitor.get(&node_2).unwrap().last_seen,
+                    age: Duration::from_millis(1500),
+                    healthy: true,
+                },
+            ]
+        );
+
+        // the ages grow with the time of the call
+        clock.advance(Duration::from_secs(1));
+        let ages = monitor
+            .staleness_report()
+            .into_iter()
+            .map(|staleness| staleness.age.as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(ages, vec![6500, 2500]);
+    }
+
+    #[test]
+    fn test_recommended_period() {
+        let ms = Duration::from_millis;
+        let secs = Duration::from_secs;
+        // (detection sla, missed threshold, rtt, recommended period)
+        let cases = [
+            (secs(10), 3, ms(0), ms(2500)),
+            (secs(10), 3, ms(200), ms(2450)),
+            (secs(10), 1, ms(100), ms(4950)),
+            (secs(10), 0, ms(100), ms(4950)),
+            (secs(3), 5, ms(50), ms(491)),
+            (secs(1), 9, ms(1), ms(99)),
+        ];
+        for (sla, missed_threshold, rtt, period) in cases {
+            let recommended = recommended_period(sla, missed_threshold, rtt);
+            assert_eq!(recommended, period);
+            assert!(detection_time(recommended, missed_threshold, rtt) <= sla);
+            // a longer period violates the sla
+            assert!(detection_time(recommended + ms(1), missed_threshold, rtt) > sla);
+        }
+
+        // the sla can't be satisfied
+        assert_eq!(recommended_period(ms(100), 3, ms(100)), Duration::ZERO);
+        assert_eq!(recommended_period(ms(103), 3, ms(100)), Duration::ZERO);
+    }
+}
@@ -0,0 +1,281 @@
+use std::{sync::Arc, time::Duration};
+
+use proto::common::HostAddr;
+use tokio::{sync::Mutex, time::Instant};
+
+use super::{gateway::ConnectRpcGateway, random_duration};
+
+/// The cluster-wide budget of reconnect attempts
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectBudget {
+    /// max number of reconnect attempts per second across all gateways
+    pub max_per_second: u32,
+    /// max random delay added to each attempt, in milliseconds
+    #[serde(default)]
+    pub max_jitter_ms: u64,
+}
+
+/// Statistics of the reconnect attempts granted by a [ReconnectCoordinator]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReconnectStats {
+    /// number of granted attempts
+    pub attempts: u64,
+    /// number of attempts which had to wait for a slot
+    pub delayed: u64,
+    /// the longest wait of an attempt
+    pub max_wait: Duration,
+}
+
+#[derive(Debug)]
+struct State {
+    /// the earliest instant the next attempt can be granted
+    next_slot: Instant,
+    stats: ReconnectStats,
+}
+
+/// [ReconnectCoordinator] staggers the reconnect attempts of all gateways.
+///
+/// After a network partition heals, every gateway reconnects at the same time and floods the network and the peers.
+/// Gateways sharing one [ReconnectCoordinator] acquire a slot before each attempt: slots are at least `1 / max_per_second` apart
+/// and each slot is delayed by a random jitter, so the recovery is spread out instead of a stampede.
+///
+/// The gateways set by `with_reconnect_coordinator` acquire a slot before recreating the client dropped on a connection error.
+#[derive(Clone, Debug)]
+pub struct ReconnectCoordinator {
+    min_interval: Duration,
+    max_jitter: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl ReconnectCoordinator {
+    pub fn new(budget: &ReconnectBudget) -> Self {
+        Self {
+            min_interval: Duration::from_secs(1) / budget.max_per_second.max(1),
+            max_jitter: Duration::from_millis(budget.max_jitter_ms),
+            state: Arc::new(Mutex::new(State {
+                next_slot: Instant::now(),
+                stats: Default::default(),
+            })),
+        }
+    }
+
+    /// Wait until a reconnect attempt is allowed by the budget
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut state = self.state.lock().await;
+            let now = Instant::now();
+            if state.next_slot > now {
+                state.stats.delayed += 1;
+            }
+            let slot = state.next_slot.max(now) + random_duration(self.max_jitter);
+            state.next_slot = slot + self.min_interval;
+
+            state.stats.attempts += 1;
+            state.stats.max_wait = state.stats.max_wait.max(slot - now);
+            slot
+        };
+        tokio::time::sleep_until(slot).await
+    }
+
+    /// Reconnect the gateway once a slot is acquired
+    pub async fn reconnect<T: ConnectRpcGateway>(
+        &self,
+        gateway: &T,
+    ) -> Result<(), tonic::transport::Error> {
+        self.acquire().await;
+        tracing::info!("reconnect [host_addr: {:?}]", gateway.get_host_addr());
+        gateway.connect().await
+    }
+
+    /// Reconnect all gateways within the budget. The results are in the same order as the gateways.
+    pub async fn reconnect_all<'a, T: ConnectRpcGateway>(
+        &self,
+        gateways: &'a [T],
+    ) -> Vec<(&'a HostAddr, Result<(), tonic::transport::Error>)> {
+        futures_util::future::join_all(
+            gateways.iter().map(|gateway| async move {
+                (gateway.get_host_addr(), self.reconnect(gateway).await)
+            }),
+        )
+        .await
+    }
+
+    pub async fn stats(&self) -> ReconnectStats {
+        self.state.lock().await.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use proto::common::HostAddr;
+    use tokio::time::Instant;
+    use tonic::async_trait;
+
+    use crate::net::gateway::{ConnectRpcGateway, RpcGateway};
+
+    use super::{ReconnectBudget, ReconnectCoordinator};
+
+    struct RecordingGateway {
+        host_addr: HostAddr,
+        attempts: Arc<Mutex<Vec<Instant>>>,
+    }
+
+    impl RpcGateway for RecordingGateway {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    #[async_trait]
+    impl ConnectRpcGateway for RecordingGateway {
+        async fn connect(&self) -> Result<(), tonic::transport::Error> {
+            self.attempts.lock().unwrap().push(Instant::now());
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnect_storm_rate_limited() {
+        let attempts = Arc::new(Mutex::new(vec![]));
+        let gateways = (0..20)
+            .map(|index| RecordingGateway {
+                host_addr: HostAddr {
+                    host: format!("198.0.0.{}", index),
+                    port: 8970,
+                },
+                attempts: attempts.clone(),
+            })
+            .collect::<Vec<_>>();
+        let coordinator = ReconnectCoordinator::new(&ReconnectBudget {
+            max_per_second: 5,
+            max_jitter_ms: 50,
+        });
+
+        // the partition heals and all gateways reconnect at the same time
+        let start = Instant::now();
+        let results = coordinator.reconnect_all(&gateways).await;
+        assert_eq!(results.len(), 20);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+
+        let mut attempts = attempts.lock().unwrap().clone();
+        attempts.sort();
+        assert_eq!(attempts.len(), 20);
+        attempts.windows(2).for_each(|pair| {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(200));
+        });
+        attempts.iter().for_each(|attempt| {
+            let in_window = attempts
+                .iter()
+                .filter(|other| **other >= *attempt && **other < *attempt + Duration::from_secs(1))
+                .count();
+            assert!(in_window <= 5);
+        });
+        assert!(start.elapsed() >= Duration::from_millis(3800));
+
+        let stats = coordinator.stats().await;
+        assert_eq!(stats.attempts, 20);
+        assert_eq!(stats.delayed, 19);
+        assert!(stats.max_wait >= Duration::from_millis(3800));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gateway_reconnects_staggered() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use proto::common::Heartbeat;
+        use tonic::{codegen::http, transport::Body};
+
+        use crate::net::gateway::{
+            is_connection_error, taskmanager::SafeTaskManagerRpcGateway, ChannelFactory,
+            GatewayChannel, ReceiveHeartbeatRpcGateway,
+        };
+
+        // every channel created is recorded, and the calls fail by a connection error while the network is partitioned
+        let channels = Arc::new(Mutex::new(vec![]));
+        let partitioned = Arc::new(AtomicBool::new(false));
+        let factory = {
+            let channels = channels.clone();
+            let partitioned = partitioned.clone();
+            ChannelFactory::new(move |_, _| {
+                channels.lock().unwrap().push(Instant::now());
+                let partitioned = partitioned.clone();
+                GatewayChannel::new(tower::service_fn(move |_: http::Request<_>| {
+                    let partitioned = partitioned.load(Ordering::SeqCst);
+                    async move {
+                        if partitioned {
+                            return Err(tonic::Status::unavailable("connection reset by peer"));
+                        }
+                        // the node is reached and answers with an error, which keeps the connection
+                        Ok(http::Response::builder()
+                            .header("content-type", "application/grpc")
+                            .header("grpc-status", "3")
+                            .body(Body::empty())
+                            .unwrap())
+                    }
+                }))
+            })
+        };
+        let coordinator = ReconnectCoordinator::new(&ReconnectBudget {
+            max_per_second: 5,
+            max_jitter_ms: 0,
+        });
+        let gateways = (0..10)
+            .map(|index| {
+                SafeTaskManagerRpcGateway::with_timeout(
+                    &HostAddr {
+                        host: format!("198.0.0.{}", index),
+                        port: 8792,
+                    },
+                    Duration::from_secs(1),
+                    Duration::from_secs(1),
+                )
+                .with_channel_factory(factory.clone())
+                .with_reconnect_coordinator(coordinator.clone())
+            })
+            .collect::<Vec<_>>();
+        let heartbeat_all = || {
+            futures_util::future::join_all(
+                gateways
+                    .iter()
+                    .map(|gateway| gateway.receive_heartbeat(Heartbeat::default())),
+            )
+        };
+
+        let results = heartbeat_all().await;
+        assert!(results
+            .iter()
+            .all(|result| !is_connection_error(result.as_ref().unwrap_err())));
+        assert_eq!(channels.lock().unwrap().len(), 10);
+
+        // the partition drops the clients of all gateways
+        partitioned.store(true, Ordering::SeqCst);
+        let results = heartbeat_all().await;
+        assert!(results
+            .iter()
+            .all(|result| is_connection_error(result.as_ref().unwrap_err())));
+
+        // on recovery, the gateways reconnect within the budget instead of all at once
+        partitioned.store(false, Ordering::SeqCst);
+        let start = Instant::now();
+        let results = heartbeat_all().await;
+        assert!(results
+            .iter()
+            .all(|result| !is_connection_error(result.as_ref().unwrap_err())));
+        assert!(start.elapsed() >= Duration::from_millis(1800));
+
+        let channels = channels.lock().unwrap().clone();
+        assert_eq!(channels.len(), 20);
+        channels[10..].windows(2).for_each(|pair| {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(200));
+        });
+
+        // a slot is taken by each client created, the first ones included
+        let stats = coordinator.stats().await;
+        assert_eq!(stats.attempts, 20);
+    }
+}
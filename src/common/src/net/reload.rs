@@ -0,0 +1,666 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{atomic::AtomicU64, Arc},
+    time::Duration,
+};
+
+use proto::common::{HostAddr, SubDataflowId};
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use crate::types::ExecutorId;
+
+use super::{
+    gateway::ReceiveHeartbeatRpcGateway, rtt::RttTracker, FailureBurstConfig, HeartbeatBuilder,
+    NodeHealthConfig, NodeHealthTracker, PreExecutionPolicy,
+};
+
+/// Error of [HeartbeatGroup::reload] when the new config changes the settings which can't be applied without a restart
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReloadError {
+    /// the heartbeats would be stamped with another cluster, i.e. the node would move to another cluster
+    ClusterIdChanged {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// the node would switch between sending heartbeats and recording them in a dry run
+    DryRunChanged { from: bool, to: bool },
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReloadError::ClusterIdChanged { from, to } => f.write_fmt(format_args!(
+                "cluster id can't be reloaded from {:?} to {:?}, restart the node instead",
+                from, to
+            )),
+            ReloadError::DryRunChanged { from, to } => f.write_fmt(format_args!(
+                "dry run can't be reloaded from {} to {}, restart the node instead",
+                from, to
+            )),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
+
+/// The nodes affected by a reload of [HeartbeatGroup]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeartbeatConfigDiff {
+    /// the new nodes, which start to be heartbeated
+    pub added: Vec<HostAddr>,
+    /// the nodes which are no longer heartbeated
+    pub removed: Vec<HostAddr>,
+    /// the nodes whose heartbeat settings change, e.g. period or timeouts. Their senders are rebuilt.
+    pub changed: Vec<HostAddr>,
+}
+
+impl HeartbeatConfigDiff {
+    /// Diff the heartbeat config of `old_nodes` by `old` against the config of `new_nodes` by `new`.
+    /// A node is changed if any of its effective settings changes, e.g. a period override or the subnet profile it falls in.
+    pub fn new(
+        old: &HeartbeatBuilder,
+        old_nodes: &[HostAddr],
+        new: &HeartbeatBuilder,
+        new_nodes: &[HostAddr],
+    ) -> Self {
+        let mut diff = Self::default();
+        new_nodes.iter().for_each(|host_addr| {
+            if !old_nodes.contains(host_addr) {
+                diff.added.push(host_addr.clone())
+            } else if NodeSettings::of(old, host_addr) != NodeSettings::of(new, host_addr) {
+                diff.changed.push(host_addr.clone())
+            }
+        });
+        diff.removed = old_nodes
+            .iter()
+            .filter(|host_addr| !new_nodes.contains(host_addr))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// the effective settings of the sender of a node, after applying the period overrides and the subnet profiles
+#[derive(PartialEq)]
+struct NodeSettings {
+    period: Duration,
+    connect_timeout: u64,
+    rpc_timeout: u64,
+    subnet: Option<String>,
+    node_health: NodeHealthConfig,
+    jitter_ms: u64,
+    skip_missed_ticks: bool,
+    env_toggles: bool,
+    failure_burst: Option<FailureBurstConfig>,
+    pre_execution: PreExecutionPolicy,
+}
+
+impl NodeSettings {
+    fn of(builder: &HeartbeatBuilder, host_addr: &HostAddr) -> Self {
+        let subnet = builder.subnet_of(host_addr);
+        Self {
+            period: builder.period_of(host_addr),
+            connect_timeout: subnet
+                .and_then(|profile| profile.connect_timeout)
+                .unwrap_or(builder.connect_timeout),
+            rpc_timeout: subnet
+                .and_then(|profile| profile.rpc_timeout)
+                .unwrap_or(builder.rpc_timeout),
+            subnet: subnet.map(|profile| profile.name.clone()),
+            node_health: subnet
+                .and_then(|profile| profile.node_health.clone())
+                .unwrap_or_else(|| builder.node_health.clone()),
+            jitter_ms: builder.jitter_ms,
+            skip_missed_ticks: builder.skip_missed_ticks,
+            env_toggles: builder.env_toggles,
+            failure_burst: builder.failure_burst.clone(),
+            pre_execution: builder.pre_execution.clone(),
+        }
+    }
+}
+
+/// A change of the nodes heartbeated by a [HeartbeatGroup], sent by [MembershipHandle]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MembershipCommand {
+    Add(HostAddr),
+    Remove(HostAddr),
+}
+
+/// [MembershipHandle] changes the nodes of a running [HeartbeatGroup] which is served by [HeartbeatGroup::serve_membership],
+/// e.g. when the cluster scales up or a node is decommissioned
+#[derive(Clone, Debug)]
+pub struct MembershipHandle {
+    tx: mpsc::UnboundedSender<MembershipCommand>,
+}
+
+impl MembershipHandle {
+    /// Start to heartbeat `host_addr`. It returns false if the group is dropped.
+    pub fn add(&self, host_addr: HostAddr) -> bool {
+        self.tx.send(MembershipCommand::Add(host_addr)).is_ok()
+    }
+
+    /// Stop heartbeating `host_addr`. It returns false if the group is dropped.
+    pub fn remove(&self, host_addr: HostAddr) -> bool {
+        self.tx.send(MembershipCommand::Remove(host_addr)).is_ok()
+    }
+}
+
+/// [HeartbeatGroup] runs a [super::HeartbeatSender] per remote node of a node, and applies the reloaded config to them live by [HeartbeatGroup::reload].
+/// All the senders share the same [NodeHealthTracker], [RttTracker] and heartbeat id sequence, which survive the reloads and membership changes.
+pub struct HeartbeatGroup<T: ReceiveHeartbeatRpcGateway, F: Fn(&HostAddr, Duration, Duration) -> T>
+{
+    builder: HeartbeatBuilder,
+    nodes: Vec<HostAddr>,
+    task_id: ExecutorId,
+    execution_id: Option<SubDataflowId>,
+    /// creates the gateway of a node by its address, connect timeout and rpc timeout
+    gateway_of: F,
+    node_health: NodeHealthTracker,
+    rtt: RttTracker,
+    heartbeat_ids: Arc<AtomicU64>,
+    senders: HashMap<HostAddr, JoinHandle<()>>,
+    membership_tx: mpsc::UnboundedSender<MembershipCommand>,
+    membership_rx: mpsc::UnboundedReceiver<MembershipCommand>,
+    /// called when the last node is removed, set by [HeartbeatGroup::with_no_targets_callback]
+    no_targets: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+impl<T: ReceiveHeartbeatRpcGateway + 'static, F: Fn(&HostAddr, Duration, Duration) -> T>
+    HeartbeatGroup<T, F>
+{
+    /// Spawn a sender to each of `nodes`. It must be called within a tokio runtime.
+    pub fn spawn(
+        builder: HeartbeatBuilder,
+        nodes: &[HostAddr],
+        task_id: ExecutorId,
+        execution_id: Option<SubDataflowId>,
+        gateway_of: F,
+    ) -> Self {
+        let (membership_tx, membership_rx) = mpsc::unbounded_channel();
+        let mut group = Self {
+            builder,
+            nodes: vec![],
+            task_id,
+            execution_id,
+            gateway_of,
+            node_health: Default::default(),
+            rtt: Default::default(),
+            heartbeat_ids: Default::default(),
+            senders: Default::default(),
+            membership_tx,
+            membership_rx,
+            no_targets: None,
+        };
+        nodes.iter().for_each(|host_addr| {
+            if !group.nodes.contains(host_addr) {
+                group.spawn_sender(host_addr);
+                group.nodes.push(host_addr.clone());
+            }
+        });
+        if group.nodes.is_empty() {
+            group.notify_no_targets();
+        }
+        group
+    }
+
+    /// Call `callback` whenever the group is left without any node to heartbeat, i.e. its last node is removed by [HeartbeatGroup::remove_node]
+    /// or a reload, so that a misconfigured or fully drained cluster is noticed instead of going silent. A warning is logged either way.
+    /// A group spawned without any node calls `callback` as soon as it's set. Adding a node to an empty group re-arms the event.
+    pub fn with_no_targets_callback<C: Fn() + Send + Sync + 'static>(
+        mut self,
+        callback: C,
+    ) -> Self {
+        if self.nodes.is_empty() {
+            callback()
+        }
+        self.no_targets = Some(Box::new(callback));
+        self
+    }
+
+    /// warn that there's no node left to heartbeat, and call the no-targets callback if there's one
+    fn notify_no_targets(&self) {
+        tracing::warn!(
+            "no targets left to heartbeat, the cluster may be misconfigured or fully drained [task_id: {}]",
+            self.task_id
+        );
+        if let Some(callback) = self.no_targets.as_ref() {
+            callback()
+        }
+    }
+
+    fn spawn_sender(&mut self, host_addr: &HostAddr) {
+        let mut sender = self
+            .builder
+            .build(host_addr, self.task_id, &self.gateway_of)
+            .with_node_health_tracker(self.node_health.clone())
+            .with_rtt_tracker(self.rtt.clone())
+            .with_heartbeat_ids(self.heartbeat_ids.clone());
+        if let Some(execution_id) = self.execution_id.as_ref() {
+            sender.update_execution_id(execution_id.clone());
+        }
+        if let Some(handle) = self.senders.insert(host_addr.clone(), tokio::spawn(sender)) {
+            handle.abort();
+        }
+    }
+
+    /// Apply `builder` and `nodes` to the running senders: the senders of the removed nodes are stopped, the added nodes start to be heartbeated,
+    /// and the senders of the nodes whose settings change are rebuilt. The other senders keep running untouched.
+    ///
+    /// A config changing the settings which can't be applied live is rejected by [ReloadError], and nothing is changed.
+    /// The [super::HeartbeatTransform] can't be loaded from a config file, so the current one is kept if `builder` has none.
+    pub fn reload(
+        &mut self,
+        mut builder: HeartbeatBuilder,
+        nodes: &[HostAddr],
+    ) -> Result<HeartbeatConfigDiff, ReloadError> {
+        if builder.cluster_id != self.builder.cluster_id {
+            return Err(ReloadError::ClusterIdChanged {
+                from: self.builder.cluster_id.clone(),
+                to: builder.cluster_id,
+            });
+        }
+        if builder.dry_run != self.builder.dry_run {
+            return Err(ReloadError::DryRunChanged {
+                from: self.builder.dry_run,
+                to: builder.dry_run,
+            });
+        }
+
+        if builder.transform.is_none() {
+            builder.transform = self.builder.transform.clone();
+        }
+
+        let mut new_nodes = Vec::<HostAddr>::with_capacity(nodes.len());
+        nodes.iter().for_each(|host_addr| {
+            if !new_nodes.contains(host_addr) {
+                new_nodes.push(host_addr.clone())
+            }
+        });
+        let diff = HeartbeatConfigDiff::new(&self.builder, &self.nodes, &builder, &new_nodes);
+        tracing::info!(
+            "reload heartbeat config [added: {:?}, removed: {:?}, changed: {:?}]",
+            &diff.added,
+            &diff.removed,
+            &diff.changed
+        );
+
+        let drained = !self.nodes.is_empty() && new_nodes.is_empty();
+        self.builder = builder;
+        self.nodes = new_nodes;
+        diff.removed.iter().for_each(|host_addr| {
+            if let Some(handle) = self.senders.remove(host_addr) {
+                handle.abort()
+            }
+        });
+        diff.added
+            .iter()
+            .chain(diff.changed.iter())
+            .for_each(|host_addr| self.spawn_sender(host_addr));
+        if drained {
+            self.notify_no_targets();
+        }
+        Ok(diff)
+    }
+
+    /// Start to heartbeat `host_addr` with the current config. It returns false if the node is heartbeated already.
+    pub fn add_node(&mut self, host_addr: &HostAddr) -> bool {
+        if self.nodes.contains(host_addr) {
+            return false;
+        }
+        tracing::info!("heartbeat node added [host_addr: {:?}]", host_addr);
+        self.spawn_sender(host_addr);
+        self.nodes.push(host_addr.clone());
+        true
+    }
+
+    /// Stop heartbeating `host_addr`. It returns false if the node isn't heartbeated.
+    /// Removing the last node is reported as described in [HeartbeatGroup::with_no_targets_callback].
+    pub fn remove_node(&mut self, host_addr: &HostAddr) -> bool {
+        match self.nodes.iter().position(|node| node == host_addr) {
+            Some(index) => {
+                tracing::info!("heartbeat node removed [host_addr: {:?}]", host_addr);
+                self.nodes.remove(index);
+                if let Some(handle) = self.senders.remove(host_addr) {
+                    handle.abort()
+                }
+                if self.nodes.is_empty() {
+                    self.notify_no_targets();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The handle to change the nodes of this group at runtime. The changes are applied by [HeartbeatGroup::serve_membership].
+    pub fn membership_handle(&self) -> MembershipHandle {
+        MembershipHandle {
+            tx: self.membership_tx.clone(),
+        }
+    }
+
+    /// Apply the changes sent by the [MembershipHandle]s as they arrive. The group keeps a handle of its own,
+    /// so it's served until the task running it is aborted, which stops all the senders as well.
+    pub async fn serve_membership(&mut self) {
+        while let Some(command) = self.membership_rx.recv().await {
+            match command {
+                MembershipCommand::Add(host_addr) => self.add_node(&host_addr),
+                MembershipCommand::Remove(host_addr) => self.remove_node(&host_addr),
+            };
+        }
+    }
+
+    /// the nodes being heartbeated
+    pub fn nodes(&self) -> &[HostAddr] {
+        &self.nodes
+    }
+
+    /// the current heartbeat config
+    pub fn builder(&self) -> &HeartbeatBuilder {
+        &self.builder
+    }
+
+    /// The [NodeHealthTracker] shared by all the senders
+    pub fn node_health(&self) -> NodeHealthTracker {
+        self.node_health.clone()
+    }
+
+    /// The [RttTracker] shared by all the senders
+    pub fn rtt_tracker(&self) -> RttTracker {
+        self.rtt.clone()
+    }
+}
+
+impl<T: ReceiveHeartbeatRpcGateway, F: Fn(&HostAddr, Duration, Duration) -> T> Drop
+    for HeartbeatGroup<T, F>
+{
+    /// stop all the senders
+    fn drop(&mut self) {
+        self.senders.values().for_each(JoinHandle::abort)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use proto::common::{Heartbeat, HostAddr};
+    use tokio::sync::mpsc;
+
+    use crate::net::{gateway::MockRpcGateway, HeartbeatBuilder, PeriodOverride};
+
+    use super::{HeartbeatConfigDiff, HeartbeatGroup, ReloadError};
+
+    /// the ids of the heartbeats received so far
+    fn drain_ids(rx: &mut mpsc::Receiver<Heartbeat>) -> Vec<u64> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|heartbeat| heartbeat.heartbeat_id)
+            .collect()
+    }
+
+    fn host_addr(host: &str) -> HostAddr {
+        HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        }
+    }
+
+    /// number of heartbeats received so far
+    fn drain(rx: &mut mpsc::Receiver<Heartbeat>) -> usize {
+        std::iter::from_fn(|| rx.try_recv().ok()).count()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_group_reload() {
+        let (a, b, c) = (
+            host_addr("198.0.0.1"),
+            host_addr("198.0.0.2"),
+            host_addr("198.0.0.3"),
+        );
+        let mut gateways = HashMap::new();
+        let mut receivers = HashMap::new();
+        for host_addr in [&a, &b, &c] {
+            let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+            gateways.insert(host_addr.clone(), gateway);
+            receivers.insert(host_addr.clone(), rx);
+        }
+
+        let builder = HeartbeatBuilder {
+            period: 1,
+            cluster_id: Some("lightflus".to_string()),
+            ..Default::default()
+        };
+        let mut group = HeartbeatGroup::spawn(
+            builder.clone(),
+            &[a.clone(), b.clone()],
+            0,
+            None,
+            |host_addr: &HostAddr, _, _| gateways[host_addr].clone(),
+        );
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        // ticks at 0s, 1s and 2s
+        assert_eq!(drain(receivers.get_mut(&a).unwrap()), 3);
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 3);
+
+        // the incompatible changes are rejected, and the senders keep running as they are
+        let err = group
+            .reload(
+                HeartbeatBuilder {
+                    cluster_id: Some("another".to_string()),
+                    ..builder.clone()
+                },
+                &[b.clone(), c.clone()],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ReloadError::ClusterIdChanged {
+                from: Some("lightflus".to_string()),
+                to: Some("another".to_string()),
+            }
+        );
+        assert!(err.to_string().contains("restart the node"));
+        assert!(group
+            .reload(
+                HeartbeatBuilder {
+                    dry_run: true,
+                    ..builder.clone()
+                },
+                group.nodes().to_vec().as_slice(),
+            )
+            .is_err());
+        assert_eq!(group.nodes(), &[a.clone(), b.clone()]);
+
+        // a is removed, c is added, and b is heartbeated every 3s instead
+        let reloaded = HeartbeatBuilder {
+            period_overrides: vec![PeriodOverride {
+                host_addr: b.clone(),
+                period_ms: 3000,
+            }],
+            ..builder.clone()
+        };
+        let diff = group
+            .reload(reloaded.clone(), &[b.clone(), c.clone()])
+            .unwrap();
+        assert_eq!(
+            diff,
+            HeartbeatConfigDiff {
+                added: vec![c.clone()],
+                removed: vec![a.clone()],
+                changed: vec![b.clone()],
+            }
+        );
+        assert_eq!(group.nodes(), &[b.clone(), c.clone()]);
+
+        tokio::time::sleep(Duration::from_millis(6500)).await;
+        assert_eq!(drain(receivers.get_mut(&a).unwrap()), 0);
+        // ticks at 0s, 3s and 6s after the reload
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 3);
+        // ticks at 0s, 1s, ..., 6s after the reload
+        assert_eq!(drain(receivers.get_mut(&c).unwrap()), 7);
+
+        // reloading the same config changes nothing
+        assert!(group
+            .reload(reloaded, &[b.clone(), c.clone()])
+            .unwrap()
+            .is_empty());
+
+        drop(group);
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 0);
+        assert_eq!(drain(receivers.get_mut(&c).unwrap()), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_group_membership() {
+        let (a, b) = (host_addr("198.0.0.1"), host_addr("198.0.0.2"));
+        let mut gateways = HashMap::new();
+        let mut receivers = HashMap::new();
+        for host_addr in [&a, &b] {
+            let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+            gateways.insert(host_addr.clone(), gateway);
+            receivers.insert(host_addr.clone(), rx);
+        }
+
+        let mut group = HeartbeatGroup::spawn(
+            HeartbeatBuilder {
+                period: 1,
+                ..Default::default()
+            },
+            std::slice::from_ref(&a),
+            0,
+            None,
+            move |host_addr: &HostAddr, _, _| gateways[host_addr].clone(),
+        );
+        let membership = group.membership_handle();
+        let served = tokio::spawn(async move {
+            group.serve_membership().await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        // ticks at 0s and 1s
+        assert_eq!(drain_ids(receivers.get_mut(&a).unwrap()), vec![0, 1]);
+
+        // b is heartbeated from now on, continuing the id sequence
+        assert!(membership.add(b.clone()));
+        assert!(membership.add(b.clone()));
+        // ticks of a at 2s, and of b at 1.5s and 2.5s
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        let ids_of_a = drain_ids(receivers.get_mut(&a).unwrap());
+        let ids_of_b = drain_ids(receivers.get_mut(&b).unwrap());
+        assert_eq!(ids_of_a.len(), 1);
+        assert_eq!(ids_of_b.len(), 2);
+        let mut ids = [ids_of_a, ids_of_b].concat();
+        ids.sort();
+        assert_eq!(ids, vec![2, 3, 4]);
+
+        // a is decommissioned
+        assert!(membership.remove(a.clone()));
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+        assert_eq!(drain(receivers.get_mut(&a).unwrap()), 0);
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 2);
+
+        // stopping the group stops its senders
+        served.abort();
+        let _ = served.await;
+        assert!(!membership.add(a.clone()));
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_heartbeat_group_no_targets() {
+        let (a, b) = (host_addr("198.0.0.1"), host_addr("198.0.0.2"));
+        let mut gateways = HashMap::new();
+        let mut receivers = HashMap::new();
+        for host_addr in [&a, &b] {
+            let (gateway, _, rx) = MockRpcGateway::with_host_addr(host_addr, 10, 100);
+            gateways.insert(host_addr.clone(), gateway);
+            receivers.insert(host_addr.clone(), rx);
+        }
+
+        let no_targets = Arc::new(AtomicUsize::new(0));
+        let builder = HeartbeatBuilder {
+            period: 1,
+            ..Default::default()
+        };
+        let mut group = HeartbeatGroup::spawn(
+            builder.clone(),
+            &[a.clone(), b.clone()],
+            0,
+            None,
+            move |host_addr: &HostAddr, _, _| gateways[host_addr].clone(),
+        )
+        .with_no_targets_callback({
+            let no_targets = no_targets.clone();
+            move || {
+                no_targets.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // the group still has a target
+        assert!(group.remove_node(&a));
+        assert_eq!(no_targets.load(Ordering::SeqCst), 0);
+
+        // all the nodes are removed at runtime
+        let membership = group.membership_handle();
+        assert!(membership.remove(b.clone()));
+        let _ = tokio::time::timeout(Duration::from_millis(100), group.serve_membership()).await;
+        assert!(group.nodes().is_empty());
+        assert_eq!(no_targets.load(Ordering::SeqCst), 1);
+
+        // removing a node from the empty group is not another transition
+        assert!(membership.remove(b.clone()));
+        let _ = tokio::time::timeout(Duration::from_millis(100), group.serve_membership()).await;
+        assert_eq!(no_targets.load(Ordering::SeqCst), 1);
+        drain(receivers.get_mut(&a).unwrap());
+        drain(receivers.get_mut(&b).unwrap());
+        tokio::time::sleep(Duration::from_millis(3000)).await;
+        assert_eq!(drain(receivers.get_mut(&a).unwrap()), 0);
+        assert_eq!(drain(receivers.get_mut(&b).unwrap()), 0);
+
+        // a reload draining the group again fires the event again
+        group
+            .reload(builder.clone(), std::slice::from_ref(&a))
+            .unwrap();
+        assert_eq!(no_targets.load(Ordering::SeqCst), 1);
+        group.reload(builder.clone(), &[]).unwrap();
+        assert_eq!(no_targets.load(Ordering::SeqCst), 2);
+
+        // adding a node re-arms the event
+        assert!(group.add_node(&b));
+        assert_eq!(no_targets.load(Ordering::SeqCst), 2);
+        assert!(group.remove_node(&b));
+        assert_eq!(no_targets.load(Ordering::SeqCst), 3);
+
+        // a group spawned without any node reports it once the callback is set
+        let no_targets = Arc::new(AtomicUsize::new(0));
+        let (gateway, _, _) = MockRpcGateway::with_host_addr(&a, 10, 100);
+        let mut group = HeartbeatGroup::spawn(builder, &[], 0, None, move |_: &HostAddr, _, _| {
+            gateway.clone()
+        })
+        .with_no_targets_callback({
+            let no_targets = no_targets.clone();
+            move || {
+                no_targets.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        assert_eq!(no_targets.load(Ordering::SeqCst), 1);
+        assert!(group.add_node(&a));
+        assert!(group.remove_node(&a));
+        assert_eq!(no_targets.load(Ordering::SeqCst), 2);
+    }
+}
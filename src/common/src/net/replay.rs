@@ -0,0 +1,238 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use proto::common::{Heartbeat, HostAddr};
+
+use super::{
+    clock::ManualClock,
+    monitor::{HeartbeatMonitor, HeartbeatOrder},
+};
+
+/// An event recorded on the receiver side of heartbeats
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceEvent {
+    /// a heartbeat is received
+    Heartbeat {
+        host_addr: HostAddr,
+        heartbeat_id: u64,
+    },
+    /// an rpc call to the node failed
+    Failure { host_addr: HostAddr },
+    /// the periodic check of missed heartbeats
+    CheckMissed,
+}
+
+/// An event with the timestamp it's recorded at, in milliseconds
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub timestamp: u64,
+    pub event: TraceEvent,
+}
+
+/// A change of the liveness or the health of a node during the replay
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeTransition {
+    /// the timestamp of the record which causes the change
+    pub timestamp: u64,
+    pub host_addr: HostAddr,
+    pub alive: bool,
+    pub healthy: bool,
+}
+
+/// [TraceReplayer] replays a recorded trace against a [HeartbeatMonitor] driven by a [ManualClock].
+///
+/// It reproduces timing issues in production deterministically: before each record is applied, the clock is moved to the timestamp of the record
+/// relative to the first one. The dedup decision of every heartbeat and the alive/dead and healthy/unhealthy transitions of every node are collected.
+/// Timestamps going backwards don't move the clock.
+#[derive(Debug)]
+pub struct TraceReplayer {
+    clock: ManualClock,
+    monitor: HeartbeatMonitor,
+    origin: Option<u64>,
+    orders: Vec<(u64, HostAddr, HeartbeatOrder)>,
+    transitions: Vec<NodeTransition>,
+    states: HashMap<HostAddr, (bool, bool)>,
+}
+
+impl TraceReplayer {
+    pub fn new(monitor: HeartbeatMonitor) -> Self {
+        let clock = ManualClock::new();
+        Self {
+            monitor: monitor.with_clock(Arc::new(clock.clone())),
+            clock,
+            origin: None,
+            orders: vec![],
+            transitions: vec![],
+            states: Default::default(),
+        }
+    }
+
+    /// parse a trace of json lines, one [TraceRecord] per line. Blank lines are skipped.
+    pub fn parse(trace: &str) -> serde_json::Result<Vec<TraceRecord>> {
+        trace
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect()
+    }
+
+    pub fn replay(&mut self, records: &[TraceRecord]) {
+        records.iter().for_each(|record| self.apply(record))
+    }
+
+    fn apply(&mut self, record: &TraceRecord) {
+        let origin = *self.origin.get_or_insert(record.timestamp);
+        self.clock.set_elapsed(Duration::from_millis(
+            record.timestamp.saturating_sub(origin),
+        ));
+
+        match &record.event {
+            TraceEvent::Heartbeat {
+                host_addr,
+                heartbeat_id,
+            } => {
+                let order = self.monitor.receive(
+                    host_addr,
+                    &Heartbeat {
+                        heartbeat_id: *heartbeat_id,
+                        ..Default::default()
+                    },
+                );
+                self.orders
+                    .push((record.timestamp, host_addr.clone(), order));
+            }
+            TraceEvent::Failure { host_addr } => self.monitor.record_failure(host_addr),
+            TraceEvent::CheckMissed => self.monitor.check_missed(),
+        }
+
+        let states = self
+            .monitor
+            .nodes()
+            .map(|(host_addr, state)| {
+                (
+                    host_addr.clone(),
+                    (self.monitor.is_alive(host_addr), state.healthy),
+                )
+            })
+            .collect::<Vec<_>>();
+        states.into_iter().for_each(|(host_addr, state)| {
+            if self.states.get(&host_addr) != Some(&state) {
+                self.transitions.push(NodeTransition {
+                    timestamp: record.timestamp,
+                    host_addr: host_addr.clone(),
+                    alive: state.0,
+                    healthy: state.1,
+                });
+                self.states.insert(host_addr, state);
+            }
+        })
+    }
+
+    pub fn monitor(&self) -> &HeartbeatMonitor {
+        &self.monitor
+    }
+
+    /// the dedup decision of every replayed heartbeat
+    pub fn orders(&self) -> &[(u64, HostAddr, HeartbeatOrder)] {
+        &self.orders
+    }
+
+    pub fn transitions(&self) -> &[NodeTransition] {
+        &self.transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proto::common::HostAddr;
+
+    use crate::net::monitor::{HeartbeatMonitor, HeartbeatOrder};
+
+    use super::{NodeTransition, TraceReplayer};
+
+    const TRACE: &str = r#"
+{"timestamp": 1665800001000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 1}}
+{"timestamp": 1665800001000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.2", "port": 8970}, "heartbeat_id": 1}}
+{"timestamp": 1665800002000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 2}}
+{"timestamp": 1665800002000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.2", "port": 8970}, "heartbeat_id": 2}}
+{"timestamp": 1665800003000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 3}}
+{"timestamp": 1665800003100, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 3}}
+{"timestamp": 1665800004000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 4}}
+{"timestamp": 1665800004000, "event": {"type": "check_missed"}}
+{"timestamp": 1665800005000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 5}}
+{"timestamp": 1665800005000, "event": {"type": "check_missed"}}
+{"timestamp": 1665800006000, "event": {"type": "heartbeat", "host_addr": {"host": "198.0.0.1", "port": 8970}, "heartbeat_id": 6}}
+{"timestamp": 1665800006000, "event": {"type": "check_missed"}}
+{"timestamp": 1665800007000, "event": {"type": "check_missed"}}
+"#;
+
+    #[test]
+    fn test_replay_heartbeat_trace() {
+        let node_1 = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8970,
+        };
+        let node_2 = HostAddr {
+            host: "198.0.0.2".to_string(),
+            port: 8970,
+        };
+        let records = TraceReplayer::parse(TRACE).unwrap();
+        assert_eq!(records.len(), 13);
+
+        let mut replayer =
+            TraceReplayer::new(HeartbeatMonitor::new(Duration::from_secs(3)).with_thresholds(2, 1));
+        replayer.replay(&records);
+
+        let reordered = replayer
+            .orders()
+            .iter()
+            .filter(|(_, _, order)| *order == HeartbeatOrder::Reordered)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reordered,
+            vec![&(1665800003100, node_1.clone(), HeartbeatOrder::Reordered)]
+        );
+
+        // node 2 stops sending heartbeats after 1665800002000: it's dead once the timeout elapses,
+        // and becomes unhealthy after two missed checks
+        let node_2_transitions = replayer
+            .transitions()
+            .iter()
+            .filter(|transition| transition.host_addr == node_2)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            node_2_transitions,
+            vec![
+                NodeTransition {
+                    timestamp: 1665800001000,
+                    host_addr: node_2.clone(),
+                    alive: true,
+                    healthy: true,
+                },
+                NodeTransition {
+                    timestamp: 1665800006000,
+                    host_addr: node_2.clone(),
+                    alive: false,
+                    healthy: true,
+                },
+                NodeTransition {
+                    timestamp: 1665800007000,
+                    host_addr: node_2.clone(),
+                    alive: false,
+                    healthy: false,
+                },
+            ]
+        );
+
+        let monitor = replayer.monitor();
+        assert!(monitor.is_alive(&node_1));
+        assert!(monitor.is_healthy(&node_1));
+        assert_eq!(monitor.get(&node_1).unwrap().highest_heartbeat_id, 6);
+        assert_eq!(monitor.reordered_count(&node_1), 1);
+        assert!(!monitor.is_alive(&node_2));
+        assert!(!monitor.is_healthy(&node_2));
+    }
+}
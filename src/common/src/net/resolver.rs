@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use proto::common::HostAddr;
+use tokio::net::TcpStream;
+use tonic::{async_trait, transport::Uri};
+
+/// [Resolver] resolves the host of a remote node into socket addresses.
+///
+/// Gateways use the system resolver by default. A custom [Resolver] can be injected for environments with unusual resolution,
+/// e.g. consul DNS or split-horizon DNS, and for testing.
+#[async_trait]
+pub trait Resolver: Debug + Send + Sync {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+/// [SystemResolver] resolves hosts by the resolver of the operating system
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemResolver;
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        tokio::net::lookup_host((host, port))
+            .await
+            .map(|addrs| addrs.collect())
+    }
+}
+
+/// [StaticResolver] resolves hosts by a fixed table. IP literals are resolved as they are.
+#[derive(Clone, Debug, Default)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    pub fn with_host(mut self, host: &str, ip: IpAddr) -> Self {
+        self.hosts.entry(host.to_string()).or_default().push(ip);
+        self
+    }
+}
+
+#[async_trait]
+impl Resolver for StaticResolver {
+    async fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        match host.parse::<IpAddr>() {
+            Ok(ip) => Ok(vec![SocketAddr::new(ip, port)]),
+            Err(_) => self
+                .hosts
+                .get(host)
+                .map(|ips| ips.iter().map(|ip| SocketAddr::new(*ip, port)).collect())
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, format!("unknown host: {}", host))
+                }),
+        }
+    }
+}
+
+/// resolve the [HostAddr] of a remote node by the resolver
+pub async fn resolve_host_addr(
+    resolver: &dyn Resolver,
+    host_addr: &HostAddr,
+) -> io::Result<Vec<SocketAddr>> {
+    resolver
+        .resolve(&host_addr.host, host_addr.port as u16)
+        .await
+}
+
+/// connect to the first reachable address of the uri resolved by the resolver
+pub(crate) async fn connect(resolver: Arc<dyn Resolver>, uri: Uri) -> io::Result<TcpStream> {
+    let host = uri
+        .host()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "uri without host"))?;
+    let port = uri.port_u16().unwrap_or(80);
+
+    let mut last_err = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no address resolved for host: {}", host),
+    );
+    for addr in resolver.resolve(host, port).await? {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => {
+                let _ = stream.set_nodelay(true);
+                return Ok(stream);
+            }
+            Err(err) => {
+                tracing::warn!("connect to {} failed: {}", addr, err);
+                last_err = err
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// create a connector of [tonic::transport::Endpoint] which resolves the host by the resolver.
+/// The uri of the endpoint is untouched, so TLS still verifies the original host name.
+pub(crate) fn connector(
+    resolver: Arc<dyn Resolver>,
+) -> impl tower::Service<
+    Uri,
+    Response = TcpStream,
+    Error = io::Error,
+    Future = impl std::future::Future<Output = io::Result<TcpStream>> + Send,
+> + Clone
+       + Send {
+    tower::service_fn(move |uri: Uri| connect(resolver.clone(), uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        sync::Arc,
+    };
+
+    use proto::common::HostAddr;
+
+    use crate::net::gateway::{taskmanager::SafeTaskManagerRpcGateway, ConnectRpcGateway};
+
+    use super::StaticResolver;
+
+    #[tokio::test]
+    async fn test_gateway_connects_to_resolved_ip() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let resolver = StaticResolver::default().with_host(
+            "taskmanager.lightflus.fake",
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+        );
+
+        let gateway = SafeTaskManagerRpcGateway::new(&HostAddr {
+            host: "taskmanager.lightflus.fake".to_string(),
+            port: port as u32,
+        })
+        .with_resolver(Arc::new(resolver));
+        let connecting = tokio::spawn(async move { gateway.connect().await });
+
+        // the fake host name is unknown to the system resolver, the gateway must connect to the resolved ip
+        let (_, peer) = tokio::time::timeout(std::time::Duration::from_secs(3), listener.accept())
+            .await
+            .expect("the gateway never connects to the resolved ip")
+            .unwrap();
+        assert!(peer.ip().is_loopback());
+        connecting.abort();
+
+        let gateway = SafeTaskManagerRpcGateway::new(&HostAddr {
+            host: "unknown.lightflus.fake".to_string(),
+            port: port as u32,
+        })
+        .with_resolver(Arc::new(StaticResolver::default()));
+        assert!(gateway.connect().await.is_err());
+    }
+}
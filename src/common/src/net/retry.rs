@@ -0,0 +1,534 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use proto::common::{Ack, Heartbeat, HostAddr, Response};
+use tonic::{async_trait, Code};
+
+use super::{
+    gateway::{ConnectRpcGateway, ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway},
+    random_duration, DEFAULT_RPC_TIMEOUT,
+};
+
+/// The retry policy of failed rpc calls, with capped exponential backoff between the attempts.
+///
+/// The backoff before the n-th retry is `base_delay_ms * multiplier ^ (n - 1)`, capped by `max_delay_ms`.
+/// With full jitter, the actual delay is a random duration within `[0, backoff]`, so that the clients failing at the same time don't retry in lockstep.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// max number of retries after the first attempt
+    pub retry: u32,
+    /// the backoff before the first retry, in milliseconds
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    /// how much the backoff grows after each retry
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// the cap of the backoff, in milliseconds
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    #[serde(default = "default_full_jitter")]
+    pub full_jitter: bool,
+}
+
+fn default_base_delay_ms() -> u64 {
+    100
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_max_delay_ms() -> u64 {
+    10_000
+}
+
+fn default_full_jitter() -> bool {
+    true
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retry: 3,
+            base_delay_ms: default_base_delay_ms(),
+            multiplier: default_multiplier(),
+            max_delay_ms: default_max_delay_ms(),
+            full_jitter: default_full_jitter(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// the backoff before the `retry`-th retry, starting from 1, without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff_ms = self.base_delay_ms as f64 * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_millis(backoff_ms.min(self.max_delay_ms as f64) as u64)
+    }
+
+    /// the delay before the `retry`-th retry, starting from 1
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.backoff(retry);
+        if self.full_jitter {
+            random_duration(backoff)
+        } else {
+            backoff
+        }
+    }
+
+    /// Call `f` until it succeeds, the error isn't retryable or the retries run out. The error of the last attempt is returned.
+    pub async fn run_with<T, E: Display, F: FnMut() -> Fut, Fut: Future<Output = Result<T, E>>>(
+        &self,
+        mut f: F,
+        retryable: impl Fn(&E) -> bool,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(err) if retry < self.retry && retryable(&err) => {
+                    retry += 1;
+                    let delay = self.delay(retry);
+                    tracing::warn!(
+                        "call failed, retry {} of {} in {:?}: {}",
+                        retry,
+                        self.retry,
+                        delay,
+                        err
+                    );
+                    tokio::time::sleep(delay).await
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Same as [RetryPolicy::run_with], retrying the rpc calls failed by [is_retryable] status
+    pub async fn run<T, F: FnMut() -> Fut, Fut: Future<Output = Result<T, tonic::Status>>>(
+        &self,
+        f: F,
+    ) -> Result<T, tonic::Status> {
+        self.run_with(f, is_retryable).await
+    }
+
+    /// Same as [RetryPolicy::run], but each attempt fails by [Code::DeadlineExceeded] if it's not responded within `attempt_timeout`,
+    /// so that a slow attempt doesn't use up the time of the retries. The whole call takes [RetryPolicy::budget] at most.
+    pub async fn run_within<
+        T,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, tonic::Status>>,
+    >(
+        &self,
+        attempt_timeout: Duration,
+        mut f: F,
+    ) -> Result<T, tonic::Status> {
+        self.run(|| {
+            let attempt = f();
+            async move {
+                match tokio::time::timeout(attempt_timeout, attempt).await {
+                    Ok(result) => result,
+                    Err(_) => Err(tonic::Status::deadline_exceeded(format!(
+                        "not responded within {:?}",
+                        attempt_timeout
+                    ))),
+                }
+            }
+        })
+        .await
+    }
+
+    /// The longest time a call retried by this policy takes if each attempt is bounded by `attempt_timeout`,
+    /// i.e. all the attempts time out, and each backoff is the longest one.
+    pub fn budget(&self, attempt_timeout: Duration) -> Duration {
+        (1..=self.retry).map(|retry| self.backoff(retry)).fold(
+            attempt_timeout.saturating_mul(self.retry.saturating_add(1)),
+            Duration::saturating_add,
+        )
+    }
+
+    /// Send a batch of acks by `f`, which returns the result of each ack. Only the acks failed by [is_undelivered] status are sent again,
+    /// so that an ack which may have been handled by the remote node is never duplicated. The results are in the same order as `acks`.
+    pub async fn run_acks<
+        F: FnMut(Vec<Ack>) -> Fut,
+        Fut: Future<Output = Vec<Result<Response, tonic::Status>>>,
+    >(
+        &self,
+        acks: Vec<Ack>,
+        mut f: F,
+    ) -> Vec<Result<Response, tonic::Status>> {
+        let mut results = f(acks.clone()).await;
+        let mut retry = 0;
+        loop {
+            let undelivered = results
+                .iter()
+                .enumerate()
+                .filter(|(_, result)| result.as_ref().err().map(is_undelivered) == Some(true))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>();
+            if undelivered.is_empty() || retry >= self.retry {
+                return results;
+            }
+            retry += 1;
+            let delay = self.delay(retry);
+            tracing::warn!(
+                "{} acks undelivered, retry {} of {} in {:?}",
+                undelivered.len(),
+                retry,
+                self.retry,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            let retried = f(undelivered
+                .iter()
+                .map(|index| acks[*index].clone())
+                .collect())
+            .await;
+            undelivered
+                .into_iter()
+                .zip(retried)
+                .for_each(|(index, result)| results[index] = result);
+        }
+    }
+}
+
+/// whether an rpc call failed by `status` may succeed if it's retried, e.g. the remote node is unavailable temporarily.
+/// The transport errors are reported with [Code::Unknown], so they're retryable as well.
+pub fn is_retryable(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::Unknown
+    )
+}
+
+/// whether a call failed by `status` was rejected before it's handled by the remote node, e.g. the connection is refused.
+/// Unlike [is_retryable], a call which may have been handled, e.g. it timed out or failed by a transport error after it's sent, is excluded,
+/// so that resending a call which is not idempotent, like an ack, never duplicates it.
+pub fn is_undelivered(status: &tonic::Status) -> bool {
+    matches!(status.code(), Code::Unavailable | Code::ResourceExhausted)
+}
+
+/// [RetryingGateway] retries the failed rpc calls of the gateway it wraps by the [RetryPolicy].
+/// The acks are only retried if they're undelivered by [is_undelivered].
+/// Each attempt of a heartbeat is bounded by the attempt timeout, which is [DEFAULT_RPC_TIMEOUT] seconds unless it's set by [RetryingGateway::with_attempt_timeout].
+#[derive(Clone, Debug)]
+pub struct RetryingGateway<T: RpcGateway> {
+    inner: T,
+    policy: RetryPolicy,
+    attempt_timeout: Duration,
+}
+
+impl<T: RpcGateway> RetryingGateway<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            attempt_timeout: Duration::from_secs(DEFAULT_RPC_TIMEOUT),
+        }
+    }
+
+    /// Fail an attempt of a heartbeat which isn't responded within `timeout`, so that it's retried within the budget of the caller
+    pub fn with_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = timeout;
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: RpcGateway> RpcGateway for RetryingGateway<T> {
+    fn get_host_addr(&self) -> &HostAddr {
+        self.inner.get_host_addr()
+    }
+}
+
+#[async_trait]
+impl<T: ReceiveAckRpcGateway> ReceiveAckRpcGateway for RetryingGateway<T> {
+    async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+        self.policy
+            .run_with(|| self.inner.receive_ack(req.clone()), is_undelivered)
+            .await
+    }
+
+    async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+        self.policy
+            .run_acks(reqs, |acks| self.inner.receive_acks(acks))
+            .await
+    }
+}
+
+#[async_trait]
+impl<T: ReceiveHeartbeatRpcGateway> ReceiveHeartbeatRpcGateway for RetryingGateway<T> {
+    async fn receive_heartbeat(&self, request: Heartbeat) -> Result<Response, tonic::Status> {
+        self.policy
+            .run_within(self.attempt_timeout, || {
+                self.inner.receive_heartbeat(request.clone())
+            })
+            .await
+    }
+
+    /// every attempt is bounded by the attempt timeout, whatever the timeout of the caller is
+    fn heartbeat_budget(&self, _: Duration) -> Duration {
+        self.policy.budget(self.attempt_timeout)
+    }
+}
+
+#[async_trait]
+impl<T: ConnectRpcGateway> ConnectRpcGateway for RetryingGateway<T> {
+    async fn connect(&self) -> Result<(), tonic::transport::Error> {
+        self.policy
+            .run_with(|| self.inner.connect(), |_| true)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use proto::common::{ack::RequestId, Ack, Heartbeat, HostAddr, Response};
+    use tokio::time::Instant;
+    use tonic::async_trait;
+
+    use crate::net::gateway::{ReceiveAckRpcGateway, ReceiveHeartbeatRpcGateway, RpcGateway};
+
+    use super::{RetryPolicy, RetryingGateway};
+
+    struct FlakyGateway {
+        host_addr: HostAddr,
+        /// the status of every failed attempt, the following attempts succeed
+        failures: Mutex<Vec<tonic::Status>>,
+        attempts: Arc<Mutex<Vec<Instant>>>,
+    }
+
+    impl FlakyGateway {
+        fn new(failures: Vec<tonic::Status>) -> Self {
+            Self {
+                host_addr: HostAddr::default(),
+                failures: Mutex::new(failures.into_iter().rev().collect()),
+                attempts: Default::default(),
+            }
+        }
+
+        /// the delays between the attempts
+        fn delays(&self) -> Vec<Duration> {
+            self.attempts
+                .lock()
+                .unwrap()
+                .windows(2)
+                .map(|pair| pair[1] - pair[0])
+                .collect()
+        }
+    }
+
+    impl RpcGateway for FlakyGateway {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    #[async_trait]
+    impl ReceiveHeartbeatRpcGateway for FlakyGateway {
+        async fn receive_heartbeat(&self, _: Heartbeat) -> Result<Response, tonic::Status> {
+            self.attempts.lock().unwrap().push(Instant::now());
+            match self.failures.lock().unwrap().pop() {
+                Some(status) => Err(status),
+                None => Ok(Response::ok()),
+            }
+        }
+    }
+
+    fn unavailable(n: usize) -> Vec<tonic::Status> {
+        (0..n)
+            .map(|attempt| tonic::Status::unavailable(format!("attempt {}", attempt + 1)))
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_backoff_capped() {
+        let policy = RetryPolicy {
+            retry: 5,
+            base_delay_ms: 100,
+            multiplier: 2.0,
+            max_delay_ms: 500,
+            full_jitter: false,
+        };
+        let millis = Duration::from_millis;
+        assert_eq!(
+            (1..=6)
+                .map(|retry| policy.backoff(retry))
+                .collect::<Vec<_>>(),
+            vec![
+                millis(100),
+                millis(200),
+                millis(400),
+                millis(500),
+                millis(500),
+                millis(500)
+            ]
+        );
+
+        // every attempt fails, the error of the last one is surfaced
+        let gateway = RetryingGateway::new(FlakyGateway::new(unavailable(10)), policy.clone());
+        let result = gateway.receive_heartbeat(Heartbeat::default()).await;
+        assert_eq!(result.unwrap_err().message(), "attempt 6");
+        assert_eq!(
+            gateway.inner().delays(),
+            vec![
+                millis(100),
+                millis(200),
+                millis(400),
+                millis(500),
+                millis(500)
+            ]
+        );
+
+        // it succeeds after 2 failures
+        let gateway = RetryingGateway::new(FlakyGateway::new(unavailable(2)), policy.clone());
+        assert!(gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .is_ok());
+        assert_eq!(gateway.inner().delays(), vec![millis(100), millis(200)]);
+
+        // an error which can't be fixed by retrying is surfaced immediately
+        let gateway = RetryingGateway::new(
+            FlakyGateway::new(vec![tonic::Status::invalid_argument("bad heartbeat")]),
+            policy,
+        );
+        let result = gateway.receive_heartbeat(Heartbeat::default()).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert_eq!(gateway.inner().attempts.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_full_jitter() {
+        let policy: RetryPolicy =
+            serde_json::from_str(r#"{"retry": 8, "base_delay_ms": 100, "max_delay_ms": 800}"#)
+                .unwrap();
+        assert!(policy.full_jitter);
+        assert_eq!(policy.multiplier, 2.0);
+
+        let gateway = RetryingGateway::new(FlakyGateway::new(unavailable(20)), policy.clone());
+        assert!(gateway
+            .receive_heartbeat(Heartbeat::default())
+            .await
+            .is_err());
+        let delays = gateway.inner().delays();
+        assert_eq!(delays.len(), 8);
+        delays
+            .iter()
+            .zip(1..)
+            .for_each(|(delay, retry)| assert!(*delay <= policy.backoff(retry)));
+    }
+
+    /// fails each attempt of an ack by the status of its next failure, records the acks of every batch it receives
+    #[derive(Default)]
+    struct FlakyAckGateway {
+        host_addr: HostAddr,
+        failures: Mutex<std::collections::HashMap<u64, Vec<tonic::Status>>>,
+        batches: Mutex<Vec<Vec<u64>>>,
+    }
+
+    impl RpcGateway for FlakyAckGateway {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    fn heartbeat_id_of(ack: &Ack) -> u64 {
+        match ack.request_id {
+            Some(RequestId::HeartbeatId(heartbeat_id)) => heartbeat_id,
+            None => 0,
+        }
+    }
+
+    #[async_trait]
+    impl ReceiveAckRpcGateway for FlakyAckGateway {
+        async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+            let failure = self
+                .failures
+                .lock()
+                .unwrap()
+                .get_mut(&heartbeat_id_of(&req))
+                .and_then(Vec::pop);
+            match failure {
+                Some(status) => Err(status),
+                None => Ok(Response::ok()),
+            }
+        }
+
+        async fn receive_acks(&self, reqs: Vec<Ack>) -> Vec<Result<Response, tonic::Status>> {
+            self.batches
+                .lock()
+                .unwrap()
+                .push(reqs.iter().map(heartbeat_id_of).collect());
+            let mut results = vec![];
+            for req in reqs {
+                results.push(self.receive_ack(req).await);
+            }
+            results
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_undelivered_acks_only() {
+        let policy = RetryPolicy {
+            retry: 2,
+            full_jitter: false,
+            ..Default::default()
+        };
+        let gateway = FlakyAckGateway::default();
+        {
+            let mut failures = gateway.failures.lock().unwrap();
+            // ack 1 is refused twice, then delivered
+            failures.insert(1, unavailable(2));
+            // ack 2 may have been handled, so it's never resent
+            failures.insert(2, vec![tonic::Status::deadline_exceeded("timeout")]);
+            // ack 3 is refused by every attempt
+            failures.insert(3, unavailable(5));
+        }
+        let gateway = RetryingGateway::new(gateway, policy);
+        let acks = (0..4)
+            .map(|heartbeat_id| Ack {
+                request_id: Some(RequestId::HeartbeatId(heartbeat_id)),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let results = gateway.receive_acks(acks).await;
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert_eq!(
+            results[2].as_ref().unwrap_err().code(),
+            tonic::Code::DeadlineExceeded
+        );
+        assert_eq!(
+            results[3].as_ref().unwrap_err().code(),
+            tonic::Code::Unavailable
+        );
+        // only the undelivered acks are resent, so no ack is duplicated
+        assert_eq!(
+            *gateway.inner().batches.lock().unwrap(),
+            vec![vec![0, 1, 2, 3], vec![1, 3], vec![1, 3]]
+        );
+
+        // a single ack which may have been handled is not retried either
+        let gateway = RetryingGateway::new(FlakyAckGateway::default(), RetryPolicy::default());
+        gateway
+            .inner()
+            .failures
+            .lock()
+            .unwrap()
+            .insert(0, vec![tonic::Status::unknown("transport error")]);
+        assert!(gateway.receive_ack(Ack::default()).await.is_err());
+        assert!(gateway.inner().failures.lock().unwrap()[&0].is_empty());
+    }
+}
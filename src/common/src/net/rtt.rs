@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use proto::common::HostAddr;
+
+/// the number of bits of each value kept exactly by [RttHistogram]. The relative error of the values is within `2 ^ -(SIGNIFICANT_BITS - 1)`, ~6%.
+const SIGNIFICANT_BITS: u32 = 5;
+/// the values below it have their own buckets
+const SUB_BUCKETS: u64 = 1 << SIGNIFICANT_BITS;
+/// number of buckets between two powers of 2, beyond [SUB_BUCKETS]
+const HALF_SUB_BUCKETS: u64 = SUB_BUCKETS / 2;
+
+/// [RttHistogram] is a streaming histogram of round-trip times in HDR style: the microseconds are counted in log-linear buckets,
+/// so that recording takes constant time and the memory is bounded by the magnitude of the largest value, not the number of samples.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RttHistogram {
+    buckets: Vec<u64>,
+    count: u64,
+    /// the sum of all values, in microseconds
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl RttHistogram {
+    pub fn record(&mut self, rtt: Duration) {
+        let value = rtt.as_micros().min(u64::MAX as u128) as u64;
+        let index = bucket_index(value);
+        if self.buckets.len() <= index {
+            self.buckets.resize(index + 1, 0);
+        }
+        self.buckets[index] += 1;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum += value as u128;
+    }
+
+    /// add the samples of `other` to this histogram
+    pub fn merge(&mut self, other: &RttHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        self.buckets
+            .iter_mut()
+            .zip(other.buckets.iter())
+            .for_each(|(count, other)| *count += other);
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// the value below which `quantile` of the samples fall, e.g. 0.99 for p99. It's [None] if nothing is recorded.
+    /// The value is the middle of its bucket, so it's within the relative error of [SIGNIFICANT_BITS].
+    pub fn percentile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.buckets
+            .iter()
+            .enumerate()
+            .find(|(_, count)| {
+                seen += **count;
+                seen >= rank
+            })
+            .map(|(index, _)| {
+                let (lowest, highest) = bucket_range(index);
+                let middle = lowest + (highest - lowest) / 2;
+                Duration::from_micros(middle.clamp(self.min, self.max))
+            })
+    }
+
+    /// It's [None] if nothing is recorded
+    pub fn snapshot(&self) -> Option<HistogramSnapshot> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(HistogramSnapshot {
+            count: self.count,
+            min: Duration::from_micros(self.min),
+            max: Duration::from_micros(self.max),
+            mean: Duration::from_micros((self.sum / self.count as u128) as u64),
+            p50: self.percentile(0.5)?,
+            p95: self.percentile(0.95)?,
+            p99: self.percentile(0.99)?,
+        })
+    }
+}
+
+/// the index of the bucket of `value`. Each bucket beyond [SUB_BUCKETS] keeps the [SIGNIFICANT_BITS] highest bits of its values.
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    let shift = (u64::BITS - value.leading_zeros()) - SIGNIFICANT_BITS;
+    let top = value >> shift;
+    (SUB_BUCKETS + (shift as u64 - 1) * HALF_SUB_BUCKETS + (top - HALF_SUB_BUCKETS)) as usize
+}
+
+/// the lowest and highest values of the bucket at `index`
+fn bucket_range(index: usize) -> (u64, u64) {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return (index, index);
+    }
+    let offset = index - SUB_BUCKETS;
+    let shift = offset / HALF_SUB_BUCKETS + 1;
+    let top = offset % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS;
+    let lowest = top << shift;
+    (lowest, lowest + ((1 << shift) - 1))
+}
+
+/// The distribution of the round-trip times recorded by [RttHistogram]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+/// [RttTracker] records the round-trip times of the heartbeats to each node. The clones share the same histograms,
+/// so the senders sharing a tracker report the cluster-wide distribution by [RttTracker::cluster_snapshot].
+#[derive(Clone, Debug, Default)]
+pub struct RttTracker {
+    histograms: Arc<Mutex<HashMap<HostAddr, RttHistogram>>>,
+}
+
+impl RttTracker {
+    pub fn record(&self, host_addr: &HostAddr, rtt: Duration) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(host_addr.clone())
+            .or_default()
+            .record(rtt)
+    }
+
+    /// the distribution of the round-trip times to the node. It's [None] if no heartbeat to the node has succeeded.
+    pub fn snapshot(&self, host_addr: &HostAddr) -> Option<HistogramSnapshot> {
+        self.histograms
+            .lock()
+            .unwrap()
+            .get(host_addr)
+            .and_then(RttHistogram::snapshot)
+    }
+
+    /// the distribution of the round-trip times to all the nodes
+    pub fn cluster_snapshot(&self) -> Option<HistogramSnapshot> {
+        self.histograms
+            .lock()
+            .unwrap()
+            .values()
+            .fold(RttHistogram::default(), |mut cluster, histogram| {
+                cluster.merge(histogram);
+                cluster
+            })
+            .snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use proto::common::HostAddr;
+
+    use super::{bucket_index, bucket_range, RttHistogram, RttTracker};
+
+    fn assert_within(actual: Duration, expected: Duration, tolerance: f64) {
+        let error = (actual.as_secs_f64() - expected.as_secs_f64()).abs() / expected.as_secs_f64();
+        assert!(
+            error <= tolerance,
+            "{:?} is not within {} of {:?}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_rtt_histogram_percentiles() {
+        [0, 1, 31, 32, 33, 63, 64, 1000, 123_456, u64::MAX]
+            .into_iter()
+            .for_each(|value| {
+                let (lowest, highest) = bucket_range(bucket_index(value));
+                assert!(lowest <= value && value <= highest, "{}", value);
+            });
+
+        let mut histogram = RttHistogram::default();
+        assert!(histogram.snapshot().is_none());
+        // 1ms, 2ms, ..., 1000ms
+        (1..=1000).for_each(|ms| histogram.record(Duration::from_millis(ms)));
+
+        let snapshot = histogram.snapshot().unwrap();
+        assert_eq!(snapshot.count, 1000);
+        assert_eq!(snapshot.min, Duration::from_millis(1));
+        assert_eq!(snapshot.max, Duration::from_millis(1000));
+        assert_eq!(snapshot.mean, Duration::from_micros(500_500));
+        assert_within(snapshot.p50, Duration::from_millis(500), 0.04);
+        assert_within(snapshot.p95, Duration::from_millis(950), 0.04);
+        assert_within(snapshot.p99, Duration::from_millis(990), 0.04);
+        assert_within(
+            histogram.percentile(0.0).unwrap(),
+            Duration::from_millis(1),
+            0.04,
+        );
+        assert_within(
+            histogram.percentile(1.0).unwrap(),
+            Duration::from_millis(1000),
+            0.04,
+        );
+
+        // the outliers show up in p99 only
+        let mut histogram = RttHistogram::default();
+        (0..98).for_each(|_| histogram.record(Duration::from_micros(20)));
+        (0..2).for_each(|_| histogram.record(Duration::from_secs(3)));
+        let snapshot = histogram.snapshot().unwrap();
+        assert_eq!(snapshot.p50, Duration::from_micros(20));
+        assert_eq!(snapshot.p95, Duration::from_micros(20));
+        assert_within(snapshot.p99, Duration::from_secs(3), 0.04);
+    }
+
+    #[test]
+    fn test_rtt_tracker_cluster_snapshot() {
+        let host_addr = |port: u32| HostAddr {
+            host: "localhost".to_string(),
+            port,
+        };
+        let tracker = RttTracker::default();
+        let shared = tracker.clone();
+        (1..=100).for_each(|ms| tracker.record(&host_addr(8970), Duration::from_millis(ms)));
+        (101..=200).for_each(|ms| shared.record(&host_addr(8971), Duration::from_millis(ms)));
+
+        assert!(tracker.snapshot(&host_addr(8972)).is_none());
+        let node = tracker.snapshot(&host_addr(8970)).unwrap();
+        assert_eq!(node.count, 100);
+        assert_within(node.p50, Duration::from_millis(50), 0.04);
+
+        let cluster = tracker.cluster_snapshot().unwrap();
+        assert_eq!(cluster.count, 200);
+        assert_eq!(cluster.min, Duration::from_millis(1));
+        assert_eq!(cluster.max, Duration::from_millis(200));
+        assert_within(cluster.p50, Duration::from_millis(100), 0.04);
+        assert_within(cluster.p99, Duration::from_millis(198), 0.04);
+
+        assert!(RttTracker::default().cluster_snapshot().is_none());
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use proto::common::{mysql_desc, HostAddr};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::db::MysqlConn;
+
+use super::{
+    hostname, local_ip,
+    resolver::{resolve_host_addr, Resolver, SystemResolver},
+    DEFAULT_CONNECT_TIMEOUT,
+};
+
+/// What a node checks by [self_test] at startup
+#[derive(Clone, Debug)]
+pub struct SelfTestConfig {
+    /// the port the node listens on
+    pub listen_port: u16,
+    /// the remote nodes the node talks to
+    pub peers: Vec<HostAddr>,
+    /// the MySQL server the node connects to, if any
+    pub mysql: Option<mysql_desc::ConnectionOpts>,
+    /// the timeout of each check
+    pub timeout: Duration,
+    /// the resolver of the hosts of the peers
+    pub resolver: Arc<dyn Resolver>,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        Self {
+            listen_port: super::DEFAULT_TASKMANAGER_PORT,
+            peers: vec![],
+            mysql: None,
+            timeout: Duration::from_secs(DEFAULT_CONNECT_TIMEOUT),
+            resolver: Arc::new(SystemResolver),
+        }
+    }
+}
+
+/// A single check of [self_test]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// discover the address of the node itself
+    LocalAddress,
+    /// bind the listen port
+    BindListenPort(u16),
+    /// resolve the host of a peer
+    ResolvePeer(HostAddr),
+    /// establish a TCP connection to a peer
+    ConnectPeer(HostAddr),
+    /// connect to the MySQL server and run a trivial query
+    Mysql(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+    pub check: Check,
+    pub passed: bool,
+    /// what is found on success, or why the check fails
+    pub detail: String,
+    pub elapsed: Duration,
+}
+
+/// The result of every check of [self_test], in the order they're run
+#[derive(Clone, Debug, Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    /// whether all the checks pass
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|result| result.passed)
+    }
+
+    pub fn failures(&self) -> Vec<&CheckResult> {
+        self.checks.iter().filter(|result| !result.passed).collect()
+    }
+
+    pub fn get(&self, check: &Check) -> Option<&CheckResult> {
+        self.checks.iter().find(|result| &result.check == check)
+    }
+
+    fn record(&mut self, check: Check, start: Instant, result: Result<String, String>) {
+        let elapsed = start.elapsed();
+        match &result {
+            Ok(detail) => tracing::info!("self test {:?} passed: {}", check, detail),
+            Err(detail) => tracing::error!("self test {:?} failed: {}", check, detail),
+        }
+        let passed = result.is_ok();
+        let detail = result.unwrap_or_else(|err| err);
+        self.checks.push(CheckResult {
+            check,
+            passed,
+            detail,
+            elapsed,
+        })
+    }
+}
+
+/// run `check` within `timeout`, and report the error as a string
+async fn within<T, E: Display, F: Future<Output = Result<T, E>>>(
+    timeout: Duration,
+    check: F,
+) -> Result<T, String> {
+    match tokio::time::timeout(timeout, check).await {
+        Ok(result) => result.map_err(|err| err.to_string()),
+        Err(_) => Err(format!("timed out after {:?}", timeout)),
+    }
+}
+
+/// Run a quick end-to-end connectivity self-test, e.g. at the startup of a node.
+///
+/// It discovers the address of the node, binds the listen port, resolves and connects to each peer, and connects to MySQL.
+/// Each check is bounded by [SelfTestConfig::timeout], and a failed check doesn't stop the following ones.
+pub async fn self_test(config: &SelfTestConfig) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+
+    let start = Instant::now();
+    let local_addr = local_ip()
+        .or_else(hostname)
+        .filter(|host| !host.is_empty())
+        .ok_or_else(|| "neither local ip nor hostname can be determined".to_string());
+    report.record(Check::LocalAddress, start, local_addr);
+
+    let start = Instant::now();
+    let bound = within(
+        config.timeout,
+        TcpListener::bind(SocketAddr::from(([0, 0, 0, 0], config.listen_port))),
+    )
+    .await
+    .and_then(|listener| listener.local_addr().map_err(|err| err.to_string()))
+    .map(|addr| format!("bound {}", addr));
+    report.record(Check::BindListenPort(config.listen_port), start, bound);
+
+    for peer in &config.peers {
+        let start = Instant::now();
+        let resolved = within(
+            config.timeout,
+            resolve_host_addr(config.resolver.as_ref(), peer),
+        )
+        .await
+        .and_then(|addrs| {
+            addrs
+                .first()
+                .copied()
+                .ok_or_else(|| "no address resolved".to_string())
+        });
+        report.record(
+            Check::ResolvePeer(peer.clone()),
+            start,
+            resolved
+                .as_ref()
+                .map(|addr| format!("resolved {}", addr))
+                .map_err(Clone::clone),
+        );
+
+        let start = Instant::now();
+        let connected = match resolved {
+            Ok(addr) => within(config.timeout, TcpStream::connect(addr))
+                .await
+                .map(|_| format!("connected {}", addr)),
+            Err(_) => Err("skipped, the host isn't resolved".to_string()),
+        };
+        report.record(Check::ConnectPeer(peer.clone()), start, connected);
+    }
+
+    if let Some(conn_opts) = config.mysql.as_ref() {
+        let start = Instant::now();
+        let mut conn = MysqlConn::from(conn_opts.clone());
+        let connected = within(config.timeout, conn.execute("select 1", vec![]))
+            .await
+            .map(|_| format!("connected {}:{}", conn_opts.host, conn_opts.port()));
+        conn.close();
+        report.record(Check::Mysql(conn_opts.host.clone()), start, connected);
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use proto::common::{mysql_desc, HostAddr};
+
+    use crate::net::resolver::StaticResolver;
+
+    use super::{self_test, Check, SelfTestConfig};
+
+    #[tokio::test]
+    async fn test_self_test_report() {
+        let occupied = tokio::net::TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let occupied_port = occupied.local_addr().unwrap().port();
+        let reachable = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let reachable_port = reachable.local_addr().unwrap().port();
+        let closed_port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let peer_of = |host: &str, port: u16| HostAddr {
+            host: host.to_string(),
+            port: port as u32,
+        };
+        let reachable_peer = peer_of("taskmanager-1.lightflus.fake", reachable_port);
+        let closed_peer = peer_of("taskmanager-2.lightflus.fake", closed_port);
+        let unknown_peer = peer_of("unknown.lightflus.fake", reachable_port);
+        let resolver = StaticResolver::default()
+            .with_host(&reachable_peer.host, IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .with_host(&closed_peer.host, IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let config = SelfTestConfig {
+            listen_port: occupied_port,
+            peers: vec![
+                reachable_peer.clone(),
+                closed_peer.clone(),
+                unknown_peer.clone(),
+            ],
+            mysql: Some(mysql_desc::ConnectionOpts {
+                host: "127.0.0.1".to_string(),
+                port: closed_port as u32,
+                ..Default::default()
+            }),
+            timeout: Duration::from_secs(3),
+            resolver: Arc::new(resolver),
+        };
+        let report = self_test(&config).await;
+
+        let passed = |check: Check| report.get(&check).unwrap().passed;
+        assert!(!passed(Check::BindListenPort(occupied_port)));
+        assert!(passed(Check::ResolvePeer(reachable_peer.clone())));
+        assert!(passed(Check::ConnectPeer(reachable_peer)));
+        assert!(passed(Check::ResolvePeer(closed_peer.clone())));
+        assert!(!passed(Check::ConnectPeer(closed_peer)));
+        assert!(!passed(Check::ResolvePeer(unknown_peer.clone())));
+        assert!(!passed(Check::ConnectPeer(unknown_peer)));
+        assert!(!passed(Check::Mysql("127.0.0.1".to_string())));
+        assert!(!report.passed());
+        // the local address, the listen port, 2 checks of each peer and MySQL
+        assert_eq!(report.checks.len(), 9);
+
+        // the listen port is free once the listener is closed
+        drop(occupied);
+        let report = self_test(&SelfTestConfig {
+            listen_port: occupied_port,
+            ..Default::default()
+        })
+        .await;
+        assert!(
+            report
+                .get(&Check::BindListenPort(occupied_port))
+                .unwrap()
+                .passed
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+use proto::common::HostAddr;
+
+use super::NodeHealthConfig;
+
+/// A subnet in CIDR notation, e.g. `10.0.1.0/24` or `fd00::/8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Subnet {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Subnet {
+    /// whether `ip` is in this subnet. An IPv4 address is never in an IPv6 subnet, and vice versa.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = String;
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid subnet {}, it should be like 10.0.1.0/24", cidr);
+        let (network, prefix_len) = cidr.split_once('/').ok_or_else(invalid)?;
+        let network = network.parse::<IpAddr>().map_err(|_| invalid())?;
+        let prefix_len = prefix_len.parse::<u8>().map_err(|_| invalid())?;
+        let max_prefix_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+/// The heartbeat settings of the nodes within a subnet, e.g. the nodes of a remote region with higher RTT.
+/// The unset ones fall back to the settings of the [super::HeartbeatBuilder].
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubnetProfile {
+    /// the name of the subnet, which the health of its nodes is grouped by
+    pub name: String,
+    /// the subnet in CIDR notation, e.g. `10.0.1.0/24`
+    pub cidr: String,
+    /// timeout of heartbeat rpc connection, in seconds
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    /// timeout of heartbeat rpc request, in seconds
+    #[serde(default)]
+    pub rpc_timeout: Option<u64>,
+    #[serde(default)]
+    pub node_health: Option<NodeHealthConfig>,
+}
+
+impl SubnetProfile {
+    /// whether the node is in the subnet. Only the nodes addressed by IP literals can be classified.
+    /// A malformed `cidr` matches nothing.
+    pub fn contains(&self, host_addr: &HostAddr) -> bool {
+        let ip = match host_addr.host.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => return false,
+        };
+        match self.cidr.parse::<Subnet>() {
+            Ok(subnet) => subnet.contains(&ip),
+            Err(err) => {
+                tracing::warn!("subnet profile {} is ignored: {}", self.name, err);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use proto::common::HostAddr;
+
+    use super::{Subnet, SubnetProfile};
+
+    #[test]
+    fn test_subnet_contains() {
+        let subnet = "10.0.1.0/24".parse::<Subnet>().unwrap();
+        assert_eq!(subnet.to_string(), "10.0.1.0/24");
+        assert!(subnet.contains(&"10.0.1.255".parse::<IpAddr>().unwrap()));
+        assert!(!subnet.contains(&"10.0.2.1".parse::<IpAddr>().unwrap()));
+        assert!(!subnet.contains(&"::1".parse::<IpAddr>().unwrap()));
+
+        let any = "0.0.0.0/0".parse::<Subnet>().unwrap();
+        assert!(any.contains(&"198.0.0.1".parse::<IpAddr>().unwrap()));
+        let v6 = "fd00::/8".parse::<Subnet>().unwrap();
+        assert!(v6.contains(&"fd12:3456::1".parse::<IpAddr>().unwrap()));
+        assert!(!v6.contains(&"fe80::1".parse::<IpAddr>().unwrap()));
+
+        assert!("10.0.1.0".parse::<Subnet>().is_err());
+        assert!("10.0.1.0/33".parse::<Subnet>().is_err());
+        assert!("localhost/24".parse::<Subnet>().is_err());
+
+        let profile = SubnetProfile {
+            name: "local".to_string(),
+            cidr: "10.0.1.0/24".to_string(),
+            connect_timeout: None,
+            rpc_timeout: None,
+            node_health: None,
+        };
+        let host_addr = |host: &str| HostAddr {
+            host: host.to_string(),
+            port: 8970,
+        };
+        assert!(profile.contains(&host_addr("10.0.1.7")));
+        assert!(!profile.contains(&host_addr("10.0.2.7")));
+        // host names can't be classified
+        assert!(!profile.contains(&host_addr("taskmanager-1")));
+    }
+}
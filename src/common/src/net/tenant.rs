@@ -0,0 +1,105 @@
+use tonic::metadata::{MetadataMap, MetadataValue};
+
+/// The metadata key of the cluster id
+pub const CLUSTER_ID_METADATA_KEY: &str = "x-lightflus-cluster-id";
+
+tokio::task_local! {
+    static CURRENT_CLUSTER_ID: String;
+}
+
+/// run `f` with `cluster_id` being the active cluster id. `f` runs as-is if there's no cluster id.
+///
+/// Senders poll their rpc futures within `f`, so every heartbeat and ack sent by them is stamped with the cluster id.
+pub(crate) fn sync_scope<R, F: FnOnce() -> R>(cluster_id: Option<&String>, f: F) -> R {
+    match cluster_id {
+        Some(cluster_id) => CURRENT_CLUSTER_ID.sync_scope(cluster_id.clone(), f),
+        None => f(),
+    }
+}
+
+/// the active cluster id of current task
+pub fn current() -> Option<String> {
+    CURRENT_CLUSTER_ID
+        .try_with(|cluster_id| cluster_id.clone())
+        .ok()
+}
+
+/// inject the active cluster id into the metadata. The metadata is untouched if there's no active cluster id.
+pub fn inject(metadata: &mut MetadataMap) {
+    if let Some(cluster_id) = current() {
+        if let Ok(value) = MetadataValue::try_from(cluster_id) {
+            metadata.insert(CLUSTER_ID_METADATA_KEY, value);
+        }
+    }
+}
+
+/// extract the cluster id from the metadata of a received request
+pub fn extract(metadata: &MetadataMap) -> Option<&str> {
+    metadata
+        .get(CLUSTER_ID_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// [ClusterValidator] guards the receiver of heartbeats and acks in multi-tenant deployments.
+///
+/// If a cluster id is configured, only requests tagged with the same cluster id are accepted.
+/// Requests tagged with a foreign cluster id, or not tagged at all, are rejected with [tonic::Code::PermissionDenied].
+/// A validator without cluster id accepts every request.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClusterValidator {
+    cluster_id: Option<String>,
+}
+
+impl ClusterValidator {
+    pub fn new(cluster_id: Option<String>) -> Self {
+        Self { cluster_id }
+    }
+
+    // the rejection is returned as is by the rpc handlers of the receiver
+    #[allow(clippy::result_large_err)]
+    pub fn validate(&self, metadata: &MetadataMap) -> Result<(), tonic::Status> {
+        match (&self.cluster_id, extract(metadata)) {
+            (None, _) => Ok(()),
+            (Some(expected), Some(actual)) if expected == actual => Ok(()),
+            (Some(expected), Some(actual)) => Err(tonic::Status::permission_denied(format!(
+                "cluster id mismatched, expected: {}, actual: {}",
+                expected, actual
+            ))),
+            (Some(expected), None) => Err(tonic::Status::permission_denied(format!(
+                "cluster id unprovided, expected: {}",
+                expected
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proto::common::Heartbeat;
+
+    use super::{sync_scope, ClusterValidator, CLUSTER_ID_METADATA_KEY};
+
+    #[test]
+    fn test_cluster_validator() {
+        let validator = ClusterValidator::new(Some("cluster-a".to_string()));
+
+        let request = sync_scope(Some(&"cluster-a".to_string()), || {
+            crate::net::gateway::new_request(Heartbeat::default())
+        });
+        assert!(validator.validate(request.metadata()).is_ok());
+
+        let request = sync_scope(Some(&"cluster-b".to_string()), || {
+            crate::net::gateway::new_request(Heartbeat::default())
+        });
+        let result = validator.validate(request.metadata());
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().code(), tonic::Code::PermissionDenied);
+
+        let request = crate::net::gateway::new_request(Heartbeat::default());
+        assert!(request.metadata().get(CLUSTER_ID_METADATA_KEY).is_none());
+        assert!(validator.validate(request.metadata()).is_err());
+        assert!(ClusterValidator::default()
+            .validate(request.metadata())
+            .is_ok());
+    }
+}
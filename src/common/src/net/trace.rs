@@ -0,0 +1,382 @@
+use std::fmt::Display;
+
+use futures_util::Future;
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tracing::{field::Visit, span, Instrument, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// The metadata key of W3C trace context
+pub const TRACEPARENT_METADATA_KEY: &str = "traceparent";
+
+const TRACEPARENT_VERSION: &str = "00";
+const FLAG_SAMPLED: u8 = 0x01;
+
+tokio::task_local! {
+    static CURRENT_TRACE_CONTEXT: TraceContext;
+}
+
+/// [TraceContext] is the W3C trace context of current `tracing` span.
+///
+/// Every span gets its context from [TraceContextLayer]. Heartbeat and ack RPCs sent within a span carry its context
+/// in the `traceparent` metadata so that spans can be linked across node boundaries.
+/// The receiving side can extract it by [extract] and continue the trace by [with_parent].
+/// Without the layer, a context can still become active by [TraceContext::scope].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 16-bytes id of the whole trace
+    pub trace_id: u128,
+    /// 8-bytes id of current span
+    pub span_id: u64,
+    /// whether the trace is sampled
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// create the root context of a new trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: uuid::Uuid::new_v4().as_u128(),
+            span_id: new_span_id(),
+            sampled: true,
+        }
+    }
+
+    /// create a child context which belongs to the same trace
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: new_span_id(),
+            sampled: self.sampled,
+        }
+    }
+
+    /// the active trace context: the context of current span, or the one made active by [TraceContext::scope]
+    pub fn current() -> Option<Self> {
+        Self::of_span(&tracing::Span::current())
+            .or_else(|| CURRENT_TRACE_CONTEXT.try_with(|ctx| *ctx).ok())
+    }
+
+    /// the context of `span` derived by [TraceContextLayer]. It's [None] if the span is disabled or the layer isn't registered.
+    pub fn of_span(span: &tracing::Span) -> Option<Self> {
+        span.with_subscriber(|(id, dispatch)| {
+            dispatch
+                .downcast_ref::<tracing_subscriber::Registry>()
+                .and_then(|registry| registry.span(id))
+                .and_then(|span| span.extensions().get::<TraceContext>().copied())
+        })
+        .flatten()
+    }
+
+    /// run `fut` with this trace context being active
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CURRENT_TRACE_CONTEXT.scope(self, fut).await
+    }
+
+    /// parse a context from the value of `traceparent` header, e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let parts = value.trim().split('-').collect::<Vec<_>>();
+        if parts.len() != 4 || parts[0] != TRACEPARENT_VERSION {
+            return None;
+        }
+        if parts[1].len() != 32 || parts[2].len() != 16 || parts[3].len() != 2 {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(parts[1], 16).ok()?;
+        let span_id = u64::from_str_radix(parts[2], 16).ok()?;
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & FLAG_SAMPLED == FLAG_SAMPLED,
+        })
+    }
+}
+
+impl Display for TraceContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}-{:032x}-{:016x}-{:02x}",
+            TRACEPARENT_VERSION,
+            self.trace_id,
+            self.span_id,
+            if self.sampled { FLAG_SAMPLED } else { 0 }
+        ))
+    }
+}
+
+/// [TraceContextLayer] derives the [TraceContext] of every span of a [tracing_subscriber::Registry].
+///
+/// A span continues the trace of the remote parent recorded in its `traceparent` field (see [with_parent]),
+/// of its parent span, or of the context made active by [TraceContext::scope]. Otherwise it starts a new trace.
+/// e.g. `tracing_subscriber::fmt().finish().with(TraceContextLayer).init()`
+pub struct TraceContextLayer;
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TraceContextLayer {
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        let mut remote_parent = RemoteParentVisitor(None);
+        attrs.record(&mut remote_parent);
+        let parent = remote_parent
+            .0
+            .or_else(|| {
+                span.parent()
+                    .and_then(|parent| parent.extensions().get::<TraceContext>().copied())
+            })
+            .or_else(|| CURRENT_TRACE_CONTEXT.try_with(|ctx| *ctx).ok());
+
+        span.extensions_mut().insert(
+            parent
+                .map(|parent| parent.child())
+                .unwrap_or_else(TraceContext::new_root),
+        );
+    }
+}
+
+struct RemoteParentVisitor(Option<TraceContext>);
+
+impl Visit for RemoteParentVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == TRACEPARENT_METADATA_KEY {
+            self.0 = TraceContext::from_traceparent(value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == TRACEPARENT_METADATA_KEY {
+            self.0 = TraceContext::from_traceparent(&format!("{:?}", value));
+        }
+    }
+}
+
+fn new_span_id() -> u64 {
+    let (high, low) = uuid::Uuid::new_v4().as_u64_pair();
+    match high ^ low {
+        0 => 1,
+        id => id,
+    }
+}
+
+/// inject the active trace context into the metadata. The metadata is untouched if there's no active context.
+pub fn inject(metadata: &mut MetadataMap) {
+    if let Some(ctx) = TraceContext::current() {
+        if let Ok(value) = MetadataValue::try_from(ctx.to_string()) {
+            metadata.insert(TRACEPARENT_METADATA_KEY, value);
+        }
+    }
+}
+
+/// extract the trace context from the metadata of a received request
+pub fn extract(metadata: &MetadataMap) -> Option<TraceContext> {
+    metadata
+        .get(TRACEPARENT_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::from_traceparent)
+}
+
+/// create an outgoing [tonic::Request] carrying the active trace context
+pub fn new_request<T>(message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    inject(request.metadata_mut());
+    request
+}
+
+/// run `fut` in a child span of `parent` if the parent context exists
+pub async fn with_parent<F: Future>(parent: Option<TraceContext>, fut: F) -> F::Output {
+    match parent {
+        Some(ctx) => {
+            let span = tracing::info_span!("remote_parent", traceparent = %ctx);
+            ctx.child().scope(fut.instrument(span)).await
+        }
+        None => fut.await,
+    }
+}
+
+/// run `fut` within `ctx`, e.g. the context captured when a sender was built, unless `fut` is already within a context.
+/// It makes the RPCs sent by spawned tasks carry the trace of the span which built them.
+pub async fn within<F: Future>(ctx: Option<TraceContext>, fut: F) -> F::Output {
+    match ctx {
+        Some(ctx) if TraceContext::current().is_none() => ctx.child().scope(fut).await,
+        _ => fut.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use proto::common::{Ack, HostAddr, Response};
+    use tonic::async_trait;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::{extract, new_request, TraceContext, TraceContextLayer, TRACEPARENT_METADATA_KEY};
+    use crate::net::{
+        gateway::{ReceiveAckRpcGateway, RpcGateway},
+        AckResponderBuilder,
+    };
+
+    fn subscriber() -> impl tracing::Subscriber + Send + Sync {
+        tracing_subscriber::registry().with(TraceContextLayer)
+    }
+
+    /// records the trace context of every request it receives
+    struct TracedGateway {
+        host_addr: HostAddr,
+        received: Arc<Mutex<Vec<Option<TraceContext>>>>,
+    }
+
+    impl RpcGateway for TracedGateway {
+        fn get_host_addr(&self) -> &HostAddr {
+            &self.host_addr
+        }
+    }
+
+    #[async_trait]
+    impl ReceiveAckRpcGateway for TracedGateway {
+        async fn receive_ack(&self, req: Ack) -> Result<Response, tonic::Status> {
+            let request = new_request(req);
+            self.received
+                .lock()
+                .unwrap()
+                .push(extract(request.metadata()));
+            Ok(Response::ok())
+        }
+    }
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let ctx = TraceContext::new_root();
+        let traceparent = ctx.to_string();
+        assert_eq!(traceparent.len(), 55);
+        assert_eq!(TraceContext::from_traceparent(&traceparent), Some(ctx));
+
+        assert_eq!(
+            TraceContext::from_traceparent(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"
+            ),
+            Some(TraceContext {
+                trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+                span_id: 0x00f067aa0ba902b7,
+                sampled: false,
+            })
+        );
+        assert!(TraceContext::from_traceparent(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        )
+        .is_none());
+        assert!(TraceContext::from_traceparent(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+        )
+        .is_none());
+        assert!(TraceContext::from_traceparent("invalid").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_traceparent_injected_into_ack_request() {
+        let request = new_request(Ack::default());
+        assert!(request.metadata().get(TRACEPARENT_METADATA_KEY).is_none());
+
+        let ctx = TraceContext::new_root();
+        let request = ctx.scope(async { new_request(Ack::default()) }).await;
+        assert!(request.metadata().get(TRACEPARENT_METADATA_KEY).is_some());
+        assert_eq!(extract(request.metadata()), Some(ctx));
+    }
+
+    #[tokio::test]
+    async fn test_with_parent_continues_trace() {
+        let parent = TraceContext::new_root();
+        let child = super::with_parent(Some(parent), async { TraceContext::current() }).await;
+        assert!(child.is_some());
+        let child = child.unwrap();
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.span_id, parent.span_id);
+
+        assert!(super::with_parent(None, async { TraceContext::current() })
+            .await
+            .is_none());
+    }
+
+    #[test]
+    fn test_trace_context_derived_from_span() {
+        tracing::subscriber::with_default(subscriber(), || {
+            assert!(TraceContext::current().is_none());
+
+            let span = tracing::info_span!("parent");
+            let _entered = span.enter();
+            let parent = TraceContext::current();
+            assert!(parent.is_some());
+            let parent = parent.unwrap();
+            assert_eq!(TraceContext::of_span(&span), Some(parent));
+            assert_eq!(
+                extract(new_request(Ack::default()).metadata()),
+                Some(parent)
+            );
+
+            let child_span = tracing::info_span!("child");
+            let child = child_span.in_scope(TraceContext::current).unwrap();
+            assert_eq!(child.trace_id, parent.trace_id);
+            assert_ne!(child.span_id, parent.span_id);
+        });
+
+        assert!(TraceContext::of_span(&tracing::Span::none()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_with_parent_enters_remote_parent_span() {
+        let _guard = tracing::subscriber::set_default(subscriber());
+        let remote = TraceContext::new_root();
+        let (ctx, span_ctx) = super::with_parent(Some(remote), async {
+            let span = tracing::info_span!("handler");
+            (
+                TraceContext::current(),
+                span.in_scope(TraceContext::current),
+            )
+        })
+        .await;
+        assert_eq!(ctx.map(|ctx| ctx.trace_id), Some(remote.trace_id));
+        assert_ne!(ctx.map(|ctx| ctx.span_id), Some(remote.span_id));
+        assert_eq!(span_ctx.map(|ctx| ctx.trace_id), Some(remote.trace_id));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spawned_responder_carries_trace_of_builder_span() {
+        let _guard = tracing::subscriber::set_default(subscriber());
+        let received = Arc::new(Mutex::new(vec![]));
+        let host_addr = HostAddr {
+            host: "198.2.0.1".to_string(),
+            port: 8080,
+        };
+        let builder = AckResponderBuilder {
+            delay: 1,
+            buf_size: 10,
+            ..Default::default()
+        };
+
+        let span = tracing::info_span!("deploy");
+        let (responder, tx) = span.in_scope(|| {
+            builder.build(&host_addr, |addr, _, _| TracedGateway {
+                host_addr: addr.clone(),
+                received: received.clone(),
+            })
+        });
+        let handler = tokio::spawn(responder);
+        tx.send(Ack::default()).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        handler.abort();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received[0].is_some());
+        assert_eq!(
+            received[0].map(|ctx| ctx.trace_id),
+            TraceContext::of_span(&span).map(|ctx| ctx.trace_id)
+        );
+    }
+}
@@ -31,6 +31,7 @@ impl PartialEq for TypedValue {
             (Self::Null, Self::Invalid) => true,
             (Self::Invalid, Self::Null) => true,
             (Self::Array(l0), Self::Array(l1)) => l0 == l1,
+            (Self::Bytes(l0), Self::Bytes(r0)) => l0 == r0,
             _ => false,
         }
     }
@@ -51,6 +52,7 @@ impl PartialOrd for TypedValue {
             (Self::Null, Self::Invalid) => Some(Ordering::Equal),
             (Self::Invalid, Self::Null) => Some(Ordering::Equal),
             (Self::Array(l0), Self::Array(l1)) => l0.partial_cmp(l1),
+            (Self::Bytes(l0), Self::Bytes(r0)) => l0.partial_cmp(r0),
             _ => None,
         }
     }
@@ -75,6 +77,8 @@ pub enum TypedValue {
     Array(Vec<TypedValue>),
     // `undefined` in Javascript
     Invalid,
+    // `Uint8Array` in Javascript
+    Bytes(Vec<u8>),
 }
 
 impl Eq for TypedValue {}
@@ -106,6 +110,7 @@ impl ToRedisArgs for TypedValue {
             TypedValue::BigInt(v) => out.write_arg(&v.to_be_bytes()),
             TypedValue::Boolean(_) => out.write_arg(self.to_string().as_bytes()),
             TypedValue::Number(v) => out.write_arg(&v.to_be_bytes()),
+            TypedValue::Bytes(v) => out.write_arg(v),
             _ => out.write_arg(self.to_string().as_bytes()),
         }
     }
@@ -134,6 +139,11 @@ impl ToString for TypedValue {
             )
             .unwrap_or_default(),
             TypedValue::Invalid => "undefined".to_string(),
+            TypedValue::Bytes(v) => v
+                .iter()
+                .map(|byte| byte.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
         }
     }
 }
@@ -237,6 +247,7 @@ impl TypedValue {
                     .map_err(|err| tracing::error!("serialize array failed: {}", err))
                     .unwrap_or_default()
             }
+            TypedValue::Bytes(value) => value.clone(),
             _ => vec![],
         };
         result.append(raw_data);
@@ -291,6 +302,7 @@ impl TypedValue {
                     val.iter().map(|data| TypedValue::from_vec(data)),
                 ))
             }
+            DataTypeEnum::Bytes => TypedValue::Bytes(data[1..data.len()].to_vec()),
         }
     }
 
@@ -341,6 +353,7 @@ impl TypedValue {
                     val.iter().map(|data| TypedValue::from_vec(data)),
                 ))
             }
+            DataTypeEnum::Bytes => TypedValue::Bytes(data[1..data.len()].to_vec()),
         }
     }
 
@@ -354,6 +367,7 @@ impl TypedValue {
             TypedValue::Object(_) => DataTypeEnum::Object,
             TypedValue::Boolean(_) => DataTypeEnum::Boolean,
             TypedValue::Array(_) => DataTypeEnum::Array,
+            TypedValue::Bytes(_) => DataTypeEnum::Bytes,
             _ => DataTypeEnum::Unspecified,
         }
     }
@@ -393,6 +407,7 @@ impl TypedValue {
                     }
                 }
             }
+            DataTypeEnum::Bytes => Self::Bytes(data.to_vec()),
         }
     }
 
@@ -443,6 +458,11 @@ impl TypedValue {
                 serde_json::Value::Array(v.iter().map(|value| value.to_json_value()).collect())
             }
             TypedValue::Invalid => serde_json::Value::Null,
+            TypedValue::Bytes(v) => serde_json::Value::Array(
+                v.iter()
+                    .map(|byte| serde_json::Value::Number(serde_json::Number::from(*byte)))
+                    .collect(),
+            ),
         }
     }
 
@@ -459,6 +479,15 @@ impl From<&Entry> for TypedValue {
     }
 }
 
+impl From<&TypedValue> for Entry {
+    fn from(value: &TypedValue) -> Self {
+        Entry {
+            data_type: value.get_type() as i32,
+            value: value.get_data_bytes(),
+        }
+    }
+}
+
 pub type RowIdx = u64;
 pub type NodeIdx = u32;
 pub type SinkId = u32;
@@ -607,6 +636,26 @@ mod tests {
         })
     }
 
+    #[test]
+    pub fn test_typed_value_bytes() {
+        use super::TypedValue;
+        use proto::common::DataTypeEnum;
+
+        let bytes = TypedValue::Bytes(vec![0, 1, 255]);
+        assert_eq!(bytes.get_type(), DataTypeEnum::Bytes);
+        let data = bytes.get_data();
+        assert_eq!(data, vec![DataTypeEnum::Bytes as u8, 0, 1, 255]);
+        assert_eq!(TypedValue::from_vec(&data), bytes);
+        assert_eq!(TypedValue::from_slice(&data), bytes);
+        assert_eq!(
+            TypedValue::from_slice_with_type(&data[1..], DataTypeEnum::Bytes),
+            bytes
+        );
+        assert_eq!(bytes.to_string(), "0,1,255");
+        assert_eq!(bytes.to_json_value(), serde_json::json!([0, 1, 255]));
+        assert_ne!(bytes, TypedValue::Array(vec![]));
+    }
+
     #[test]
     pub fn test_typed_value_left_int_dual_op() {
         let a1 = super::TypedValue::BigInt(100);
@@ -1480,6 +1529,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_typed_value_entry_round_trip() {
+        use proto::common::{DataTypeEnum, Entry};
+
+        use super::TypedValue;
+
+        [
+            TypedValue::String("lightflus".to_string()),
+            TypedValue::BigInt(1),
+            TypedValue::Number(1.5),
+            TypedValue::Boolean(true),
+            TypedValue::Null,
+        ]
+        .iter()
+        .for_each(|value| {
+            let entry = Entry::from(value);
+            assert_eq!(entry.data_type(), value.get_type());
+            assert_eq!(&TypedValue::from(&entry), value);
+        });
+        assert_eq!(
+            Entry::from(&TypedValue::BigInt(1)).data_type(),
+            DataTypeEnum::Bigint
+        );
+    }
+
     #[test]
     fn test_from_slice_with_type() {
         use proto::common::DataTypeEnum;
@@ -1,5 +1,11 @@
-use common::{db::MysqlConn, types::TypedValue};
-use proto::common::mysql_desc;
+use std::{collections::BTreeMap, sync::Arc};
+
+use common::{
+    db::{rows_to_proto, ConflictPolicy, DecodeOptions, MysqlConn, MysqlConnPool},
+    types::TypedValue,
+};
+use futures_util::{StreamExt, TryStreamExt};
+use proto::common::{mysql_desc, Entry};
 use sqlx::Row;
 
 #[tokio::test]
@@ -9,6 +15,11 @@ async fn test_mysql_execute() {
         username: "ci".to_string(),
         password: "123".to_string(),
         database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
     };
 
     let mut conn = MysqlConn::from(conn_opts);
@@ -61,3 +72,813 @@ async fn test_mysql_execute() {
     let result = conn.execute("drop table if exists person", vec![]).await;
     assert!(result.is_ok());
 }
+
+#[tokio::test]
+async fn test_mysql_fetch_tinyint_as_boolean() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts.clone());
+
+    let result = conn
+        .execute(
+            "create table if not exists flags (id int NOT NULL, flag tinyint(1), PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = conn
+        .execute(
+            "insert into flags (id, flag) values (1, 0), (2, 1), (3, 2)",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let rows = conn
+        .fetch_typed("select flag from flags order by id", vec![])
+        .await;
+    assert_eq!(
+        rows.unwrap(),
+        vec![
+            vec![TypedValue::Boolean(false)],
+            vec![TypedValue::Boolean(true)],
+            vec![TypedValue::Boolean(true)],
+        ]
+    );
+
+    let mut strict_conn = MysqlConn::from(conn_opts).with_decode_options(DecodeOptions {
+        truthiness: Arc::new(|value| value == 1),
+    });
+    let rows = strict_conn
+        .fetch_typed("select flag from flags order by id", vec![])
+        .await;
+    assert_eq!(
+        rows.unwrap(),
+        vec![
+            vec![TypedValue::Boolean(false)],
+            vec![TypedValue::Boolean(true)],
+            vec![TypedValue::Boolean(false)],
+        ]
+    );
+
+    let result = conn.execute("drop table if exists flags", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_max_execution_time_applied_on_connect() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 1500,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let rows = conn
+        .fetch_typed("select @@session.max_execution_time", vec![])
+        .await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1500)]]);
+}
+
+#[tokio::test]
+async fn test_mysql_extra_params_applied_on_connect() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    }
+    .with_extra_param("sql_mode", "ANSI_QUOTES,STRICT_TRANS_TABLES")
+    .with_extra_param("wait_timeout", "600");
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let rows = conn
+        .fetch_typed("select @@session.sql_mode, @@session.wait_timeout", vec![])
+        .await;
+    assert_eq!(
+        rows.unwrap(),
+        vec![vec![
+            TypedValue::String("ANSI_QUOTES,STRICT_TRANS_TABLES".to_string()),
+            TypedValue::BigInt(600)
+        ]]
+    );
+}
+
+#[tokio::test]
+async fn test_mysql_snapshot_hides_concurrent_writes() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut writer = MysqlConn::from(conn_opts.clone());
+    let result = writer
+        .execute(
+            "create table if not exists snapshot_items (id int NOT NULL, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = writer
+        .execute("insert into snapshot_items (id) values (1)", vec![])
+        .await;
+    assert!(result.is_ok());
+
+    let mut reader = MysqlConn::from(conn_opts);
+    let snapshot = reader.begin_snapshot().await;
+    assert!(snapshot.is_ok());
+    let mut snapshot = snapshot.unwrap();
+
+    let count_statement = "select count(*) from snapshot_items";
+    let rows = snapshot.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1)]]);
+
+    let result = writer
+        .execute("insert into snapshot_items (id) values (2)", vec![])
+        .await;
+    assert!(result.is_ok());
+
+    let rows = snapshot.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1)]]);
+    assert!(snapshot.commit().await.is_ok());
+
+    let rows = reader.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(2)]]);
+
+    let result = writer
+        .execute("drop table if exists snapshot_items", vec![])
+        .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_warm_pool() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    assert_eq!(conn.pool_size(), 0);
+    let errors = conn.warm_pool(3).await;
+    assert!(errors.is_empty());
+    assert_eq!(conn.pool_size(), 3);
+
+    // a query takes a warm connection rather than opening a new one
+    let rows = conn.fetch_typed("select 1", vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1)]]);
+    assert_eq!(conn.pool_size(), 3);
+
+    let errors = conn.warm_pool(2).await;
+    assert!(errors.is_empty());
+    assert_eq!(conn.pool_size(), 3);
+}
+
+#[tokio::test]
+async fn test_mysql_fetch_bit_as_bigint() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists bits (id int NOT NULL, flags bit(8), PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = conn
+        .execute(
+            "insert into bits (id, flags) values (1, b'10100101')",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = conn
+        .execute(
+            "insert into bits (id, flags) values (?, ?)",
+            vec![TypedValue::BigInt(2), TypedValue::BigInt(3)],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let rows = conn
+        .fetch_typed("select flags from bits order by id", vec![])
+        .await;
+    assert_eq!(
+        rows.unwrap(),
+        vec![vec![TypedValue::BigInt(165)], vec![TypedValue::BigInt(3)]]
+    );
+
+    let result = conn.execute("drop table if exists bits", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_array_round_trip() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists arrays (id int NOT NULL, tags json, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let array = TypedValue::Array(vec![
+        TypedValue::BigInt(1),
+        TypedValue::String("lightflus".to_string()),
+        TypedValue::Boolean(true),
+        TypedValue::Number(1.5),
+    ]);
+    let result = conn
+        .execute(
+            "insert into arrays (id, tags) values (?, ?)",
+            vec![TypedValue::BigInt(1), array.clone()],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let rows = conn
+        .fetch_typed("select tags from arrays where id = 1", vec![])
+        .await;
+    assert_eq!(rows.unwrap(), vec![vec![array]]);
+
+    let result = conn.execute("drop table if exists arrays", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_rows_to_proto() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists proto_rows (id int NOT NULL, name varchar(36), score double, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let result = conn
+        .execute(
+            "insert into proto_rows (id, name, score) values (1, 'lightflus', 9.5), (2, NULL, 7)",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let mut rows = vec![];
+    let result = conn
+        .try_for_each(
+            "select id, name, score from proto_rows order by id",
+            vec![],
+            |row| {
+                rows.push(row);
+                futures_util::future::ready(Ok(()))
+            },
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let entries = rows_to_proto(&rows, &DecodeOptions::default());
+    let entry = |value: TypedValue| Entry::from(&value);
+    assert_eq!(
+        entries.unwrap(),
+        vec![
+            vec![
+                entry(TypedValue::BigInt(1)),
+                entry(TypedValue::String("lightflus".to_string())),
+                entry(TypedValue::Number(9.5)),
+            ],
+            vec![
+                entry(TypedValue::BigInt(2)),
+                entry(TypedValue::Null),
+                entry(TypedValue::Number(7.0)),
+            ],
+        ]
+    );
+
+    let result = conn
+        .execute("drop table if exists proto_rows", vec![])
+        .await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_fetch_limited_drains_connection() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists numbers (id int NOT NULL, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    for id in 0..100 {
+        let result = conn
+            .execute(
+                "insert into numbers (id) values (?)",
+                vec![TypedValue::BigInt(id)],
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    // only a part of the result set is read
+    let rows = conn
+        .fetch_typed_limited("select id from numbers order by id", vec![], 3)
+        .await;
+    assert_eq!(
+        rows.unwrap(),
+        (0..3)
+            .map(|id| vec![TypedValue::BigInt(id)])
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(conn.pool_size(), 1);
+
+    // the same connection is reused by the next query
+    let rows = conn
+        .fetch_typed("select count(*) from numbers", vec![])
+        .await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(100)]]);
+    assert_eq!(conn.pool_size(), 1);
+
+    let result = conn.execute("drop table if exists numbers", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_conn_pool_shared_across_tasks() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let pool = MysqlConnPool::new(&conn_opts, 1).unwrap();
+    let result = pool
+        .execute(
+            "create table if not exists pooled (id int NOT NULL, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    // the tasks share the single connection of the pool one after another
+    let handlers = (0..8)
+        .map(|id| {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                pool.execute(
+                    "insert into pooled (id) values (?)",
+                    vec![TypedValue::BigInt(id)],
+                )
+                .await
+                .map(|result| result.rows_affected())
+            })
+        })
+        .collect::<Vec<_>>();
+    for handler in handlers {
+        assert_eq!(handler.await.unwrap().unwrap(), 1);
+    }
+    assert_eq!(pool.size(), 1);
+
+    let mut ids = vec![];
+    let result = pool
+        .try_for_each("select id from pooled order by id", vec![], |row| {
+            ids.push(row.get::<i32, _>(0));
+            futures_util::future::ready(Ok(()))
+        })
+        .await;
+    assert_eq!(result.unwrap(), 8);
+    assert_eq!(ids, (0..8).collect::<Vec<_>>());
+
+    let result = pool.execute("drop table if exists pooled", vec![]).await;
+    assert!(result.is_ok());
+    pool.close().await;
+    assert_eq!(pool.size(), 0);
+}
+
+#[tokio::test]
+async fn test_mysql_geometry_round_trip() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists places (id int NOT NULL, location geometry, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    // POINT(1 2) in little-endian WKB
+    let mut wkb = vec![0x01, 0x01, 0x00, 0x00, 0x00];
+    wkb.extend_from_slice(&1f64.to_le_bytes());
+    wkb.extend_from_slice(&2f64.to_le_bytes());
+    let result = conn
+        .execute(
+            "insert into places (id, location) values (?, ST_GeomFromWKB(?))",
+            vec![TypedValue::BigInt(1), TypedValue::Bytes(wkb.clone())],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let rows = conn
+        .fetch_typed("select location from places where id = 1", vec![])
+        .await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::Bytes(wkb)]]);
+
+    let result = conn.execute("drop table if exists places", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_bind_round_trip() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists bindings (id int NOT NULL, v_string varchar(32), v_bigint bigint, \
+            v_boolean boolean, v_number double, v_null varchar(32), v_bytes blob, v_array json, v_object json, \
+            PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let values = vec![
+        TypedValue::String("lightflus".to_string()),
+        TypedValue::BigInt(1 << 40),
+        TypedValue::Boolean(true),
+        TypedValue::Number(1.5),
+        TypedValue::Null,
+        TypedValue::Bytes(vec![0, 1, 255]),
+        TypedValue::Array(vec![TypedValue::BigInt(1), TypedValue::Null]),
+        TypedValue::Object(BTreeMap::from([(
+            "name".to_string(),
+            TypedValue::String("lightflus".to_string()),
+        )])),
+    ];
+    let mut arguments = vec![TypedValue::BigInt(1)];
+    arguments.extend(values.iter().cloned());
+    let result = conn
+        .execute(
+            "insert into bindings values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            arguments,
+        )
+        .await;
+    assert!(result.is_ok());
+
+    let rows = conn
+        .fetch_typed(
+            "select v_string, v_bigint, v_boolean, v_number, v_null, v_bytes, v_array, v_object \
+            from bindings where id = ?",
+            vec![TypedValue::BigInt(1)],
+        )
+        .await;
+    assert_eq!(rows.unwrap(), vec![values]);
+
+    let result = conn.execute("drop table if exists bindings", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_fetch_stream() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists streamed (id int NOT NULL, name varchar(32), score double, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    for id in 0..5 {
+        let result = conn
+            .execute(
+                "insert into streamed (id, name, score) values (?, ?, ?)",
+                vec![
+                    TypedValue::BigInt(id),
+                    TypedValue::String(format!("name-{}", id)),
+                    TypedValue::Number(id as f64 / 2.0),
+                ],
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+    let row_of = |id: i64| {
+        vec![
+            TypedValue::BigInt(id),
+            TypedValue::String(format!("name-{}", id)),
+            TypedValue::Number(id as f64 / 2.0),
+        ]
+    };
+
+    let rows = conn
+        .fetch(
+            "select id, name, score from streamed where id >= ? order by id",
+            vec![TypedValue::BigInt(1)],
+        )
+        .try_collect::<Vec<_>>()
+        .await;
+    assert_eq!(rows.unwrap(), (1..5).map(row_of).collect::<Vec<_>>());
+
+    // the rows are decoded lazily, so only the taken ones are read
+    let rows = conn
+        .fetch("select id, name, score from streamed order by id", vec![])
+        .take(2)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(
+        rows.into_iter().collect::<Result<Vec<_>, _>>().unwrap(),
+        (0..2).map(row_of).collect::<Vec<_>>()
+    );
+
+    let result = conn.execute("drop table if exists streamed", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_transaction_rolls_back_failed_batch() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let result = conn
+        .execute(
+            "create table if not exists tx_items (id int NOT NULL, name varchar(32) NOT NULL, PRIMARY KEY (id))",
+            vec![],
+        )
+        .await;
+    assert!(result.is_ok());
+    let insert = "insert into tx_items (id, name) values (?, ?)";
+    let count_statement = "select count(*) from tx_items";
+
+    // the second insert violates the primary key, so the first one is rolled back
+    let mut tx = conn.begin().await.unwrap();
+    let result = tx
+        .execute(
+            insert,
+            vec![TypedValue::BigInt(1), TypedValue::String("a".to_string())],
+        )
+        .await;
+    assert!(result.is_ok());
+    let rows = tx.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1)]]);
+    let result = tx
+        .execute(
+            insert,
+            vec![TypedValue::BigInt(1), TypedValue::String("b".to_string())],
+        )
+        .await;
+    assert!(result.is_err());
+    assert!(tx.rollback().await.is_ok());
+
+    let rows = conn.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(0)]]);
+
+    // a dropped transaction is rolled back as well
+    {
+        let mut tx = conn.begin().await.unwrap();
+        let result = tx
+            .execute(
+                insert,
+                vec![TypedValue::BigInt(2), TypedValue::String("c".to_string())],
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+    let rows = conn.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(0)]]);
+
+    // a committed transaction takes effect
+    let mut tx = conn.begin().await.unwrap();
+    let result = tx
+        .execute(
+            insert,
+            vec![TypedValue::BigInt(3), TypedValue::String("d".to_string())],
+        )
+        .await;
+    assert!(result.is_ok());
+    let processed = tx
+        .try_for_each("select name from tx_items", vec![], |row| async move {
+            assert_eq!(row.try_get::<&str, usize>(0)?, "d");
+            Ok(())
+        })
+        .await;
+    assert_eq!(processed.unwrap(), 1);
+    assert!(tx.commit().await.is_ok());
+
+    let rows = conn.fetch_typed(count_statement, vec![]).await;
+    assert_eq!(rows.unwrap(), vec![vec![TypedValue::BigInt(1)]]);
+
+    let result = conn.execute("drop table if exists tx_items", vec![]).await;
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn test_mysql_insert_batch_conflict_policy() {
+    let conn_opts = mysql_desc::ConnectionOpts {
+        host: "localhost".to_string(),
+        username: "ci".to_string(),
+        password: "123".to_string(),
+        database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
+    };
+
+    let mut conn = MysqlConn::from(conn_opts);
+    let columns = ["id", "name", "score"];
+    let row = |id: i64, name: &str, score: i64| {
+        vec![
+            TypedValue::BigInt(id),
+            TypedValue::String(name.to_string()),
+            TypedValue::BigInt(score),
+        ]
+    };
+    let select = "select id, name, score from upserts order by id";
+
+    // (policy, the affected rows of the conflicting insert, the table after it)
+    let cases = [
+        (ConflictPolicy::Error, None, vec![row(1, "a", 1)]),
+        (
+            ConflictPolicy::Ignore,
+            Some(1),
+            vec![row(1, "a", 1), row(2, "c", 3)],
+        ),
+        (
+            ConflictPolicy::Overwrite,
+            Some(3),
+            vec![row(1, "b", 2), row(2, "c", 3)],
+        ),
+        (
+            ConflictPolicy::UpdateColumns(vec!["score"]),
+            Some(3),
+            vec![row(1, "a", 2), row(2, "c", 3)],
+        ),
+    ];
+    for (policy, affected_rows, expected) in cases {
+        let result = conn.execute("drop table if exists upserts", vec![]).await;
+        assert!(result.is_ok());
+        let result = conn
+            .execute(
+                "create table upserts (id int NOT NULL, name varchar(32), score int, PRIMARY KEY (id))",
+                vec![],
+            )
+            .await;
+        assert!(result.is_ok());
+        let result = conn
+            .insert_batch("upserts", &columns, vec![row(1, "a", 1)], &policy)
+            .await;
+        assert_eq!(result.unwrap().rows_affected(), 1);
+
+        // the first row collides with the existing one
+        let result = conn
+            .insert_batch(
+                "upserts",
+                &columns,
+                vec![row(1, "b", 2), row(2, "c", 3)],
+                &policy,
+            )
+            .await;
+        match affected_rows {
+            Some(affected_rows) => assert_eq!(result.unwrap().rows_affected(), affected_rows),
+            None => assert!(result.is_err()),
+        }
+        let rows = conn.fetch_typed(select, vec![]).await;
+        assert_eq!(rows.unwrap(), expected, "{:?}", policy);
+    }
+
+    let result = conn.execute("drop table if exists upserts", vec![]).await;
+    assert!(result.is_ok());
+}
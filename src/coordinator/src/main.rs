@@ -1,6 +1,7 @@
 use std::{env, time::Duration};
 
 use actix_web::{web, App};
+use common::net::trace::TraceContextLayer;
 
 use lightflus_core::{
     apiserver::handler::{
@@ -15,10 +16,14 @@ use lightflus_core::{
 
 use proto::coordinator::coordinator_api_server::CoordinatorApiServer;
 use tonic::transport::Server;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt()
+        .finish()
+        .with(TraceContextLayer)
+        .init();
     let builder = &mut load_builder();
 
     replace_builder_args_by_env(builder);
@@ -1,7 +1,11 @@
 use crate::new_rpc_response;
 
 use super::coord;
-use proto::common::{Ack, Dataflow, DataflowStates, Heartbeat, ResourceId, Response};
+use common::net::trace;
+use proto::common::{
+    Ack, AckBatch, AckBatchResponse, AckResult, Dataflow, DataflowStates, Heartbeat, ResourceId,
+    Response,
+};
 
 use proto::coordinator::coordinator_api_server::CoordinatorApi;
 use proto::coordinator::GetDataflowRequest;
@@ -28,7 +32,15 @@ impl CoordinatorApi for CoordinatorApiImpl {
         &self,
         request: tonic::Request<Heartbeat>,
     ) -> Result<tonic::Response<Response>, tonic::Status> {
-        self.coordinator.receive_heartbeart(request.get_ref()).await;
+        self.coordinator.validate_cluster(request.metadata())?;
+        self.coordinator
+            .audit_heartbeat(request.remote_addr(), request.get_ref());
+        let parent = trace::extract(request.metadata());
+        trace::with_parent(
+            parent,
+            self.coordinator.receive_heartbeart(request.get_ref()),
+        )
+        .await;
         Ok(tonic::Response::new(Response::ok()))
     }
 
@@ -36,10 +48,29 @@ impl CoordinatorApi for CoordinatorApiImpl {
         &self,
         request: tonic::Request<Ack>,
     ) -> Result<tonic::Response<Response>, tonic::Status> {
-        self.coordinator.receive_ack(request.into_inner()).await;
+        self.coordinator.validate_cluster(request.metadata())?;
+        let parent = trace::extract(request.metadata());
+        trace::with_parent(parent, self.coordinator.receive_ack(request.into_inner())).await;
         Ok(tonic::Response::new(Response::ok()))
     }
 
+    async fn receive_acks(
+        &self,
+        request: tonic::Request<AckBatch>,
+    ) -> Result<tonic::Response<AckBatchResponse>, tonic::Status> {
+        self.coordinator.validate_cluster(request.metadata())?;
+        let parent = trace::extract(request.metadata());
+        let acks = request.into_inner().acks;
+        let results = acks.iter().map(|_| AckResult::default()).collect();
+        trace::with_parent(parent, async {
+            for ack in acks {
+                self.coordinator.receive_ack(ack).await
+            }
+        })
+        .await;
+        Ok(tonic::Response::new(AckBatchResponse { results }))
+    }
+
     async fn create_dataflow(
         &self,
         request: tonic::Request<Dataflow>,
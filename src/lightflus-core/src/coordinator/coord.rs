@@ -1,6 +1,10 @@
 use std::fs;
 
+use std::net::SocketAddr;
+
+use common::net::audit::{AuditRecord, AuditWriter, FileAuditSinkBuilder};
 use common::net::cluster;
+use common::net::tenant::ClusterValidator;
 use common::net::AckResponderBuilder;
 use common::net::HeartbeatBuilder;
 use common::utils;
@@ -10,6 +14,7 @@ use proto::common::DataflowStates;
 use proto::common::DataflowStatus;
 
 use proto::common::Heartbeat;
+use proto::common::HostAddr;
 use proto::common::NodeType;
 use proto::common::ResourceId;
 
@@ -30,6 +35,12 @@ pub struct CoordinatorBuilder {
     pub heartbeat: HeartbeatBuilder,
     // ack responder builder
     pub ack: AckResponderBuilder,
+    /// id of the cluster. If it's set, heartbeats and acks are stamped with it and only the ones from the same cluster are accepted
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    /// where the received heartbeats are recorded. No heartbeat is recorded if it's unset
+    #[serde(default)]
+    pub audit: Option<FileAuditSinkBuilder>,
 }
 
 impl CoordinatorBuilder {
@@ -38,10 +49,22 @@ impl CoordinatorBuilder {
             dispatcher: Dispatcher::new(
                 &self.cluster,
                 &self.storage,
-                &self.heartbeat,
-                &self.ack,
+                &HeartbeatBuilder {
+                    cluster_id: self.cluster_id.clone(),
+                    ..self.heartbeat.clone()
+                },
+                &AckResponderBuilder {
+                    cluster_id: self.cluster_id.clone(),
+                    ..self.ack.clone()
+                },
                 self.port,
             ),
+            cluster_validator: ClusterValidator::new(self.cluster_id.clone()),
+            audit: self.audit.as_ref().map(|audit| {
+                audit
+                    .build_writer()
+                    .expect("fail to open heartbeat audit file: ")
+            }),
         }
     }
 }
@@ -71,6 +94,8 @@ pub fn load_builder() -> CoordinatorBuilder {
 /// - Scale Up and Scale Down
 pub struct Coordinator {
     dispatcher: Dispatcher,
+    cluster_validator: ClusterValidator,
+    audit: Option<AuditWriter>,
 }
 
 impl Coordinator {
@@ -115,6 +140,22 @@ impl Coordinator {
             .map_err(|err| err.to_tonic_status())
     }
 
+    /// reject the heartbeat or ack if it comes from another cluster
+    pub(crate) fn validate_cluster(
+        &self,
+        metadata: &tonic::metadata::MetadataMap,
+    ) -> Result<(), tonic::Status> {
+        self.cluster_validator.validate(metadata)
+    }
+
+    /// record a received heartbeat if the coordinator is audited
+    pub(crate) fn audit_heartbeat(&self, remote_addr: Option<SocketAddr>, heartbeat: &Heartbeat) {
+        if let Some(writer) = self.audit.as_ref() {
+            let host_addr = remote_addr.map(HostAddr::from).unwrap_or_default();
+            writer.write(AuditRecord::received(&host_addr, heartbeat))
+        }
+    }
+
     pub(crate) async fn receive_heartbeart(&self, heartbeat: &Heartbeat) {
         self.dispatcher
             .update_task_manager_heartbeat_status(heartbeat)
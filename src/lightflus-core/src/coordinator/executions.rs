@@ -1,9 +1,12 @@
-use std::{collections::BTreeMap, sync::atomic::AtomicU64};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{atomic::AtomicU64, Arc, Mutex},
+};
 
 use common::{
     net::{
         cluster::Node, gateway::taskmanager::SafeTaskManagerRpcGateway, AckResponderBuilder,
-        HeartbeatBuilder,
+        HeartbeatBuilder, HeartbeatCoalescer,
     },
     types::ExecutorId,
     utils,
@@ -40,6 +43,70 @@ use tokio::{sync::mpsc, task::JoinHandle};
 ///
 ///
 
+/// [`ExecutorHeartbeats`] heartbeats the executors of all the jobs deployed by the coordinator.
+/// The jobs running the same executor on the same TaskManager share one heartbeat per tick by a [`HeartbeatCoalescer`] of the executor,
+/// which the TaskManager splits back into one heartbeat per job.
+#[derive(Clone)]
+pub(crate) struct ExecutorHeartbeats {
+    builder: HeartbeatBuilder,
+    coalescers: Arc<Mutex<HashMap<ExecutorId, HeartbeatCoalescer>>>,
+}
+
+impl ExecutorHeartbeats {
+    pub(crate) fn new(builder: &HeartbeatBuilder) -> Self {
+        Self {
+            builder: builder.clone(),
+            coalescers: Default::default(),
+        }
+    }
+
+    /// Start to heartbeat the executor of a job. The task of the heartbeat sender is returned if the executor on `host_addr` wasn't heartbeated yet,
+    /// otherwise the job joins the existing heartbeat.
+    fn register(
+        &self,
+        host_addr: &HostAddr,
+        executor_id: ExecutorId,
+        execution_id: &SubDataflowId,
+    ) -> Option<JoinHandle<()>> {
+        self.coalescers
+            .lock()
+            .unwrap()
+            .entry(executor_id)
+            .or_insert_with(|| HeartbeatCoalescer::new(&self.builder, executor_id))
+            .register(
+                host_addr,
+                execution_id.clone(),
+                |host_addr, connect_timeout, rpc_timeout| {
+                    SafeTaskManagerRpcGateway::with_timeout(host_addr, connect_timeout, rpc_timeout)
+                },
+            )
+            .map(tokio::spawn)
+    }
+
+    /// Stop heartbeating the executor of a job. The shared sender stops once no job is left.
+    fn deregister(
+        &self,
+        host_addr: &HostAddr,
+        executor_id: ExecutorId,
+        execution_id: &SubDataflowId,
+    ) {
+        if let Some(coalescer) = self.coalescers.lock().unwrap().get_mut(&executor_id) {
+            coalescer.deregister(host_addr, execution_id)
+        }
+    }
+
+    /// the execution ids of the jobs heartbeating the executor on `host_addr`
+    #[cfg(test)]
+    fn execution_ids(&self, host_addr: &HostAddr, executor_id: ExecutorId) -> Vec<SubDataflowId> {
+        self.coalescers
+            .lock()
+            .unwrap()
+            .get(&executor_id)
+            .map(|coalescer| coalescer.execution_ids(host_addr))
+            .unwrap_or_default()
+    }
+}
+
 /// A [`VertexExecution`] represents an execution context for a [`LocalExecutor`].
 /// - watch each LocalExecutor's state details
 /// - restart LocalExecutor while it stops unexpectedly
@@ -54,8 +121,12 @@ pub(crate) struct VertexExecution {
     _ack_handler: JoinHandle<()>,
     /// the enqueue-entrypoint of a ack request queue
     ack_request_queue: mpsc::Sender<Ack>,
-    // the asynchronous task of the heartbeat sender
-    heartbeat_handler: JoinHandle<()>,
+    /// the asynchronous task of the heartbeat sender, if this execution started the heartbeat of the executor
+    _heartbeat_handler: Option<JoinHandle<()>>,
+    /// the shared heartbeats, which this execution leaves on drop
+    heartbeats: ExecutorHeartbeats,
+    host_addr: HostAddr,
+    execution_id: SubDataflowId,
     /// the latest heartbeat ack id
     latest_ack_heartbeat_id: AtomicU64,
     /// the latest heartbeat timestamp
@@ -68,25 +139,20 @@ impl VertexExecution {
         executor_id: ExecutorId,
         operator: &OperatorInfo,
         ack_builder: &AckResponderBuilder,
-        heartbeat_builder: &HeartbeatBuilder,
+        heartbeats: &ExecutorHeartbeats,
     ) -> Self {
         let host_addr = operator.get_host_addr();
         let (ack, sender) = ack_builder.build(&host_addr, |addr, connect_timeout, rpc_timout| {
             SafeTaskManagerRpcGateway::with_timeout(addr, connect_timeout, rpc_timout)
         });
 
-        let mut heartbeat = heartbeat_builder.build(
-            &host_addr,
-            executor_id,
-            |host_addr, connect_timeout, rpc_timeout| {
-                SafeTaskManagerRpcGateway::with_timeout(host_addr, connect_timeout, rpc_timeout)
-            },
-        );
-        heartbeat.update_execution_id(execution_id.clone());
         Self {
             executor_id,
             operator: operator.clone(),
-            heartbeat_handler: tokio::spawn(heartbeat),
+            _heartbeat_handler: heartbeats.register(&host_addr, executor_id, execution_id),
+            heartbeats: heartbeats.clone(),
+            host_addr,
+            execution_id: execution_id.clone(),
             _ack_handler: tokio::spawn(ack),
             ack_request_queue: sender,
             latest_ack_heartbeat_id: Default::default(),
@@ -95,6 +161,13 @@ impl VertexExecution {
     }
 }
 
+impl Drop for VertexExecution {
+    fn drop(&mut self) {
+        self.heartbeats
+            .deregister(&self.host_addr, self.executor_id, &self.execution_id)
+    }
+}
+
 /// A [`SubdataflowDeploymentPlan`] represents a description for a subdataflow [`Dataflow`] deployment. It may contains following properties:
 /// - the structure of subdataflow
 /// - the execution id of the subdataflow
@@ -113,7 +186,7 @@ pub(crate) struct SubdataflowDeploymentPlan<'a> {
     /// ack responder
     ack: &'a AckResponderBuilder,
     // heartbeat sender
-    heartbeat: &'a HeartbeatBuilder,
+    heartbeat: &'a ExecutorHeartbeats,
 }
 
 impl<'a> SubdataflowDeploymentPlan<'a> {
@@ -122,7 +195,7 @@ impl<'a> SubdataflowDeploymentPlan<'a> {
         job_id: &'a ResourceId,
         node: Option<&'a Node>,
         ack_builder: &'a AckResponderBuilder,
-        heartbeat_builder: &'a HeartbeatBuilder,
+        heartbeats: &'a ExecutorHeartbeats,
     ) -> Self {
         Self {
             subdataflow: subdataflow.1,
@@ -130,7 +203,7 @@ impl<'a> SubdataflowDeploymentPlan<'a> {
             job_id,
             node,
             ack: ack_builder,
-            heartbeat: heartbeat_builder,
+            heartbeat: heartbeats,
         }
     }
 
@@ -192,7 +265,7 @@ impl SubdataflowExecution {
         subdataflow: &Dataflow,
         execution_id: SubDataflowId,
         ack: &AckResponderBuilder,
-        heartbeat: &HeartbeatBuilder,
+        heartbeat: &ExecutorHeartbeats,
     ) -> Self {
         Self {
             worker,
@@ -276,6 +349,7 @@ mod tests {
             buf_size: 10,
             connect_timeout: 3,
             rpc_timeout: 3,
+            ..Default::default()
         };
 
         let (gateway, mut ack_rx, _) = MockRpcGateway::new(ack_responder_builder.buf_size, 10);
@@ -302,6 +376,8 @@ mod tests {
                     sub_id: 0,
                 }),
                 task_id: 0,
+                coalesced_subdataflow_ids: vec![],
+                membership_digest: 0,
             })
             .await;
 
@@ -323,6 +399,49 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_executor_heartbeats_coalesced() {
+        use common::net::HeartbeatBuilder;
+        use proto::common::ResourceId;
+
+        let heartbeats = super::ExecutorHeartbeats::new(&HeartbeatBuilder::default());
+        let host_addr = HostAddr {
+            host: "198.0.0.1".to_string(),
+            port: 8792,
+        };
+        let execution_id = |job: &str| SubDataflowId {
+            job_id: Some(ResourceId {
+                resource_id: job.to_string(),
+                namespace_id: Default::default(),
+            }),
+            sub_id: 0,
+        };
+
+        // the jobs running executor 1 on the same node share one sender
+        let sender = heartbeats.register(&host_addr, 1, &execution_id("job-1"));
+        assert!(sender.is_some());
+        assert!(heartbeats
+            .register(&host_addr, 1, &execution_id("job-2"))
+            .is_none());
+        assert_eq!(
+            heartbeats.execution_ids(&host_addr, 1),
+            vec![execution_id("job-1"), execution_id("job-2")]
+        );
+        // another executor is heartbeated on its own
+        assert!(heartbeats
+            .register(&host_addr, 2, &execution_id("job-1"))
+            .is_some());
+
+        heartbeats.deregister(&host_addr, 1, &execution_id("job-1"));
+        assert_eq!(
+            heartbeats.execution_ids(&host_addr, 1),
+            vec![execution_id("job-2")]
+        );
+        heartbeats.deregister(&host_addr, 1, &execution_id("job-2"));
+        assert!(heartbeats.execution_ids(&host_addr, 1).is_empty());
+        sender.unwrap().abort();
+    }
+
     #[tokio::test]
     async fn test_subdataflow_execution_ack_heartbeat() {
         let ack_responder_builder = AckResponderBuilder {
@@ -330,6 +449,7 @@ mod tests {
             buf_size: 10,
             connect_timeout: 3,
             rpc_timeout: 3,
+            ..Default::default()
         };
 
         let (gateway, _, _) = MockRpcGateway::new(ack_responder_builder.buf_size, 10);
@@ -357,6 +477,7 @@ mod tests {
                     sub_id: 1,
                 }),
                 request_id: Some(RequestId::HeartbeatId(2)),
+                priority: 0,
             });
         }
 
@@ -371,6 +492,7 @@ mod tests {
                     sub_id: 1,
                 }),
                 request_id: Some(RequestId::HeartbeatId(1)),
+                priority: 0,
             });
         }
     }
@@ -12,7 +12,7 @@ use crate::errors::coordinator::{
 };
 
 use super::{
-    executions::{SubdataflowDeploymentPlan, TaskDeploymentException},
+    executions::{ExecutorHeartbeats, SubdataflowDeploymentPlan, TaskDeploymentException},
     scheduler::Scheduler,
     storage::{DataflowStorage, DataflowStorageBuilder},
 };
@@ -49,7 +49,7 @@ impl JobManager {
     async fn deploy_dataflow(
         &mut self,
         cluster: &cluster::Cluster,
-        heartbeat_builder: &HeartbeatBuilder,
+        heartbeats: &ExecutorHeartbeats,
         ack_builder: &AckResponderBuilder,
     ) -> Result<(), TaskDeploymentException> {
         let _ = self.storage.save(&self.dataflow);
@@ -63,7 +63,7 @@ impl JobManager {
                 &self.job_id,
                 cluster.get_node(host_addr),
                 ack_builder,
-                heartbeat_builder,
+                heartbeats,
             );
             plan
         });
@@ -112,7 +112,7 @@ pub(crate) struct Dispatcher {
     managers: SkipMap<ResourceId, JobManager>,
     cluster: cluster::Cluster,
     location: HostAddr,
-    heartbeat: HeartbeatBuilder,
+    heartbeat: ExecutorHeartbeats,
     ack: AckResponderBuilder,
     storage: DataflowStorageBuilder,
 }
@@ -130,7 +130,7 @@ impl Dispatcher {
             managers: Default::default(),
             cluster,
             location: local(port),
-            heartbeat: heartbeat_builder.clone(),
+            heartbeat: ExecutorHeartbeats::new(heartbeat_builder),
             ack: ack_builder.clone(),
             storage: storage_builder.clone(),
         }
@@ -1,10 +1,18 @@
 use std::fs;
 
-use common::utils;
+use common::{
+    net::{
+        audit::{AuditRecord, AuditWriter, FileAuditSinkBuilder},
+        tenant::ClusterValidator,
+        trace,
+    },
+    utils::{self, times},
+};
 use crossbeam_skiplist::SkipMap;
 use proto::{
     common::{
-        Ack, DataflowStatus, Heartbeat, KeyedDataEvent, KeyedEventSet, ResourceId, Response,
+        Ack, AckBatch, AckBatchResponse, AckResult, DataflowStatus, HealthCheckRequest,
+        HealthStatus, Heartbeat, HostAddr, KeyedDataEvent, KeyedEventSet, ResourceId, Response,
         SubDataflowStates,
     },
     taskmanager::{
@@ -29,6 +37,12 @@ pub struct TaskManagerBuilder {
     pub port: usize,
     // max available number of jobs
     pub max_job_nums: usize,
+    /// id of the cluster. If it's set, only heartbeats and acks from the same cluster are accepted
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    /// where the received heartbeats are recorded. No heartbeat is recorded if it's unset
+    #[serde(default)]
+    pub audit: Option<FileAuditSinkBuilder>,
 }
 
 pub fn load_builder() -> TaskManagerBuilder {
@@ -51,12 +65,40 @@ pub fn load_builder() -> TaskManagerBuilder {
 impl TaskManagerBuilder {
     pub fn build(&self) -> TaskManagerApiServer<TaskManager> {
         let workers = SkipMap::new();
-        TaskManagerApiServer::new(TaskManager { workers })
+        TaskManagerApiServer::new(TaskManager {
+            workers,
+            cluster_validator: ClusterValidator::new(self.cluster_id.clone()),
+            audit: self.audit.as_ref().map(|audit| {
+                audit
+                    .build_writer()
+                    .expect("fail to open heartbeat audit file: ")
+            }),
+        })
     }
 }
 
 pub struct TaskManager {
     workers: SkipMap<ResourceId, TaskWorker>,
+    cluster_validator: ClusterValidator,
+    audit: Option<AuditWriter>,
+}
+
+impl TaskManager {
+    /// deliver an ack to the worker of its job
+    fn dispatch_ack(&self, ack: &Ack) -> Result<(), tonic::Status> {
+        match ack
+            .get_execution_id()
+            .and_then(|subdataflow_id| subdataflow_id.job_id.as_ref())
+        {
+            Some(job_id) => {
+                if let Some(entry) = self.workers.get(job_id) {
+                    entry.value().receive_ack(ack)
+                }
+                Ok(())
+            }
+            None => Err(execution_id_unprovided().into_tonic_status()),
+        }
+    }
 }
 
 #[async_trait]
@@ -133,42 +175,65 @@ impl TaskManagerApi for TaskManager {
     }
 
     async fn receive_heartbeat(&self, request: RpcRequest<Heartbeat>) -> RpcResponse<Response> {
+        self.cluster_validator.validate(request.metadata())?;
+        if let Some(writer) = self.audit.as_ref() {
+            let host_addr = request
+                .remote_addr()
+                .map(HostAddr::from)
+                .unwrap_or_default();
+            writer.write(AuditRecord::received(&host_addr, request.get_ref()))
+        }
+        let parent = trace::extract(request.metadata());
         let heartbeat = request.into_inner();
-        match heartbeat
-            .get_subdataflow_id()
-            .and_then(|subdataflow_id| subdataflow_id.job_id.as_ref())
-        {
-            Some(job_id) => {
-                for entry in self.workers.get(job_id).iter() {
-                    let worker = entry.value();
-                    worker.receive_heartbeat(&heartbeat)
+        // a coalesced heartbeat is delivered to the worker of each job it carries
+        let heartbeats = heartbeat.split_coalesced();
+        let execution_id_provided = trace::with_parent(parent, async {
+            let mut execution_id_provided = false;
+            for heartbeat in heartbeats.iter() {
+                if let Some(job_id) = heartbeat
+                    .get_subdataflow_id()
+                    .and_then(|subdataflow_id| subdataflow_id.job_id.as_ref())
+                {
+                    execution_id_provided = true;
+                    for entry in self.workers.get(job_id).iter() {
+                        let worker = entry.value();
+                        worker.receive_heartbeat(heartbeat)
+                    }
                 }
-
-                Ok(new_rpc_response(Response::ok()))
             }
-            None => Err(execution_id_unprovided().into_tonic_status()),
-        }
-    }
+            execution_id_provided
+        })
+        .await;
 
-    async fn receive_ack(&self, request: RpcRequest<Ack>) -> RpcResponse<Response> {
-        let ack = request.into_inner();
-        if let Some(job_id) = ack
-            .get_execution_id()
-            .and_then(|subdataflow_id| subdataflow_id.job_id.as_ref())
-        {
-            match self.workers.get(job_id) {
-                Some(entry) => {
-                    let worker = entry.value();
-                    worker.receive_ack(&ack)
-                }
-                None => {}
-            };
+        if execution_id_provided {
             Ok(new_rpc_response(Response::ok()))
         } else {
             Err(execution_id_unprovided().into_tonic_status())
         }
     }
 
+    async fn receive_ack(&self, request: RpcRequest<Ack>) -> RpcResponse<Response> {
+        self.cluster_validator.validate(request.metadata())?;
+        let parent = trace::extract(request.metadata());
+        let ack = request.into_inner();
+        trace::with_parent(parent, async { self.dispatch_ack(&ack) })
+            .await
+            .map(|_| new_rpc_response(Response::ok()))
+    }
+
+    async fn receive_acks(&self, request: RpcRequest<AckBatch>) -> RpcResponse<AckBatchResponse> {
+        self.cluster_validator.validate(request.metadata())?;
+        let parent = trace::extract(request.metadata());
+        let acks = request.into_inner().acks;
+        let results = trace::with_parent(parent, async {
+            acks.iter()
+                .map(|ack| AckResult::from_result(&self.dispatch_ack(ack)))
+                .collect::<Vec<_>>()
+        })
+        .await;
+        Ok(new_rpc_response(AckBatchResponse { results }))
+    }
+
     async fn batch_send_events_to_operator(
         &self,
         request: RpcRequest<KeyedEventSet>,
@@ -199,4 +264,16 @@ impl TaskManagerApi for TaskManager {
             None => Err(no_found_worker().into_tonic_status()),
         }
     }
+
+    async fn check_health(
+        &self,
+        request: RpcRequest<HealthCheckRequest>,
+    ) -> RpcResponse<HealthStatus> {
+        self.cluster_validator.validate(request.metadata())?;
+        Ok(new_rpc_response(HealthStatus {
+            alive: true,
+            running_subdataflows: self.workers.len() as u32,
+            timestamp: Some(times::prost_now()),
+        }))
+    }
 }
@@ -189,6 +189,8 @@ fn setup_builder(port: usize) -> TaskManagerBuilder {
     TaskManagerBuilder {
         port,
         max_job_nums: 10,
+        cluster_id: None,
+        audit: None,
     }
 }
 
@@ -215,13 +217,17 @@ async fn test_e2e() {
             period: 3,
             connect_timeout: 3,
             rpc_timeout: 3,
+            ..Default::default()
         },
         ack: AckResponderBuilder {
             delay: 3,
             buf_size: 10,
             connect_timeout: 5,
             rpc_timeout: 5,
+            ..Default::default()
         },
+        cluster_id: None,
+        audit: None,
     };
 
     let addr = format!("0.0.0.0:{}", builder.port).parse().expect("msg");
@@ -17,6 +17,8 @@ fn setup_builder(port: usize) -> TaskManagerBuilder {
     TaskManagerBuilder {
         port,
         max_job_nums: 10,
+        cluster_id: None,
+        audit: None,
     }
 }
 
@@ -29,7 +31,6 @@ pub fn setup() {
     })
 }
 
-
 fn setup_server(port: usize) -> JoinHandle<Result<(), Error>> {
     let builder = setup_builder(port);
     let server = builder.build();
@@ -141,4 +142,4 @@ async fn test_taskmanager_deploy_and_terminate() {
     assert!(r.is_ok());
 
     server_1.abort();
-}
\ No newline at end of file
+}
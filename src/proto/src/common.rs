@@ -72,6 +72,12 @@ pub struct Heartbeat {
     /// id of task executor
     #[prost(uint32, tag = "5")]
     pub task_id: u32,
+    /// Execution Ids of the other sub-dataflows whose heartbeats are coalesced into this one
+    #[prost(message, repeated, tag = "6")]
+    pub coalesced_subdataflow_ids: ::prost::alloc::vec::Vec<SubDataflowId>,
+    /// order-independent digest of the cluster members known by the sender. Zero if it's not set
+    #[prost(uint64, tag = "7")]
+    pub membership_digest: u64,
 }
 /// Some requests from client needs server responds ack asynchronously, like:
 /// - Heartbeat
@@ -92,6 +98,9 @@ pub struct Ack {
     /// the execution id
     #[prost(message, optional, tag = "6")]
     pub execution_id: ::core::option::Option<SubDataflowId>,
+    /// the priority of the ack, the higher the more urgent
+    #[prost(uint32, tag = "7")]
+    pub priority: u32,
     /// the id which sent by the request needs to ack. it may points to multiple semantics:
     /// - for heartbeat, it represents heartbeat id
     /// - for checkpoint, it represents checkpoint id
@@ -146,6 +155,49 @@ pub mod ack {
         HeartbeatId(u64),
     }
 }
+/// a batch of acks sent in one call
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AckBatch {
+    #[prost(message, repeated, tag = "1")]
+    pub acks: ::prost::alloc::vec::Vec<Ack>,
+}
+/// the result of an ack in an AckBatch
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AckResult {
+    /// the grpc status code, which is 0 if the ack is accepted
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    /// the error message of a rejected ack
+    #[prost(string, tag = "2")]
+    pub message: ::prost::alloc::string::String,
+}
+/// the results of an AckBatch, one for each ack in the same order
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AckBatchResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub results: ::prost::alloc::vec::Vec<AckResult>,
+}
+/// request of the health check of a node, which is answered without any heartbeat
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HealthCheckRequest {}
+/// the liveness and load of a node
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HealthStatus {
+    /// whether the node is able to accept work
+    #[prost(bool, tag = "1")]
+    pub alive: bool,
+    /// the number of sub-dataflows running on the node
+    #[prost(uint32, tag = "2")]
+    pub running_subdataflows: u32,
+    /// The timestamp when the status is reported
+    #[prost(message, optional, tag = "3")]
+    pub timestamp: ::core::option::Option<::prost_types::Timestamp>,
+}
 /// Basic information of task
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -186,6 +238,8 @@ pub enum DataTypeEnum {
     Object = 6,
     /// array
     Array = 7,
+    /// binary data, Uint8Array in JavaScript
+    Bytes = 8,
 }
 impl DataTypeEnum {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -202,6 +256,7 @@ impl DataTypeEnum {
             DataTypeEnum::Boolean => "DATA_TYPE_ENUM_BOOLEAN",
             DataTypeEnum::Object => "DATA_TYPE_ENUM_OBJECT",
             DataTypeEnum::Array => "DATA_TYPE_ENUM_ARRAY",
+            DataTypeEnum::Bytes => "DATA_TYPE_ENUM_BYTES",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
@@ -215,6 +270,7 @@ impl DataTypeEnum {
             "DATA_TYPE_ENUM_BOOLEAN" => Some(Self::Boolean),
             "DATA_TYPE_ENUM_OBJECT" => Some(Self::Object),
             "DATA_TYPE_ENUM_ARRAY" => Some(Self::Array),
+            "DATA_TYPE_ENUM_BYTES" => Some(Self::Bytes),
             _ => None,
         }
     }
@@ -659,6 +715,64 @@ pub mod mysql_desc {
         pub password: ::prost::alloc::string::String,
         #[prost(string, tag = "4")]
         pub database: ::prost::alloc::string::String,
+        /// server-side timeout of SELECT statements in milliseconds, 0 means no timeout
+        #[prost(uint64, tag = "5")]
+        pub max_execution_time_ms: u64,
+        /// port of the server, 0 means the default port 3306
+        #[prost(uint32, tag = "6")]
+        pub port: u32,
+        /// the TLS mode of the connection
+        #[prost(enumeration = "connection_opts::SslMode", tag = "7")]
+        pub ssl_mode: i32,
+        /// path of the CA certificate to verify the server with, empty means the system roots
+        #[prost(string, tag = "8")]
+        pub ssl_ca: ::prost::alloc::string::String,
+        /// session variables set on every connection, e.g. sql_mode, for the options not modeled by the fields above
+        #[prost(map = "string, string", tag = "9")]
+        pub extra_params: ::std::collections::HashMap<
+            ::prost::alloc::string::String,
+            ::prost::alloc::string::String,
+        >,
+    }
+    /// Nested message and enum types in `ConnectionOpts`.
+    pub mod connection_opts {
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum SslMode {
+            Preferred = 0,
+            Disabled = 1,
+            Required = 2,
+            VerifyCa = 3,
+            VerifyIdentity = 4,
+        }
+        impl SslMode {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    SslMode::Preferred => "PREFERRED",
+                    SslMode::Disabled => "DISABLED",
+                    SslMode::Required => "REQUIRED",
+                    SslMode::VerifyCa => "VERIFY_CA",
+                    SslMode::VerifyIdentity => "VERIFY_IDENTITY",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "PREFERRED" => Some(Self::Preferred),
+                    "DISABLED" => Some(Self::Disabled),
+                    "REQUIRED" => Some(Self::Required),
+                    "VERIFY_CA" => Some(Self::VerifyCa),
+                    "VERIFY_IDENTITY" => Some(Self::VerifyIdentity),
+                    _ => None,
+                }
+            }
+        }
     }
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Message)]
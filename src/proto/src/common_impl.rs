@@ -1,18 +1,22 @@
+use std::{fmt::Display, str::FromStr};
+
 use chrono::Duration;
 
 use crate::common::{
-    mysql_desc::{self, Statement},
+    mysql_desc::{self, connection_opts::SslMode, Statement},
     operator_info::Details,
     sink, source,
     trigger::Watermark,
     window::{self, FixedWindow, SessionWindow, SlidingWindow},
-    Ack, DataTypeEnum, Dataflow, Entry, SubDataflowId, Func, Heartbeat, HostAddr, KafkaDesc,
-    KeyedDataEvent, MysqlDesc, OperatorInfo, RedisDesc, ResourceId, Response, Sink, Source, Time,
-    Trigger, Window,
+    Ack, AckBatchResponse, AckResult, DataTypeEnum, Dataflow, Entry, Func, Heartbeat, HostAddr,
+    KafkaDesc, KeyedDataEvent, MysqlDesc, OperatorInfo, RedisDesc, ResourceId, Response, Sink,
+    Source, SubDataflowId, Time, Trigger, Window,
 };
 
 pub const SUCCESS_RPC_RESPONSE: &str = "success";
 
+pub const DEFAULT_MYSQL_PORT: u32 = 3306;
+
 const RESOURCE_ID_SCHEMA: &str = r#"{
     "name": "ResourceId", 
     "type": "record", 
@@ -456,6 +460,80 @@ impl mysql_desc::ConnectionOpts {
 
         format!("mysql://{user}:{password}@{host}/{db}")
     }
+
+    /// connection options of Amazon RDS with IAM database authentication.
+    /// The token is sent as the password, which RDS only accepts over TLS.
+    /// To verify the server, set the RDS CA bundle by [mysql_desc::ConnectionOpts::with_ssl_ca] and the mode to `VerifyIdentity`.
+    pub fn rds(endpoint: &str, user: &str, token: &str) -> Self {
+        Self::managed(endpoint, user, token, SslMode::Required)
+    }
+
+    /// connection options of Google Cloud SQL connected by its ip address.
+    /// The certificates of Cloud SQL don't carry the ip address, so the identity of the server can't be verified.
+    pub fn cloud_sql(endpoint: &str, user: &str, password: &str) -> Self {
+        Self::managed(endpoint, user, password, SslMode::Required)
+    }
+
+    /// connection options of PlanetScale, whose certificates are signed by public CAs
+    pub fn planetscale(endpoint: &str, user: &str, password: &str) -> Self {
+        Self::managed(endpoint, user, password, SslMode::VerifyIdentity)
+    }
+
+    /// the endpoint is either `host` or `host:port`, parsed the same as [HostAddr]. An IPv6 host is either bare, e.g. `2001:db8::1`,
+    /// or bracketed, e.g. `[::1]` or `[::1]:3306`. The default port is used if the endpoint has no valid port.
+    fn managed(endpoint: &str, user: &str, password: &str, ssl_mode: SslMode) -> Self {
+        let (host, port) = match endpoint.parse::<HostAddr>() {
+            Ok(addr) => (addr.host, addr.port),
+            Err(_) => (
+                endpoint
+                    .strip_prefix('[')
+                    .and_then(|host| host.strip_suffix(']'))
+                    .unwrap_or(endpoint)
+                    .to_string(),
+                DEFAULT_MYSQL_PORT,
+            ),
+        };
+
+        Self {
+            host,
+            username: user.to_string(),
+            password: password.to_string(),
+            port,
+            ssl_mode: ssl_mode as i32,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_database(mut self, database: &str) -> Self {
+        self.database = database.to_string();
+        self
+    }
+
+    pub fn with_ssl_ca(mut self, ssl_ca: &str) -> Self {
+        self.ssl_ca = ssl_ca.to_string();
+        self
+    }
+
+    pub fn with_ssl_mode(mut self, ssl_mode: SslMode) -> Self {
+        self.ssl_mode = ssl_mode as i32;
+        self
+    }
+
+    /// set the session variable `name` to `value` on every connection, e.g. `sql_mode`
+    pub fn with_extra_param(mut self, name: &str, value: &str) -> Self {
+        self.extra_params
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// the port of the server, the default port 3306 if it's unset
+    pub fn port(&self) -> u16 {
+        if self.port == 0 {
+            DEFAULT_MYSQL_PORT as u16
+        } else {
+            self.port as u16
+        }
+    }
 }
 
 impl Response {
@@ -506,21 +584,158 @@ get_func!(Reducer, reducer);
 get_func!(KeyBy, key_by);
 get_func!(Filter, filter);
 
+impl From<std::net::SocketAddr> for HostAddr {
+    fn from(addr: std::net::SocketAddr) -> Self {
+        Self {
+            host: addr.ip().to_string(),
+            port: addr.port() as u32,
+        }
+    }
+}
+
 impl HostAddr {
+    /// the plaintext uri of the node, e.g. `http://[::1]:8080`
     pub fn as_uri(&self) -> String {
-        format!("http://{}:{}", &self.host, self.port)
+        self.as_uri_with_scheme("http")
+    }
+
+    /// the uri of the node terminating TLS, e.g. of the clusters running mutual TLS between nodes
+    pub fn as_secure_uri(&self) -> String {
+        self.as_uri_with_scheme("https")
+    }
+
+    /// the uri of the node with `scheme`. An IPv6 host is bracketed.
+    pub fn as_uri_with_scheme(&self, scheme: &str) -> String {
+        format!("{}://{}", scheme, self)
     }
 
     pub fn is_valid(&self) -> bool {
         !self.host.is_empty() && self.port > 0
     }
+
+    /// the address with its host trimmed, lowercased, unbracketed and without the trailing dot of a fully qualified name,
+    /// so that different spellings of the same node compare equal
+    pub fn normalized(&self) -> HostAddr {
+        HostAddr {
+            host: self
+                .host
+                .trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .trim_end_matches('.')
+                .to_ascii_lowercase(),
+            port: self.port,
+        }
+    }
 }
 
+/// The address is rendered as `host:port`, and an IPv6 host is bracketed like `[::1]:8080`
+impl Display for HostAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') && !self.host.starts_with('[') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Parse an address like `localhost:8080`, `10.0.0.1:8080` or `[::1]:8080`, e.g. read from env vars or CLI args.
+/// The brackets of an IPv6 host are removed.
+impl FromStr for HostAddr {
+    type Err = HostAddrParseError;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        let (host, port) = match addr.strip_prefix('[') {
+            Some(bracketed) => match bracketed.split_once(']') {
+                Some((host, rest)) => match rest.strip_prefix(':') {
+                    Some(port) => (host, port),
+                    None if rest.is_empty() => {
+                        return Err(HostAddrParseError::MissingPort(addr.to_string()))
+                    }
+                    None => return Err(HostAddrParseError::InvalidHost(addr.to_string())),
+                },
+                None => return Err(HostAddrParseError::InvalidHost(addr.to_string())),
+            },
+            None => match addr.rsplit_once(':') {
+                // an IPv6 host must be bracketed, otherwise its port is ambiguous
+                Some((host, _)) if host.contains(':') => {
+                    return Err(HostAddrParseError::InvalidHost(addr.to_string()))
+                }
+                Some((host, port)) => (host, port),
+                None => return Err(HostAddrParseError::MissingPort(addr.to_string())),
+            },
+        };
+
+        if host.is_empty() {
+            return Err(HostAddrParseError::EmptyHost(addr.to_string()));
+        }
+        match port.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(HostAddr {
+                host: host.to_string(),
+                port: port as u32,
+            }),
+            _ => Err(HostAddrParseError::InvalidPort(addr.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostAddrParseError {
+    EmptyHost(String),
+    InvalidHost(String),
+    MissingPort(String),
+    InvalidPort(String),
+}
+
+impl Display for HostAddrParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyHost(addr) => write!(f, "missing host in address {:?}", addr),
+            Self::InvalidHost(addr) => write!(
+                f,
+                "invalid host in address {:?}, an IPv6 host should be bracketed like [::1]:8080",
+                addr
+            ),
+            Self::MissingPort(addr) => {
+                write!(
+                    f,
+                    "missing port in address {:?}, it should be host:port",
+                    addr
+                )
+            }
+            Self::InvalidPort(addr) => write!(
+                f,
+                "invalid port in address {:?}, it should be within 1-65535",
+                addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HostAddrParseError {}
+
 impl Heartbeat {
     #[inline]
     pub fn get_subdataflow_id(&self) -> Option<&SubDataflowId> {
         self.subdataflow_id.as_ref()
     }
+
+    /// split a coalesced heartbeat into one heartbeat per sub-dataflow. A heartbeat of single sub-dataflow is returned as it is.
+    pub fn split_coalesced(&self) -> Vec<Heartbeat> {
+        if self.coalesced_subdataflow_ids.is_empty() {
+            return vec![self.clone()];
+        }
+        self.subdataflow_id
+            .iter()
+            .chain(self.coalesced_subdataflow_ids.iter())
+            .map(|subdataflow_id| Heartbeat {
+                subdataflow_id: Some(subdataflow_id.clone()),
+                coalesced_subdataflow_ids: vec![],
+                ..self.clone()
+            })
+            .collect()
+    }
 }
 
 impl Ack {
@@ -530,6 +745,46 @@ impl Ack {
     }
 }
 
+impl AckResult {
+    pub fn from_result<T>(result: &Result<T, tonic::Status>) -> Self {
+        match result {
+            Ok(_) => Self::default(),
+            Err(status) => Self {
+                code: status.code() as i32,
+                message: status.message().to_string(),
+            },
+        }
+    }
+
+    // the same result as the ReceiveAck rpc, which is what the gateways return for each ack
+    #[allow(clippy::result_large_err)]
+    pub fn into_result(self) -> Result<Response, tonic::Status> {
+        match tonic::Code::from_i32(self.code) {
+            tonic::Code::Ok => Ok(Response::ok()),
+            code => Err(tonic::Status::new(code, self.message)),
+        }
+    }
+}
+
+impl AckBatchResponse {
+    /// the result of each of the `len` acks in the batch. An ack without a result is failed as [tonic::Code::Internal].
+    pub fn into_results(self, len: usize) -> Vec<Result<Response, tonic::Status>> {
+        let mut results = self
+            .results
+            .into_iter()
+            .take(len)
+            .map(AckResult::into_result)
+            .collect::<Vec<_>>();
+        while results.len() < len {
+            results.push(Err(tonic::Status::internal(format!(
+                "no result of ack {} in the batch response",
+                results.len()
+            ))));
+        }
+        results
+    }
+}
+
 impl KeyedDataEvent {
     pub fn as_bytes(self) -> Result<bytes::Bytes, KeyedDataEventError> {
         apache_avro::Schema::parse_list(&[
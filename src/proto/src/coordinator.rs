@@ -161,6 +161,29 @@ pub mod coordinator_api_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        /// / Receive a batch of acks, which are handled in order
+        pub async fn receive_acks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::super::common::AckBatch>,
+        ) -> Result<
+            tonic::Response<super::super::common::AckBatchResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/coordinator.CoordinatorApi/ReceiveAcks",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// / Receive heartbeat
         pub async fn receive_heartbeat(
             &mut self,
@@ -217,6 +240,14 @@ pub mod coordinator_api_server {
             &self,
             request: tonic::Request<super::super::common::Ack>,
         ) -> Result<tonic::Response<super::super::common::Response>, tonic::Status>;
+        /// / Receive a batch of acks, which are handled in order
+        async fn receive_acks(
+            &self,
+            request: tonic::Request<super::super::common::AckBatch>,
+        ) -> Result<
+            tonic::Response<super::super::common::AckBatchResponse>,
+            tonic::Status,
+        >;
         /// / Receive heartbeat
         async fn receive_heartbeat(
             &self,
@@ -441,6 +472,44 @@ pub mod coordinator_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/coordinator.CoordinatorApi/ReceiveAcks" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReceiveAcksSvc<T: CoordinatorApi>(pub Arc<T>);
+                    impl<
+                        T: CoordinatorApi,
+                    > tonic::server::UnaryService<super::super::common::AckBatch>
+                    for ReceiveAcksSvc<T> {
+                        type Response = super::super::common::AckBatchResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::super::common::AckBatch>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).receive_acks(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ReceiveAcksSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/coordinator.CoordinatorApi/ReceiveHeartbeat" => {
                     #[allow(non_camel_case_types)]
                     struct ReceiveHeartbeatSvc<T: CoordinatorApi>(pub Arc<T>);
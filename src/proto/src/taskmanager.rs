@@ -226,6 +226,29 @@ pub mod task_manager_api_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        /// / Receive a batch of acks, which are handled in order
+        pub async fn receive_acks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::super::common::AckBatch>,
+        ) -> Result<
+            tonic::Response<super::super::common::AckBatchResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/taskmanager.TaskManagerApi/ReceiveAcks",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         /// / Send all events batchly
         pub async fn batch_send_events_to_operator(
             &mut self,
@@ -272,6 +295,26 @@ pub mod task_manager_api_client {
             );
             self.inner.unary(request.into_request(), path, codec).await
         }
+        /// / Check the liveness and load of the task manager
+        pub async fn check_health(
+            &mut self,
+            request: impl tonic::IntoRequest<super::super::common::HealthCheckRequest>,
+        ) -> Result<tonic::Response<super::super::common::HealthStatus>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/taskmanager.TaskManagerApi/CheckHealth",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -306,6 +349,14 @@ pub mod task_manager_api_server {
             &self,
             request: tonic::Request<super::super::common::Ack>,
         ) -> Result<tonic::Response<super::super::common::Response>, tonic::Status>;
+        /// / Receive a batch of acks, which are handled in order
+        async fn receive_acks(
+            &self,
+            request: tonic::Request<super::super::common::AckBatch>,
+        ) -> Result<
+            tonic::Response<super::super::common::AckBatchResponse>,
+            tonic::Status,
+        >;
         /// / Send all events batchly
         async fn batch_send_events_to_operator(
             &self,
@@ -322,6 +373,11 @@ pub mod task_manager_api_server {
             tonic::Response<super::super::common::SubDataflowStates>,
             tonic::Status,
         >;
+        /// / Check the liveness and load of the task manager
+        async fn check_health(
+            &self,
+            request: tonic::Request<super::super::common::HealthCheckRequest>,
+        ) -> Result<tonic::Response<super::super::common::HealthStatus>, tonic::Status>;
     }
     /// / RPC Api for Task Manager
     #[derive(Debug)]
@@ -581,6 +637,44 @@ pub mod task_manager_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/taskmanager.TaskManagerApi/ReceiveAcks" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReceiveAcksSvc<T: TaskManagerApi>(pub Arc<T>);
+                    impl<
+                        T: TaskManagerApi,
+                    > tonic::server::UnaryService<super::super::common::AckBatch>
+                    for ReceiveAcksSvc<T> {
+                        type Response = super::super::common::AckBatchResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::super::common::AckBatch>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).receive_acks(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ReceiveAcksSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/taskmanager.TaskManagerApi/BatchSendEventsToOperator" => {
                     #[allow(non_camel_case_types)]
                     struct BatchSendEventsToOperatorSvc<T: TaskManagerApi>(pub Arc<T>);
@@ -661,6 +755,46 @@ pub mod task_manager_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/taskmanager.TaskManagerApi/CheckHealth" => {
+                    #[allow(non_camel_case_types)]
+                    struct CheckHealthSvc<T: TaskManagerApi>(pub Arc<T>);
+                    impl<
+                        T: TaskManagerApi,
+                    > tonic::server::UnaryService<super::super::common::HealthCheckRequest>
+                    for CheckHealthSvc<T> {
+                        type Response = super::super::common::HealthStatus;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::super::common::HealthCheckRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move {
+                                (*inner).check_health(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CheckHealthSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -730,6 +730,11 @@ mod tests {
                 username: "root".to_string(),
                 password: "123".to_string(),
                 database: "test".to_string(),
+                max_execution_time_ms: 0,
+                port: 0,
+                ssl_mode: 0,
+                ssl_ca: "".to_string(),
+                extra_params: Default::default(),
             }),
             statement: Some(mysql_desc::Statement {
                 statement: "INSERT INTO table VALUES (?, ?)".to_string(),
@@ -870,6 +875,11 @@ mod tests {
                 username: "root".to_string(),
                 password: "123".to_string(),
                 database: "test".to_string(),
+                max_execution_time_ms: 0,
+                port: 0,
+                ssl_mode: 0,
+                ssl_ca: "".to_string(),
+                extra_params: Default::default(),
             }),
             statement: Some(mysql_desc::Statement {
                 statement: "statement".to_string(),
@@ -174,6 +174,18 @@ where
                 None => v8::Local::from(v8::undefined(context_scope)),
             }
         }
+        TypedValue::Bytes(value) => {
+            let ctx = v8::Context::new(scope);
+            let context_scope = &mut v8::ContextScope::new(scope, ctx);
+
+            let backing_store =
+                v8::ArrayBuffer::new_backing_store_from_vec(value.clone()).make_shared();
+            let buf = v8::ArrayBuffer::with_backing_store(context_scope, &backing_store);
+            match v8::Uint8Array::new(context_scope, buf, 0, value.len()) {
+                Some(local) => v8::Local::<v8::Value>::from(local),
+                None => v8::Local::from(v8::undefined(context_scope)),
+            }
+        }
     }
 }
 
@@ -217,6 +229,16 @@ pub fn to_typed_value<'s>(
             .map(|val| TypedValue::String(val.to_rust_string_lossy(handle_scope)));
     }
 
+    if local.is_uint8_array() {
+        return v8::Local::<v8::Uint8Array>::try_from(local)
+            .ok()
+            .map(|arr| {
+                let mut data = vec![0; arr.byte_length()];
+                arr.copy_contents(&mut data);
+                TypedValue::Bytes(data)
+            });
+    }
+
     if local.is_array() {
         return local.to_object(handle_scope).map(|obj| {
             let mut arr = vec![];
@@ -700,4 +722,20 @@ mod tests {
             _ => panic!("unexpected type"),
         }
     }
+
+    #[test]
+    fn test_bytes_wrap_value() {
+        use common::types::TypedValue;
+        let _setup_guard = setup();
+        let isolate = &mut v8::Isolate::new(Default::default());
+        let ref mut scope = v8::HandleScope::new(isolate);
+        let ctx = v8::Context::new(scope);
+        let context_scope = &mut v8::ContextScope::new(scope, ctx);
+        let ref mut scope = v8::HandleScope::new(context_scope);
+
+        let val = TypedValue::Bytes(vec![0, 1, 255]);
+        let wrapped = wrap_value(&val, scope);
+        assert!(wrapped.is_uint8_array());
+        assert_eq!(super::to_typed_value(wrapped, scope), Some(val));
+    }
 }
@@ -221,6 +221,11 @@ async fn test_mysql_sink() {
         username: "ci".to_string(),
         password: "123".to_string(),
         database: "ci".to_string(),
+        max_execution_time_ms: 0,
+        port: 0,
+        ssl_mode: 0,
+        ssl_ca: "".to_string(),
+        extra_params: Default::default(),
     };
 
     let mut conn = MysqlConn::from(conn_opts.clone());
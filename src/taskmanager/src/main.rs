@@ -1,13 +1,17 @@
-use common::utils::get_env;
+use common::{net::trace::TraceContextLayer, utils::get_env};
 use lightflus_core::taskmanager::rpc::load_builder;
 
 use stream::initialize_v8;
 use tonic::transport::Server;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 const DEFAULT_WORKER_THREADS_NUM: usize = 100;
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt()
+        .finish()
+        .with(TraceContextLayer)
+        .init();
     let worker_threads = get_env("WORKER_THREADS")
         .and_then(|num| num.parse::<usize>().ok())
         .unwrap_or(DEFAULT_WORKER_THREADS_NUM);
@@ -4368,7 +4368,8 @@ export namespace common {
         DATA_TYPE_ENUM_STRING = 4,
         DATA_TYPE_ENUM_BOOLEAN = 5,
         DATA_TYPE_ENUM_OBJECT = 6,
-        DATA_TYPE_ENUM_ARRAY = 7
+        DATA_TYPE_ENUM_ARRAY = 7,
+        DATA_TYPE_ENUM_BYTES = 8
     }
 
     /** Properties of a Time. */